@@ -1,74 +1,175 @@
-use crate::error::DefaultResult;
+use crate::{error::DefaultResult, OutputFormat};
 use colored::*;
 use holochain_core::{
-    agent::{
-        chain_store::ChainStore,
-        state::{AgentState, AgentStateSnapshot},
-    },
+    agent::{chain_store::ChainStore, state::AgentStateSnapshot},
     content_store::GetContent,
 };
-use holochain_core_types::{chain_header::ChainHeader, entry::Entry};
+use holochain_core_types::{chain_header::ChainHeader, entry::Entry, time::Iso8601};
 use holochain_locksmith::RwLock;
-use holochain_persistence_api::cas::content::Address;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use holochain_persistence_file::cas::file::FilesystemStorage;
-use std::{fs, path::PathBuf};
+use std::{fs, path::PathBuf, sync::Arc, thread, time::Duration};
 
 // TODO: use system-agnostic default path
 const DEFAULT_CHAIN_PATH: &str = "TODO";
 
-pub fn chain_log(storage_path: Option<PathBuf>, instance_id: String) -> DefaultResult<()> {
+/// How often `hc chain --follow` looks for new chain headers
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Selects which headers of a chain get displayed.
+/// Empty fields don't filter.
+#[derive(Clone, Debug, Default)]
+pub struct ChainFilter {
+    /// Only headers of any of these entry types
+    pub entry_types: Vec<String>,
+    /// Only headers committed at or after this time
+    pub since: Option<Iso8601>,
+    /// Only headers committed at or before this time
+    pub until: Option<Iso8601>,
+    /// Only the header with this address or the headers of the entry with this address
+    pub address: Option<Address>,
+}
+
+impl ChainFilter {
+    pub fn matches(&self, header: &ChainHeader) -> bool {
+        (self.entry_types.is_empty()
+            || self
+                .entry_types
+                .contains(&String::from(header.entry_type().clone())))
+            && self
+                .since
+                .as_ref()
+                .map_or(true, |since| header.timestamp() >= since)
+            && self
+                .until
+                .as_ref()
+                .map_or(true, |until| header.timestamp() <= until)
+            && self.address.as_ref().map_or(true, |address| {
+                &header.address() == address || header.entry_address() == address
+            })
+    }
+}
+
+/// Returns the storage directory of the given instance in the storage directory of a conductor.
+pub(crate) fn instance_storage_path(
+    storage_path: Option<PathBuf>,
+    instance_id: &str,
+) -> DefaultResult<PathBuf> {
     let storage_path = storage_path.ok_or_else(|| {
         format_err!("Please specify the path to CAS storage with the --path option.")
     })?;
-    let cas_path = storage_path.join(instance_id).join("cas");
-    let chain_store = ChainStore::new(std::sync::Arc::new(RwLock::new(
+    Ok(storage_path.join(instance_id))
+}
+
+pub(crate) fn open_chain_store(cas_path: &PathBuf) -> ChainStore {
+    ChainStore::new(Arc::new(RwLock::new(
         FilesystemStorage::new(cas_path.clone()).expect("Could not create chain store"),
-    )));
+    )))
+}
 
-    let agent = chain_store
+fn top_chain_header(
+    chain_store: &ChainStore,
+    cas_path: &PathBuf,
+) -> DefaultResult<Option<ChainHeader>> {
+    chain_store
         .get_raw(&Address::from("AgentState"))?
         .ok_or("Chain does not exist or has not been initialized")
         .and_then(|snapshot_json| {
             AgentStateSnapshot::from_json_str(&snapshot_json.to_string())
                 .map_err(|_| "AgentState is malformed")
         })
-        .map(|snapshot| {
-            let top_header = snapshot.top_chain_header().to_owned();
-            AgentState::new_with_top_chain_header(
-                chain_store.clone(),
-                top_header.cloned(),
-                Address::new(),
-            )
-        })
+        .map(|snapshot| snapshot.top_chain_header().cloned())
         .map_err(|err| {
             format_err!(
                 "Could not display chain for '{}': {}",
                 cas_path.to_string_lossy(),
                 err.to_string()
             )
-        })?;
+        })
+}
 
-    println!(
-        "\nChain entries for '{}' (latest on top):\n",
-        cas_path.to_string_lossy()
-    );
-    for ref header in agent.iter_chain() {
-        let entry = chain_store
-            .get(header.entry_address())
-            .expect("Panic while fetching from CAS!")
-            .ok_or_else(|| {
-                println!(
-                    "{:?} referenced in header but not found in CAS!",
-                    header.entry_address(),
-                )
-            })
-            .unwrap();
-        display_header(&header, &entry);
+pub fn chain_log(
+    storage_path: Option<PathBuf>,
+    instance_id: String,
+    filter: ChainFilter,
+    format: OutputFormat,
+    follow: bool,
+) -> DefaultResult<()> {
+    let cas_path = instance_storage_path(storage_path, &instance_id)?.join("cas");
+    let chain_store = open_chain_store(&cas_path);
+    let mut top_header = top_chain_header(&chain_store, &cas_path)?;
+    let mut headers: Vec<ChainHeader> = chain_store.iter(&top_header).collect();
+
+    if format == OutputFormat::Text {
+        println!(
+            "\nChain entries for '{}' ({}):\n",
+            cas_path.to_string_lossy(),
+            if follow {
+                "latest at the bottom"
+            } else {
+                "latest on top"
+            }
+        );
     }
 
+    // When following the chain new headers get appended at the bottom, like with `tail -f`
+    if follow {
+        headers.reverse();
+    }
+    display_headers(&chain_store, &headers, &filter, format)?;
+
+    while follow {
+        thread::sleep(FOLLOW_INTERVAL);
+        let new_top_header = top_chain_header(&chain_store, &cas_path)?;
+        let known_top = top_header.as_ref().map(|header| header.address());
+        if new_top_header.as_ref().map(|header| header.address()) == known_top {
+            continue;
+        }
+        let mut new_headers: Vec<ChainHeader> = chain_store
+            .iter(&new_top_header)
+            .take_while(|header| Some(header.address()) != known_top)
+            .collect();
+        new_headers.reverse();
+        display_headers(&chain_store, &new_headers, &filter, format)?;
+        top_header = new_top_header;
+    }
+
+    Ok(())
+}
+
+fn display_headers(
+    chain_store: &ChainStore,
+    headers: &[ChainHeader],
+    filter: &ChainFilter,
+    format: OutputFormat,
+) -> DefaultResult<()> {
+    for header in headers.iter().filter(|header| filter.matches(header)) {
+        let entry = chain_store.get(header.entry_address())?.ok_or_else(|| {
+            format_err!(
+                "{:?} referenced in header but not found in CAS!",
+                header.entry_address(),
+            )
+        })?;
+        match format {
+            OutputFormat::Text => display_header(header, &entry),
+            OutputFormat::Json => println!("{}", header_to_json(header, &entry)),
+        }
+    }
     Ok(())
 }
 
+/// One line of `hc chain --format json` output
+fn header_to_json(header: &ChainHeader, entry: &Entry) -> serde_json::Value {
+    json!({
+        "address": header.address(),
+        "timestamp": header.timestamp().to_string(),
+        "entry_type": String::from(header.entry_type().clone()),
+        "entry_address": header.entry_address(),
+        "provenances": header.provenances(),
+        "entry": entry,
+    })
+}
+
 pub fn chain_list(path: Option<PathBuf>) {
     let path = path.unwrap_or_else(|| PathBuf::from(DEFAULT_CHAIN_PATH));
     println!("Please specify an instance ID to view its chain.");
@@ -81,10 +182,48 @@ pub fn chain_list(path: Option<PathBuf>) {
 
 fn display_header(header: &ChainHeader, entry: &Entry) {
     println!(
-        "{} {}",
+        "{} {} {}",
         header.timestamp().to_string().bright_black(),
-        // format!("{:?}", header.entry_type()).blue().bold(),
+        String::from(header.entry_type().clone()).blue().bold(),
         header.entry_address().to_string().yellow(),
     );
     println!("{:#?}", entry);
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core_types::{
+        chain_header::test_chain_header, entry::entry_type::test_entry_type,
+    };
+    use std::str::FromStr;
+
+    #[test]
+    fn test_chain_filter() {
+        let header = test_chain_header();
+        assert!(ChainFilter::default().matches(&header));
+
+        let by_type = |entry_type: String| ChainFilter {
+            entry_types: vec![entry_type],
+            ..Default::default()
+        };
+        assert!(by_type(String::from(test_entry_type())).matches(&header));
+        assert!(!by_type("%agent_id".to_string()).matches(&header));
+
+        let by_time = |since: &str, until: &str| ChainFilter {
+            since: Some(Iso8601::from_str(since).unwrap()),
+            until: Some(Iso8601::from_str(until).unwrap()),
+            ..Default::default()
+        };
+        assert!(by_time("2018-10-11T00:00:00Z", "2018-10-12T00:00:00Z").matches(&header));
+        assert!(!by_time("2018-10-12T00:00:00Z", "2018-10-13T00:00:00Z").matches(&header));
+
+        let by_address = |address: Address| ChainFilter {
+            address: Some(address),
+            ..Default::default()
+        };
+        assert!(by_address(header.address()).matches(&header));
+        assert!(by_address(header.entry_address().clone()).matches(&header));
+        assert!(!by_address(Address::from("QmOther")).matches(&header));
+    }
+}
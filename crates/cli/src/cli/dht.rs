@@ -0,0 +1,281 @@
+use crate::{
+    cli::chain_log::{instance_storage_path, open_chain_store},
+    error::DefaultResult,
+    OutputFormat,
+};
+use colored::*;
+use holochain_core::{
+    content_store::GetContent,
+    dht::dht_store::{DhtStoreSnapshot, DHT_STORE_SNAPSHOT_ADDRESS},
+};
+use holochain_core_types::{
+    eav::{Attribute, EaviQuery, EntityAttributeValueIndex},
+    entry::Entry,
+    error::HolochainError,
+};
+use holochain_persistence_api::{cas::content::Address, eav::EntityAttributeValueStorage};
+use holochain_persistence_file::eav::file::EavFileStorage;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    path::PathBuf,
+};
+
+/// A link of a base entry as stored in the EAVI store of the DHT shard
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct DhtLink {
+    pub link_type: String,
+    pub tag: String,
+    /// Address of the LinkAdd entry
+    pub link_add: Address,
+    /// Target of the link, if the LinkAdd entry is held
+    pub target: Option<Address>,
+    pub removed: bool,
+}
+
+/// Everything the DHT shard of an instance knows about one address
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct DhtAddressInfo {
+    pub address: Address,
+    pub held_aspects: Vec<String>,
+    pub crud_status: Option<String>,
+    pub crud_link: Option<Address>,
+    pub headers: Vec<Address>,
+    pub links: Vec<DhtLink>,
+}
+
+/// Groups the EAVIs of a DHT shard and its held aspects by address.
+fn collect_dht_info(
+    chain_store: &impl GetContent,
+    eavis: BTreeSet<EntityAttributeValueIndex>,
+    held_aspects: BTreeMap<String, Vec<String>>,
+) -> BTreeMap<Address, DhtAddressInfo> {
+    let mut infos = BTreeMap::new();
+
+    for (entry_address, aspects) in held_aspects {
+        info_for(&mut infos, Address::from(entry_address)).held_aspects = aspects;
+    }
+
+    for eavi in eavis {
+        let info = info_for(&mut infos, eavi.entity());
+        match eavi.attribute() {
+            Attribute::CrudStatus => info.crud_status = Some(eavi.value().to_string()),
+            Attribute::CrudLink => info.crud_link = Some(eavi.value()),
+            Attribute::EntryHeader => info.headers.push(eavi.value()),
+            Attribute::LinkTag(link_type, tag) | Attribute::RemovedLink(link_type, tag) => {
+                let removed = match eavi.attribute() {
+                    Attribute::RemovedLink(_, _) => true,
+                    _ => false,
+                };
+                let target = match chain_store.get(&eavi.value()) {
+                    Ok(Some(Entry::LinkAdd(link_data))) => Some(link_data.link.target().clone()),
+                    _ => None,
+                };
+                info.links.push(DhtLink {
+                    link_type,
+                    tag,
+                    link_add: eavi.value(),
+                    target,
+                    removed,
+                });
+            }
+            _ => (),
+        }
+    }
+    infos
+}
+
+fn info_for(
+    infos: &mut BTreeMap<Address, DhtAddressInfo>,
+    address: Address,
+) -> &mut DhtAddressInfo {
+    infos
+        .entry(address.clone())
+        .or_insert_with(|| DhtAddressInfo {
+            address,
+            held_aspects: Vec::new(),
+            crud_status: None,
+            crud_link: None,
+            headers: Vec::new(),
+            links: Vec::new(),
+        })
+}
+
+pub fn dht(
+    storage_path: Option<PathBuf>,
+    instance_id: String,
+    address: Option<Address>,
+    format: OutputFormat,
+) -> DefaultResult<()> {
+    let instance_path = instance_storage_path(storage_path, &instance_id)?;
+    let chain_store = open_chain_store(&instance_path.join("cas"));
+
+    let held_aspects = match chain_store.get_raw(&Address::from(DHT_STORE_SNAPSHOT_ADDRESS))? {
+        Some(content) => DhtStoreSnapshot::try_from(content)
+            .map_err(|_| {
+                format_err!(
+                    "DHT state in '{}' is malformed",
+                    instance_path.to_string_lossy()
+                )
+            })?
+            .holding_map
+            .iter()
+            .map(|(entry_hash, aspects)| {
+                let mut aspects: Vec<String> =
+                    aspects.iter().map(|aspect| aspect.to_string()).collect();
+                aspects.sort();
+                (entry_hash.to_string(), aspects)
+            })
+            .collect(),
+        None => BTreeMap::new(),
+    };
+
+    let eav_storage: EavFileStorage<Attribute> =
+        EavFileStorage::new(instance_path.join("eav")).map_err(HolochainError::from)?;
+    let eavis = eav_storage
+        .fetch_eavi(&EaviQuery::default())
+        .map_err(HolochainError::from)?;
+
+    let infos = collect_dht_info(&chain_store, eavis, held_aspects);
+    let infos = infos.values().filter(|info| {
+        address
+            .as_ref()
+            .map_or(true, |address| &info.address == address)
+    });
+
+    if format == OutputFormat::Text {
+        println!("\nDHT shard of '{}':\n", instance_path.to_string_lossy());
+    }
+    for info in infos {
+        match format {
+            OutputFormat::Text => display_info(info),
+            OutputFormat::Json => println!("{}", serde_json::to_string(info)?),
+        }
+    }
+    Ok(())
+}
+
+fn display_info(info: &DhtAddressInfo) {
+    println!(
+        "{} {}",
+        info.address.to_string().yellow(),
+        info.crud_status
+            .clone()
+            .unwrap_or_else(|| "no status".to_string())
+            .blue()
+            .bold()
+    );
+    if let Some(ref crud_link) = info.crud_link {
+        println!("  updated to {}", crud_link);
+    }
+    if !info.held_aspects.is_empty() {
+        println!("  held aspects:");
+        for aspect in info.held_aspects.iter() {
+            println!("    {}", aspect);
+        }
+    }
+    if !info.headers.is_empty() {
+        println!("  headers:");
+        for header in info.headers.iter() {
+            println!("    {}", header);
+        }
+    }
+    if !info.links.is_empty() {
+        println!("  links:");
+        for link in info.links.iter() {
+            println!(
+                "    {}#{} -> {}{}",
+                link.link_type,
+                link.tag,
+                link.target
+                    .as_ref()
+                    .map(|target| target.to_string())
+                    .unwrap_or_else(|| format!("(LinkAdd {} not held)", link.link_add)),
+                if link.removed { " [removed]" } else { "" }
+            );
+        }
+    }
+    println!();
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core::content_store::AddContent;
+    use holochain_core_types::{
+        agent::test_agent_id, chain_header::test_chain_header, link::link_data::LinkData,
+    };
+    use holochain_persistence_api::cas::content::AddressableContent;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_collect_dht_info() {
+        let dir = tempdir().unwrap();
+        let mut chain_store = open_chain_store(&dir.path().to_path_buf());
+        let base = Address::from("QmBase");
+        let target = Address::from("QmTarget");
+        let link_add = Entry::LinkAdd(LinkData::new_add(
+            &base,
+            &target,
+            "",
+            "comments",
+            test_chain_header(),
+            test_agent_id(),
+        ));
+        chain_store.add(&link_add).unwrap();
+
+        let eavis = vec![
+            EntityAttributeValueIndex::new(
+                &base,
+                &Attribute::LinkTag("comments".to_string(), "".to_string()),
+                &link_add.address(),
+            )
+            .unwrap(),
+            EntityAttributeValueIndex::new(
+                &base,
+                &Attribute::RemovedLink("likes".to_string(), "".to_string()),
+                &Address::from("QmMissing"),
+            )
+            .unwrap(),
+            EntityAttributeValueIndex::new(
+                &target,
+                &Attribute::CrudLink,
+                &Address::from("QmNewTarget"),
+            )
+            .unwrap(),
+        ]
+        .into_iter()
+        .collect();
+        let mut held_aspects = BTreeMap::new();
+        held_aspects.insert("QmBase".to_string(), vec!["QmAspect".to_string()]);
+
+        let infos = collect_dht_info(&chain_store, eavis, held_aspects);
+        assert_eq!(infos.len(), 2);
+
+        let base_info = infos.get(&base).unwrap();
+        assert_eq!(base_info.held_aspects, vec!["QmAspect".to_string()]);
+        assert_eq!(
+            base_info.links,
+            vec![
+                DhtLink {
+                    link_type: "comments".to_string(),
+                    tag: "".to_string(),
+                    link_add: link_add.address(),
+                    target: Some(target.clone()),
+                    removed: false,
+                },
+                DhtLink {
+                    link_type: "likes".to_string(),
+                    tag: "".to_string(),
+                    link_add: Address::from("QmMissing"),
+                    target: None,
+                    removed: true,
+                },
+            ]
+        );
+        assert_eq!(
+            infos.get(&target).unwrap().crud_link,
+            Some(Address::from("QmNewTarget"))
+        );
+    }
+}
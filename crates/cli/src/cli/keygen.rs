@@ -1,14 +1,48 @@
 use error::DefaultResult;
 use holochain_common::paths::keys_directory;
-use holochain_conductor_lib::{key_loaders::mock_passphrase_manager, keystore::Keystore};
+use holochain_conductor_lib::{
+    key_loaders::mock_passphrase_manager,
+    keystore::{Keystore, STANDALONE_ROOT_SEED},
+};
 use rpassword;
 use std::{
     fs::create_dir_all,
-    io::{self, Write},
+    io::{self, BufRead, Write},
     path::PathBuf,
 };
 
-pub fn keygen(path: Option<PathBuf>, passphrase: Option<String>, quiet: bool) -> DefaultResult<()> {
+/// Reads the mnemonic phrase of a root seed from stdin, for `hc keygen --from-mnemonic`.
+/// The phrase may span several lines; reading stops at the first empty line or end of input.
+pub fn read_mnemonic(quiet: bool) -> DefaultResult<String> {
+    if !quiet {
+        println!(
+            "
+Please enter the mnemonic phrase of the keystore you want to restore,
+followed by an empty line:"
+        );
+    }
+    let stdin = io::stdin();
+    let mut words = Vec::new();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() && !words.is_empty() {
+            break;
+        }
+        words.extend(line.split_whitespace().map(String::from));
+    }
+    Ok(words.join(" "))
+}
+
+/// Creates a new agent keystore. If `mnemonic` is given, the root seed is restored from it
+/// instead of being randomly generated, which recreates the agent of the keystore that
+/// phrase was shown for with `show_mnemonic`.
+pub fn keygen(
+    path: Option<PathBuf>,
+    passphrase: Option<String>,
+    quiet: bool,
+    mnemonic: Option<String>,
+    show_mnemonic: bool,
+) -> DefaultResult<()> {
     let passphrase = passphrase.unwrap_or_else(|| {
         if !quiet {
             println!(
@@ -40,7 +74,19 @@ when unlocking the keybundle to use within a Holochain conductor."
     if !quiet {
         println!("Generating keystore (this will take a few moments)...");
     }
-    let (keystore, pub_key) = Keystore::new_standalone(mock_passphrase_manager(passphrase), None)?;
+    let (mut keystore, pub_key) = match mnemonic {
+        Some(mnemonic) => Keystore::new_standalone_from_mnemonic(
+            mock_passphrase_manager(passphrase),
+            None,
+            mnemonic,
+        )?,
+        None => Keystore::new_standalone(mock_passphrase_manager(passphrase), None)?,
+    };
+    let mnemonic = if show_mnemonic {
+        Some(keystore.get_seed_mnemonic(STANDALONE_ROOT_SEED)?)
+    } else {
+        None
+    };
 
     let path = if None == path {
         let p = keys_directory();
@@ -56,6 +102,9 @@ when unlocking the keybundle to use within a Holochain conductor."
     if quiet {
         println!("{}", pub_key);
         println!("{}", path_str);
+        if let Some(mnemonic) = mnemonic {
+            println!("{}", mnemonic);
+        }
     } else {
         println!("");
         println!("Succesfully created new agent keystore.");
@@ -64,6 +113,19 @@ when unlocking the keybundle to use within a Holochain conductor."
         println!("Keystore written to: {}", path_str);
         println!("");
         println!("You can set this file in a conductor config as keystore_file for an agent.");
+        if let Some(mnemonic) = mnemonic {
+            println!(
+                "
+Mnemonic phrase of the keystore's root seed:
+
+{}
+
+Write these words down and keep them somewhere safe. Anyone who knows them
+can act as this agent. Run `hc keygen --from-mnemonic` and enter them to
+restore the agent on another machine.",
+                mnemonic
+            );
+        }
     }
     Ok(())
 }
@@ -82,7 +144,14 @@ pub mod test {
         let path = PathBuf::new().join("test.key");
         let passphrase = String::from("secret");
 
-        keygen(Some(path.clone()), Some(passphrase.clone()), true).expect("Keygen should work");
+        keygen(
+            Some(path.clone()),
+            Some(passphrase.clone()),
+            true,
+            None,
+            false,
+        )
+        .expect("Keygen should work");
 
         let mut keystore =
             Keystore::new_from_file(path.clone(), mock_passphrase_manager(passphrase), None)
@@ -94,4 +163,31 @@ pub mod test {
 
         let _ = remove_file(path);
     }
+
+    #[test]
+    fn keygen_from_mnemonic_restores_agent() {
+        let path = PathBuf::new().join("test_mnemonic.key");
+        let passphrase = String::from("secret");
+
+        let (mut keystore, pub_key) =
+            Keystore::new_standalone(mock_passphrase_manager(passphrase.clone()), None).unwrap();
+        let mnemonic = keystore.get_seed_mnemonic(STANDALONE_ROOT_SEED).unwrap();
+
+        keygen(
+            Some(path.clone()),
+            Some(passphrase.clone()),
+            true,
+            Some(mnemonic),
+            false,
+        )
+        .expect("Keygen from mnemonic should work");
+
+        let mut restored =
+            Keystore::new_from_file(path.clone(), mock_passphrase_manager(passphrase), None)
+                .unwrap();
+        let keybundle = restored.get_keybundle(PRIMARY_KEYBUNDLE_ID).unwrap();
+        assert_eq!(keybundle.get_id(), pub_key);
+
+        let _ = remove_file(path);
+    }
 }
@@ -0,0 +1,636 @@
+use crate::cli::package;
+use colored::*;
+use error::DefaultResult;
+use holochain_conductor_lib::{conductor::Conductor, happ_bundle::HappBundle};
+use holochain_core::nucleus::ribosome::api::ZomeApiFunction;
+use holochain_core_types::{
+    dna::{bridges::BridgePresence, wasm::DnaWasm, zome::Zome, Dna},
+    entry::entry_type::EntryType,
+};
+use holochain_persistence_api::cas::content::AddressableContent;
+use std::{collections::BTreeSet, fmt, fs, path::PathBuf, str::FromStr};
+use wasmi::{
+    Error as InterpreterError, FuncInstance, FuncRef, ImportsBuilder, ModuleImportResolver,
+    ModuleInstance, ModuleRef, Signature,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "{}", "warning".yellow().bold()),
+            Severity::Error => write!(f, "{}", "error".red().bold()),
+        }
+    }
+}
+
+/// A single finding of `hc lint`.
+/// `location` is the path to the offending part of the DNA, e.g.
+/// `zomes.blog.entry_types.post.links_to[0]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintMessage {
+    pub severity: Severity,
+    pub rule: &'static str,
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for LintMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}\n  --> {}",
+            self.severity, self.rule, self.message, self.location
+        )
+    }
+}
+
+struct Linter {
+    messages: Vec<LintMessage>,
+}
+
+impl Linter {
+    fn new() -> Self {
+        Linter {
+            messages: Vec::new(),
+        }
+    }
+
+    fn report(
+        &mut self,
+        severity: Severity,
+        rule: &'static str,
+        location: String,
+        message: String,
+    ) {
+        self.messages.push(LintMessage {
+            severity,
+            rule,
+            location,
+            message,
+        });
+    }
+
+    fn check_dna(&mut self, dna: &Dna) {
+        // Entry types are shared between all zomes of a DNA
+        let app_entry_types: BTreeSet<String> = dna
+            .zomes
+            .values()
+            .flat_map(|zome| zome.entry_types.keys())
+            .filter(|entry_type| entry_type.is_app())
+            .map(|entry_type| String::from(entry_type.clone()))
+            .collect();
+
+        let links: BTreeSet<(String, String, String)> = dna
+            .zomes
+            .values()
+            .flat_map(|zome| zome.entry_types.iter())
+            .flat_map(|(entry_type, def)| {
+                def.links_to.iter().map(move |link| {
+                    (
+                        String::from(entry_type.clone()),
+                        link.target_type.clone(),
+                        link.link_type.clone(),
+                    )
+                })
+            })
+            .collect();
+
+        if dna.zomes.is_empty() {
+            self.report(
+                Severity::Warning,
+                "no-zomes",
+                "zomes".to_string(),
+                "DNA does not define any zomes".to_string(),
+            );
+        }
+
+        for (zome_name, zome) in dna.zomes.iter() {
+            let location = format!("zomes.{}", zome_name);
+            self.check_entry_types(&location, zome, &app_entry_types, &links);
+            self.check_traits(&location, zome);
+            self.check_bridges(&location, zome);
+            self.check_code(&location, zome);
+        }
+    }
+
+    fn is_known_type(type_name: &str, app_entry_types: &BTreeSet<String>) -> bool {
+        match EntryType::from_str(type_name) {
+            Ok(EntryType::App(_)) | Err(_) => app_entry_types.contains(type_name),
+            Ok(_) => true,
+        }
+    }
+
+    fn check_entry_types(
+        &mut self,
+        location: &str,
+        zome: &Zome,
+        app_entry_types: &BTreeSet<String>,
+        links: &BTreeSet<(String, String, String)>,
+    ) {
+        for (entry_type, def) in zome.entry_types.iter() {
+            let entry_type_name = String::from(entry_type.clone());
+            let location = format!("{}.entry_types.{}", location, entry_type_name);
+            if !entry_type.is_app() {
+                self.report(
+                    Severity::Error,
+                    "reserved-entry-type",
+                    location.clone(),
+                    format!(
+                        "'{}' is a system entry type and can't be defined",
+                        entry_type_name
+                    ),
+                );
+            }
+
+            for (index, link) in def.links_to.iter().enumerate() {
+                let location = format!("{}.links_to[{}]", location, index);
+                if link.link_type.is_empty() {
+                    self.report(
+                        Severity::Error,
+                        "empty-link-type",
+                        location.clone(),
+                        "link definition has no link type".to_string(),
+                    );
+                }
+                if !Linter::is_known_type(&link.target_type, app_entry_types) {
+                    self.report(
+                        Severity::Error,
+                        "undefined-link-target",
+                        location,
+                        format!(
+                            "link '{}' targets entry type '{}' which is not defined in this DNA",
+                            link.link_type, link.target_type
+                        ),
+                    );
+                }
+            }
+
+            for (index, link) in def.linked_from.iter().enumerate() {
+                let location = format!("{}.linked_from[{}]", location, index);
+                if link.link_type.is_empty() {
+                    self.report(
+                        Severity::Error,
+                        "empty-link-type",
+                        location.clone(),
+                        "link definition has no link type".to_string(),
+                    );
+                }
+                if !Linter::is_known_type(&link.base_type, app_entry_types) {
+                    self.report(
+                        Severity::Error,
+                        "undefined-link-base",
+                        location,
+                        format!(
+                            "link '{}' comes from entry type '{}' which is not defined in this DNA",
+                            link.link_type, link.base_type
+                        ),
+                    );
+                } else if app_entry_types.contains(&link.base_type)
+                    && !links.contains(&(
+                        link.base_type.clone(),
+                        entry_type_name.clone(),
+                        link.link_type.clone(),
+                    ))
+                {
+                    self.report(
+                        Severity::Warning,
+                        "unmatched-linked-from",
+                        location,
+                        format!(
+                            "entry type '{}' does not declare a matching links_to for link '{}'",
+                            link.base_type, link.link_type
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_traits(&mut self, location: &str, zome: &Zome) {
+        let mut declared = BTreeSet::new();
+        for (index, declaration) in zome.fn_declarations.iter().enumerate() {
+            if !declared.insert(declaration.name.clone()) {
+                self.report(
+                    Severity::Error,
+                    "duplicate-function",
+                    format!("{}.fn_declarations[{}]", location, index),
+                    format!("function '{}' is declared more than once", declaration.name),
+                );
+            }
+        }
+
+        for (trait_name, trait_fns) in zome.traits.iter() {
+            for (index, function) in trait_fns.functions.iter().enumerate() {
+                if !declared.contains(function) {
+                    self.report(
+                        Severity::Error,
+                        "undeclared-trait-function",
+                        format!("{}.traits.{}.functions[{}]", location, trait_name, index),
+                        format!(
+                            "trait '{}' references function '{}' which is not declared in this zome",
+                            trait_name, function
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_bridges(&mut self, location: &str, zome: &Zome) {
+        let mut handles = BTreeSet::new();
+        for (index, bridge) in zome.bridges.iter().enumerate() {
+            let location = format!("{}.bridges[{}]", location, index);
+            if bridge.handle.is_empty() {
+                self.report(
+                    Severity::Error,
+                    "empty-bridge-handle",
+                    location,
+                    "bridge has no handle".to_string(),
+                );
+            } else if !handles.insert(bridge.handle.clone()) {
+                self.report(
+                    Severity::Error,
+                    "duplicate-bridge-handle",
+                    location,
+                    format!("bridge handle '{}' is used more than once", bridge.handle),
+                );
+            }
+        }
+    }
+
+    fn check_code(&mut self, location: &str, zome: &Zome) {
+        if zome.code.code.is_empty() {
+            self.report(
+                Severity::Error,
+                "missing-code",
+                format!("{}.code", location),
+                "zome has no WASM code".to_string(),
+            );
+            return;
+        }
+
+        let instance = match instantiate(&zome.code) {
+            Ok(instance) => instance,
+            Err(error) => {
+                self.report(
+                    Severity::Error,
+                    "invalid-wasm",
+                    format!("{}.code", location),
+                    error,
+                );
+                return;
+            }
+        };
+
+        for (index, declaration) in zome.fn_declarations.iter().enumerate() {
+            let exported = instance
+                .export_by_name(&declaration.name)
+                .and_then(|export| export.as_func().cloned())
+                .is_some();
+            if !exported {
+                self.report(
+                    Severity::Error,
+                    "unexported-function",
+                    format!("{}.fn_declarations[{}]", location, index),
+                    format!(
+                        "function '{}' is declared but not exported by the zome's WASM",
+                        declaration.name
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_bundle(&mut self, dna: &Dna, bundle: &HappBundle) {
+        let dna_hash = dna.address().to_string();
+        let instances: Vec<_> = bundle
+            .instances
+            .iter()
+            .filter(|instance| instance.dna_hash == dna_hash)
+            .collect();
+
+        if instances.is_empty() {
+            self.report(
+                Severity::Warning,
+                "not-in-bundle",
+                "bundle.instances".to_string(),
+                format!("no instance in the bundle uses this DNA ({})", dna_hash),
+            );
+            return;
+        }
+
+        for (zome_name, zome) in dna.zomes.iter() {
+            for (index, bridge) in zome.bridges.iter().enumerate() {
+                if bridge.presence != BridgePresence::Required {
+                    continue;
+                }
+                for instance in instances.iter() {
+                    let satisfied = bundle.bridges.iter().any(|bundle_bridge| {
+                        bundle_bridge.caller_id == instance.id
+                            && bundle_bridge.handle == bridge.handle
+                    });
+                    if !satisfied {
+                        self.report(
+                            Severity::Error,
+                            "unsatisfied-bridge",
+                            format!("zomes.{}.bridges[{}]", zome_name, index),
+                            format!(
+                                "required bridge '{}' is not configured for bundle instance '{}'",
+                                bridge.handle, instance.id
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Accepts the Zome API functions the ribosome provides so that zome code can be
+/// instantiated without running it.
+struct LintImportResolver;
+
+impl ModuleImportResolver for LintImportResolver {
+    fn resolve_func(
+        &self,
+        field_name: &str,
+        signature: &Signature,
+    ) -> Result<FuncRef, InterpreterError> {
+        ZomeApiFunction::from_str(field_name)
+            .map(|api_fn| FuncInstance::alloc_host(signature.clone(), api_fn as usize))
+            .map_err(|_| {
+                InterpreterError::Function(format!(
+                    "zome code imports unknown host function {}",
+                    field_name
+                ))
+            })
+    }
+}
+
+/// Instantiates the given zome code without running its start function.
+fn instantiate(code: &DnaWasm) -> Result<ModuleRef, String> {
+    let module = code
+        .get_wasm_module()
+        .map_err(|e| format!("zome code is not valid WASM: {}", e))?;
+    let mut imports = ImportsBuilder::new();
+    imports.push_resolver("env", &LintImportResolver);
+    let instance = ModuleInstance::new(&module, &imports)
+        .map_err(|e| format!("zome code can't be instantiated: {}", e))?;
+    Ok(instance.not_started_instance().clone())
+}
+
+/// Runs all lint rules over the given DNA and, if given, checks its required
+/// bridges against a hApp bundle.
+pub fn lint_dna(dna: &Dna, bundle: Option<&HappBundle>) -> Vec<LintMessage> {
+    let mut linter = Linter::new();
+    linter.check_dna(dna);
+    if let Some(bundle) = bundle {
+        linter.check_bundle(dna, bundle);
+    }
+    linter.messages
+}
+
+fn load_bundle(path: &PathBuf) -> DefaultResult<HappBundle> {
+    let contents = fs::read_to_string(path)?;
+    toml::from_str::<HappBundle>(&contents)
+        .map_err(|e| format_err!("Couldn't parse bundle file {:?}: {}", path, e))
+}
+
+/// Lints a packaged DNA file or, if `path` is a directory, the DNA sources in it.
+pub fn lint(
+    path: &PathBuf,
+    bundle_path: Option<PathBuf>,
+    deny_warnings: bool,
+) -> DefaultResult<()> {
+    let dna = if path.is_dir() {
+        package::build_dna(path)?
+    } else {
+        Conductor::load_dna(path)?
+    };
+
+    let bundle = match bundle_path {
+        Some(bundle_path) => Some(load_bundle(&bundle_path)?),
+        None => None,
+    };
+
+    let messages = lint_dna(&dna, bundle.as_ref());
+    for message in messages.iter() {
+        println!("{}\n", message);
+    }
+
+    let errors = messages
+        .iter()
+        .filter(|m| m.severity == Severity::Error)
+        .count();
+    let warnings = messages.len() - errors;
+    println!(
+        "{} {:?}: {} error(s), {} warning(s)",
+        "Linted".green().bold(),
+        path,
+        errors,
+        warnings
+    );
+
+    if errors > 0 || (deny_warnings && warnings > 0) {
+        bail!("DNA did not pass linting");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_conductor_lib::{
+        config::Bridge as BundleBridge, happ_bundle::HappBundleInstance,
+    };
+    use holochain_core_types::dna::{
+        bridges::{Bridge, BridgeReference},
+        entry_types::{EntryTypeDef, LinkedFrom, LinksTo},
+        fn_declarations::{FnDeclaration, TraitFns},
+    };
+    use holochain_persistence_api::cas::content::Address;
+
+    // A module exporting a single function `foo` and nothing else
+    const FOO_WASM: &str = r#"
+        (module
+            (func (export "foo") (param i64) (result i64) (get_local 0))
+        )
+    "#;
+
+    fn test_dna() -> Dna {
+        let mut zome = Zome::empty();
+        zome.code = DnaWasm::from_bytes(wabt::wat2wasm(FOO_WASM).unwrap());
+        let mut post = EntryTypeDef::new();
+        post.links_to.push(LinksTo {
+            target_type: "comment".to_string(),
+            link_type: "comments".to_string(),
+        });
+        let mut comment = EntryTypeDef::new();
+        comment.linked_from.push(LinkedFrom {
+            base_type: "post".to_string(),
+            link_type: "comments".to_string(),
+        });
+        comment.linked_from.push(LinkedFrom {
+            base_type: "%agent_id".to_string(),
+            link_type: "authored".to_string(),
+        });
+        zome.entry_types.insert("post".into(), post);
+        zome.entry_types.insert("comment".into(), comment);
+        let mut declaration = FnDeclaration::new();
+        declaration.name = "foo".to_string();
+        zome.fn_declarations.push(declaration);
+        let mut trait_fns = TraitFns::new();
+        trait_fns.functions.push("foo".to_string());
+        zome.traits.insert("hc_public".to_string(), trait_fns);
+
+        let mut dna = Dna::new();
+        dna.zomes.insert("blog".to_string(), zome);
+        dna
+    }
+
+    fn rules(messages: &[LintMessage]) -> Vec<&'static str> {
+        messages.iter().map(|m| m.rule).collect()
+    }
+
+    #[test]
+    fn test_lint_clean_dna() {
+        assert_eq!(lint_dna(&test_dna(), None), Vec::new());
+    }
+
+    #[test]
+    fn test_lint_undefined_link_types() {
+        let mut dna = test_dna();
+        {
+            let zome = dna.zomes.get_mut("blog").unwrap();
+            zome.entry_types
+                .get_mut(&EntryType::from("post"))
+                .unwrap()
+                .links_to
+                .push(LinksTo {
+                    target_type: "tag".to_string(),
+                    link_type: "tags".to_string(),
+                });
+            zome.entry_types
+                .get_mut(&EntryType::from("comment"))
+                .unwrap()
+                .linked_from
+                .push(LinkedFrom {
+                    base_type: "post".to_string(),
+                    link_type: "replies".to_string(),
+                });
+        }
+        let messages = lint_dna(&dna, None);
+        assert_eq!(
+            rules(&messages),
+            vec!["unmatched-linked-from", "undefined-link-target"]
+        );
+        assert_eq!(messages[0].severity, Severity::Warning);
+        assert_eq!(
+            messages[0].location,
+            "zomes.blog.entry_types.comment.linked_from[2]"
+        );
+        assert_eq!(messages[1].severity, Severity::Error);
+        assert_eq!(
+            messages[1].location,
+            "zomes.blog.entry_types.post.links_to[1]"
+        );
+    }
+
+    #[test]
+    fn test_lint_traits_and_exports() {
+        let mut dna = test_dna();
+        {
+            let zome = dna.zomes.get_mut("blog").unwrap();
+            zome.traits
+                .get_mut("hc_public")
+                .unwrap()
+                .functions
+                .push("bar".to_string());
+            let mut declaration = FnDeclaration::new();
+            declaration.name = "baz".to_string();
+            zome.fn_declarations.push(declaration);
+        }
+        let messages = lint_dna(&dna, None);
+        assert_eq!(
+            rules(&messages),
+            vec!["undeclared-trait-function", "unexported-function"]
+        );
+        assert_eq!(
+            messages[0].location,
+            "zomes.blog.traits.hc_public.functions[1]"
+        );
+        assert_eq!(messages[1].location, "zomes.blog.fn_declarations[1]");
+    }
+
+    #[test]
+    fn test_lint_invalid_code() {
+        let mut dna = test_dna();
+        dna.zomes.get_mut("blog").unwrap().code = DnaWasm::from_bytes(vec![1, 2, 3]);
+        assert_eq!(rules(&lint_dna(&dna, None)), vec!["invalid-wasm"]);
+    }
+
+    #[test]
+    fn test_lint_bridges() {
+        let mut dna = test_dna();
+        let bridge = |handle: &str| Bridge {
+            presence: BridgePresence::Required,
+            handle: handle.to_string(),
+            reference: BridgeReference::Address {
+                dna_address: Address::from("QmOther"),
+            },
+        };
+        {
+            let zome = dna.zomes.get_mut("blog").unwrap();
+            zome.bridges.push(bridge("other"));
+            zome.bridges.push(bridge("other"));
+            zome.bridges.push(bridge(""));
+        }
+        assert_eq!(
+            rules(&lint_dna(&dna, None)),
+            vec!["duplicate-bridge-handle", "empty-bridge-handle"]
+        );
+
+        dna.zomes.get_mut("blog").unwrap().bridges.truncate(1);
+        let mut bundle = HappBundle {
+            instances: vec![HappBundleInstance {
+                name: "blog".to_string(),
+                id: "blog-instance".to_string(),
+                dna_hash: dna.address().to_string(),
+                uri: "file:blog.dna.json".to_string(),
+                dna_properties: None,
+            }],
+            bridges: Vec::new(),
+            uis: Vec::new(),
+        };
+        assert_eq!(
+            rules(&lint_dna(&dna, Some(&bundle))),
+            vec!["unsatisfied-bridge"]
+        );
+
+        bundle.bridges.push(BundleBridge {
+            caller_id: "blog-instance".to_string(),
+            callee_id: "other-instance".to_string(),
+            handle: "other".to_string(),
+        });
+        assert_eq!(lint_dna(&dna, Some(&bundle)), Vec::new());
+    }
+
+    #[test]
+    fn test_lint_dna_not_in_bundle() {
+        let bundle = HappBundle {
+            instances: Vec::new(),
+            bridges: Vec::new(),
+            uis: Vec::new(),
+        };
+        assert_eq!(
+            rules(&lint_dna(&test_dna(), Some(&bundle))),
+            vec!["not-in-bundle"]
+        );
+    }
+}
@@ -1,19 +1,27 @@
 mod chain_log;
+mod dht;
 mod generate;
 mod hash_dna;
 mod init;
 mod keygen;
+mod lint;
 pub mod package;
+mod replay;
 pub mod run;
 pub mod test;
+mod verify;
 
 pub use self::{
-    chain_log::{chain_list, chain_log},
+    chain_log::{chain_list, chain_log, ChainFilter},
+    dht::dht,
     generate::generate,
     hash_dna::hash_dna,
     init::init,
-    keygen::keygen,
-    package::package,
+    keygen::{keygen, read_mnemonic},
+    lint::lint,
+    package::{package, package_reproducible},
+    replay::replay,
     run::{get_interface_type_string, hc_run_bundle_configuration, hc_run_configuration, run},
     test::{test, TEST_DIR_NAME},
+    verify::verify,
 };
@@ -1,20 +1,32 @@
 use crate::{config_files::Build, error::DefaultResult, util};
 use base64;
 use colored::*;
+use holochain_conductor_lib::{
+    conductor::passphrase_manager::{
+        PassphraseManager, PassphraseService, PassphraseServiceCmd, PassphraseServiceFile,
+    },
+    keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
+};
 use holochain_core::nucleus::ribosome::{run_dna, WasmCallData};
 use holochain_core_types::dna::Dna;
+use holochain_dpki::key_bundle::KeyBundle;
 use holochain_json_api::json::JsonString;
-use holochain_persistence_api::cas::content::AddressableContent;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use ignore::WalkBuilder;
 use json_patch::merge;
+use lib3h_sodium::secbuf::SecBuf;
+use multihash::Hash;
 use serde_json::{self, Map, Value};
 use std::{
+    collections::BTreeMap,
     convert::TryFrom,
     fs::{self, File},
     io::Read,
-    path::PathBuf,
-    sync::Arc,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
 };
+use toml;
 
 use holochain_core_types::hdk_version::{HDKVersion, HDK_VERSION};
 
@@ -26,8 +38,133 @@ pub const IGNORE_FILE_NAME: &str = ".hcignore";
 
 const CARGO_FILE_NAME: &str = "Cargo.toml";
 
+/// Key of the build manifest within a package built with `--reproducible`
+pub const BUILD_MANIFEST_KEY: &str = "build_manifest";
+
+/// Key of the signature of the build manifest within a signed package
+pub const BUILD_SIGNATURE_KEY: &str = "build_signature";
+
+/// Used for `SOURCE_DATE_EPOCH` in reproducible builds unless it is already set
+const DEFAULT_SOURCE_DATE_EPOCH: &str = "0";
+
+/// Target zomes get compiled for, whose rustflags in cargo configs apply to their builds
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
 pub type Object = Map<String, Value>;
 
+/// Describes how a reproducible package was built. Building the same sources with the
+/// same toolchain gives a package with the same DNA hash.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BuildManifest {
+    pub dna_hash: Address,
+    /// Hashes of the WASM code of each zome
+    pub zomes: BTreeMap<String, Address>,
+    pub hc_version: String,
+    pub hdk_version: String,
+    pub rustc_version: Option<String>,
+    pub cargo_version: Option<String>,
+    /// Contents of the project's rust-toolchain file
+    pub rust_toolchain: Option<String>,
+    pub source_date_epoch: String,
+}
+
+impl BuildManifest {
+    fn new(project_dir: &PathBuf, dna: &Dna, source_date_epoch: String) -> BuildManifest {
+        BuildManifest {
+            dna_hash: dna.address(),
+            zomes: dna
+                .zomes
+                .iter()
+                .map(|(name, zome)| {
+                    let code = base64::encode(&*zome.code.code);
+                    (name.clone(), Address::encode_from_str(&code, Hash::SHA2256))
+                })
+                .collect(),
+            hc_version: env!("CARGO_PKG_VERSION").to_string(),
+            hdk_version: HDK_VERSION.to_string(),
+            rustc_version: tool_version("rustc", project_dir),
+            cargo_version: tool_version("cargo", project_dir),
+            rust_toolchain: fs::read_to_string(project_dir.join("rust-toolchain"))
+                .ok()
+                .map(|toolchain| toolchain.trim().to_string()),
+            source_date_epoch,
+        }
+    }
+}
+
+/// Signature of the build manifest of a package by an agent
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct BuildSignature {
+    /// Public key of the signing agent
+    pub agent: String,
+    /// Base64 encoded signature of the manifest's canonical JSON
+    pub signature: String,
+}
+
+fn tool_version(bin: &str, dir: &PathBuf) -> Option<String> {
+    Command::new(bin)
+        .arg("--version")
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Rebuilds all JSON objects with their keys in sorted order
+pub(crate) fn canonical_json(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, canonical_json(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonical_json).collect()),
+        other => other,
+    }
+}
+
+/// The data a build manifest signature is made over
+pub(crate) fn manifest_payload(manifest: &Value) -> DefaultResult<String> {
+    Ok(serde_json::to_string(&canonical_json(manifest.clone()))?)
+}
+
+pub(crate) fn sign_manifest(
+    keybundle: &mut KeyBundle,
+    manifest: &Value,
+) -> DefaultResult<BuildSignature> {
+    let mut message = SecBuf::with_insecure_from_string(manifest_payload(manifest)?);
+    let mut signature = keybundle.sign(&mut message)?;
+    let signature = signature.read_lock();
+    Ok(BuildSignature {
+        agent: keybundle.get_id(),
+        signature: base64::encode(&**signature),
+    })
+}
+
+/// Unlocks the primary agent key of a keystore file, asking for its passphrase
+/// or reading it from the given file
+fn load_signing_key(
+    keystore_path: PathBuf,
+    passphrase_file: Option<PathBuf>,
+) -> DefaultResult<KeyBundle> {
+    let passphrase_service: Arc<Mutex<dyn PassphraseService + Send>> = match passphrase_file {
+        Some(path) => Arc::new(Mutex::new(PassphraseServiceFile { path })),
+        None => Arc::new(Mutex::new(PassphraseServiceCmd {})),
+    };
+    let mut keystore = Keystore::new_from_file(
+        keystore_path,
+        Arc::new(PassphraseManager::new(passphrase_service)),
+        None,
+    )?;
+    Ok(keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID)?)
+}
+
 fn hdk_version_compare(hdk_version: &HDKVersion, cargo_toml: &str) -> DefaultResult<bool> {
     let toml: Value = toml::from_str(cargo_toml)?;
     let dependancies = toml
@@ -45,14 +182,25 @@ fn hdk_version_compare(hdk_version: &HDKVersion, cargo_toml: &str) -> DefaultRes
     Ok(hdk_version == &hdk_version_from_toml)
 }
 
-struct Packager {}
+struct Packager {
+    /// Pins the build environment, normalizes ordering and embeds a build manifest
+    reproducible: bool,
+    /// Key the build manifest gets signed with
+    signing_key: Option<KeyBundle>,
+    /// Environment variables the build steps of all zomes run with
+    build_env: Vec<(String, String)>,
+}
 
 impl Packager {
     fn new() -> Packager {
-        Packager {}
+        Packager {
+            reproducible: false,
+            signing_key: None,
+            build_env: Vec::new(),
+        }
     }
 
-    pub fn package(output: PathBuf, properties: Value) -> DefaultResult<()> {
+    pub fn package(mut self, output: PathBuf, properties: Value) -> DefaultResult<()> {
         // First, check whether they have `cargo` installed, since it will be needed for packaging
         // TODO: in the future, don't check for this here, since other build tools and languages
         // could be used
@@ -69,12 +217,64 @@ impl Packager {
             return Ok(());
         }
 
-        Packager::new().run(&output, properties)
+        self.run(&output, properties)
     }
 
-    fn run(&self, output: &PathBuf, mut properties: Value) -> DefaultResult<()> {
+    fn run(&mut self, output: &PathBuf, properties: Value) -> DefaultResult<()> {
         let current_dir = std::env::current_dir()?;
-        let dir_obj_bundle = Value::from(
+        if self.reproducible {
+            self.build_env = reproducible_build_env(&current_dir)?;
+        }
+        let (mut dir_obj_bundle, dna) = self.build(&current_dir, properties)?;
+
+        if self.reproducible {
+            let manifest =
+                serde_json::to_value(BuildManifest::new(&current_dir, &dna, source_date_epoch()))?;
+            let signature = match self.signing_key {
+                Some(ref mut keybundle) => Some(sign_manifest(keybundle, &manifest)?),
+                None => None,
+            };
+            let bundle = dir_obj_bundle
+                .as_object_mut()
+                .expect("DNA bundle must be an object");
+            ensure!(
+                !bundle.contains_key(BUILD_MANIFEST_KEY)
+                    && !bundle.contains_key(BUILD_SIGNATURE_KEY),
+                "DNA sources must not contain {} or {}",
+                BUILD_MANIFEST_KEY,
+                BUILD_SIGNATURE_KEY
+            );
+            bundle.insert(BUILD_MANIFEST_KEY.to_string(), manifest);
+            if let Some(signature) = signature {
+                println!("Signed by agent {}", signature.agent);
+                bundle.insert(
+                    BUILD_SIGNATURE_KEY.to_string(),
+                    serde_json::to_value(signature)?,
+                );
+            }
+            dir_obj_bundle = canonical_json(dir_obj_bundle);
+        }
+
+        let out_file = File::create(&output)
+            .map_err(|e| format_err!("Couldn't create DNA output file {:?}; {}", output, e))?;
+
+        serde_json::to_writer_pretty(&out_file, &(dir_obj_bundle))?;
+
+        // CLI feedback
+        println!(
+            "{} DNA package file at {:?}",
+            "Created".green().bold(),
+            output
+        );
+        println!("DNA hash: {}", dna.address());
+
+        Ok(())
+    }
+
+    /// Builds the DNA sources in the given directory and returns the bundled JSON
+    /// together with the DNA parsed from it.
+    fn build(&self, current_dir: &PathBuf, mut properties: Value) -> DefaultResult<(Value, Dna)> {
+        let mut dir_obj_bundle = Value::from(
             self.bundle_recurse(&current_dir)
                 .map(|mut val| {
                     if let Some(props_from_dir) = val.get("properties") {
@@ -91,6 +291,9 @@ impl Packager {
                     )
                 })?,
         );
+        if self.reproducible {
+            dir_obj_bundle = canonical_json(dir_obj_bundle);
+        }
 
         let dna_str =
             serde_json::to_string_pretty(&dir_obj_bundle).expect("failed to make pretty DNA");
@@ -104,20 +307,20 @@ impl Packager {
             )
         })?;
 
-        let out_file = File::create(&output)
-            .map_err(|e| format_err!("Couldn't create DNA output file {:?}; {}", output, e))?;
-
-        serde_json::to_writer_pretty(&out_file, &(dir_obj_bundle))?;
-
-        // CLI feedback
-        println!(
-            "{} DNA package file at {:?}",
-            "Created".green().bold(),
-            output
-        );
-        println!("DNA hash: {}", dna.address());
+        Ok((dir_obj_bundle, dna))
+    }
 
-        Ok(())
+    /// Paths of a directory's entries, sorted when packaging reproducibly
+    fn dir_entries(&self, dir: &PathBuf) -> DefaultResult<Vec<PathBuf>> {
+        let mut entries: Vec<PathBuf> = dir
+            .read_dir()?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .collect();
+        if self.reproducible {
+            entries.sort();
+        }
+        Ok(entries)
     }
 
     fn bundle_recurse(&self, path: &PathBuf) -> DefaultResult<Object> {
@@ -127,10 +330,13 @@ impl Packager {
             .build()
             .skip(1);
 
-        let root: Vec<_> = root_dir
+        let mut root: Vec<_> = root_dir
             .filter_map(|e| e.ok())
             .map(|e| e.path().to_path_buf())
             .collect();
+        if self.reproducible {
+            root.sort();
+        }
 
         let root_json_files: Vec<&PathBuf> = root
             .iter()
@@ -175,15 +381,14 @@ impl Packager {
             if node.is_dir() {
                 // a folder within this folder has a .hcbuild in it, meaning this node
                 // should build the json and insert it for this zome
-                if let Some(dir_with_code) = node
-                    .read_dir()?
-                    .filter_map(|e| e.ok())
-                    .map(|e| e.path())
+                if let Some(dir_with_code) = self
+                    .dir_entries(node)?
+                    .into_iter()
                     .filter(|path| path.is_dir())
                     .find(|path| path.join(BUILD_CONFIG_FILE_NAME).exists())
                 {
                     let build = Build::from_file(dir_with_code.join(BUILD_CONFIG_FILE_NAME))?;
-                    let wasm = build.run(&dir_with_code)?;
+                    let wasm = build.run(&dir_with_code, &self.build_env)?;
                     let wasm_binary = Arc::new(base64::decode(&wasm)?);
 
                     let json_string = run_dna(
@@ -206,10 +411,9 @@ impl Packager {
                     // here insert json generated by the wasm, alongside the rest of the sub-tree
                     main_tree.insert(file_name.clone(), sub_tree_content.into());
                 // this is the code folder itself, with a .hcbuild file in it
-                } else if let Some(build_config) = node
-                    .read_dir()?
-                    .filter_map(|e| e.ok())
-                    .map(|e| e.path())
+                } else if let Some(build_config) = self
+                    .dir_entries(node)?
+                    .into_iter()
                     .find(|path| path.ends_with(BUILD_CONFIG_FILE_NAME))
                 {
                     let build = Build::from_file(build_config)?;
@@ -244,7 +448,7 @@ impl Packager {
                         });
                     }
 
-                    let wasm = build.run(&node)?;
+                    let wasm = build.run(&node, &self.build_env)?;
 
                     // here insert the wasm itself
                     main_tree.insert(file_name.clone(), json!({ "code": wasm }));
@@ -259,8 +463,141 @@ impl Packager {
     }
 }
 
+fn source_date_epoch() -> String {
+    std::env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| DEFAULT_SOURCE_DATE_EPOCH.to_string())
+}
+
+fn cargo_home() -> Option<PathBuf> {
+    std::env::var("CARGO_HOME")
+        .ok()
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var("HOME")
+                .ok()
+                .map(|home| PathBuf::from(home).join(".cargo"))
+        })
+}
+
+/// Environment the build steps of a reproducible package run in, so that the
+/// WASM doesn't depend on where and when it was compiled
+fn reproducible_build_env(project_dir: &PathBuf) -> DefaultResult<Vec<(String, String)>> {
+    let cargo_home = cargo_home();
+    // Setting RUSTFLAGS makes cargo ignore the rustflags of its configs, so these get kept
+    let mut rustflags = match std::env::var("RUSTFLAGS") {
+        Ok(rustflags) => rustflags.split_whitespace().map(String::from).collect(),
+        Err(_) => configured_rustflags(project_dir, cargo_home.as_ref())?,
+    };
+
+    let mut remapped_paths = vec![(project_dir.clone(), "/build")];
+    if let Some(cargo_home) = cargo_home {
+        remapped_paths.push((cargo_home, "/cargo"));
+    }
+    for (from, to) in remapped_paths {
+        let from = from.display().to_string();
+        // RUSTFLAGS gets split on whitespace, so it can't hold such a path
+        ensure!(
+            !from.contains(char::is_whitespace),
+            "Can't package reproducibly from {} since its path contains whitespace",
+            from
+        );
+        rustflags.push(format!("--remap-path-prefix={}={}", from, to));
+    }
+
+    Ok(vec![
+        ("SOURCE_DATE_EPOCH".to_string(), source_date_epoch()),
+        ("CARGO_INCREMENTAL".to_string(), "0".to_string()),
+        ("RUSTFLAGS".to_string(), rustflags.join(" ")),
+        ("TZ".to_string(), "UTC".to_string()),
+        ("LC_ALL".to_string(), "C".to_string()),
+    ])
+}
+
+/// Rustflags the cargo configs of the project directory, its ancestors and cargo home
+/// set for the WASM target, which take precedence over the ones set for every build
+fn configured_rustflags(
+    project_dir: &Path,
+    cargo_home: Option<&PathBuf>,
+) -> DefaultResult<Vec<String>> {
+    let mut config_files: Vec<PathBuf> = project_dir
+        .ancestors()
+        .map(|dir| dir.join(".cargo").join("config"))
+        .collect();
+    if let Some(cargo_home) = cargo_home {
+        let home_config = cargo_home.join("config");
+        if !config_files.contains(&home_config) {
+            config_files.push(home_config);
+        }
+    }
+
+    let mut target_rustflags = Vec::new();
+    let mut build_rustflags = Vec::new();
+    for config_file in config_files.iter().filter(|path| path.is_file()) {
+        let config: toml::Value = toml::from_str(&fs::read_to_string(config_file)?)
+            .map_err(|e| format_err!("Couldn't parse cargo config {:?}: {}", config_file, e))?;
+        let target_flags = config
+            .get("target")
+            .and_then(|target| target.get(WASM_TARGET))
+            .and_then(|target| target.get("rustflags"));
+        if let Some(flags) = target_flags {
+            target_rustflags.extend(rustflags_from_config(flags, config_file)?);
+        }
+        if let Some(flags) = config.get("build").and_then(|build| build.get("rustflags")) {
+            build_rustflags.extend(rustflags_from_config(flags, config_file)?);
+        }
+    }
+    if target_rustflags.is_empty() {
+        Ok(build_rustflags)
+    } else {
+        Ok(target_rustflags)
+    }
+}
+
+/// Rustflags can be configured as a string of flags separated by whitespace or an array
+fn rustflags_from_config(flags: &toml::Value, config_file: &PathBuf) -> DefaultResult<Vec<String>> {
+    match flags {
+        toml::Value::String(flags) => Ok(flags.split_whitespace().map(String::from).collect()),
+        toml::Value::Array(flags) => flags
+            .iter()
+            .map(|flag| {
+                flag.as_str().map(String::from).ok_or_else(|| {
+                    format_err!("Non-string rustflag in cargo config {:?}", config_file)
+                })
+            })
+            .collect(),
+        _ => bail!(
+            "rustflags in cargo config {:?} must be a string or an array",
+            config_file
+        ),
+    }
+}
+
 pub fn package(output: PathBuf, properties: serde_json::Value) -> DefaultResult<()> {
-    Packager::package(output, properties)
+    Packager::new().package(output, properties)
+}
+
+/// Packages the DNA reproducibly with a build manifest, signed with the agent key of
+/// the given keystore file if there is one
+pub fn package_reproducible(
+    output: PathBuf,
+    properties: serde_json::Value,
+    keystore_path: Option<PathBuf>,
+    passphrase_file: Option<PathBuf>,
+) -> DefaultResult<()> {
+    let signing_key = match keystore_path {
+        Some(path) => Some(load_signing_key(path, passphrase_file)?),
+        None => None,
+    };
+    Packager {
+        reproducible: true,
+        signing_key,
+        build_env: Vec::new(),
+    }
+    .package(output, properties)
+}
+
+/// Builds the DNA sources in the given directory without writing a package file
+pub fn build_dna(path: &PathBuf) -> DefaultResult<Dna> {
+    Packager::new().build(path, json!({})).map(|(_, dna)| dna)
 }
 
 #[cfg(test)]
@@ -300,6 +637,49 @@ mod tests {
         shared_space.close().unwrap();
     }
 
+    #[test]
+    fn configured_rustflags_test() {
+        let project = tempfile::tempdir().unwrap();
+        let zome_dir = project.path().join("zomes").join("my_zome");
+        fs::create_dir_all(zome_dir.join(".cargo")).unwrap();
+        fs::create_dir_all(project.path().join(".cargo")).unwrap();
+        let cargo_home = tempfile::tempdir().unwrap();
+        let cargo_home = cargo_home.path().to_path_buf();
+
+        assert_eq!(
+            configured_rustflags(&zome_dir, Some(&cargo_home)).unwrap(),
+            Vec::<String>::new()
+        );
+
+        fs::write(
+            project.path().join(".cargo").join("config"),
+            "[build]\nrustflags = \"-C opt-level=z\"\n",
+        )
+        .unwrap();
+        fs::write(
+            cargo_home.join("config"),
+            "[build]\nrustflags = [\"-C\", \"debuginfo=0\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            configured_rustflags(&zome_dir, Some(&cargo_home)).unwrap(),
+            vec!["-C", "opt-level=z", "-C", "debuginfo=0"]
+        );
+
+        fs::write(
+            zome_dir.join(".cargo").join("config"),
+            "[target.wasm32-unknown-unknown]\nrustflags = [\"-C\", \"link-arg=-s\"]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            configured_rustflags(&zome_dir, Some(&cargo_home)).unwrap(),
+            vec!["-C", "link-arg=-s"]
+        );
+
+        fs::write(cargo_home.join("config"), "[build]\nrustflags = 3\n").unwrap();
+        assert!(configured_rustflags(&zome_dir, Some(&cargo_home)).is_err());
+    }
+
     #[test]
     fn hdk_version_compare_test() {
         //compare same
@@ -330,6 +710,16 @@ mod tests {
         .expect("Could not compare"))
     }
 
+    #[test]
+    fn canonical_json_sorts_keys() {
+        let value: Value =
+            serde_json::from_str(r#"{"b": {"d": 1, "c": [{"f": 2, "e": 3}]}, "a": null}"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&canonical_json(value)).unwrap(),
+            r#"{"a":null,"b":{"c":[{"e":3,"f":2}],"d":1}}"#
+        );
+    }
+
     #[test]
     #[cfg(feature = "broken-tests")]
     fn aborts_if_multiple_json_in_root() {
@@ -0,0 +1,67 @@
+use crate::error::DefaultResult;
+use holochain_core::{
+    recording::{RecordedEvent, Recording},
+    state::StateWrapper,
+    state_dump::StateDump,
+};
+use serde_json::Value;
+use std::path::PathBuf;
+
+/// Actions are tagged with `action_type`, network messages with the variant as only key
+fn variant_name(value: &Value) -> String {
+    value["action_type"]
+        .as_str()
+        .map(|name| name.to_string())
+        .or_else(|| value.as_object().and_then(|o| o.keys().next().cloned()))
+        .or_else(|| value.as_str().map(|name| name.to_string()))
+        .unwrap_or_default()
+}
+
+/// Rebuilds the state of an instance from an action recording and prints it as a state dump,
+/// or, with `list`, prints what got recorded.
+pub fn replay(recording_path: PathBuf, until: Option<usize>, list: bool) -> DefaultResult<()> {
+    let recording = Recording::load(&recording_path).map_err(|err| {
+        format_err!(
+            "Could not load recording '{}': {}",
+            recording_path.to_string_lossy(),
+            err
+        )
+    })?;
+
+    if list {
+        println!(
+            "Recording of instance '{}' started at {:?}:\n",
+            recording.header.instance_name, recording.header.started
+        );
+        let mut action_count = 0;
+        for line in recording.lines.iter() {
+            match &line.event {
+                RecordedEvent::Action { action_wrapper, .. } => {
+                    action_count += 1;
+                    let action = serde_json::to_value(action_wrapper.action())?;
+                    println!(
+                        "{:>6} {:?} {}",
+                        action_count,
+                        line.time,
+                        variant_name(&action)
+                    );
+                }
+                RecordedEvent::NetworkInput(message) => {
+                    let message = serde_json::to_value(message)?;
+                    println!(
+                        "{:>6} {:?} network: {}",
+                        "",
+                        line.time,
+                        variant_name(&message)
+                    );
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let state = recording.replay(recording.replay_context(), until);
+    let dump = StateDump::from(&StateWrapper::from(state));
+    println!("{}", serde_json::to_string_pretty(&dump)?);
+    Ok(())
+}
@@ -1,10 +1,10 @@
 use crate::NetworkingType;
-use cli;
+use cli::{self, package::IGNORE_FILE_NAME};
 use colored::*;
 use error::DefaultResult;
 use holochain_common::env_vars::EnvVar;
 use holochain_conductor_lib::{
-    conductor::{mount_conductor_from_config, Conductor, CONDUCTOR},
+    conductor::{mount_conductor_from_config, Conductor, ConductorAdmin, CONDUCTOR},
     config::*,
     happ_bundle::HappBundle,
     key_loaders::{test_keystore, test_keystore_loader},
@@ -14,44 +14,83 @@ use holochain_conductor_lib::{
 use holochain_core_types::agent::AgentId;
 use holochain_net::sim2h_worker::Sim2hConfig;
 use holochain_persistence_api::cas::content::AddressableContent;
-use std::{fs, path::PathBuf};
+use ignore::WalkBuilder;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    thread,
+    time::{Duration, SystemTime},
+};
 
 pub enum Networking {
     N3h,
     Sim2h(String),
 }
 
+/// How often `hc run --watch` checks the project's files for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Starts a minimal configuration Conductor with the current application running
 pub fn run(
     dna_path: PathBuf,
     package: bool,
+    watch: bool,
     port: u16,
     interface_type: String,
     conductor_config: Configuration,
 ) -> DefaultResult<()> {
-    if package {
-        cli::package(dna_path, json!({}))?;
+    if package || watch {
+        cli::package(dna_path.clone(), json!({}))?;
     }
 
-    mount_conductor_from_config(conductor_config);
-    let mut conductor_guard = CONDUCTOR.lock().unwrap();
-    let conductor = conductor_guard.as_mut().expect("Conductor must be mounted");
-    conductor.key_loader = test_keystore_loader();
-
-    conductor
-        .boot_from_config()
-        .map_err(|err| format_err!("{}", err))?;
+    let agent_interfaces: Vec<(String, u16)> = conductor_config
+        .interfaces
+        .iter()
+        .filter_map(|interface| {
+            let port = match interface.driver {
+                InterfaceDriver::Websocket { port } | InterfaceDriver::Http { port } => port,
+                _ => return None,
+            };
+            let instance = interface.instances.first()?.id.clone();
+            Some((instance, port))
+        })
+        .collect();
 
-    conductor.start_all_interfaces();
-    conductor.start_all_instances()?;
-    conductor
-        .start_all_static_servers()
-        .map_err(|e| failure::err_msg(e))?;
+    mount_conductor_from_config(conductor_config);
+    {
+        let mut conductor_guard = CONDUCTOR.lock().unwrap();
+        let conductor = conductor_guard.as_mut().expect("Conductor must be mounted");
+        conductor.key_loader = test_keystore_loader();
+
+        conductor
+            .boot_from_config()
+            .map_err(|err| format_err!("{}", err))?;
+
+        conductor.start_all_interfaces();
+        conductor.start_all_instances()?;
+        conductor
+            .start_all_static_servers()
+            .map_err(|e| failure::err_msg(e))?;
+    }
 
     println!(
         "Holochain development conductor started. Running {} server on port {}",
         interface_type, port
     );
+    if agent_interfaces.len() > 1 {
+        for (instance_id, port) in agent_interfaces {
+            println!("  instance \"{}\" on port {}", instance_id, port);
+        }
+    }
+    if watch {
+        let project_path = std::env::current_dir()?;
+        println!(
+            "Watching {} for changes to rebuild and reload the DNA",
+            project_path.to_string_lossy()
+        );
+        watch_project(project_path, dna_path);
+    }
     println!("Type 'exit' to stop the conductor and exit the program");
 
     let mut rl = rustyline::Editor::<()>::new();
@@ -72,6 +111,78 @@ pub fn run(
     Ok(())
 }
 
+/// Modification times of all files of the project that go into the DNA package
+fn project_files(project_path: &PathBuf, dna_path: &PathBuf) -> BTreeMap<PathBuf, SystemTime> {
+    let dist_path = dna_path.parent().map(PathBuf::from);
+    WalkBuilder::new(project_path)
+        .add_custom_ignore_filename(IGNORE_FILE_NAME)
+        .filter_entry(move |entry| {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy();
+            !(file_name == "target"
+                || file_name == "node_modules"
+                || file_name == LOCAL_STORAGE_PATH
+                || dist_path
+                    .as_ref()
+                    .map_or(false, |dist| path == dist.as_path()))
+        })
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file() && entry.path() != dna_path.as_path())
+        .filter_map(|entry| {
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((entry.path().to_path_buf(), modified))
+        })
+        .collect()
+}
+
+/// Polls the project's files and rebuilds and reloads the DNA when they change
+fn watch_project(project_path: PathBuf, dna_path: PathBuf) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut files = project_files(&project_path, &dna_path);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current_files = project_files(&project_path, &dna_path);
+            if current_files == files {
+                continue;
+            }
+            println!("{} Rebuilding DNA...", "Changes detected.".yellow().bold());
+            if let Err(error) = reload_dna(&dna_path) {
+                eprintln!("{} {}", "Could not reload DNA:".red().bold(), error);
+            }
+            // Don't rebuild again because of files the build itself touched
+            files = project_files(&project_path, &dna_path);
+        }
+    })
+}
+
+/// Packages the DNA and hot-swaps it in the running conductor
+fn reload_dna(dna_path: &PathBuf) -> DefaultResult<()> {
+    cli::package(dna_path.clone(), json!({}))?;
+
+    let mut conductor_guard = CONDUCTOR.lock().unwrap();
+    let conductor = conductor_guard.as_mut().expect("Conductor must be mounted");
+    let dna_ids: Vec<String> = conductor
+        .config()
+        .dnas
+        .iter()
+        .filter(|dna| PathBuf::from(&dna.file) == *dna_path)
+        .map(|dna| dna.id.clone())
+        .collect();
+    for id in dna_ids {
+        if conductor.hot_swap_dna(&id, dna_path.clone())? {
+            println!(
+                "{} DNA \"{}\", instance storage was reset",
+                "Reloaded".green().bold(),
+                id
+            );
+        } else {
+            println!("DNA \"{}\" did not change", id);
+        }
+    }
+    Ok(())
+}
+
 pub fn get_interface_type_string(given_type: String) -> String {
     // note that this behaviour is documented within
     // holochain_common::env_vars module and should be updated
@@ -88,12 +199,48 @@ pub fn hc_run_configuration(
     interface_type: &String,
     logging: bool,
     agent_name: String,
+    agent_count: u16,
 ) -> DefaultResult<Configuration> {
+    if agent_count == 0 {
+        return Err(format_err!("hc run needs at least one agent"));
+    }
+    if u32::from(port) + u32::from(agent_count) - 1 > u32::from(u16::max_value()) {
+        return Err(format_err!(
+            "not enough ports above {} for {} agents",
+            port,
+            agent_count
+        ));
+    }
+
+    let mut agents = Vec::new();
+    let mut instances = Vec::new();
+    let mut interfaces = Vec::new();
+    for index in 1..=agent_count {
+        let agent = agent_configuration_at(agent_name.clone(), index, agent_count);
+        let storage = if agent_count == 1 {
+            storage_configuration(persist)?
+        } else {
+            instance_storage_configuration(persist, &numbered_id(INSTANCE_CONFIG_ID, index))?
+        };
+        let instance = instance_configuration(storage, &agent.id, index, agent_count);
+        interfaces.push(interface_configuration(
+            &interface_type,
+            port + index - 1,
+            &instance.id,
+            index,
+            agent_count,
+        )?);
+        instances.push(instance);
+        agents.push(agent);
+    }
+
+    // Without a network config all instances of the conductor share one in-memory
+    // network, with sim2h they all connect to the same server
     Ok(Configuration {
-        agents: vec![agent_configuration(agent_name)],
+        agents,
         dnas: vec![dna_configuration(&dna_path)],
-        instances: vec![instance_configuration(storage_configuration(persist)?)],
-        interfaces: vec![interface_configuration(&interface_type, port)?],
+        instances,
+        interfaces,
         network: networking_configuration(networked),
         logger: logger_configuration(logging),
         ..Default::default()
@@ -123,11 +270,28 @@ pub fn hc_run_bundle_configuration(
 pub(crate) const AGENT_NAME_DEFAULT: &str = "testAgent";
 const AGENT_CONFIG_ID: &str = "hc-run-agent";
 
+/// Config ids and agent names get an index suffix when `hc run` starts several agents
+fn numbered_id(id: &str, index: u16) -> String {
+    format!("{}-{}", id, index)
+}
+
 fn agent_configuration(agent_name: String) -> AgentConfiguration {
+    agent_configuration_at(agent_name, 1, 1)
+}
+
+fn agent_configuration_at(agent_name: String, index: u16, agent_count: u16) -> AgentConfiguration {
     // note that this behaviour is documented within
     // holochain_common::env_vars module and should be updated
     // if this logic changes
     let agent_name = EnvVar::Agent.value().ok().unwrap_or_else(|| agent_name);
+    let (id, agent_name) = if agent_count == 1 {
+        (AGENT_CONFIG_ID.to_string(), agent_name)
+    } else {
+        (
+            numbered_id(AGENT_CONFIG_ID, index),
+            numbered_id(&agent_name, index),
+        )
+    };
     let mut keystore = test_keystore(&agent_name);
     let pub_key = keystore
         .get_keybundle(PRIMARY_KEYBUNDLE_ID)
@@ -135,12 +299,13 @@ fn agent_configuration(agent_name: String) -> AgentConfiguration {
         .get_id();
     let agent_id = AgentId::new(&agent_name, pub_key);
     AgentConfiguration {
-        id: AGENT_CONFIG_ID.into(),
+        id,
         name: agent_id.nick,
         public_address: agent_id.pub_sign_key,
         keystore_file: agent_name,
         holo_remote_key: None,
         test_agent: Some(true),
+        keystore_service: None,
     }
 }
 
@@ -180,15 +345,42 @@ fn storage_configuration(persist: bool) -> DefaultResult<StorageConfiguration> {
     }
 }
 
+/// Storage of one of several agents, persisted in its own directory below `.hc`
+fn instance_storage_configuration(
+    persist: bool,
+    instance_id: &str,
+) -> DefaultResult<StorageConfiguration> {
+    if persist {
+        let path = PathBuf::from(LOCAL_STORAGE_PATH).join(instance_id);
+        fs::create_dir_all(&path)?;
+
+        Ok(StorageConfiguration::Pickle {
+            path: path.to_string_lossy().to_string(),
+        })
+    } else {
+        Ok(StorageConfiguration::Memory)
+    }
+}
+
 // INSTANCE
 const INSTANCE_CONFIG_ID: &str = "test-instance";
 
-fn instance_configuration(storage: StorageConfiguration) -> InstanceConfiguration {
+fn instance_configuration(
+    storage: StorageConfiguration,
+    agent_id: &str,
+    index: u16,
+    agent_count: u16,
+) -> InstanceConfiguration {
     InstanceConfiguration {
-        id: INSTANCE_CONFIG_ID.into(),
+        id: if agent_count == 1 {
+            INSTANCE_CONFIG_ID.into()
+        } else {
+            numbered_id(INSTANCE_CONFIG_ID, index)
+        },
         dna: DNA_CONFIG_ID.into(),
-        agent: AGENT_CONFIG_ID.into(),
+        agent: agent_id.into(),
         storage,
+        record_actions: None,
     }
 }
 
@@ -198,6 +390,9 @@ const INTERFACE_CONFIG_ID: &str = "websocket-interface";
 fn interface_configuration(
     interface_type: &String,
     port: u16,
+    instance_id: &str,
+    index: u16,
+    agent_count: u16,
 ) -> DefaultResult<InterfaceConfiguration> {
     let driver = if interface_type == &String::from("websocket") {
         InterfaceDriver::Websocket { port }
@@ -208,11 +403,15 @@ fn interface_configuration(
     };
 
     Ok(InterfaceConfiguration {
-        id: INTERFACE_CONFIG_ID.into(),
+        id: if agent_count == 1 {
+            INTERFACE_CONFIG_ID.into()
+        } else {
+            numbered_id(INTERFACE_CONFIG_ID, index)
+        },
         driver,
         admin: true,
         instances: vec![InstanceReferenceConfiguration {
-            id: INSTANCE_CONFIG_ID.into(),
+            id: instance_id.into(),
             alias: None,
         }],
         choose_free_port: None,
@@ -230,6 +429,7 @@ fn logger_configuration(logging: bool) -> LoggerConfiguration {
             LogRules::new()
         },
         state_dump: true,
+        ..Default::default()
     }
 }
 
@@ -294,7 +494,10 @@ mod tests {
     use holochain_core_types::dna::Dna;
     use holochain_net::sim2h_worker::Sim2hConfig;
     use holochain_persistence_api::cas::content::AddressableContent;
-    use std::fs::{create_dir, File};
+    use std::{
+        fs::{create_dir, File},
+        sync::Arc,
+    };
 
     #[test]
     // flagged as broken for:
@@ -340,6 +543,7 @@ mod tests {
                 keystore_file: "testAgent".to_string(),
                 holo_remote_key: None,
                 test_agent: Some(true),
+                keystore_service: None,
             },
         );
     }
@@ -384,7 +588,7 @@ mod tests {
     #[test]
     fn test_instance_configuration() {
         let storage = super::storage_configuration(false).unwrap();
-        let instance = super::instance_configuration(storage);
+        let instance = super::instance_configuration(storage, "hc-run-agent", 1, 1);
         assert_eq!(
             instance,
             InstanceConfiguration {
@@ -392,13 +596,16 @@ mod tests {
                 dna: "hc-run-dna".to_string(),
                 agent: "hc-run-agent".to_string(),
                 storage: StorageConfiguration::Memory,
+                record_actions: None,
             }
         )
     }
 
     #[test]
     fn test_interface_configuration() {
-        let http_interface = super::interface_configuration(&"http".to_string(), 4444).unwrap();
+        let http_interface =
+            super::interface_configuration(&"http".to_string(), 4444, "test-instance", 1, 1)
+                .unwrap();
         assert_eq!(
             http_interface,
             InterfaceConfiguration {
@@ -414,7 +621,8 @@ mod tests {
         );
 
         let websocket_interface =
-            super::interface_configuration(&"websocket".to_string(), 5555).unwrap();
+            super::interface_configuration(&"websocket".to_string(), 5555, "test-instance", 1, 1)
+                .unwrap();
         assert_eq!(
             websocket_interface,
             InterfaceConfiguration {
@@ -429,10 +637,77 @@ mod tests {
             }
         );
 
-        let invalid_type = super::interface_configuration(&"funny".to_string(), 4444);
+        let invalid_type =
+            super::interface_configuration(&"funny".to_string(), 4444, "test-instance", 1, 1);
         assert!(invalid_type.is_err());
     }
 
+    #[test]
+    fn test_hc_run_configuration_with_several_agents() {
+        let dna = Dna::new();
+        let dir = tempdir().expect("Could not get tempdir");
+        let dna_path = dir.path().join("test_dna.json");
+        let out_file = File::create(&dna_path).expect("Could not create temp file for test DNA");
+        serde_json::to_writer_pretty(&out_file, &dna).expect("Could not write test DNA to file");
+
+        let config = super::hc_run_configuration(
+            &dna_path,
+            8888,
+            false,
+            None,
+            &"websocket".to_string(),
+            false,
+            super::AGENT_NAME_DEFAULT.to_string(),
+            3,
+        )
+        .unwrap();
+
+        assert_eq!(config.dnas.len(), 1);
+        assert_eq!(
+            config
+                .agents
+                .iter()
+                .map(|agent| agent.name.clone())
+                .collect::<Vec<_>>(),
+            vec!["testAgent-1", "testAgent-2", "testAgent-3"]
+        );
+        assert_ne!(
+            config.agents[0].public_address,
+            config.agents[1].public_address
+        );
+        assert_eq!(config.instances[1].id, "test-instance-2");
+        assert_eq!(config.instances[1].agent, "hc-run-agent-2");
+        assert_eq!(
+            config.interfaces[2],
+            InterfaceConfiguration {
+                id: "websocket-interface-3".to_string(),
+                driver: InterfaceDriver::Websocket { port: 8890 },
+                admin: true,
+                instances: vec![InstanceReferenceConfiguration {
+                    id: "test-instance-3".to_string(),
+                    alias: None,
+                }],
+                choose_free_port: None,
+            }
+        );
+        assert!(config.network.is_none());
+        assert!(config
+            .check_consistency(&mut Arc::new(Box::new(super::Conductor::load_dna)))
+            .is_ok());
+
+        let no_agents = super::hc_run_configuration(
+            &dna_path,
+            8888,
+            false,
+            None,
+            &"websocket".to_string(),
+            false,
+            super::AGENT_NAME_DEFAULT.to_string(),
+            0,
+        );
+        assert!(no_agents.is_err());
+    }
+
     #[test]
     fn test_networking_configuration() {
         let networking = super::networking_configuration(Some(Networking::N3h));
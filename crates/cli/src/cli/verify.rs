@@ -0,0 +1,204 @@
+use crate::{
+    cli::{
+        hash_dna::hash_dna,
+        package::{
+            manifest_payload, BuildManifest, BuildSignature, BUILD_MANIFEST_KEY,
+            BUILD_SIGNATURE_KEY,
+        },
+    },
+    error::DefaultResult,
+};
+use colored::*;
+use holochain_core_types::signature::Signature;
+use holochain_dpki::utils;
+use holochain_persistence_api::cas::content::Address;
+use serde_json::Value;
+use std::{fs, path::PathBuf};
+
+/// Checks the build manifest of a package against the DNA hash of the package,
+/// and its signature if there is one
+fn verify_package(
+    contents: &str,
+    dna_hash: &Address,
+) -> DefaultResult<(BuildManifest, Option<BuildSignature>)> {
+    let package: Value = serde_json::from_str(contents)?;
+    let manifest_json = package.get(BUILD_MANIFEST_KEY).ok_or_else(|| {
+        format_err!("Package has no build manifest, build it with `hc package --reproducible`")
+    })?;
+    let manifest: BuildManifest = serde_json::from_value(manifest_json.clone())?;
+    ensure!(
+        &manifest.dna_hash == dna_hash,
+        "DNA hash {} of the package does not match hash {} of its build manifest",
+        dna_hash,
+        manifest.dna_hash
+    );
+
+    let signature: Option<BuildSignature> = match package.get(BUILD_SIGNATURE_KEY) {
+        Some(signature) => Some(serde_json::from_value(signature.clone())?),
+        None => None,
+    };
+    if let Some(ref signature) = signature {
+        let valid = utils::verify(
+            Address::from(signature.agent.clone()),
+            manifest_payload(manifest_json)?,
+            Signature::from(signature.signature.clone()),
+        )?;
+        ensure!(
+            valid,
+            "Signature of agent {} over the build manifest is invalid",
+            signature.agent
+        );
+    }
+    Ok((manifest, signature))
+}
+
+/// Checks a verified package against what the caller expects of it. A signature only
+/// proves anything if it is made by a known agent, so either the agent or the DNA hash
+/// has to be expected.
+fn check_expectations(
+    dna_hash: &Address,
+    signature: &Option<BuildSignature>,
+    agent: &Option<String>,
+    expected_hash: &Option<Address>,
+) -> DefaultResult<()> {
+    ensure!(
+        agent.is_some() || expected_hash.is_some(),
+        "Nothing to verify the package against, pass --agent and/or --dna-hash"
+    );
+    if let Some(ref expected_hash) = expected_hash {
+        ensure!(
+            dna_hash == expected_hash,
+            "DNA hash {} of the package is not the expected {}",
+            dna_hash,
+            expected_hash
+        );
+    }
+    match (agent, signature) {
+        (Some(agent), Some(signature)) => ensure!(
+            signature.agent == *agent,
+            "Package is signed by agent {}, not {}",
+            signature.agent,
+            agent
+        ),
+        (Some(agent), None) => bail!("Package is not signed, expected a signature of {}", agent),
+        _ => (),
+    }
+    Ok(())
+}
+
+pub fn verify(
+    package_path: &PathBuf,
+    agent: Option<String>,
+    expected_hash: Option<Address>,
+) -> DefaultResult<()> {
+    let contents = fs::read_to_string(package_path)?;
+    let dna_hash = hash_dna(package_path, None)?;
+    let (manifest, signature) = verify_package(&contents, &dna_hash)?;
+    check_expectations(&dna_hash, &signature, &agent, &expected_hash)?;
+
+    println!("DNA hash: {}", dna_hash);
+    println!(
+        "Built with hc {}, HDK {}, {}",
+        manifest.hc_version,
+        manifest.hdk_version,
+        manifest
+            .rustc_version
+            .unwrap_or_else(|| "unknown rustc".to_string())
+    );
+    match signature {
+        Some(signature) => println!(
+            "{} package signed by agent {}",
+            "Verified".green().bold(),
+            signature.agent
+        ),
+        None => println!(
+            "{} unsigned package against the expected DNA hash",
+            "Verified".yellow().bold()
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::cli::package::{canonical_json, sign_manifest};
+    use holochain_conductor_lib::{key_loaders::test_keystore, keystore::PRIMARY_KEYBUNDLE_ID};
+    use holochain_core_types::dna::Dna;
+    use holochain_persistence_api::cas::content::AddressableContent;
+    use std::collections::BTreeMap;
+
+    fn test_package(manifest: &BuildManifest, signed: bool) -> Value {
+        let mut package = serde_json::to_value(Dna::new()).unwrap();
+        let manifest = serde_json::to_value(manifest).unwrap();
+        if signed {
+            let mut keybundle = test_keystore(&"alice".to_string())
+                .get_keybundle(PRIMARY_KEYBUNDLE_ID)
+                .unwrap();
+            let signature = sign_manifest(&mut keybundle, &manifest).unwrap();
+            package[BUILD_SIGNATURE_KEY] = serde_json::to_value(signature).unwrap();
+        }
+        package[BUILD_MANIFEST_KEY] = manifest;
+        canonical_json(package)
+    }
+
+    fn test_manifest() -> BuildManifest {
+        BuildManifest {
+            dna_hash: Dna::new().address(),
+            zomes: BTreeMap::new(),
+            hc_version: "0.0.0".to_string(),
+            hdk_version: "0.0.0".to_string(),
+            rustc_version: None,
+            cargo_version: None,
+            rust_toolchain: None,
+            source_date_epoch: "0".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_verify_package() {
+        let dna_hash = Dna::new().address();
+        let manifest = test_manifest();
+
+        let unsigned = test_package(&manifest, false).to_string();
+        let (verified_manifest, signature) = verify_package(&unsigned, &dna_hash).unwrap();
+        assert_eq!(verified_manifest, manifest);
+        assert!(signature.is_none());
+
+        let signed = test_package(&manifest, true).to_string();
+        let (_, signature) = verify_package(&signed, &dna_hash).unwrap();
+        assert!(signature.is_some());
+
+        assert!(verify_package(&signed, &Address::from("QmOtherDna")).is_err());
+
+        let mut tampered: Value = serde_json::from_str(&signed).unwrap();
+        tampered[BUILD_MANIFEST_KEY]["hdk_version"] = json!("0.0.1");
+        assert!(verify_package(&tampered.to_string(), &dna_hash).is_err());
+
+        let no_manifest = serde_json::to_value(Dna::new()).unwrap().to_string();
+        assert!(verify_package(&no_manifest, &dna_hash).is_err());
+    }
+
+    #[test]
+    fn test_check_expectations() {
+        let dna_hash = Dna::new().address();
+        let signature = Some(BuildSignature {
+            agent: "HcScAlice".to_string(),
+            signature: "sig".to_string(),
+        });
+        let alice = Some("HcScAlice".to_string());
+        let bob = Some("HcScBob".to_string());
+
+        assert!(check_expectations(&dna_hash, &signature, &None, &None).is_err());
+        assert!(check_expectations(&dna_hash, &None, &None, &None).is_err());
+
+        assert!(check_expectations(&dna_hash, &signature, &alice, &None).is_ok());
+        assert!(check_expectations(&dna_hash, &signature, &bob, &None).is_err());
+        assert!(check_expectations(&dna_hash, &None, &alice, &None).is_err());
+
+        assert!(check_expectations(&dna_hash, &None, &None, &Some(dna_hash.clone())).is_ok());
+        assert!(
+            check_expectations(&dna_hash, &signature, &None, &Some("QmOtherDna".into())).is_err()
+        );
+    }
+}
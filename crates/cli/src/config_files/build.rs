@@ -29,14 +29,16 @@ impl Build {
         Ok(build)
     }
 
-    /// Starts the build using the supplied build steps and returns the contents of the artifact
-    pub fn run(&self, base_path: &PathBuf) -> DefaultResult<String> {
+    /// Starts the build using the supplied build steps, with the given additional
+    /// environment variables, and returns the contents of the artifact
+    pub fn run(&self, base_path: &PathBuf, env: &[(String, String)]) -> DefaultResult<String> {
         for build_step in &self.steps {
             let slice_vec: Vec<_> = build_step.arguments.iter().map(|e| e.as_str()).collect();
-            util::run_cmd(
+            util::run_cmd_with_env(
                 base_path.to_path_buf(),
                 build_step.command.clone(),
                 &slice_vec[..],
+                env,
             )?;
         }
 
@@ -3,6 +3,7 @@ extern crate holochain_common;
 extern crate holochain_conductor_lib;
 extern crate holochain_core;
 extern crate holochain_core_types;
+extern crate holochain_dpki;
 extern crate holochain_json_api;
 extern crate holochain_locksmith;
 extern crate holochain_net;
@@ -10,6 +11,7 @@ extern crate holochain_persistence_api;
 extern crate holochain_persistence_file;
 extern crate json_patch;
 extern crate lib3h_sodium;
+extern crate multihash;
 extern crate structopt;
 #[macro_use]
 extern crate failure;
@@ -27,6 +29,9 @@ extern crate rpassword;
 extern crate tar;
 extern crate tempfile;
 extern crate tera;
+#[cfg(test)]
+extern crate wabt;
+extern crate wasmi;
 
 mod cli;
 mod config_files;
@@ -35,6 +40,8 @@ mod util;
 
 use crate::error::{HolochainError, HolochainResult};
 use holochain_conductor_lib::happ_bundle::HappBundle;
+use holochain_core_types::time::Iso8601;
+use holochain_persistence_api::cas::content::Address;
 use std::{fs::File, io::Read, path::PathBuf, str::FromStr};
 use structopt::{clap::arg_enum, StructOpt};
 
@@ -48,6 +55,15 @@ enum Cli {
         output: Option<PathBuf>,
         #[structopt(long, short)]
         properties: Option<String>,
+        #[structopt(long)]
+        /// Pin the build environment, normalize ordering and embed a build manifest, so that the same sources and toolchain always give the same package
+        reproducible: bool,
+        #[structopt(long, parse(from_os_str), requires = "reproducible")]
+        /// Sign the build manifest with the agent key of this keystore file
+        sign: Option<PathBuf>,
+        #[structopt(long = "passphrase-file", parse(from_os_str), requires = "sign")]
+        /// Read the keystore passphrase from this file instead of asking for it
+        passphrase_file: Option<PathBuf>,
     },
     #[structopt(alias = "i")]
     /// Initializes a new Holochain app at the given directory
@@ -74,6 +90,9 @@ enum Cli {
         #[structopt(long, short = "b")]
         /// Automatically package project before running
         package: bool,
+        #[structopt(long, short = "w")]
+        /// Watch the project, and rebuild and reload the DNA in the running conductor when files change
+        watch: bool,
         #[structopt(long = "dna", short = "d", parse(from_os_str))]
         /// Absolute path to the .dna.json file to run. [default: ./dist/<dna-name>.dna.json]
         dna_path: Option<PathBuf>,
@@ -95,6 +114,9 @@ enum Cli {
         #[structopt(long, short, default_value = cli::run::AGENT_NAME_DEFAULT)]
         /// Specify agent name which will be used to generate the %agent_id.
         agent_name: String,
+        #[structopt(long, default_value = "1")]
+        /// Number of agents to start on a shared network, each with its own interface on consecutive ports starting at --port
+        agents: u16,
     },
     #[structopt(alias = "t")]
     /// Runs tests written in the test folder
@@ -124,6 +146,12 @@ enum Cli {
         #[structopt(long, short)]
         /// Don't ask for passphrase
         nullpass: bool,
+        #[structopt(long = "from-mnemonic")]
+        /// Restore a keystore from the mnemonic phrase of its root seed, read from stdin
+        from_mnemonic: bool,
+        #[structopt(long = "show-mnemonic")]
+        /// Print the mnemonic phrase of the new keystore's root seed to write down as a backup
+        show_mnemonic: bool,
     },
     #[structopt(name = "chain")]
     /// View the contents of a source chain
@@ -137,6 +165,40 @@ enum Cli {
         #[structopt(long, short)]
         /// List available instances
         list: bool,
+        #[structopt(long = "entry-type", short = "t")]
+        /// Only show entries of this type (can be given more than once)
+        entry_types: Vec<String>,
+        #[structopt(long)]
+        /// Only show entries committed at or after this time (ISO 8601)
+        since: Option<Iso8601>,
+        #[structopt(long)]
+        /// Only show entries committed at or before this time (ISO 8601)
+        until: Option<Iso8601>,
+        #[structopt(long, short, parse(from_str))]
+        /// Only show the header with this address or the headers of the entry with this address
+        address: Option<Address>,
+        #[structopt(long, short, possible_values = &OutputFormat::variants(), case_insensitive = true, default_value = "text")]
+        /// Output format: text/json (json prints one object per line)
+        format: OutputFormat,
+        #[structopt(long)]
+        /// Keep watching the chain and show new entries as they get committed
+        follow: bool,
+    },
+    #[structopt(name = "dht")]
+    /// View the DHT shard of an instance: held aspects, links and CRUD status per address
+    Dht {
+        #[structopt(name = "INSTANCE")]
+        /// Instance ID to view
+        instance_id: String,
+        #[structopt(long, short, parse(from_os_str))]
+        /// Location of the instance storage
+        path: Option<PathBuf>,
+        #[structopt(long, short, parse(from_str))]
+        /// Only show what is stored about this address
+        address: Option<Address>,
+        #[structopt(long, short, possible_values = &OutputFormat::variants(), case_insensitive = true, default_value = "text")]
+        /// Output format: text/json (json prints one object per line)
+        format: OutputFormat,
     },
     #[structopt(name = "hash")]
     /// Parse and hash a DNA file to determine its unique network hash
@@ -148,6 +210,45 @@ enum Cli {
         /// Property (in the form 'name=value') that gets set/overwritten before calculating hash
         property: Option<Vec<String>>,
     },
+    #[structopt(name = "verify")]
+    /// Checks that a package built with `hc package --reproducible` has the DNA hash recorded in its build manifest, and the signature of the manifest if it is signed
+    Verify {
+        #[structopt(long, short, parse(from_os_str))]
+        /// Path to .dna.json file [default: dist/<dna-name>.dna.json]
+        path: Option<PathBuf>,
+        #[structopt(long, short)]
+        /// Require the package to be signed by the agent with this public key
+        agent: Option<String>,
+        #[structopt(long = "dna-hash")]
+        /// Require the package to have this DNA hash. Either this or --agent is needed, and unsigned packages need this
+        dna_hash: Option<Address>,
+    },
+    #[structopt(name = "lint")]
+    /// Checks a DNA for undefined entry and link types, undeclared or unexported zome functions and unsatisfiable bridges
+    Lint {
+        #[structopt(long, short, parse(from_os_str))]
+        /// Path to a .dna.json file or to a DNA source directory [default: dist/<dna-name>.dna.json]
+        path: Option<PathBuf>,
+        #[structopt(long, short, parse(from_os_str))]
+        /// hApp bundle file to check the DNA's required bridges against
+        bundle: Option<PathBuf>,
+        #[structopt(long = "deny-warnings", short = "D")]
+        /// Fail on warnings as well as on errors
+        deny_warnings: bool,
+    },
+    #[structopt(name = "replay")]
+    /// Rebuilds the state of an instance from an action recording (see the `record_actions` instance setting) and prints it as a state dump
+    Replay {
+        #[structopt(name = "RECORDING", parse(from_os_str))]
+        /// Path of the recording file
+        recording: PathBuf,
+        #[structopt(long, short)]
+        /// Only replay this many actions
+        until: Option<usize>,
+        #[structopt(long, short)]
+        /// List the recorded actions and network messages instead of replaying them
+        list: bool,
+    },
 }
 arg_enum! {
     #[derive(Debug)]
@@ -156,6 +257,13 @@ arg_enum! {
         Sim2h,
     }
 }
+arg_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum OutputFormat {
+        Text,
+        Json,
+    }
+}
 
 fn main() {
     lib3h_sodium::check_init();
@@ -176,6 +284,9 @@ fn run() -> HolochainResult<()> {
         Cli::Package {
             output,
             properties: properties_string,
+            reproducible,
+            sign,
+            passphrase_file,
         } => {
             let output = if let Some(output_inner) = output {
                 output_inner
@@ -189,7 +300,12 @@ fn run() -> HolochainResult<()> {
 
             match properties {
                 Ok(properties) => {
-                    cli::package(output, properties).map_err(HolochainError::Default)?
+                    let packaged = if reproducible {
+                        cli::package_reproducible(output, properties, sign, passphrase_file)
+                    } else {
+                        cli::package(output, properties)
+                    };
+                    packaged.map_err(HolochainError::Default)?
                 }
                 Err(e) => {
                     return Err(HolochainError::Default(format_err!(
@@ -208,6 +324,7 @@ fn run() -> HolochainResult<()> {
 
         Cli::Run {
             package,
+            watch,
             port,
             dna_path,
             persist,
@@ -216,6 +333,7 @@ fn run() -> HolochainResult<()> {
             interface,
             logging,
             agent_name,
+            agents,
         } => {
             let dna_path = dna_path
                 .unwrap_or(util::std_package_path(&project_path).map_err(HolochainError::Default)?);
@@ -224,6 +342,11 @@ fn run() -> HolochainResult<()> {
             let bundle_path = project_path.join("bundle.toml");
             let networked = networked.map(|n| cli::run::Networking::new(n, sim2h_server));
             let conductor_config = if bundle_path.exists() {
+                if agents > 1 {
+                    return Err(HolochainError::Default(format_err!(
+                        "--agents is not supported for projects with a bundle.toml"
+                    )));
+                }
                 let mut f = File::open(bundle_path)
                     .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
                 let mut contents = String::new();
@@ -250,6 +373,7 @@ fn run() -> HolochainResult<()> {
                     &interface_type,
                     logging,
                     agent_name,
+                    agents,
                 )
                 .map_err(HolochainError::Default)?
             };
@@ -257,8 +381,15 @@ fn run() -> HolochainResult<()> {
                 "Booting conductor with following configuration: {:?}",
                 conductor_config
             );
-            cli::run(dna_path, package, port, interface_type, conductor_config)
-                .map_err(HolochainError::Default)?
+            cli::run(
+                dna_path,
+                package,
+                watch,
+                port,
+                interface_type,
+                conductor_config,
+            )
+            .map_err(HolochainError::Default)?
         }
 
         Cli::Test {
@@ -277,13 +408,23 @@ fn run() -> HolochainResult<()> {
             path,
             quiet,
             nullpass,
+            from_mnemonic,
+            show_mnemonic,
         } => {
+            let mnemonic = if from_mnemonic {
+                Some(
+                    cli::read_mnemonic(quiet)
+                        .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
+                )
+            } else {
+                None
+            };
             let passphrase = if nullpass {
                 Some(String::from(holochain_common::DEFAULT_PASSPHRASE))
             } else {
                 None
             };
-            cli::keygen(path, passphrase, quiet)
+            cli::keygen(path, passphrase, quiet, mnemonic, show_mnemonic)
                 .map_err(|e| HolochainError::Default(format_err!("{}", e)))?
         }
 
@@ -291,6 +432,12 @@ fn run() -> HolochainResult<()> {
             instance_id,
             list,
             path,
+            entry_types,
+            since,
+            until,
+            address,
+            format,
+            follow,
         } => match (list, instance_id) {
             (true, _) => cli::chain_list(path),
             (false, None) => {
@@ -298,10 +445,23 @@ fn run() -> HolochainResult<()> {
                 println!("\n\nTry `hc help chain` for more info");
             }
             (false, Some(instance_id)) => {
-                cli::chain_log(path, instance_id)
+                let filter = cli::ChainFilter {
+                    entry_types,
+                    since,
+                    until,
+                    address,
+                };
+                cli::chain_log(path, instance_id, filter, format, follow)
                     .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
             }
         },
+        Cli::Dht {
+            instance_id,
+            path,
+            address,
+            format,
+        } => cli::dht(path, instance_id, address, format)
+            .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
         Cli::HashDna { path, property } => {
             let dna_path = path
                 .unwrap_or(util::std_package_path(&project_path).map_err(HolochainError::Default)?);
@@ -310,6 +470,30 @@ fn run() -> HolochainResult<()> {
                 .map_err(|e| HolochainError::Default(format_err!("{}", e)))?;
             println!("DNA Hash: {}", dna_hash);
         }
+        Cli::Verify {
+            path,
+            agent,
+            dna_hash,
+        } => {
+            let dna_path = path
+                .unwrap_or(util::std_package_path(&project_path).map_err(HolochainError::Default)?);
+            cli::verify(&dna_path, agent, dna_hash).map_err(HolochainError::Default)?
+        }
+        Cli::Lint {
+            path,
+            bundle,
+            deny_warnings,
+        } => {
+            let dna_path = path
+                .unwrap_or(util::std_package_path(&project_path).map_err(HolochainError::Default)?);
+            cli::lint(&dna_path, bundle, deny_warnings).map_err(HolochainError::Default)?
+        }
+        Cli::Replay {
+            recording,
+            until,
+            list,
+        } => cli::replay(recording, until, list)
+            .map_err(|e| HolochainError::Default(format_err!("{}", e)))?,
     }
 
     Ok(())
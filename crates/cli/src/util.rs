@@ -9,12 +9,23 @@ use std::{
 };
 
 pub fn run_cmd(base_path: PathBuf, bin: String, args: &[&str]) -> DefaultResult<()> {
+    run_cmd_with_env(base_path, bin, args, &[])
+}
+
+/// Like `run_cmd`, with additional environment variables set for the command
+pub fn run_cmd_with_env(
+    base_path: PathBuf,
+    bin: String,
+    args: &[&str],
+    env: &[(String, String)],
+) -> DefaultResult<()> {
     let pretty_command = format!("{} {}", bin.green(), args.join(" ").cyan());
 
     println!("> {}", pretty_command);
 
     let status = Command::new(bin)
         .args(args)
+        .envs(env.iter().cloned())
         .current_dir(base_path)
         .status()?;
 
@@ -2,14 +2,19 @@ use crate::{
     conductor::{base::notify, Conductor},
     config::{
         AgentConfiguration, Bridge, DnaConfiguration, InstanceConfiguration,
-        InstanceReferenceConfiguration, InterfaceConfiguration, StorageConfiguration,
+        InstanceReferenceConfiguration, InterfaceConfiguration, RevokedKeyConfiguration,
+        StorageConfiguration,
     },
     dpki_instance::DpkiInstance,
+    holochain::Holochain,
     keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
+    logger::LogRules,
 };
-use holochain_core_types::error::HolochainError;
+use holochain_core::dht::pending_validations::StuckValidation;
+use holochain_core_types::{agent::Base32, error::HolochainError, time::Iso8601};
 use holochain_locksmith::RwLock;
 use holochain_persistence_api::{cas::content::AddressableContent, hash::HashString};
+use lib3h_sodium::secbuf::SecBuf;
 
 use json_patch;
 use std::{
@@ -17,7 +22,7 @@ use std::{
     path::PathBuf,
     sync::Arc,
     thread::sleep,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 /// how many milliseconds sleep all bugs under rugs
@@ -63,12 +68,24 @@ pub trait ConductorAdmin {
         holo_remote_key: Option<&str>,
     ) -> Result<String, HolochainError>;
     fn remove_agent(&mut self, id: &String) -> Result<(), HolochainError>;
+    fn revoke_agent(&mut self, id: &String) -> Result<(), HolochainError>;
+    fn rotate_agent(&mut self, id: &String) -> Result<Base32, HolochainError>;
     fn add_bridge(&mut self, new_bridge: Bridge) -> Result<(), HolochainError>;
     fn remove_bridge(
         &mut self,
         caller_id: &String,
         callee_id: &String,
     ) -> Result<(), HolochainError>;
+    fn log_rules(&self) -> Result<LogRules, HolochainError>;
+    fn set_log_rules(&mut self, rules: LogRules) -> Result<(), HolochainError>;
+    fn stuck_validations(
+        &self,
+        instance_id: &String,
+        min_attempts: u32,
+    ) -> Result<Vec<StuckValidation>, HolochainError>;
+    fn lock_keystore(&mut self) -> Result<(), HolochainError>;
+    fn unlock_keystore(&mut self, passphrase: Option<String>) -> Result<(), HolochainError>;
+    fn hot_swap_dna(&mut self, id: &String, path: PathBuf) -> Result<bool, HolochainError>;
 }
 
 impl ConductorAdmin for Conductor {
@@ -231,6 +248,7 @@ impl ConductorAdmin for Conductor {
             dna: dna_id.to_string(),
             agent: agent_id.to_string(),
             storage: storage_config,
+            record_actions: None,
         };
         new_config.instances.push(new_instance_config);
         new_config.check_consistency(&mut self.dna_loader)?;
@@ -442,39 +460,12 @@ impl ConductorAdmin for Conductor {
         let (keystore_file, public_address) = if let Some(public_address) = holo_remote_key {
             ("::ignored::".to_string(), public_address.to_string())
         } else {
-            let (keystore, public_address) = if self.using_dpki() {
-                let dpki_instance_id = self.dpki_instance_id().unwrap();
-
-                // try to create the keystore first so that if the passphrase fails we don't have
-                // to clean-up any dkpi calls
-                let mut keystore =
-                    Keystore::new(self.passphrase_manager.clone(), self.hash_config.clone())?;
-                {
-                    let instance = self.instances.get(&dpki_instance_id)?;
-                    let hc_lock = instance.clone();
-                    let hc_lock_inner = hc_lock;
-                    let mut hc = hc_lock_inner.write().unwrap();
-                    hc.dpki_create_agent_key(id.clone())?;
-                }
-                // TODO: how do we clean-up now if this fails? i.e. the dpki dna will have registered
-                // the identity to its DHT, but we failed, for what ever reason, to set up
-                // the agent in the conductor, so we should do something...
-                let dpki_config = self.config.instance_by_id(&dpki_instance_id)?;
-                let dpki_keystore = self.get_keystore_for_agent(&dpki_config.agent)?;
-                let mut dpki_keystore = dpki_keystore.lock().unwrap();
-                let mut keybundle = dpki_keystore.get_keybundle(&id)?;
-                keystore.add_keybundle(PRIMARY_KEYBUNDLE_ID, &mut keybundle)?;
-                (keystore, keybundle.get_id())
-            } else {
-                Keystore::new_standalone(self.passphrase_manager.clone(), self.hash_config.clone())?
-            };
-            let keystore_file = self
-                .instance_storage_dir_path()
-                .join(public_address.clone());
-            create_dir_all(self.instance_storage_dir_path())?;
-            keystore.save(keystore_file.clone())?;
-            self.add_agent_keystore(id.clone(), keystore);
-            (keystore_file.to_string_lossy().into_owned(), public_address)
+            let (keystore, public_address) = self.new_agent_keystore(|hc| {
+                hc.dpki_create_agent_key(id.clone())?;
+                Ok(id.clone())
+            })?;
+            let keystore_file = self.save_agent_keystore(&id, keystore, &public_address)?;
+            (keystore_file, public_address)
         };
 
         let new_agent = AgentConfiguration {
@@ -484,6 +475,7 @@ impl ConductorAdmin for Conductor {
             keystore_file: keystore_file,
             holo_remote_key: holo_remote_key.map(|_| true),
             test_agent: None,
+            keystore_service: None,
         };
 
         new_config.agents.push(new_agent);
@@ -543,6 +535,104 @@ impl ConductorAdmin for Conductor {
         Ok(())
     }
 
+    /// Revokes the key of the given agent, through the DPKI instance if there is one.
+    /// Commits signed with that key from now on fail validation in all instances of this
+    /// conductor, so the agent's instances get stopped.
+    fn revoke_agent(&mut self, id: &String) -> Result<(), HolochainError> {
+        let agent_config = self.revocable_agent(id)?;
+        let revoked_at = revocation_time();
+
+        if self.using_dpki() {
+            let dpki_instance_id = self.dpki_instance_id().unwrap();
+            let instance = self.instances.get(&dpki_instance_id)?;
+            instance.write().unwrap().dpki_revoke_agent_key(
+                id.clone(),
+                agent_config.public_address.clone(),
+                revoked_at.clone(),
+            )?;
+        }
+        self.record_key_revocation(&agent_config.public_address, revoked_at)?;
+
+        for instance_id in self.agent_instance_ids(id) {
+            if let Err(error) = self.stop_instance(&instance_id) {
+                notify(format!(
+                    "Error stopping instance {}: \"{}\".",
+                    instance_id, error
+                ));
+            }
+        }
+
+        notify(format!(
+            "Revoked key {} of agent \"{}\".",
+            agent_config.public_address, id
+        ));
+        Ok(())
+    }
+
+    /// Replaces the key of the given agent with a new one and revokes the old key.
+    /// With a DPKI instance the new key gets created by its `rotate_agent_key` function,
+    /// otherwise it is derived from a new random seed.
+    /// The agent's instances get restarted with the new key, except for those that fail to stop.
+    /// Returns the new public address of the agent.
+    fn rotate_agent(&mut self, id: &String) -> Result<Base32, HolochainError> {
+        let agent_config = self.revocable_agent(id)?;
+        if agent_config.holo_remote_key.unwrap_or_default()
+            || agent_config.test_agent.unwrap_or_default()
+            || agent_config.keystore_service.is_some()
+        {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Agent '{}' has no keystore that could be rotated",
+                id
+            )));
+        }
+
+        let revoked_at = revocation_time();
+        let (keystore, public_address) =
+            self.new_agent_keystore(|hc| hc.dpki_rotate_agent_key(id.clone(), revoked_at.clone()))?;
+        let keystore_file = self.save_agent_keystore(id, keystore, &public_address)?;
+
+        let mut new_config = self.config.clone();
+        for agent in new_config.agents.iter_mut().filter(|agent| agent.id == *id) {
+            agent.public_address = public_address.clone();
+            agent.keystore_file = keystore_file.clone();
+        }
+        new_config.check_consistency(&mut self.dna_loader)?;
+        self.config = new_config;
+        self.record_key_revocation(&agent_config.public_address, revoked_at)?;
+
+        for instance_id in self.agent_instance_ids(id) {
+            if let Err(error) = self.stop_instance(&instance_id) {
+                notify(format!(
+                    "Error stopping instance {}, not restarting it with the new key: \"{}\".",
+                    instance_id, error
+                ));
+                continue;
+            }
+            let restarted = self
+                .instantiate_from_config(&instance_id)
+                .map_err(HolochainError::ErrorGeneric)
+                .and_then(|instance| {
+                    self.instances
+                        .insert(instance_id.clone(), Arc::new(RwLock::new(instance)));
+                    self.start_instance(&instance_id)
+                        .map_err(|error| HolochainError::ErrorGeneric(error.to_string()))
+                });
+            if let Err(error) = restarted {
+                notify(format!(
+                    "Error restarting instance {} with the new key: \"{}\".",
+                    instance_id, error
+                ));
+            }
+        }
+        let _ = self.start_signal_multiplexer();
+
+        notify(format!(
+            "Rotated key of agent \"{}\" from {} to {}.",
+            id, agent_config.public_address, public_address
+        ));
+        Ok(public_address)
+    }
+
     fn add_bridge(&mut self, new_bridge: Bridge) -> Result<(), HolochainError> {
         let mut new_config = self.config.clone();
         if new_config
@@ -608,6 +698,284 @@ impl ConductorAdmin for Conductor {
 
         Ok(())
     }
+
+    /// The rules log records currently get filtered with
+    fn log_rules(&self) -> Result<LogRules, HolochainError> {
+        self.logger.rules()
+    }
+
+    /// Swaps the rules log records get filtered with while the conductor runs.
+    /// Does not change the config, so the configured rules apply again after a restart.
+    fn set_log_rules(&mut self, rules: LogRules) -> Result<(), HolochainError> {
+        self.logger.set_rules(rules)?;
+        notify("Log rules updated".to_string());
+        Ok(())
+    }
+
+    /// The queued validations of an instance that could not be run
+    /// `min_attempts` times or more, with the dependencies they are waiting on
+    fn stuck_validations(
+        &self,
+        instance_id: &String,
+        min_attempts: u32,
+    ) -> Result<Vec<StuckValidation>, HolochainError> {
+        let hc = self.instances.get(instance_id)?;
+        Ok(hc.read().unwrap().get_stuck_validations(min_attempts)?)
+    }
+
+    /// Wipes the cached keystore passphrase from memory.
+    /// Keys that were already unlocked stay usable, but anything else that needs the
+    /// passphrase requests it from the passphrase service again.
+    fn lock_keystore(&mut self) -> Result<(), HolochainError> {
+        self.passphrase_manager.lock();
+        notify("Keystore passphrase locked".to_string());
+        Ok(())
+    }
+
+    /// Caches the given keystore passphrase, or requests it from the passphrase service
+    /// right away if none is given. Fails if it can't decrypt the agents' keystores.
+    fn unlock_keystore(&mut self, passphrase: Option<String>) -> Result<(), HolochainError> {
+        let mut passphrase = match passphrase {
+            Some(passphrase) => SecBuf::with_insecure_from_string(passphrase),
+            None => self.passphrase_manager.get_passphrase()?,
+        };
+        if let Err(err) = self.check_keystore_passphrase(&mut passphrase) {
+            self.passphrase_manager.lock();
+            return Err(err);
+        }
+        self.passphrase_manager.unlock(passphrase)?;
+        notify("Keystore passphrase unlocked".to_string());
+        Ok(())
+    }
+
+    /// Replaces the DNA given by id with the DNA package at the given path.
+    /// If that changes the DNA's hash, all instances of the DNA get stopped, their storage
+    /// gets wiped and they get re-created and started with the new DNA.
+    /// Interfaces and bridges keep pointing to the re-created instances.
+    /// Returns whether the DNA hash changed.
+    fn hot_swap_dna(&mut self, id: &String, path: PathBuf) -> Result<bool, HolochainError> {
+        let dna_config = self.config.dna_by_id(id).ok_or_else(|| {
+            HolochainError::ConfigError(format!("DNA with ID '{}' does not exist", id))
+        })?;
+        let path_string = path
+            .to_str()
+            .ok_or_else(|| HolochainError::ConfigError("invalid path".into()))?;
+        let mut dna = Arc::get_mut(&mut self.dna_loader).unwrap()(&path).map_err(|e| {
+            HolochainError::ConfigError(format!(
+                "Could not load DNA file \"{}\", Error: {}",
+                path_string,
+                e.to_string()
+            ))
+        })?;
+        if let Some(uuid) = dna_config.uuid.clone() {
+            dna.uuid = uuid;
+        }
+        let new_hash = dna.address().to_string();
+        if new_hash == dna_config.hash {
+            notify(format!("DNA \"{}\" is unchanged.", id));
+            return Ok(false);
+        }
+
+        let mut new_config = self.config.clone();
+        for dna_config in new_config.dnas.iter_mut().filter(|dna| dna.id == *id) {
+            dna_config.file = path_string.into();
+            dna_config.hash = new_hash.clone();
+        }
+        new_config.check_consistency(&mut self.dna_loader)?;
+        self.config = new_config;
+        self.save_config()?;
+
+        let instance_ids: Vec<String> = self
+            .config
+            .instances
+            .iter()
+            .filter(|instance| instance.dna == *id)
+            .map(|instance| instance.id.clone())
+            .collect();
+        for instance_id in instance_ids {
+            let _ = self.stop_instance(&instance_id);
+            if let Some(instance) = self.instances.get(&instance_id) {
+                instance.write().unwrap().kill();
+            }
+            self.reset_instance_storage(&instance_id)?;
+            let new_instance = self
+                .instantiate_from_config(&instance_id)
+                .map_err(HolochainError::ErrorGeneric)?;
+            // Swap the instance in place so that interfaces and bridges, which hold on to
+            // the instance's Arc, use the new one
+            match self.instances.get(&instance_id) {
+                Some(instance) => *instance.write().unwrap() = new_instance,
+                None => {
+                    self.instances
+                        .insert(instance_id.clone(), Arc::new(RwLock::new(new_instance)));
+                }
+            }
+            self.start_instance(&instance_id)?;
+        }
+        let _ = self.start_signal_multiplexer();
+
+        notify(format!(
+            "Swapped DNA \"{}\" to {} with hash {}.",
+            id, path_string, new_hash
+        ));
+        Ok(true)
+    }
+}
+
+impl Conductor {
+    /// Deletes all data of an instance with persistent storage
+    fn reset_instance_storage(&self, instance_id: &String) -> Result<(), HolochainError> {
+        let instance_config = self.config.instance_by_id(instance_id).ok_or_else(|| {
+            HolochainError::ConfigError(format!("Instance '{}' does not exist", instance_id))
+        })?;
+        match instance_config.storage {
+            StorageConfiguration::Memory => (),
+            StorageConfiguration::File { path }
+            | StorageConfiguration::Pickle { path }
+            | StorageConfiguration::Lmdb { path, .. } => {
+                let path = PathBuf::from(path);
+                if path.exists() {
+                    fs::remove_dir_all(&path)?;
+                }
+                create_dir_all(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the keystore for a new agent key. With a DPKI instance, `dpki_call` gets
+    /// called on it to create the key, and has to return the ID of the new key bundle
+    /// in the DPKI instance's keystore. Otherwise a standalone keystore gets created.
+    /// Returns the keystore and the public address of its key.
+    fn new_agent_keystore<F>(&mut self, dpki_call: F) -> Result<(Keystore, Base32), HolochainError>
+    where
+        F: FnOnce(&mut Holochain) -> Result<String, HolochainError>,
+    {
+        if self.using_dpki() {
+            let dpki_instance_id = self.dpki_instance_id().unwrap();
+
+            // try to create the keystore first so that if the passphrase fails we don't have
+            // to clean-up any dkpi calls
+            let mut keystore =
+                Keystore::new(self.passphrase_manager.clone(), self.hash_config.clone())?;
+            let keybundle_id = {
+                let instance = self.instances.get(&dpki_instance_id)?;
+                let mut hc = instance.write().unwrap();
+                dpki_call(&mut *hc)?
+            };
+            // TODO: how do we clean-up now if this fails? i.e. the dpki dna will have registered
+            // the identity to its DHT, but we failed, for what ever reason, to set up
+            // the agent in the conductor, so we should do something...
+            let dpki_config = self.config.instance_by_id(&dpki_instance_id)?;
+            let dpki_keystore = self.get_keystore_for_agent(&dpki_config.agent)?;
+            let mut dpki_keystore = dpki_keystore.lock().unwrap();
+            let mut keybundle = dpki_keystore.get_keybundle(&keybundle_id)?;
+            keystore.add_keybundle(PRIMARY_KEYBUNDLE_ID, &mut keybundle)?;
+            Ok((keystore, keybundle.get_id()))
+        } else {
+            Keystore::new_standalone(self.passphrase_manager.clone(), self.hash_config.clone())
+        }
+    }
+
+    /// Saves an agent's keystore to the instance storage directory and makes it the keystore
+    /// used for that agent. Returns the path of the keystore file.
+    fn save_agent_keystore(
+        &mut self,
+        agent_id: &String,
+        keystore: Keystore,
+        public_address: &Base32,
+    ) -> Result<String, HolochainError> {
+        let keystore_file = self
+            .instance_storage_dir_path()
+            .join(public_address.clone());
+        create_dir_all(self.instance_storage_dir_path())?;
+        keystore.save(keystore_file.clone())?;
+        self.add_agent_keystore(agent_id.clone(), keystore);
+        Ok(keystore_file.to_string_lossy().into_owned())
+    }
+
+    /// Returns the configuration of the given agent if its key can still be revoked
+    fn revocable_agent(&self, id: &String) -> Result<AgentConfiguration, HolochainError> {
+        let agent_config = self.config.agent_by_id(id).ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!("Agent with ID '{}' does not exist", id))
+        })?;
+        if self
+            .key_revocations
+            .read()
+            .unwrap()
+            .revoked_at(&agent_config.public_address.clone().into())
+            .is_some()
+        {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Key of agent '{}' is already revoked",
+                id
+            )));
+        }
+        Ok(agent_config)
+    }
+
+    /// Revokes the given key as of `revoked_at` and persists that in the config
+    fn record_key_revocation(
+        &mut self,
+        public_address: &Base32,
+        revoked_at: Iso8601,
+    ) -> Result<(), HolochainError> {
+        let mut new_config = self.config.clone();
+        new_config.revoked_keys.push(RevokedKeyConfiguration {
+            public_address: public_address.clone(),
+            revoked_at: revoked_at.to_string(),
+        });
+        self.config = new_config;
+        self.save_config()?;
+
+        self.key_revocations
+            .write()
+            .unwrap()
+            .revoke(public_address.clone().into(), revoked_at);
+        Ok(())
+    }
+
+    /// IDs of all configured instances of the given agent
+    fn agent_instance_ids(&self, agent_id: &String) -> Vec<String> {
+        self.config
+            .instances
+            .iter()
+            .filter(|instance| instance.agent == *agent_id)
+            .map(|instance| instance.id.clone())
+            .collect()
+    }
+
+    /// Checks the passphrase against the keystore of the first agent that has one loaded.
+    /// Test agents are skipped since their keystores have passphrases of their own,
+    /// so without any other agent there is nothing the passphrase could be checked against.
+    fn check_keystore_passphrase(&self, passphrase: &mut SecBuf) -> Result<(), HolochainError> {
+        let keystore = self
+            .config
+            .agents
+            .iter()
+            .filter(|agent| !agent.test_agent.unwrap_or_default())
+            .filter_map(|agent| self.agent_keys.get(&agent.id))
+            .next()
+            .ok_or_else(|| {
+                HolochainError::ErrorGeneric(
+                    "No agent keystore loaded to check the passphrase against".to_string(),
+                )
+            })?;
+        if !keystore.lock().unwrap().check_passphrase(passphrase)? {
+            return Err(HolochainError::ErrorGeneric(
+                "Wrong keystore passphrase".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The time from which on a key that gets revoked now counts as revoked
+fn revocation_time() -> Iso8601 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time must not be before UNIX EPOCH");
+    Iso8601::from(since_epoch.as_secs())
 }
 
 #[cfg(test)]
@@ -1669,6 +2037,174 @@ type = 'websocket'"#,
         assert_eq!(config_contents, toml,);
     }
 
+    #[test]
+    fn test_revoke_agent() {
+        let test_name = "test_revoke_agent";
+        let mut conductor = create_test_conductor(test_name, 3012);
+        let old_address = test_keybundle(1).get_id();
+
+        assert_eq!(
+            conductor.revoke_agent(&String::from("test-agent-1")),
+            Ok(())
+        );
+        assert!(conductor
+            .key_revocations
+            .read()
+            .unwrap()
+            .revoked_at(&old_address.clone().into())
+            .is_some());
+        let config = conductor.config();
+        assert_eq!(config.revoked_keys.len(), 1);
+        assert_eq!(config.revoked_keys[0].public_address, old_address);
+        // the agent stays configured but its instance is stopped
+        assert!(config.agent_by_id("test-agent-1").is_some());
+        assert!(!conductor
+            .instances
+            .get("test-instance-1")
+            .unwrap()
+            .read()
+            .unwrap()
+            .active());
+
+        assert_eq!(
+            conductor.revoke_agent(&String::from("test-agent-1")),
+            Err(HolochainError::ErrorGeneric(
+                "Key of agent 'test-agent-1' is already revoked".to_string()
+            )),
+        );
+        assert!(conductor
+            .rotate_agent(&String::from("test-agent-1"))
+            .is_err());
+        assert!(conductor
+            .revoke_agent(&String::from("unknown-agent"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_lock_and_unlock_keystore() {
+        let test_name = "test_lock_and_unlock_keystore";
+        let mut conductor = create_test_conductor(test_name, 3014);
+
+        // Test keystores are encrypted with their agent's ID as passphrase
+        assert_eq!(
+            conductor.unlock_keystore(Some(String::from("test-agent-1"))),
+            Ok(())
+        );
+        assert!(!conductor.passphrase_manager.is_locked());
+
+        assert_eq!(conductor.lock_keystore(), Ok(()));
+        assert!(conductor.passphrase_manager.is_locked());
+
+        assert!(conductor
+            .unlock_keystore(Some(String::from("wrong passphrase")))
+            .is_err());
+        assert!(conductor.passphrase_manager.is_locked());
+
+        // Without a passphrase it gets requested from the (mock) passphrase service
+        // which answers with the test name
+        assert!(conductor.unlock_keystore(None).is_err());
+        assert!(conductor.passphrase_manager.is_locked());
+
+        // Test agents' keystores are not checked, so with only those any passphrase is rejected
+        let toml = add_block(
+            header_block(test_name),
+            format!("{}\ntest_agent = true", agent1()),
+        );
+        let mut conductor = create_test_conductor_from_toml(&toml, test_name);
+        assert!(conductor
+            .unlock_keystore(Some(String::from("test-agent-1")))
+            .is_err());
+        assert!(conductor.passphrase_manager.is_locked());
+    }
+
+    #[test]
+    fn test_hot_swap_dna() {
+        let test_name = "test_hot_swap_dna";
+        let mut conductor = create_test_conductor(test_name, 3015);
+        let changed_dna = || {
+            let mut dna = Dna::try_from(JsonString::from_json(&example_dna_string())).unwrap();
+            dna.properties = json!({"changed": true});
+            dna
+        };
+        conductor.dna_loader = Arc::new(Box::new(move |path: &PathBuf| {
+            Ok(match path.to_str().unwrap() {
+                "changed.dna.json" => changed_dna(),
+                _ => Dna::try_from(JsonString::from_json(&example_dna_string())).unwrap(),
+            })
+        })
+            as Box<dyn FnMut(&PathBuf) -> Result<Dna, HolochainError> + Send + Sync>);
+        let instance_before = conductor.instances.get("test-instance-1").unwrap().clone();
+
+        assert_eq!(
+            conductor.hot_swap_dna(
+                &String::from("test-dna"),
+                PathBuf::from("app_spec.dna.json")
+            ),
+            Ok(false)
+        );
+
+        assert_eq!(
+            conductor.hot_swap_dna(&String::from("test-dna"), PathBuf::from("changed.dna.json")),
+            Ok(true)
+        );
+        let dna_config = conductor.config().dna_by_id("test-dna").unwrap();
+        assert_eq!(dna_config.file, "changed.dna.json");
+        assert_eq!(dna_config.hash, changed_dna().address().to_string());
+
+        // The instance got re-created with the new DNA behind the same Arc
+        let instance = conductor.instances.get("test-instance-1").unwrap();
+        assert!(Arc::ptr_eq(instance, &instance_before));
+        let dna = instance
+            .read()
+            .unwrap()
+            .state()
+            .unwrap()
+            .nucleus()
+            .dna()
+            .unwrap();
+        assert_eq!(dna.address(), changed_dna().address());
+    }
+
+    #[test]
+    fn test_rotate_agent() {
+        let test_name = "test_rotate_agent";
+        let mut conductor = create_test_conductor(test_name, 3013);
+        let old_address = test_keybundle(2).get_id();
+
+        let new_address = conductor
+            .rotate_agent(&String::from("test-agent-2"))
+            .expect("Agent key should rotate");
+        assert_ne!(new_address, old_address);
+
+        let config = conductor.config();
+        let agent = config.agent_by_id("test-agent-2").unwrap();
+        assert_eq!(agent.public_address, new_address);
+        assert_eq!(
+            PathBuf::from(agent.keystore_file),
+            conductor
+                .instance_storage_dir_path()
+                .join(new_address.clone())
+        );
+        assert_eq!(config.revoked_keys.len(), 1);
+        assert_eq!(config.revoked_keys[0].public_address, old_address);
+
+        // the instance got restarted with the new key
+        let context = conductor
+            .instances
+            .get("test-instance-2")
+            .unwrap()
+            .read()
+            .unwrap()
+            .context()
+            .unwrap();
+        assert_eq!(context.agent_id.pub_sign_key, new_address);
+        assert!(context
+            .key_revocations
+            .read()
+            .unwrap()
+            .is_revoked(&old_address.into()));
+    }
+
     #[test]
     fn test_add_and_remove_bridge() {
         let test_name = "test_add_and_remove_bridge";
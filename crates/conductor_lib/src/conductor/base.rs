@@ -5,16 +5,19 @@ use crate::{
         NetworkConfig, StorageConfiguration,
     },
     context_builder::ContextBuilder,
-    dpki_instance::DpkiInstance,
+    dpki_instance::{DpkiInstance, DpkiKeyRevocationSource},
     error::HolochainInstanceError,
     key_loaders::test_keystore,
     keystore::{Keystore, PRIMARY_KEYBUNDLE_ID},
+    logger::ConductorLogger,
     port_utils::{try_with_port, INTERFACE_CONNECT_ATTEMPTS_MAX},
     Holochain,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use holochain_common::paths::DNA_EXTENSION;
-use holochain_core::{logger::Logger, signal::Signal};
+use holochain_core::{
+    agent::revocations::KeyRevocations, logger::Logger, recording::ActionRecorder, signal::Signal,
+};
 use holochain_core_types::{
     agent::AgentId,
     dna::Dna,
@@ -26,7 +29,6 @@ use holochain_json_api::json::JsonString;
 use holochain_persistence_api::{cas::content::AddressableContent, hash::HashString};
 
 use holochain_dpki::{key_bundle::KeyBundle, password_encryption::PwHashConfig};
-use holochain_logging::{rule::RuleFilter, FastLogger, FastLoggerBuilder};
 use jsonrpc_ws_server::jsonrpc_core::IoHandler;
 use std::{
     clone::Clone,
@@ -42,10 +44,13 @@ use std::{
 };
 
 #[cfg(unix)]
-use crate::conductor::passphrase_manager::PassphraseServiceUnixSocket;
+use crate::conductor::passphrase_manager::{PassphraseServiceFd, PassphraseServiceUnixSocket};
+#[cfg(unix)]
+use crate::keystore_service::{KeystoreClient, KeystoreRequest};
 use crate::{
     conductor::passphrase_manager::{
-        PassphraseManager, PassphraseService, PassphraseServiceCmd, PassphraseServiceMock,
+        PassphraseManager, PassphraseService, PassphraseServiceCmd, PassphraseServiceEnv,
+        PassphraseServiceFile, PassphraseServiceMock,
     },
     config::{AgentConfiguration, PassphraseServiceConfig},
     interface::{ConductorApiBuilder, InstanceMap, Interface},
@@ -58,6 +63,10 @@ use crate::{
 use boolinator::Boolinator;
 use holochain_core::context::InstanceStats;
 use holochain_core_types::dna::bridges::BridgePresence;
+use holochain_metrics::{
+    prometheus::{prometheus_registry, PrometheusRegistry},
+    MetricPublisherConfig,
+};
 use holochain_net::{
     connection::net_connection::NetHandler,
     ipc::spawn::{ipc_spawn, SpawnResult},
@@ -113,7 +122,7 @@ pub fn mount_conductor_from_config(config: Configuration) {
 pub struct Conductor {
     pub(in crate::conductor) instances: InstanceMap,
     instance_signal_receivers: Arc<RwLock<HashMap<String, Receiver<Signal>>>>,
-    agent_keys: HashMap<String, Arc<Mutex<Keystore>>>,
+    pub(in crate::conductor) agent_keys: HashMap<String, Arc<Mutex<Keystore>>>,
     pub(in crate::conductor) config: Configuration,
     pub(in crate::conductor) static_servers: HashMap<String, StaticServer>,
     pub(in crate::conductor) interface_threads: HashMap<String, Sender<()>>,
@@ -125,7 +134,9 @@ pub struct Conductor {
     pub(in crate::conductor) dna_loader: DnaLoader,
     pub(in crate::conductor) ui_dir_copier: UiDirCopier,
     signal_tx: Option<SignalSender>,
-    logger: FastLogger,
+    pub(in crate::conductor) logger: ConductorLogger,
+    /// Revoked agent keys, shared with every instance's context
+    pub(in crate::conductor) key_revocations: Arc<RwLock<KeyRevocations>>,
     p2p_config: Option<P2pConfig>,
     network_spawn: Option<SpawnResult>,
     pub passphrase_manager: Arc<PassphraseManager>,
@@ -178,24 +189,64 @@ pub fn notify(msg: String) {
     println!("{}", msg);
 }
 
+/// Exports the instance stats as prometheus gauges labelled with the instance id.
+fn export_instance_stats(
+    registry: &PrometheusRegistry,
+    instance_stats: &HashMap<String, InstanceStats>,
+) {
+    for (id, stats) in instance_stats.iter() {
+        let labels = [("instance", id.as_str())];
+        registry.set_gauge(
+            "instance_held_entries",
+            &labels,
+            stats.number_held_entries as f64,
+        );
+        registry.set_gauge(
+            "instance_held_aspects",
+            &labels,
+            stats.number_held_aspects as f64,
+        );
+        registry.set_gauge(
+            "instance_pending_validations",
+            &labels,
+            stats.number_pending_validations as f64,
+        );
+        registry.set_gauge(
+            "instance_delayed_validations",
+            &labels,
+            stats.number_delayed_validations as f64,
+        );
+        registry.set_gauge(
+            "instance_running_zome_calls",
+            &labels,
+            stats.number_running_zome_calls as f64,
+        );
+        registry.set_gauge(
+            "instance_offline",
+            &labels,
+            if stats.offline { 1.0 } else { 0.0 },
+        );
+    }
+}
+
 impl Conductor {
     pub fn from_config(config: Configuration) -> Self {
         lib3h_sodium::check_init();
-        let _rules = config.logger.rules.clone();
-        let mut logger_builder = FastLoggerBuilder::new();
-        logger_builder.set_level_from_str(&config.logger.logger_level.as_str());
-
-        for rule in config.logger.rules.rules.iter() {
-            logger_builder.add_rule_filter(RuleFilter::new(
-                rule.pattern.as_str(),
-                rule.exclude,
-                rule.color.as_ref().unwrap_or(&String::default()).as_str(),
-            ));
-        }
-
-        let logger = logger_builder
-            .build()
+        // Take the passphrase out of the environment before anything spawns threads
+        let mut passphrase_service_env = match config.passphrase_service.clone() {
+            PassphraseServiceConfig::Env { variable } => Some(PassphraseServiceEnv::new(variable)),
+            _ => None,
+        };
+        let logger = ConductorLogger::init(&config.logger)
             .expect("Fail to instanciate the logging factory.");
+        let key_revocations = config
+            .key_revocations()
+            .expect("Invalid revoked_keys in conductor config");
+        holochain_locksmith::configure(holochain_locksmith::LocksmithConfig {
+            contention_profiling: config.lock_contention_profiling,
+            deadlock_detection: config.lock_deadlock_detection,
+            ..holochain_locksmith::locksmith_config()
+        });
 
         if config.ui_bundles.len() > 0 || config.ui_interfaces.len() > 0 {
             println!();
@@ -215,15 +266,34 @@ impl Conductor {
 
                 #[cfg(unix)]
                 Arc::new(Mutex::new(PassphraseServiceUnixSocket::new(path)))
+            } else if let PassphraseServiceConfig::Fd { fd } = config.passphrase_service.clone() {
+                #[cfg(not(unix))]
+                let _ = fd;
+                #[cfg(not(unix))]
+                panic!("Reading the passphrase from a file descriptor is only supported on Unix systems.");
+
+                #[cfg(unix)]
+                Arc::new(Mutex::new(PassphraseServiceFd { fd }))
             } else {
                 match config.passphrase_service.clone() {
                     PassphraseServiceConfig::Cmd => Arc::new(Mutex::new(PassphraseServiceCmd {})),
                     PassphraseServiceConfig::Mock { passphrase } => {
                         Arc::new(Mutex::new(PassphraseServiceMock { passphrase }))
                     }
+                    PassphraseServiceConfig::Env { .. } => {
+                        Arc::new(Mutex::new(passphrase_service_env.take().expect(
+                            "Env passphrase service taken from the environment above",
+                        )))
+                    }
+                    PassphraseServiceConfig::File { path } => {
+                        Arc::new(Mutex::new(PassphraseServiceFile {
+                            path: PathBuf::from(path),
+                        }))
+                    }
                     _ => unreachable!(),
                 }
             };
+        let passphrase_cache_policy = config.passphrase_cache_policy();
 
         Conductor {
             instances: HashMap::new(),
@@ -241,9 +311,13 @@ impl Conductor {
             ui_dir_copier: Arc::new(Box::new(Self::copy_ui_dir)),
             signal_tx: None,
             logger,
+            key_revocations: Arc::new(RwLock::new(key_revocations)),
             p2p_config: None,
             network_spawn: None,
-            passphrase_manager: Arc::new(PassphraseManager::new(passphrase_service)),
+            passphrase_manager: Arc::new(PassphraseManager::new_with_cache_policy(
+                passphrase_service,
+                passphrase_cache_policy,
+            )),
             hash_config: test_hash_config(),
             n3h_keepalive_network: None,
         }
@@ -252,6 +326,10 @@ impl Conductor {
     pub fn spawn_stats_thread(&mut self) {
         self.stop_stats_thread();
         let instances = self.instances.clone();
+        let prometheus = match &self.config.metric_publisher {
+            Some(MetricPublisherConfig::Prometheus(config)) => Some(prometheus_registry(config)),
+            _ => None,
+        };
         let (kill_switch_tx, kill_switch_rx) = unbounded();
         let (stats_tx, stats_rx) = unbounded();
         self.stats_thread_kill_switch = Some(kill_switch_tx);
@@ -283,6 +361,10 @@ impl Conductor {
                     }
                 }
 
+                if let Some(registry) = &prometheus {
+                    export_instance_stats(registry, &instance_stats);
+                }
+
                 if let Err(e) = stats_tx.send(instance_stats) {
                     error!("Could not send stats signal over channel: {:?}", e);
                 }
@@ -375,9 +457,11 @@ impl Conductor {
                                         }
                                     }
 
-                                    // Pass through user-defined  signals to the according interfaces
-                                    // in which the source instance is exposed:
-                                    Signal::User(_) => {
+                                    // Pass through user-defined signals, delivery receipts and given up
+                                    // validations to the according interfaces in which the source instance is exposed:
+                                    Signal::User(_)
+                                    | Signal::DirectMessageReceipt(_)
+                                    | Signal::ValidationGivenUp(_) => {
                                         println!(
                                             "SIGNAL for instance[{}]: {:?}",
                                             instance_id, signal
@@ -869,7 +953,7 @@ impl Conductor {
                     self.save_config()?;
                 }
 
-                context_builder = context_builder.with_agent(agent_address);
+                context_builder = context_builder.with_agent(agent_address.clone());
 
                 context_builder = context_builder.with_p2p_config(self.get_p2p_config());
 
@@ -921,6 +1005,38 @@ impl Conductor {
 
                 context_builder = context_builder.with_instance_name(&instance_name);
 
+                if let Some(path) = &instance_config.record_actions {
+                    let recorder = ActionRecorder::open(path, &instance_name, &agent_address)
+                        .map_err(|hc_err| {
+                            format!("Error opening action recording: {}", hc_err.to_string())
+                        })?;
+                    context_builder = context_builder.with_action_recorder(Arc::new(recorder));
+                }
+
+                if let Some(retry_config) = &self.config.validation_retry {
+                    context_builder = context_builder
+                        .with_validation_retry_policy(retry_config.clone().into());
+                }
+
+                context_builder =
+                    context_builder.with_key_revocations(self.key_revocations.clone());
+
+                // All instances but the DPKI instance itself look up the revocations
+                // published through DPKI:
+                if let Some(dpki_instance) = self
+                    .dpki_instance_id()
+                    .filter(|dpki_instance_id| dpki_instance_id != id)
+                    .and_then(|dpki_instance_id| self.instances.get(&dpki_instance_id).cloned())
+                {
+                    context_builder = context_builder.with_key_revocation_source(Arc::new(
+                        DpkiKeyRevocationSource::new(dpki_instance),
+                    ));
+                }
+
+                if let Some(size) = self.config.state_history_size.filter(|size| *size > 0) {
+                    context_builder = context_builder.with_state_history(size);
+                }
+
                 if let Some(metric_publisher_config) = &self.config.metric_publisher {
                     debug!("Setting metric publisher in context_builder to: {:?}", metric_publisher_config);
                     context_builder = context_builder.with_metric_publisher(&metric_publisher_config);
@@ -1066,6 +1182,18 @@ impl Conductor {
                     .clone()
                     .expect("holo_remote_key needs decryption_service_uri set"),
             );
+        } else if let Some(ref path) = agent_config.keystore_service {
+            #[cfg(not(unix))]
+            let _ = path;
+            #[cfg(not(unix))]
+            return Err(HolochainError::ConfigError(
+                "Keystore services are only available on Unix systems".to_string(),
+            ));
+            #[cfg(unix)]
+            {
+                api_builder = api_builder
+                    .with_keystore_service_functions(Arc::new(KeystoreClient::new(path.clone())));
+            }
         } else {
             api_builder = api_builder.with_agent_signature_callback(
                 self.get_keybundle_for_agent(&instance_config.agent)?,
@@ -1114,6 +1242,10 @@ impl Conductor {
             // Holo closed-alpha hack:
             // !!!!!!!!!!!!!!!!!!!!!!!
             AgentId::new(&agent_config.name, agent_config.public_address.clone())
+        } else if agent_config.keystore_service.is_some() {
+            // The keystore service was checked to hold the key of this address
+            // by check_load_key_for_agent()
+            AgentId::new(&agent_config.name, agent_config.public_address.clone())
         } else {
             let keybundle_arc = self.get_keybundle_for_agent(&agent_config.id)?;
             let keybundle = keybundle_arc.lock().unwrap();
@@ -1137,6 +1269,29 @@ impl Conductor {
             // !!!!!!!!!!!!!!!!!!!!!!!
             return Ok(());
         }
+        let agent_config = self
+            .config
+            .agent_by_id(agent_id)
+            .ok_or_else(|| format!("Agent '{}' not found", agent_id))?;
+        if let Some(ref path) = agent_config.keystore_service {
+            #[cfg(not(unix))]
+            let _ = path;
+            #[cfg(not(unix))]
+            return Err("Keystore services are only available on Unix systems".to_string());
+            #[cfg(unix)]
+            {
+                let agent_address = KeystoreClient::new(path.clone())
+                    .request(KeystoreRequest::AgentId)
+                    .map_err(|err| err.to_string())?;
+                if agent_address["agent_id"] != json!(agent_config.public_address) {
+                    return Err(format!(
+                        "Key from keystore service '{}' ({}) does not match public address {} mentioned in config!",
+                        path, agent_address["agent_id"], agent_config.public_address,
+                    ));
+                }
+                return Ok(());
+            }
+        }
         self.get_keystore_for_agent(agent_id)?;
         Ok(())
     }
@@ -1236,6 +1391,9 @@ impl Conductor {
             if let Some(true) = agent_config.holo_remote_key {
                 return Err("agent is holo_remote, no keystore".to_string());
             }
+            if agent_config.keystore_service.is_some() {
+                return Err("agent uses a keystore service, no keystore".to_string());
+            }
 
             let mut keystore = match agent_config.test_agent {
                 Some(true) => test_keystore(&agent_config.name),
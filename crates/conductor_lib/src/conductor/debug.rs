@@ -1,11 +1,16 @@
 use crate::conductor::Conductor;
-use holochain_core::state_dump::StateDump;
+use holochain_core::{state_dump::StateDump, state_history::StateTransitionDump};
 use holochain_core_types::error::HolochainError;
 use holochain_persistence_api::cas::content::Address;
 
 pub trait ConductorDebug {
     fn running_instances(&self) -> Result<Vec<String>, HolochainError>;
     fn state_dump_for_instance(&self, instance_id: &String) -> Result<StateDump, HolochainError>;
+    fn state_history_for_instance(
+        &self,
+        instance_id: &String,
+        count: Option<usize>,
+    ) -> Result<Vec<StateTransitionDump>, HolochainError>;
     fn get_type_and_content_from_cas(
         &self,
         address: &Address,
@@ -23,6 +28,15 @@ impl ConductorDebug for Conductor {
         Ok(hc.read().unwrap().get_state_dump()?)
     }
 
+    fn state_history_for_instance(
+        &self,
+        instance_id: &String,
+        count: Option<usize>,
+    ) -> Result<Vec<StateTransitionDump>, HolochainError> {
+        let hc = self.instances.get(instance_id)?;
+        Ok(hc.read().unwrap().get_state_history(count)?)
+    }
+
     fn get_type_and_content_from_cas(
         &self,
         address: &Address,
@@ -5,38 +5,95 @@ use lib3h_sodium::secbuf::SecBuf;
 #[cfg(unix)]
 use log::Level;
 use std::{
-    io::{self, Write},
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
     sync::Arc,
     thread,
     time::{Duration, Instant},
 };
 
 #[cfg(unix)]
-use std::io::{BufRead, BufReader};
-#[cfg(unix)]
-use std::os::unix::net::{UnixListener, UnixStream};
+use std::{
+    io::{Seek, SeekFrom},
+    mem::ManuallyDrop,
+    os::unix::{
+        io::{FromRawFd, RawFd},
+        net::{UnixListener, UnixStream},
+    },
+};
 
-/// We are caching the passphrase for 10 minutes.
+/// By default we are caching the passphrase for 10 minutes after its last use.
 const PASSPHRASE_CACHE_DURATION_SECS: u64 = 600;
 
 pub trait PassphraseService {
     fn request_passphrase(&self) -> Result<SecBuf, HolochainError>;
 }
 
+/// When a cached passphrase gets forgotten and wiped from memory
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassphraseCachePolicy {
+    /// Forget the passphrase once it was not used for this long, None keeps it while unused
+    pub idle_timeout: Option<Duration>,
+    /// Forget the passphrase this long after it was provided, even if it is in use
+    pub timeout: Option<Duration>,
+}
+
+impl Default for PassphraseCachePolicy {
+    fn default() -> Self {
+        PassphraseCachePolicy {
+            idle_timeout: Some(Duration::from_secs(PASSPHRASE_CACHE_DURATION_SECS)),
+            timeout: None,
+        }
+    }
+}
+
+impl PassphraseCachePolicy {
+    /// Keeps the passphrase until the keystore gets locked explicitly, for passphrase
+    /// services that can provide it only once
+    pub fn never_expire() -> Self {
+        PassphraseCachePolicy {
+            idle_timeout: None,
+            timeout: None,
+        }
+    }
+
+    fn is_expired(&self, provided_at: Instant, last_read: Instant, now: Instant) -> bool {
+        self.idle_timeout
+            .map(|idle_timeout| now.duration_since(last_read) > idle_timeout)
+            .unwrap_or(false)
+            || self
+                .timeout
+                .map(|timeout| now.duration_since(provided_at) > timeout)
+                .unwrap_or(false)
+    }
+}
+
 #[derive(Clone)]
 pub struct PassphraseManager {
     passphrase_cache: Arc<Mutex<Option<SecBuf>>>,
     passphrase_service: Arc<Mutex<dyn PassphraseService + Send>>,
+    cache_policy: PassphraseCachePolicy,
+    provided_at: Arc<Mutex<Instant>>,
     last_read: Arc<Mutex<Instant>>,
     timeout_kill_switch: Sender<()>,
 }
 
 impl PassphraseManager {
     pub fn new(passphrase_service: Arc<Mutex<dyn PassphraseService + Send>>) -> Self {
+        Self::new_with_cache_policy(passphrase_service, PassphraseCachePolicy::default())
+    }
+
+    pub fn new_with_cache_policy(
+        passphrase_service: Arc<Mutex<dyn PassphraseService + Send>>,
+        cache_policy: PassphraseCachePolicy,
+    ) -> Self {
         let (kill_switch_tx, kill_switch_rx) = unbounded::<()>();
         let pm = PassphraseManager {
             passphrase_cache: Arc::new(Mutex::new(None)),
             passphrase_service,
+            cache_policy,
+            provided_at: Arc::new(Mutex::new(Instant::now())),
             last_read: Arc::new(Mutex::new(Instant::now())),
             timeout_kill_switch: kill_switch_tx,
         };
@@ -50,15 +107,14 @@ impl PassphraseManager {
                     return;
                 }
 
-                if pm_clone.passphrase_cache.lock().unwrap().is_some() {
-                    let duration_since_last_read =
-                        Instant::now().duration_since(*pm_clone.last_read.lock().unwrap());
-
-                    if duration_since_last_read
-                        > Duration::from_secs(PASSPHRASE_CACHE_DURATION_SECS)
-                    {
-                        pm_clone.forget_passphrase();
-                    }
+                if !pm_clone.is_locked()
+                    && pm_clone.cache_policy.is_expired(
+                        *pm_clone.provided_at.lock().unwrap(),
+                        *pm_clone.last_read.lock().unwrap(),
+                        Instant::now(),
+                    )
+                {
+                    pm_clone.lock();
                 }
 
                 thread::sleep(Duration::from_secs(1));
@@ -70,12 +126,14 @@ impl PassphraseManager {
     pub fn get_passphrase(&self) -> Result<SecBuf, HolochainError> {
         let mut passphrase = self.passphrase_cache.lock().unwrap();
         if passphrase.is_none() {
-            *passphrase = Some(
-                self.passphrase_service
-                    .lock()
-                    .unwrap()
-                    .request_passphrase()?,
-            );
+            let mut requested = self
+                .passphrase_service
+                .lock()
+                .unwrap()
+                .request_passphrase()?;
+            *passphrase = Some(Self::secure_copy(&mut requested)?);
+            Self::wipe(&mut requested);
+            *(self.provided_at.lock().unwrap()) = Instant::now();
         }
 
         *(self.last_read.lock().unwrap()) = Instant::now();
@@ -90,10 +148,49 @@ impl PassphraseManager {
         }
     }
 
-    fn forget_passphrase(&self) {
+    /// Caches the given passphrase as if it was just provided by the passphrase service
+    pub fn unlock(&self, mut passphrase: SecBuf) -> Result<(), HolochainError> {
+        let copy = Self::secure_copy(&mut passphrase)?;
+        Self::wipe(&mut passphrase);
+        let mut cache = self.passphrase_cache.lock().unwrap();
+        if let Some(ref mut old) = *cache {
+            Self::wipe(old);
+        }
+        *cache = Some(copy);
+        let now = Instant::now();
+        *(self.provided_at.lock().unwrap()) = now;
+        *(self.last_read.lock().unwrap()) = now;
+        Ok(())
+    }
+
+    /// Wipes the cached passphrase from memory so that it gets requested from the
+    /// passphrase service again the next time it is needed
+    pub fn lock(&self) {
         let mut passphrase = self.passphrase_cache.lock().unwrap();
+        if let Some(ref mut passphrase_buf) = *passphrase {
+            Self::wipe(passphrase_buf);
+        }
         *passphrase = None;
     }
+
+    pub fn is_locked(&self) -> bool {
+        self.passphrase_cache.lock().unwrap().is_none()
+    }
+
+    fn secure_copy(passphrase: &mut SecBuf) -> Result<SecBuf, HolochainError> {
+        let mut copy = if passphrase.len() > 0 {
+            SecBuf::with_secure(passphrase.len())
+        } else {
+            SecBuf::with_insecure(0)
+        };
+        copy.write(0, &*(passphrase.read_lock()))?;
+        Ok(copy)
+    }
+
+    fn wipe(passphrase: &mut SecBuf) {
+        let zeros = vec![0u8; passphrase.len()];
+        let _ = passphrase.write(0, &zeros);
+    }
 }
 
 impl Drop for PassphraseManager {
@@ -134,6 +231,91 @@ impl PassphraseService for PassphraseServiceMock {
     }
 }
 
+/// Moves a passphrase read as a line into secure memory, without the line break,
+/// and overwrites the line with zeros
+fn passphrase_from_line(mut line: String) -> Result<SecBuf, HolochainError> {
+    let passphrase_len = line.trim_end_matches(|c| c == '\n' || c == '\r').len();
+    let passphrase_bytes = unsafe { line.as_mut_vec() };
+    let mut passphrase_buf = SecBuf::with_insecure(passphrase_len);
+    passphrase_buf.write(0, &passphrase_bytes[..passphrase_len])?;
+    for byte in passphrase_bytes.iter_mut() {
+        *byte = 0u8;
+    }
+    Ok(passphrase_buf)
+}
+
+/// Provides the passphrase from an environment variable, for headless conductors.
+/// The variable gets read and removed when the service is created, so that child
+/// processes don't inherit it, which means it can provide the passphrase only once.
+pub struct PassphraseServiceEnv {
+    variable: String,
+    passphrase: Mutex<Option<String>>,
+}
+
+impl PassphraseServiceEnv {
+    /// Takes the passphrase out of the environment. Changing the environment
+    /// races with other threads reading it, so this has to be called before
+    /// the conductor spawns any.
+    pub fn new(variable: String) -> Self {
+        let passphrase = std::env::var(&variable).ok();
+        std::env::remove_var(&variable);
+        PassphraseServiceEnv {
+            variable,
+            passphrase: Mutex::new(passphrase),
+        }
+    }
+}
+
+impl PassphraseService for PassphraseServiceEnv {
+    fn request_passphrase(&self) -> Result<SecBuf, HolochainError> {
+        let passphrase = self.passphrase.lock().unwrap().take().ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!(
+                "Passphrase environment variable {} is not set",
+                self.variable
+            ))
+        })?;
+        passphrase_from_line(passphrase)
+    }
+}
+
+/// Reads the passphrase from the first line of a file, e.g. one provided by a secrets manager
+pub struct PassphraseServiceFile {
+    pub path: PathBuf,
+}
+
+impl PassphraseService for PassphraseServiceFile {
+    fn request_passphrase(&self) -> Result<SecBuf, HolochainError> {
+        let mut line = String::new();
+        BufReader::new(File::open(&self.path)?).read_line(&mut line)?;
+        passphrase_from_line(line)
+    }
+}
+
+/// Reads the passphrase from the first line of a file descriptor the conductor was started
+/// with, e.g. `holochain 3<passphrase_file`. Since a pipe can provide it only once, the
+/// passphrase gets cached until the keystore is locked explicitly.
+#[cfg(unix)]
+pub struct PassphraseServiceFd {
+    pub fd: RawFd,
+}
+
+#[cfg(unix)]
+impl PassphraseService for PassphraseServiceFd {
+    fn request_passphrase(&self) -> Result<SecBuf, HolochainError> {
+        // The descriptor is not ours to close
+        let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(self.fd) });
+        let _ = file.seek(SeekFrom::Start(0));
+        let mut line = String::new();
+        if BufReader::new(&*file).read_line(&mut line)? == 0 {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Nothing to read from passphrase file descriptor {}",
+                self.fd
+            )));
+        }
+        passphrase_from_line(line)
+    }
+}
+
 #[cfg(unix)]
 pub struct PassphraseServiceUnixSocket {
     path: String,
@@ -217,3 +399,68 @@ impl PassphraseService for PassphraseServiceUnixSocket {
         Ok(passphrase_buf)
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn read(passphrase: &mut SecBuf) -> String {
+        String::from_utf8(passphrase.read_lock().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_cache_policy_expiry() {
+        let start = Instant::now();
+        let policy = PassphraseCachePolicy {
+            idle_timeout: Some(Duration::from_secs(10)),
+            timeout: Some(Duration::from_secs(60)),
+        };
+        let later = |secs| start + Duration::from_secs(secs);
+        assert!(!policy.is_expired(start, start, later(5)));
+        assert!(policy.is_expired(start, start, later(11)));
+        assert!(!policy.is_expired(start, later(55), later(58)));
+        assert!(policy.is_expired(start, later(58), later(61)));
+
+        let keep_while_idle = PassphraseCachePolicy::never_expire();
+        assert!(!keep_while_idle.is_expired(start, start, later(100_000)));
+    }
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let pm = PassphraseManager::new(Arc::new(Mutex::new(PassphraseServiceMock {
+            passphrase: "from service".to_string(),
+        })));
+        assert!(pm.is_locked());
+        assert_eq!(read(&mut pm.get_passphrase().unwrap()), "from service");
+        assert!(!pm.is_locked());
+
+        pm.lock();
+        assert!(pm.is_locked());
+        pm.unlock(SecBuf::with_insecure_from_string("unlocked".to_string()))
+            .unwrap();
+        assert_eq!(read(&mut pm.get_passphrase().unwrap()), "unlocked");
+    }
+
+    #[test]
+    fn test_file_and_env_passphrase_services() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passphrase");
+        std::fs::write(&path, "file passphrase\nignored").unwrap();
+        let service = PassphraseServiceFile { path };
+        assert_eq!(
+            read(&mut service.request_passphrase().unwrap()),
+            "file passphrase"
+        );
+
+        std::env::set_var("HC_TEST_PASSPHRASE_SERVICE_ENV", "env passphrase");
+        let service = PassphraseServiceEnv::new("HC_TEST_PASSPHRASE_SERVICE_ENV".to_string());
+        assert!(std::env::var("HC_TEST_PASSPHRASE_SERVICE_ENV").is_err());
+        assert_eq!(
+            read(&mut service.request_passphrase().unwrap()),
+            "env passphrase"
+        );
+        assert!(service.request_passphrase().is_err());
+        let service = PassphraseServiceEnv::new("HC_TEST_PASSPHRASE_SERVICE_UNSET".to_string());
+        assert!(service.request_passphrase().is_err());
+    }
+}
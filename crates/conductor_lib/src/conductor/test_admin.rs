@@ -31,6 +31,7 @@ impl ConductorTestAdmin for Conductor {
             keystore_file: name,
             holo_remote_key: None,
             test_agent: Some(true),
+            keystore_service: None,
         };
         new_config.agents.push(new_agent);
         new_config.check_consistency(&mut self.dna_loader)?;
@@ -1,4 +1,7 @@
-use crate::{conductor::base::DnaLoader, logger::LogRules};
+use crate::{
+    conductor::{base::DnaLoader, passphrase_manager::PassphraseCachePolicy},
+    logger::LogRules,
+};
 /// Conductor Configuration
 /// This module provides structs that represent the different aspects of how
 /// a conductor can be configured.
@@ -18,8 +21,13 @@ use holochain_core_types::{
         Dna,
     },
     error::{HcResult, HolochainError},
+    time::Iso8601,
 };
 
+use holochain_core::{
+    agent::revocations::KeyRevocations,
+    dht::pending_validations::{ValidationGiveUp, ValidationRetryPolicy},
+};
 use holochain_json_api::json::JsonString;
 use holochain_persistence_api::cas::content::AddressableContent;
 use lib3h::engine::EngineConfig;
@@ -37,6 +45,7 @@ use std::{
     net::Ipv4Addr,
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 use toml;
 /// Main conductor configuration struct
@@ -115,8 +124,81 @@ pub struct Configuration {
     #[serde(default)]
     pub passphrase_service: PassphraseServiceConfig,
 
+    /// When the passphrase gets forgotten again once it was provided by the passphrase service.
+    /// Optional, defaults to forgetting it after 10 minutes of not being used.
+    #[serde(default)]
+    pub passphrase_cache: Option<PassphraseCacheConfiguration>,
+
     #[serde(default)]
     pub metric_publisher: Option<MetricPublisherConfig>,
+
+    /// How many of their most recent states to keep per instance for `debug/state_history`.
+    /// Optional, no state history is kept if not set.
+    #[serde(default)]
+    pub state_history_size: Option<usize>,
+
+    /// Gather the lock contention stats reported by `debug/lock_stats`.
+    /// Optional, defaults to false.
+    #[serde(default)]
+    pub lock_contention_profiling: bool,
+
+    /// Fail lock calls that would deadlock right away and log the threads involved,
+    /// instead of waiting for the lock timeout. Makes every contended lock call
+    /// capture a backtrace, so it is meant for debugging.
+    /// Optional, defaults to false.
+    #[serde(default)]
+    pub lock_deadlock_detection: bool,
+
+    /// How validations that can't be run yet because something is missing get retried.
+    /// Optional, defaults to retrying forever with exponential backoff.
+    #[serde(default)]
+    pub validation_retry: Option<ValidationRetryConfiguration>,
+
+    /// Agent keys revoked through `admin/agent/revoke` or `admin/agent/rotate`.
+    /// Commits signed with one of these keys after it got revoked fail validation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revoked_keys: Vec<RevokedKeyConfiguration>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct RevokedKeyConfiguration {
+    pub public_address: Base32,
+    /// ISO 8601 time of the revocation
+    pub revoked_at: String,
+}
+
+/// Retry policy for pending validations. Every field is optional and defaults to
+/// the one of `ValidationRetryPolicy::default()`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ValidationRetryConfiguration {
+    /// Delay before the first retry
+    pub initial_delay_ms: Option<u64>,
+    /// Every further retry waits this many times as long as the one before
+    pub multiplier: Option<u32>,
+    pub max_delay_ms: Option<u64>,
+    /// Retries forever if not set
+    pub max_attempts: Option<u32>,
+    /// `abandon` or `reject` a validation after its last attempt
+    pub give_up: Option<ValidationGiveUp>,
+}
+
+impl From<ValidationRetryConfiguration> for ValidationRetryPolicy {
+    fn from(config: ValidationRetryConfiguration) -> Self {
+        let default = ValidationRetryPolicy::default();
+        ValidationRetryPolicy {
+            initial_delay: config
+                .initial_delay_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.initial_delay),
+            multiplier: config.multiplier.unwrap_or(default.multiplier),
+            max_delay: config
+                .max_delay_ms
+                .map(Duration::from_millis)
+                .unwrap_or(default.max_delay),
+            max_attempts: config.max_attempts.or(default.max_attempts),
+            give_up: config.give_up.unwrap_or(default.give_up),
+        }
+    }
 }
 
 /// The default passphrase service is `Cmd` which will ask for a passphrase via stdout stdin.
@@ -126,12 +208,44 @@ pub struct Configuration {
 /// arbitrary UIs to connect to the conductor and prompt the user for a passphrase.
 /// The according `PassphraseServiceUnixSocket` will send a request message over the socket
 /// then receives bytes as passphrase until a newline is sent.
+/// Headless services can hand the passphrase over through an inherited file descriptor ("fd"),
+/// an environment variable ("env") or a file ("file") instead.
 #[derive(Deserialize, Serialize, Clone, Debug)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum PassphraseServiceConfig {
     Cmd,
-    UnixSocket { path: String },
-    Mock { passphrase: String },
+    UnixSocket {
+        path: String,
+    },
+    Mock {
+        passphrase: String,
+    },
+    /// Reads the first line of an already open file descriptor, e.g. `fd = 3` with `3<file`
+    Fd {
+        fd: i32,
+    },
+    Env {
+        #[serde(default = "default_passphrase_env_variable")]
+        variable: String,
+    },
+    /// Reads the first line of the given file
+    File {
+        path: String,
+    },
+}
+
+impl PassphraseServiceConfig {
+    /// Whether the passphrase can be requested only once, so it must not be forgotten
+    pub fn provides_passphrase_once(&self) -> bool {
+        match self {
+            PassphraseServiceConfig::Fd { .. } | PassphraseServiceConfig::Env { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+fn default_passphrase_env_variable() -> String {
+    String::from("HC_PASSPHRASE")
 }
 
 impl Default for PassphraseServiceConfig {
@@ -140,6 +254,33 @@ impl Default for PassphraseServiceConfig {
     }
 }
 
+/// Passphrase cache policy. Every field is optional and defaults to
+/// the one of `PassphraseCachePolicy::default()`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct PassphraseCacheConfiguration {
+    /// Forget the passphrase once it was not used for this many seconds, 0 never does
+    pub idle_timeout_secs: Option<u64>,
+    /// Forget the passphrase this many seconds after it was provided, even if it is in use
+    pub timeout_secs: Option<u64>,
+}
+
+impl From<PassphraseCacheConfiguration> for PassphraseCachePolicy {
+    fn from(config: PassphraseCacheConfiguration) -> Self {
+        let default = PassphraseCachePolicy::default();
+        PassphraseCachePolicy {
+            idle_timeout: match config.idle_timeout_secs {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => default.idle_timeout,
+            },
+            timeout: config
+                .timeout_secs
+                .map(Duration::from_secs)
+                .or(default.timeout),
+        }
+    }
+}
+
 pub fn default_persistence_dir() -> PathBuf {
     holochain_common::paths::config_root().join("conductor")
 }
@@ -165,6 +306,8 @@ pub fn default_persistence_dir() -> PathBuf {
 ///     logger_level: "debug".to_string(),
 ///     rules: rules,
 ///     state_dump: true,
+///     format: config::LogFormat::Json,
+///     file: None,
 ///     };
 /// ```
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -173,9 +316,14 @@ pub struct LoggerConfiguration {
     pub logger_level: String,
     #[serde(default)]
     pub rules: LogRules,
-    //    pub file: Option<String>,
     #[serde(default)]
     pub state_dump: bool,
+    /// How log records get written. Optional, defaults to colored text.
+    #[serde(default, skip_serializing_if = "LogFormat::is_default")]
+    pub format: LogFormat,
+    /// Write logs to this file instead of stdout, text without colors. Optional.
+    #[serde(default)]
+    pub file: Option<LogFileConfiguration>,
 }
 
 impl Default for LoggerConfiguration {
@@ -184,10 +332,52 @@ impl Default for LoggerConfiguration {
             logger_level: "debug".into(),
             rules: Default::default(),
             state_dump: false,
+            format: LogFormat::default(),
+            file: None,
         }
     }
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable, colored lines
+    Text,
+    /// One JSON object per line with the record's level, target and message plus
+    /// the instance, agent, zome, function and action it was logged for, where known
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> LogFormat {
+        LogFormat::Text
+    }
+}
+
+impl LogFormat {
+    fn is_default(&self) -> bool {
+        *self == LogFormat::default()
+    }
+}
+
+/// A log file that gets rotated once it reaches `max_size` bytes:
+/// `<path>` gets renamed to `<path>.1`, `<path>.1` to `<path>.2` and so on,
+/// dropping what would become `<path>.<max_files + 1>`.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct LogFileConfiguration {
+    pub path: PathBuf,
+    /// Never rotates if not set
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    /// How many rotated files to keep
+    #[serde(default = "default_max_log_files")]
+    pub max_files: usize,
+}
+
+fn default_max_log_files() -> usize {
+    5
+}
+
 /// Check for duplicate items in a list of strings
 fn detect_dupes<'a, I: Iterator<Item = &'a String>>(
     name: &'static str,
@@ -223,6 +413,10 @@ impl Configuration {
 
         detect_dupes("interface", self.interfaces.iter().map(|c| &c.id))?;
 
+        self.key_revocations()?;
+        self.check_validation_retry()?;
+        self.check_passphrase_cache()?;
+
         for ref instance in self.instances.iter() {
             self.agent_by_id(&instance.agent).is_some().ok_or_else(|| {
                 format!(
@@ -467,7 +661,61 @@ impl Configuration {
         self.agents.iter().find(|ac| &ac.id == id).cloned()
     }
 
-    /// Returns the agent configuration with the given ID if present
+    /// Checks that `validation_retry` doesn't retry validations in a busy loop
+    /// because of a zero delay or multiplier
+    pub fn check_validation_retry(&self) -> Result<(), String> {
+        if let Some(retry_config) = &self.validation_retry {
+            if retry_config.initial_delay_ms == Some(0) {
+                return Err("validation_retry.initial_delay_ms must be greater than 0".to_string());
+            }
+            if retry_config.multiplier == Some(0) {
+                return Err("validation_retry.multiplier must be greater than 0".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Rejects cache timeouts for passphrase services that can provide the passphrase only once
+    pub fn check_passphrase_cache(&self) -> Result<(), String> {
+        if let Some(cache_config) = &self.passphrase_cache {
+            let times_out = cache_config.idle_timeout_secs.unwrap_or(0) > 0
+                || cache_config.timeout_secs.is_some();
+            if times_out && self.passphrase_service.provides_passphrase_once() {
+                return Err(
+                    "passphrase_cache can't time out with a passphrase_service of type \"fd\" or \"env\" since it provides the passphrase only once"
+                        .to_string(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The cache policy for the configured passphrase service
+    pub fn passphrase_cache_policy(&self) -> PassphraseCachePolicy {
+        if self.passphrase_service.provides_passphrase_once() {
+            return PassphraseCachePolicy::never_expire();
+        }
+        self.passphrase_cache
+            .clone()
+            .map(PassphraseCachePolicy::from)
+            .unwrap_or_default()
+    }
+
+    /// Returns the revocations of all keys listed in `revoked_keys`
+    pub fn key_revocations(&self) -> Result<KeyRevocations, String> {
+        let mut revocations = KeyRevocations::new();
+        for revoked_key in self.revoked_keys.iter() {
+            let revoked_at = Iso8601::try_from(revoked_key.revoked_at.clone()).map_err(|_| {
+                format!(
+                    "Invalid time \"{}\" given for the revocation of key {}",
+                    revoked_key.revoked_at, revoked_key.public_address
+                )
+            })?;
+            revocations.revoke(revoked_key.public_address.clone().into(), revoked_at);
+        }
+        Ok(revocations)
+    }
+
     pub fn update_agent_address_by_id(&mut self, id: &str, agent_id: &AgentId) {
         self.agents.iter_mut().for_each(|ac| {
             if &ac.id == id {
@@ -656,6 +904,10 @@ pub struct AgentConfiguration {
     pub holo_remote_key: Option<bool>,
     /// If true this agent will use dummy keys rather than a keystore file
     pub test_agent: Option<bool>,
+    /// Path of the unix domain socket of a keystore service (see the `holochain_keystore`
+    /// executable) that holds this agent's keys. If set, conductor will ignore keystore_file
+    /// and send all crypto requests to that service.
+    pub keystore_service: Option<String>,
 }
 
 impl From<AgentConfiguration> for AgentId {
@@ -694,6 +946,10 @@ pub struct InstanceConfiguration {
     pub dna: String,
     pub agent: String,
     pub storage: StorageConfiguration,
+    /// Path of a file to record every action of this instance to, so that its state
+    /// can be replayed elsewhere (e.g. with `hc replay`) to reproduce a bug.
+    #[serde(default)]
+    pub record_actions: Option<String>,
 }
 
 /// This configures the Content Addressable Storage (CAS) that
@@ -1189,6 +1445,130 @@ pub mod tests {
         assert_eq!(config.network, None);
     }
 
+    #[test]
+    fn test_load_json_logger_config() {
+        let toml = r#"
+    [logger]
+    type = "info"
+    format = "json"
+        [logger.file]
+        path = "/var/log/holochain/conductor.log"
+        max_size = 1048576
+    "#;
+        let logger = load_configuration::<Configuration>(toml).unwrap().logger;
+        assert_eq!(logger.format, LogFormat::Json);
+        assert_eq!(
+            logger.file,
+            Some(LogFileConfiguration {
+                path: PathBuf::from("/var/log/holochain/conductor.log"),
+                max_size: Some(1048576),
+                max_files: 5,
+            })
+        );
+
+        let logger = load_configuration::<Configuration>("").unwrap().logger;
+        assert_eq!(logger.format, LogFormat::Text);
+        assert_eq!(logger.file, None);
+    }
+
+    #[test]
+    fn test_load_revoked_keys() {
+        let toml = r#"
+    [[revoked_keys]]
+    public_address = "HcScKey"
+    revoked_at = "2019-11-04T10:00:00+00:00"
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        let revocations = config.key_revocations().unwrap();
+        assert!(revocations.is_revoked(&"HcScKey".to_string().into()));
+        assert_eq!(
+            revocations.revoked_at(&"HcScKey".to_string().into()),
+            Some(&Iso8601::try_from("2019-11-04T10:00:00+00:00").unwrap())
+        );
+
+        let toml = r#"
+    [[revoked_keys]]
+    public_address = "HcScKey"
+    revoked_at = "yesterday"
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(
+            config.key_revocations(),
+            Err("Invalid time \"yesterday\" given for the revocation of key HcScKey".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_passphrase_cache() {
+        let toml = r#"
+    [passphrase_service]
+    type = "file"
+    path = "passphrase"
+
+    [passphrase_cache]
+    timeout_secs = 60
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(config.check_passphrase_cache(), Ok(()));
+        assert_eq!(
+            config.passphrase_cache_policy().timeout,
+            Some(Duration::from_secs(60))
+        );
+
+        let toml = r#"
+    [passphrase_service]
+    type = "fd"
+    fd = 3
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(config.check_passphrase_cache(), Ok(()));
+        assert_eq!(
+            config.passphrase_cache_policy(),
+            PassphraseCachePolicy::never_expire()
+        );
+
+        let toml = r#"
+    [passphrase_service]
+    type = "env"
+
+    [passphrase_cache]
+    idle_timeout_secs = 600
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert!(config.check_passphrase_cache().is_err());
+    }
+
+    #[test]
+    fn test_check_validation_retry() {
+        let toml = r#"
+    [validation_retry]
+    initial_delay_ms = 500
+    multiplier = 3
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(config.check_validation_retry(), Ok(()));
+
+        let toml = r#"
+    [validation_retry]
+    multiplier = 0
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(
+            config.check_validation_retry(),
+            Err("validation_retry.multiplier must be greater than 0".to_string())
+        );
+
+        let toml = r#"
+    [validation_retry]
+    initial_delay_ms = 0
+    "#;
+        let config = load_configuration::<Configuration>(toml).unwrap();
+        assert_eq!(
+            config.check_validation_retry(),
+            Err("validation_retry.initial_delay_ms must be greater than 0".to_string())
+        );
+    }
+
     #[test]
     fn test_load_bad_network_config() {
         let base_toml = r#"
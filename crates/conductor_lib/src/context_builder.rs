@@ -1,6 +1,14 @@
-use holochain_core::{context::Context, persister::SimplePersister, signal::SignalSender};
+use holochain_core::{
+    agent::revocations::{KeyRevocationSource, KeyRevocations},
+    context::Context,
+    dht::pending_validations::ValidationRetryPolicy,
+    persister::SimplePersister,
+    recording::ActionRecorder,
+    signal::SignalSender,
+    state_history::StateHistory,
+};
 use holochain_core_types::{agent::AgentId, eav::Attribute, error::HolochainError};
-use holochain_locksmith::RwLock;
+use holochain_locksmith::{Mutex, RwLock};
 use holochain_net::p2p_config::P2pConfig;
 use holochain_persistence_api::{
     cas::storage::ContentAddressableStorage, eav::EntityAttributeValueStorage,
@@ -41,6 +49,11 @@ pub struct ContextBuilder {
     signal_tx: Option<SignalSender>,
     state_dump_logging: bool,
     metric_publisher: Option<Arc<RwLock<dyn MetricPublisher>>>,
+    action_recorder: Option<Arc<ActionRecorder>>,
+    state_history_size: Option<usize>,
+    validation_retry_policy: Option<ValidationRetryPolicy>,
+    key_revocations: Option<Arc<RwLock<KeyRevocations>>>,
+    key_revocation_source: Option<Arc<dyn KeyRevocationSource>>,
 }
 
 impl ContextBuilder {
@@ -56,6 +69,11 @@ impl ContextBuilder {
             signal_tx: None,
             state_dump_logging: false,
             metric_publisher: None,
+            action_recorder: None,
+            state_history_size: None,
+            validation_retry_policy: None,
+            key_revocations: None,
+            key_revocation_source: None,
         }
     }
 
@@ -182,11 +200,49 @@ impl ContextBuilder {
                 )
             }
             MetricPublisherConfig::Logger => MetricPublisherConfig::Logger,
+            MetricPublisherConfig::Prometheus(config) => {
+                MetricPublisherConfig::Prometheus(config.clone())
+            }
         };
         self.metric_publisher = Some(config.create_metric_publisher());
         self
     }
 
+    /// Makes the instance write every action it reduces to the given recorder,
+    /// so its state can be rebuilt elsewhere with `holochain_core::recording::Recording`.
+    pub fn with_action_recorder(mut self, recorder: Arc<ActionRecorder>) -> Self {
+        self.action_recorder = Some(recorder);
+        self
+    }
+
+    /// Makes the instance keep its `size` most recent states, together with the
+    /// actions that led to them, for inspection through `debug/state_history`.
+    pub fn with_state_history(mut self, size: usize) -> Self {
+        self.state_history_size = Some(size);
+        self
+    }
+
+    /// Sets how validations that can't be run yet get retried.
+    /// Defaults to `ValidationRetryPolicy::default()`.
+    pub fn with_validation_retry_policy(mut self, policy: ValidationRetryPolicy) -> Self {
+        self.validation_retry_policy = Some(policy);
+        self
+    }
+
+    /// Sets the revoked agent keys the instance validates against. Conductors pass
+    /// the same revocations to all their instances so that revoking a key applies everywhere.
+    pub fn with_key_revocations(mut self, revocations: Arc<RwLock<KeyRevocations>>) -> Self {
+        self.key_revocations = Some(revocations);
+        self
+    }
+
+    /// Sets where the instance looks up the key revocations other agents published,
+    /// so that validation does not only rely on the revocations known to this conductor.
+    pub fn with_key_revocation_source(mut self, source: Arc<dyn KeyRevocationSource>) -> Self {
+        self.key_revocation_source = Some(source);
+        self
+    }
+
     /// Actually creates the context.
     /// Defaults to memory storages, an in-memory network config and a fake agent called "alice".
     /// The persister gets set to SimplePersister based on the chain storage.
@@ -204,7 +260,7 @@ impl ContextBuilder {
             .metric_publisher
             .unwrap_or_else(|| Arc::new(RwLock::new(DefaultMetricPublisher::default())));
 
-        Context::new(
+        let mut context = Context::new(
             &self
                 .instance_name
                 .unwrap_or_else(|| "Anonymous-instance".to_string()),
@@ -221,7 +277,19 @@ impl ContextBuilder {
             self.signal_tx,
             self.state_dump_logging,
             metric_publisher,
-        )
+        );
+        context.action_recorder = self.action_recorder;
+        context.state_history = self
+            .state_history_size
+            .map(|size| Arc::new(Mutex::new(StateHistory::new(size))));
+        if let Some(policy) = self.validation_retry_policy {
+            context.validation_retry_policy = policy;
+        }
+        if let Some(revocations) = self.key_revocations {
+            context.key_revocations = revocations;
+        }
+        context.key_revocation_source = self.key_revocation_source;
+        context
     }
 }
 
@@ -1,18 +1,25 @@
 /// Defines a rust wrapper trait to match the Dpki exposed function trait definition
 /// for the conductor to use in the various dpki related flows, e.g. bootstrap and agent addition
 use crate::holochain::Holochain;
-use holochain_core_types::{dna::capabilities::CapabilityRequest, error::HolochainError};
+use holochain_core_types::{
+    dna::capabilities::CapabilityRequest, error::HolochainError, time::Iso8601,
+};
 use holochain_json_api::json::JsonString;
-use holochain_persistence_api::cas::content::AddressableContent;
+use holochain_locksmith::RwLock;
+use holochain_persistence_api::cas::content::{Address, AddressableContent};
 
 use holochain_core::{
-    context::Context, nucleus::actions::call_zome_function::make_cap_request_for_call,
+    agent::revocations::KeyRevocationSource, context::Context,
+    nucleus::actions::call_zome_function::make_cap_request_for_call,
 };
 
 pub const DPKI_ZOME_NAME: &str = "dpki";
 pub const DPKI_TRAIT_FN_ADD_AGENT: &str = "create_agent_key";
 pub const DPKI_TRAIT_FN_INIT: &str = "init_dpki";
 pub const DPKI_TRAIT_FN_IS_INITIALIZED: &str = "is_initialized";
+pub const DPKI_TRAIT_FN_REVOKE_AGENT: &str = "revoke_agent_key";
+pub const DPKI_TRAIT_FN_ROTATE_AGENT: &str = "rotate_agent_key";
+pub const DPKI_TRAIT_FN_GET_KEY_REVOCATION: &str = "get_key_revocation";
 
 use std::{convert::TryInto, sync::Arc};
 
@@ -20,6 +27,18 @@ pub trait DpkiInstance {
     fn dpki_create_agent_key(&mut self, agent_name: String) -> Result<(), HolochainError>;
     fn dpki_init(&mut self, params: String) -> Result<(), HolochainError>;
     fn dpki_is_initialized(&mut self) -> Result<bool, HolochainError>;
+    fn dpki_revoke_agent_key(
+        &mut self,
+        agent_name: String,
+        key: String,
+        revoked_at: Iso8601,
+    ) -> Result<(), HolochainError>;
+    fn dpki_rotate_agent_key(
+        &mut self,
+        agent_name: String,
+        revoked_at: Iso8601,
+    ) -> Result<String, HolochainError>;
+    fn dpki_get_key_revocation(&self, key: String) -> Result<Option<Iso8601>, HolochainError>;
 }
 
 /// create a capability request for a given dpki call
@@ -84,4 +103,94 @@ impl DpkiInstance for Holochain {
         }
         result
     }
+
+    /// wrapper for the dpki revoke_agent_key trait function
+    /// which publishes that the agent's key is revoked from `revoked_at` on
+    fn dpki_revoke_agent_key(
+        &mut self,
+        agent_name: String,
+        key: String,
+        revoked_at: Iso8601,
+    ) -> Result<(), HolochainError> {
+        let params = json!({
+            "agent_name": agent_name,
+            "key": key,
+            "revoked_at": revoked_at.to_string(),
+        })
+        .to_string();
+        let cap_request = dpki_cap_request(self.context()?, DPKI_TRAIT_FN_REVOKE_AGENT, &params)?;
+        let _result = Holochain::call_zome_function(
+            self.context()?,
+            DPKI_ZOME_NAME,
+            cap_request,
+            DPKI_TRAIT_FN_REVOKE_AGENT,
+            &params,
+        )?;
+        Ok(())
+    }
+
+    /// wrapper for the dpki rotate_agent_key trait function
+    /// which publishes the revocation of the agent's old key as of `revoked_at` and
+    /// returns the id of the agent's new key bundle in the dpki instance's keystore
+    fn dpki_rotate_agent_key(
+        &mut self,
+        agent_name: String,
+        revoked_at: Iso8601,
+    ) -> Result<String, HolochainError> {
+        let params = json!({
+            "agent_name": agent_name,
+            "revoked_at": revoked_at.to_string(),
+        })
+        .to_string();
+        let cap_request = dpki_cap_request(self.context()?, DPKI_TRAIT_FN_ROTATE_AGENT, &params)?;
+        let result = Holochain::call_zome_function(
+            self.context()?,
+            DPKI_ZOME_NAME,
+            cap_request,
+            DPKI_TRAIT_FN_ROTATE_AGENT,
+            &params,
+        )?;
+        let result: Result<String, HolochainError> = result.try_into()?;
+        result
+    }
+
+    /// wrapper for the dpki get_key_revocation trait function
+    /// which returns when the given key got revoked, if it was
+    fn dpki_get_key_revocation(&self, key: String) -> Result<Option<Iso8601>, HolochainError> {
+        let params = json!({ "key": key }).to_string();
+        let cap_request =
+            dpki_cap_request(self.context()?, DPKI_TRAIT_FN_GET_KEY_REVOCATION, &params)?;
+        let result = Holochain::call_zome_function(
+            self.context()?,
+            DPKI_ZOME_NAME,
+            cap_request,
+            DPKI_TRAIT_FN_GET_KEY_REVOCATION,
+            &params,
+        )?;
+        let result: Result<Option<Iso8601>, HolochainError> =
+            serde_json::from_str(&String::from(result))?;
+        result
+    }
+}
+
+/// Looks up key revocations in the DPKI instance, so that the other instances of a conductor
+/// validate against all revocations published through DPKI and not only the ones this
+/// conductor made itself.
+pub struct DpkiKeyRevocationSource {
+    dpki_instance: Arc<RwLock<Holochain>>,
+}
+
+impl DpkiKeyRevocationSource {
+    pub fn new(dpki_instance: Arc<RwLock<Holochain>>) -> Self {
+        DpkiKeyRevocationSource { dpki_instance }
+    }
+}
+
+impl KeyRevocationSource for DpkiKeyRevocationSource {
+    fn revoked_at(&self, key: &Address) -> Result<Option<Iso8601>, HolochainError> {
+        self.dpki_instance
+            .read()
+            .unwrap()
+            .dpki_get_key_revocation(key.to_string())
+    }
 }
@@ -124,6 +124,7 @@ impl HappBundle {
                 dna: happ_instance.id.clone(),
                 agent: agent_config.id.clone(),
                 storage: storage.clone(),
+                record_actions: None,
             })
             .collect::<Vec<_>>();
 
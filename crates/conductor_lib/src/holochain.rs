@@ -94,6 +94,7 @@
 use crate::error::{HolochainInstanceError, HolochainResult};
 use holochain_core::{
     context::Context,
+    dht::pending_validations::StuckValidation,
     instance::Instance,
     nucleus::{
         call_zome_function,
@@ -112,6 +113,7 @@ use holochain_json_api::json::JsonString;
 use holochain_core::{
     state::StateWrapper,
     state_dump::{address_to_content_and_type, StateDump},
+    state_history::StateTransitionDump,
 };
 use holochain_persistence_api::cas::content::Address;
 use jsonrpc_core::IoHandler;
@@ -298,6 +300,34 @@ impl Holochain {
         )))
     }
 
+    /// The `count` most recent state transitions of the instance, or all that are kept,
+    /// oldest first. Empty if the instance doesn't keep a state history.
+    pub fn get_state_history(
+        &self,
+        count: Option<usize>,
+    ) -> Result<Vec<StateTransitionDump>, HolochainInstanceError> {
+        self.check_instance()?;
+        let context = self
+            .context
+            .as_ref()
+            .expect("Context must be Some since we've checked it with check_instance()? above");
+        // the reducer takes this lock for every action, so dumping happens on a copy
+        let history = match &context.state_history {
+            Some(history) => history.lock().map_err(HolochainError::from)?.clone(),
+            None => return Ok(Vec::new()),
+        };
+        Ok(history.dump(count))
+    }
+
+    /// The queued validations of the instance that could not be run
+    /// `min_attempts` times or more, with the dependencies they are waiting on
+    pub fn get_stuck_validations(
+        &self,
+        min_attempts: u32,
+    ) -> Result<Vec<StuckValidation>, HolochainInstanceError> {
+        Ok(self.state()?.dht().stuck_validations(min_attempts))
+    }
+
     pub fn get_type_and_content_from_cas(
         &self,
         address: &Address,
@@ -9,13 +9,15 @@ use holochain_core_types::{
 };
 use holochain_dpki::key_bundle::KeyBundle;
 use holochain_json_api::json::JsonString;
-use holochain_locksmith::{Mutex, RwLock};
+use holochain_locksmith::{lock_site_stats, take_lock_site_stats, Mutex, RwLock};
 use holochain_persistence_api::cas::content::Address;
 use lib3h_sodium::secbuf::SecBuf;
 
 use jsonrpc_core::{self, types::params::Params, IoHandler, Value};
 use std::{collections::HashMap, convert::TryFrom, path::PathBuf, sync::Arc, thread};
 
+#[cfg(unix)]
+use crate::keystore_service::{KeystoreClient, KeystoreRequest};
 use crate::{
     conductor::{ConductorAdmin, ConductorDebug, ConductorTestAdmin, ConductorUiAdmin, CONDUCTOR},
     config::{
@@ -23,6 +25,7 @@ use crate::{
         InterfaceConfiguration, InterfaceDriver, UiBundleConfiguration, UiInterfaceConfiguration,
     },
     keystore::{KeyType, Keystore, Secret},
+    logger::{LogRule, LogRules},
 };
 use holochain_dpki::utils::SeedContext;
 use serde_json::{self, map::Map};
@@ -415,6 +418,16 @@ impl ConductorApiBuilder {
     ///  * `admin/instance/running`
     ///     Returns an array of all instances that are running.
     ///
+    ///  * `admin/instance/stuck_validations`
+    ///     Returns the queued validations of an instance that could not be run yet,
+    ///     because something they depend on is missing.
+    ///     Params:
+    ///     * `id`: [string] Which instance to look at?
+    ///     * `min_attempts`: [number] (Optional) Only list validations that failed this often, defaults to 1
+    ///     Returns an array of objects of the form
+    ///     {entry_address, workflow, attempts, waiting_on, retry_in_ms}
+    ///     where `waiting_on` lists the dependencies not in the instance's shard yet.
+    ///
     ///  * `admin/interface/add`
     ///     Adds a new DNA / zome / conductor interface (that provides access to zome functions
     ///     of selected instances and conductor functions, depending on the interfaces config).
@@ -466,6 +479,33 @@ impl ConductorApiBuilder {
     ///  * `admin/agent/list`
     ///     Returns an array of all configured agents.
     ///
+    ///  * `admin/agent/revoke`
+    ///     Revoke an agent's key, through the DPKI instance if one is configured.
+    ///     Commits signed with the key from now on fail validation and the agent's
+    ///     instances get stopped.
+    ///     Params:
+    ///     * `id`: Which agent's key to revoke?
+    ///
+    ///  * `admin/agent/rotate`
+    ///     Replace an agent's key with a new one, created by the DPKI instance if one is
+    ///     configured, and revoke the old key. The agent's instances get restarted with the
+    ///     new key.
+    ///     Returns the agent's new `public_address`.
+    ///     Params:
+    ///     * `id`: Which agent's key to rotate?
+    ///
+    ///  * `admin/keystore/lock`
+    ///     Wipe the cached keystore passphrase from memory. Anything that needs it
+    ///     afterwards requests it from the passphrase service again.
+    ///
+    ///  * `admin/keystore/unlock`
+    ///     Cache the keystore passphrase so that it does not get requested from the
+    ///     passphrase service, until the passphrase cache policy or `admin/keystore/lock`
+    ///     forgets it again. Fails if the passphrase can't decrypt the agents' keystores.
+    ///     Params:
+    ///     * `passphrase`: [string] (Optional) If not given, the passphrase gets requested
+    ///             from the passphrase service right away.
+    ///
     ///  * `admin/bridge/add`
     ///     Add a bridge between two instances to enable the caller to call the callee's
     ///     zome functions.
@@ -484,6 +524,18 @@ impl ConductorApiBuilder {
     ///  * `admin/bridge/list`
     ///     Returns an array of all bridges.
     ///
+    ///  * `admin/logger/get_rules`
+    ///     Returns the rules log records currently get filtered with, as an array of rules.
+    ///
+    ///  * `admin/logger/set_rules`
+    ///     Replaces the rules log records get filtered with until the conductor restarts.
+    ///     The first rule that applies to a record decides whether it gets logged.
+    ///     Params:
+    ///     * `rules`: [array] Rules of the form
+    ///         {pattern, exclude?, color?, instance_id?, zome?, level?}
+    ///         where `pattern` is a regex matched against the record's target and
+    ///         `level` is the most verbose level let through, instead of the logger's level
+    ///
     pub fn with_admin_dna_functions(mut self) -> Self {
         self.io
             .add_method("admin/dna/install_from_file", move |params| {
@@ -621,6 +673,41 @@ impl ConductorApiBuilder {
                 ))
             });
 
+        self.io
+            .add_method("admin/instance/stuck_validations", move |params| {
+                let params_map = Self::unwrap_params_map(params)?;
+                let id = Self::get_as_string("id", &params_map)?;
+                let min_attempts = match params_map.get("min_attempts") {
+                    Some(_) => {
+                        let min_attempts = Self::get_as_int("min_attempts", &params_map)?;
+                        if min_attempts < 0 {
+                            return Err(jsonrpc_core::Error::invalid_params(
+                                "`min_attempts` has to be a positive number",
+                            ));
+                        }
+                        min_attempts as u32
+                    }
+                    None => 1,
+                };
+                let stuck = conductor_call!(|c| c.stuck_validations(&id, min_attempts))?;
+                Ok(serde_json::Value::Array(
+                    stuck
+                        .into_iter()
+                        .map(|validation| {
+                            json!({
+                                "entry_address": validation.entry_address,
+                                "workflow": validation.workflow,
+                                "attempts": validation.attempts,
+                                "waiting_on": validation.waiting_on,
+                                "retry_in_ms": validation
+                                    .retry_in
+                                    .map(|retry_in| retry_in.as_millis() as u64),
+                            })
+                        })
+                        .collect(),
+                ))
+            });
+
         self.io.add_method("admin/interface/add", move |params| {
             let params_map = Self::unwrap_params_map(params)?;
 
@@ -716,6 +803,40 @@ impl ConductorApiBuilder {
             Ok(json!({"success": true}))
         });
 
+        self.io.add_method("admin/agent/revoke", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            conductor_call!(|c| c.revoke_agent(&id))?;
+            Ok(json!({"success": true}))
+        });
+
+        self.io.add_method("admin/agent/rotate", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let id = Self::get_as_string("id", &params_map)?;
+            let public_address = conductor_call!(|c| c.rotate_agent(&id))?;
+            Ok(json!({"success": true, "public_address": public_address}))
+        });
+
+        self.io.add_method("admin/keystore/lock", move |_params| {
+            conductor_call!(|c| c.lock_keystore())?;
+            Ok(json!({"success": true}))
+        });
+
+        self.io.add_method("admin/keystore/unlock", move |params| {
+            let passphrase = match params {
+                Params::None => None,
+                params => {
+                    let params_map = Self::unwrap_params_map(params)?;
+                    match params_map.get("passphrase") {
+                        Some(_) => Some(Self::get_as_string("passphrase", &params_map)?),
+                        None => None,
+                    }
+                }
+            };
+            conductor_call!(|c| c.unlock_keystore(passphrase))?;
+            Ok(json!({"success": true}))
+        });
+
         self.io.add_method("admin/agent/list", move |_params| {
             let agents = conductor_call!(
                 |c| Ok(c.config().agents) as Result<Vec<AgentConfiguration>, String>
@@ -752,6 +873,25 @@ impl ConductorApiBuilder {
             Ok(serde_json::to_value(bridges).map_err(|_| jsonrpc_core::Error::internal_error())?)
         });
 
+        self.io
+            .add_method("admin/logger/get_rules", move |_params| {
+                let rules = conductor_call!(|c| c.log_rules())?;
+                Ok(serde_json::to_value(rules.rules)
+                    .map_err(|_| jsonrpc_core::Error::internal_error())?)
+            });
+
+        self.io.add_method("admin/logger/set_rules", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let rules = params_map
+                .get("rules")
+                .ok_or_else(|| jsonrpc_core::Error::invalid_params("`rules` param not provided"))?;
+            let rules: Vec<LogRule> = serde_json::from_value(rules.clone()).map_err(|e| {
+                jsonrpc_core::Error::invalid_params(format!("`rules` are invalid: {}", e))
+            })?;
+            conductor_call!(|c| c.set_log_rules(LogRules { rules }))?;
+            Ok(json!({"success": true}))
+        });
+
         self
     }
 
@@ -769,6 +909,16 @@ impl ConductorApiBuilder {
     ///   - `held_aspects` [bool] (optional) If set to false, will exclude the holding map entries
     ///   - `queued_holding_workflows` [bool] (optional If set to false, will exclude contents of the validation queue
     ///
+    /// - `debug/state_history`
+    ///   Returns the most recent states of an instance, each with the action that led to it.
+    ///   How many states an instance keeps is set with `state_history_size` in the conductor config.
+    ///   Params:
+    ///   - `instance_id` ID of the instance of which the state history is requested
+    ///   - `count` [int] (optional) Only return this many of the most recent states
+    ///   - `source_chain`, `held_aspects` and `queued_holding_workflows` as for `debug/state_dump`
+    ///   Returns an array of objects of the form {action, time, state}, oldest first,
+    ///   where `time` is in milliseconds since the UNIX epoch and `state` is a state dump.
+    ///
     /// - `debug/fetch_cas`
     ///   Returns content of a given instance's CAS.
     ///   Params:
@@ -776,6 +926,16 @@ impl ConductorApiBuilder {
     ///   - `address` Address (hash) of the content that is requests
     ///   Returns an object of the form: {type:"<entry type>", content: "<content>"}
     ///
+    /// - `debug/lock_stats`
+    ///   Returns the lock contention stats gathered by locksmith, one object per
+    ///   lock site (guard annotation or call site), the longest held first.
+    ///   Times are in microseconds. Stats are only gathered if
+    ///   `lock_contention_profiling` is set in the conductor config.
+    ///   Params:
+    ///   - `reset` [bool] (optional) If set to true, starts gathering from scratch after this call
+    ///   Returns an array of objects of the form:
+    ///   {site, acquisitions, total_wait_us, total_hold_us, max_hold_us}
+    ///
     pub fn with_debug_functions(mut self) -> Self {
         self.io
             .add_method("debug/running_instances", move |_params| {
@@ -806,6 +966,41 @@ impl ConductorApiBuilder {
                 .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?)
         });
 
+        self.io.add_method("debug/state_history", move |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            let instance_id = Self::get_as_string("instance_id", &params_map)?;
+            let count = match params_map.get("count") {
+                Some(_) => {
+                    let count = Self::get_as_int("count", &params_map)?;
+                    if count < 0 {
+                        return Err(jsonrpc_core::Error::invalid_params(
+                            "`count` has to be a positive number",
+                        ));
+                    }
+                    Some(count as usize)
+                }
+                None => None,
+            };
+
+            let mut history =
+                conductor_call!(|c| c.state_history_for_instance(&instance_id, count))?;
+
+            for transition in history.iter_mut() {
+                if Ok(false) == Self::get_as_bool("source_chain", &params_map) {
+                    transition.state.source_chain.clear()
+                }
+                if Ok(false) == Self::get_as_bool("held_aspects", &params_map) {
+                    transition.state.held_aspects.clear()
+                }
+                if Ok(false) == Self::get_as_bool("queued_holding_workflows", &params_map) {
+                    transition.state.queued_holding_workflows.clear()
+                }
+            }
+
+            Ok(serde_json::to_value(history)
+                .map_err(|e| jsonrpc_core::Error::invalid_params(e.to_string()))?)
+        });
+
         self.io.add_method("debug/fetch_cas", move |params| {
             let params_map = Self::unwrap_params_map(params)?;
             let instance_id = Self::get_as_string("instance_id", &params_map)?;
@@ -821,6 +1016,34 @@ impl ConductorApiBuilder {
             }))
         });
 
+        self.io.add_method("debug/lock_stats", move |params| {
+            let reset = match params {
+                Params::None => false,
+                params => {
+                    Self::get_as_bool("reset", &Self::unwrap_params_map(params)?).unwrap_or(false)
+                }
+            };
+            let stats = if reset {
+                take_lock_site_stats()
+            } else {
+                lock_site_stats()
+            };
+            Ok(Value::Array(
+                stats
+                    .into_iter()
+                    .map(|site| {
+                        json!({
+                            "site": site.site,
+                            "acquisitions": site.acquisitions,
+                            "total_wait_us": site.total_wait.as_micros() as u64,
+                            "total_hold_us": site.total_hold.as_micros() as u64,
+                            "max_hold_us": site.max_hold.as_micros() as u64,
+                        })
+                    })
+                    .collect(),
+            ))
+        });
+
         self
     }
 
@@ -1249,6 +1472,83 @@ impl ConductorApiBuilder {
 
         self
     }
+
+    /// Adds the agent's crypto functions with the same params and results as
+    /// [with_agent_signature_callback], [with_agent_encryption_callback],
+    /// [with_agent_decryption_callback] and [with_agent_keystore_functions],
+    /// but answered by the out-of-process keystore service behind `client`.
+    #[cfg(unix)]
+    pub fn with_keystore_service_functions(mut self, client: Arc<KeystoreClient>) -> Self {
+        type ToRequest = fn(Params) -> Result<KeystoreRequest, jsonrpc_core::Error>;
+        let mut add = |method: &str, to_request: ToRequest| {
+            let client = client.clone();
+            self.io.add_method(method, move |params| {
+                let request = to_request(params)?;
+                client.request(request).map_err(|err| {
+                    let mut error = jsonrpc_core::Error::internal_error();
+                    error.message = err.to_string();
+                    error
+                })
+            });
+        };
+        add("agent/sign", |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            Ok(KeystoreRequest::AgentSign {
+                payload: Self::get_as_crypto_string("payload", &params_map)?,
+            })
+        });
+        add("agent/encrypt", |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            Ok(KeystoreRequest::AgentEncrypt {
+                payload: Self::get_as_crypto_string("payload", &params_map)?,
+            })
+        });
+        add("agent/decrypt", |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            Ok(KeystoreRequest::AgentDecrypt {
+                payload: Self::get_as_crypto_string("payload", &params_map)?,
+            })
+        });
+        add("agent/keystore/list", |_params| Ok(KeystoreRequest::List));
+        add("agent/keystore/add_random_seed", |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            Ok(KeystoreRequest::AddRandomSeed {
+                dst_id: Self::get_as_string("dst_id", &params_map)?,
+                size: Self::get_as_int("size", &params_map)? as usize,
+            })
+        });
+        add("agent/keystore/add_seed_from_seed", |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            Ok(KeystoreRequest::AddSeedFromSeed {
+                src_id: Self::get_as_string("src_id", &params_map)?,
+                dst_id: Self::get_as_string("dst_id", &params_map)?,
+                context: Self::get_as_string("context", &params_map)?,
+                index: Self::get_as_int("index", &params_map)? as u64,
+            })
+        });
+        add("agent/keystore/add_key_from_seed", |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            Ok(KeystoreRequest::AddKeyFromSeed {
+                src_id: Self::get_as_string("src_id", &params_map)?,
+                dst_id: Self::get_as_string("dst_id", &params_map)?,
+                key_type: Self::get_as_string("key_type", &params_map)?,
+            })
+        });
+        add("agent/keystore/sign", |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            Ok(KeystoreRequest::Sign {
+                src_id: Self::get_as_string("src_id", &params_map)?,
+                payload: Self::get_as_string("payload", &params_map)?,
+            })
+        });
+        add("agent/keystore/get_public_key", |params| {
+            let params_map = Self::unwrap_params_map(params)?;
+            Ok(KeystoreRequest::GetPublicKey {
+                src_id: Self::get_as_string("src_id", &params_map)?,
+            })
+        });
+        self
+    }
 }
 
 /// A Broadcaster is something that knows how to send a Signal back to a client.
@@ -7,7 +7,7 @@ use holochain_dpki::{
     key_blob::{BlobType, Blobbable, KeyBlob},
     key_bundle::KeyBundle,
     keypair::{EncryptingKeyPair, KeyPair, SigningKeyPair},
-    seed::Seed,
+    seed::{MnemonicableSeed, RootSeed, Seed, SeedTrait},
     utils::{
         decrypt_with_passphrase_buf, encrypt_with_passphrase_buf, generate_derived_seed_buf,
         generate_random_buf, SeedContext,
@@ -145,6 +145,25 @@ impl Keystore {
         Ok((keystore, pub_key))
     }
 
+    /// Re-create a "standalone" keystore from the mnemonic of its root seed as returned by
+    /// [get_seed_mnemonic]. Since the primary keybundle is derived from the root seed,
+    /// this restores the same agent identity.
+    pub fn new_standalone_from_mnemonic(
+        passphrase_manager: Arc<PassphraseManager>,
+        hash_config: Option<PwHashConfig>,
+        mnemonic: String,
+    ) -> HcResult<(Self, Base32)> {
+        let mut root_seed = RootSeed::new_with_mnemonic(mnemonic, SeedType::Root)?;
+        let mut keystore = Keystore::new(passphrase_manager, hash_config)?;
+        {
+            let seed_buf = root_seed.seed_mut().buf.read_lock();
+            keystore.add_seed(STANDALONE_ROOT_SEED, &*seed_buf)?;
+        }
+        let (pub_key, _) =
+            keystore.add_keybundle_from_seed(STANDALONE_ROOT_SEED, PRIMARY_KEYBUNDLE_ID)?;
+        Ok((keystore, pub_key))
+    }
+
     /// Load a keystore from file.
     /// This won't ask for a passphrase until a secret is used via the other functions.
     /// Secrets will get loaded to memory instantly but stay encrypted until requested.
@@ -165,7 +184,7 @@ impl Keystore {
     /// This tries to decrypt `passphrase_check` with the given passphrase and
     /// expects to read `PCHECK_HEADER` from the decrypted text, ignoring the
     /// random bytes following the header.
-    fn check_passphrase(&self, mut passphrase: &mut SecBuf) -> HcResult<bool> {
+    pub(crate) fn check_passphrase(&self, mut passphrase: &mut SecBuf) -> HcResult<bool> {
         let mut decrypted_buf = decrypt_with_passphrase_buf(
             &self.passphrase_check,
             &mut passphrase,
@@ -309,6 +328,28 @@ impl Keystore {
         self.add(dst_id_str, secret)
     }
 
+    /// returns the BIP39 mnemonic phrase of a seed in the keystore, to be written down as a
+    /// backup of that seed
+    pub fn get_seed_mnemonic(&mut self, src_id_str: &str) -> HcResult<String> {
+        let secret = self.get(src_id_str)?;
+        let mut secret = secret.lock().unwrap();
+        match *secret {
+            Secret::Seed(ref mut src) => {
+                if src.len() != SEED_SIZE {
+                    return Err(HolochainError::ErrorGeneric(
+                        "only seeds of the default size can be exported as mnemonic".to_string(),
+                    ));
+                }
+                let mut seed_buf = SecBuf::with_secure(SEED_SIZE);
+                seed_buf.from_array(&*src.read_lock())?;
+                Seed::new(seed_buf, SeedType::Root).get_mnemonic()
+            }
+            _ => Err(HolochainError::ErrorGeneric(
+                "source secret is not a seed".to_string(),
+            )),
+        }
+    }
+
     fn check_dst_identifier(&self, dst_id_str: &str) -> HcResult<String> {
         let dst_id = dst_id_str.to_string();
         if self.secrets.contains_key(&dst_id) {
@@ -677,6 +718,40 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_keystore_standalone_mnemonic_roundtrip() {
+        let passphrase = random_test_passphrase();
+        let (mut keystore, pub_key) = Keystore::new_standalone(
+            mock_passphrase_manager(passphrase.clone()),
+            test_hash_config(),
+        )
+        .unwrap();
+        let mnemonic = keystore.get_seed_mnemonic(STANDALONE_ROOT_SEED).unwrap();
+        assert_eq!(mnemonic.split(' ').count(), 24);
+        assert!(keystore
+            .get_seed_mnemonic(&[PRIMARY_KEYBUNDLE_ID, KEYBUNDLE_SIGNKEY_SUFFIX].join(""))
+            .is_err());
+
+        let (mut restored, restored_pub_key) = Keystore::new_standalone_from_mnemonic(
+            mock_passphrase_manager(random_test_passphrase()),
+            test_hash_config(),
+            mnemonic.clone(),
+        )
+        .unwrap();
+        assert_eq!(pub_key, restored_pub_key);
+        assert_eq!(
+            restored.get_seed_mnemonic(STANDALONE_ROOT_SEED),
+            Ok(mnemonic)
+        );
+
+        assert!(Keystore::new_standalone_from_mnemonic(
+            mock_passphrase_manager(passphrase),
+            test_hash_config(),
+            "not a mnemonic".to_string(),
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_keystore_add_seed_from_seed() {
         let mut keystore = new_test_keystore(random_test_passphrase());
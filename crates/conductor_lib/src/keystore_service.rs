@@ -0,0 +1,592 @@
+//! Out-of-process keystore service.
+//!
+//! A [KeystoreService] holds an agent's [Keystore] in its own process (see the
+//! `holochain_keystore` executable) and answers requests that come in as lines of JSON over a
+//! unix domain socket. A conductor whose agent has `keystore_service` set uses a
+//! [KeystoreClient] for the agent's crypto functions (`agent/sign`, `agent/encrypt`,
+//! `agent/decrypt` and `agent/keystore/*`), so decrypted key material never lives in
+//! the conductor process.
+//!
+//! Every request is a single line like
+//! `{"method":"agent_sign","params":{"payload":"..."}}` and gets answered by a single line
+//! that is either `{"ok":<result>}` or `{"error":"<message>"}`. Results have the same shape
+//! as the results of the corresponding conductor API functions.
+//!
+//! The socket is only accessible to the user running the service, and connections from
+//! processes of other users are refused.
+
+use crate::keystore::{KeyType, Keystore, Secret, PRIMARY_KEYBUNDLE_ID};
+use holochain_core_types::error::{HcResult, HolochainError};
+use holochain_dpki::{key_bundle::KeyBundle, utils::SeedContext};
+use holochain_locksmith::Mutex;
+use lib3h_sodium::secbuf::SecBuf;
+use serde_json::Value;
+use std::sync::Arc;
+
+#[cfg(unix)]
+use log::Level;
+#[cfg(unix)]
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::{FileTypeExt, PermissionsExt},
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    thread,
+};
+
+/// A request to the keystore service
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+pub enum KeystoreRequest {
+    /// Public address of the agent's primary keybundle
+    AgentId,
+    /// Sign the payload with the agent's key
+    AgentSign {
+        payload: String,
+    },
+    /// Encrypt the payload with the agent's key
+    AgentEncrypt {
+        payload: String,
+    },
+    /// Decrypt the base64 encoded payload with the agent's key
+    AgentDecrypt {
+        payload: String,
+    },
+    List,
+    AddRandomSeed {
+        dst_id: String,
+        size: usize,
+    },
+    AddSeedFromSeed {
+        src_id: String,
+        dst_id: String,
+        context: String,
+        index: u64,
+    },
+    AddKeyFromSeed {
+        src_id: String,
+        dst_id: String,
+        key_type: String,
+    },
+    Sign {
+        src_id: String,
+        payload: String,
+    },
+    GetPublicKey {
+        src_id: String,
+    },
+}
+
+/// The answer of the keystore service to a [KeystoreRequest]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeystoreResponse {
+    Ok(Value),
+    Error(String),
+}
+
+impl From<HcResult<Value>> for KeystoreResponse {
+    fn from(result: HcResult<Value>) -> Self {
+        match result {
+            Ok(value) => KeystoreResponse::Ok(value),
+            Err(err) => KeystoreResponse::Error(err.to_string()),
+        }
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Service
+//--------------------------------------------------------------------------------------------------
+
+/// Holds a keystore and answers [KeystoreRequest]s with it
+#[derive(Clone)]
+pub struct KeystoreService {
+    keystore: Arc<Mutex<Keystore>>,
+    keybundle: Arc<Mutex<KeyBundle>>,
+}
+
+impl KeystoreService {
+    /// Unlocks the agent's primary keybundle of the given keystore right away, so a
+    /// passphrase is requested on start-up and not with the first request.
+    pub fn new(mut keystore: Keystore) -> HcResult<Self> {
+        let keybundle = keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID)?;
+        Ok(KeystoreService {
+            keystore: Arc::new(Mutex::new(keystore)),
+            keybundle: Arc::new(Mutex::new(keybundle)),
+        })
+    }
+
+    pub fn handle(&self, request: KeystoreRequest) -> HcResult<Value> {
+        match request {
+            KeystoreRequest::AgentId => Ok(json!({
+                "agent_id": self.keybundle.lock().unwrap().get_id()
+            })),
+            KeystoreRequest::AgentSign { payload } => {
+                let mut message = SecBuf::with_insecure_from_string(payload);
+                let mut signature = self.keybundle.lock().unwrap().sign(&mut message)?;
+                let signature = signature.read_lock();
+                Ok(json!({ "signature": base64::encode(&**signature) }))
+            }
+            KeystoreRequest::AgentEncrypt { payload } => {
+                let mut message = SecBuf::with_insecure_from_string(payload);
+                let mut encrypted = self.keybundle.lock().unwrap().encrypt(&mut message)?;
+                let encrypted = encrypted.read_lock();
+                Ok(json!({ "message": base64::encode(&**encrypted) }))
+            }
+            KeystoreRequest::AgentDecrypt { payload } => {
+                let decoded = base64::decode(&payload).map_err(|_| {
+                    HolochainError::ErrorGeneric("payload is invalid base64".to_string())
+                })?;
+                let mut decoded_buf = SecBuf::with_insecure(decoded.len());
+                decoded_buf.from_array(&decoded)?;
+                let mut decrypted = self.keybundle.lock().unwrap().decrypt(&mut decoded_buf)?;
+                let decrypted = decrypted.read_lock();
+                let message = std::str::from_utf8(&**decrypted).map_err(|_| {
+                    HolochainError::ErrorGeneric("decrypted message is invalid utf8".to_string())
+                })?;
+                Ok(json!({ "message": message }))
+            }
+            KeystoreRequest::List => Ok(json!(self.keystore.lock().unwrap().list())),
+            KeystoreRequest::AddRandomSeed { dst_id, size } => {
+                self.keystore
+                    .lock()
+                    .unwrap()
+                    .add_random_seed(&dst_id, size)?;
+                Ok(json!({"success": true}))
+            }
+            KeystoreRequest::AddSeedFromSeed {
+                src_id,
+                dst_id,
+                context,
+                index,
+            } => {
+                let context_bytes = context.as_bytes();
+                if context_bytes.len() != 8 {
+                    return Err(HolochainError::ErrorGeneric(
+                        "`context` has to be 8 bytes".to_string(),
+                    ));
+                }
+                let mut context_bytes_array: [u8; 8] = Default::default();
+                context_bytes_array.copy_from_slice(context_bytes);
+                self.keystore.lock().unwrap().add_seed_from_seed(
+                    &src_id,
+                    &dst_id,
+                    &SeedContext::new(context_bytes_array),
+                    index,
+                )?;
+                Ok(json!({"success": true}))
+            }
+            KeystoreRequest::AddKeyFromSeed {
+                src_id,
+                dst_id,
+                key_type,
+            } => {
+                let key_type = match key_type.to_lowercase().as_str() {
+                    "signing" => KeyType::Signing,
+                    "encrypting" => KeyType::Encrypting,
+                    _ => {
+                        return Err(HolochainError::ErrorGeneric(
+                            "`key_type` has to be one of 'signing' or 'encrypting'".to_string(),
+                        ))
+                    }
+                };
+                let pub_key = self
+                    .keystore
+                    .lock()
+                    .unwrap()
+                    .add_key_from_seed(&src_id, &dst_id, key_type)?;
+                Ok(json!({ "pub_key": pub_key }))
+            }
+            KeystoreRequest::Sign { src_id, payload } => {
+                let signature = self.keystore.lock().unwrap().sign(&src_id, payload)?;
+                Ok(json!({ "signature": String::from(signature) }))
+            }
+            KeystoreRequest::GetPublicKey { src_id } => {
+                let secret = self.keystore.lock().unwrap().get(&src_id)?;
+                let pub_key = match *secret.lock().unwrap() {
+                    Secret::SigningKey(ref mut keypair) => keypair.public.to_owned(),
+                    Secret::EncryptingKey(ref mut keypair) => keypair.public.to_owned(),
+                    _ => {
+                        return Err(HolochainError::ErrorGeneric(format!(
+                            r#""{}" must be a signing or encrypting key"#,
+                            src_id
+                        )))
+                    }
+                };
+                Ok(json!({ "pub_key": pub_key }))
+            }
+        }
+    }
+
+    /// Answers a single line of the protocol with a single line (without the newline)
+    pub fn handle_line(&self, line: &str) -> String {
+        let response = serde_json::from_str::<KeystoreRequest>(line)
+            .map_err(|e| HolochainError::ErrorGeneric(format!("Invalid request: {}", e)))
+            .and_then(|request| self.handle(request));
+        serde_json::to_string(&KeystoreResponse::from(response))
+            .expect("KeystoreResponse must serialize")
+    }
+
+    /// Listens on a unix domain socket at the given path and answers requests from
+    /// every connection in its own thread. Blocks for as long as the socket is open.
+    #[cfg(unix)]
+    pub fn serve(&self, path: &str) -> HcResult<()> {
+        remove_stale_socket(path)?;
+        let listener = UnixListener::bind(path).map_err(|e| {
+            HolochainError::ErrorGeneric(format!(
+                "Could not create unix socket '{}' for keystore service: {}",
+                path, e
+            ))
+        })?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        log_info!(target: "keystore_service", "Listening on {}", path);
+        // Only processes of our own user may use the agent's keys
+        let own_uid = unsafe { libc::geteuid() };
+        for stream in listener.incoming() {
+            let stream = stream?;
+            match peer_uid(&stream) {
+                Ok(uid) if uid == own_uid => (),
+                Ok(uid) => {
+                    log_warn!(target: "keystore_service", "Refused connection from uid {}", uid);
+                    continue;
+                }
+                Err(e) => {
+                    log_warn!(target: "keystore_service", "Refused connection of unknown peer: {}", e);
+                    continue;
+                }
+            }
+            let service = self.clone();
+            thread::Builder::new()
+                .name("keystore_service_connection".to_string())
+                .spawn(move || service.serve_connection(stream))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn serve_connection(&self, stream: UnixStream) {
+        log_debug!(target: "keystore_service", "Client connected");
+        let mut writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(e) => {
+                log_error!(target: "keystore_service", "Could not clone connection: {}", e);
+                return;
+            }
+        };
+        for line in BufReader::new(stream).lines() {
+            let response = match line {
+                Ok(line) => self.handle_line(&line),
+                Err(_) => break,
+            };
+            if writeln!(writer, "{}", response).is_err() {
+                break;
+            }
+        }
+        log_debug!(target: "keystore_service", "Client disconnected");
+    }
+}
+
+/// Removes the socket file a crashed keystore service left behind, so that binding the
+/// socket again doesn't fail. Fails if a service is still listening on it or if the path
+/// is not a socket.
+#[cfg(unix)]
+fn remove_stale_socket(path: &str) -> HcResult<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "'{}' exists and is not a socket",
+            path
+        )));
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "Another keystore service is already listening on '{}'",
+            path
+        )));
+    }
+    log_debug!(target: "keystore_service", "Removing stale socket {}", path);
+    fs::remove_file(Path::new(path))?;
+    Ok(())
+}
+
+/// User id of the process on the other end of a connection
+#[cfg(target_os = "linux")]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result == 0 {
+        Ok(credentials.uid)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// User id of the process on the other end of a connection
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut uid = 0;
+    let mut gid = 0;
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } == 0 {
+        Ok(uid)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// User id of the process on the other end of a connection
+#[cfg(all(
+    unix,
+    not(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))
+))]
+fn peer_uid(_stream: &UnixStream) -> io::Result<libc::uid_t> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "peer credentials are not supported on this platform",
+    ))
+}
+
+//--------------------------------------------------------------------------------------------------
+// Client
+//--------------------------------------------------------------------------------------------------
+
+/// Connection to a [KeystoreService] used by the conductor in place of a [Keystore].
+/// Connects on the first request and reconnects after the connection broke.
+#[cfg(unix)]
+pub struct KeystoreClient {
+    path: String,
+    stream: Mutex<Option<BufReader<UnixStream>>>,
+}
+
+#[cfg(unix)]
+impl KeystoreClient {
+    pub fn new(path: String) -> Self {
+        KeystoreClient {
+            path,
+            stream: Mutex::new(None),
+        }
+    }
+
+    pub fn request(&self, request: KeystoreRequest) -> HcResult<Value> {
+        let mut stream = self.stream.lock().unwrap();
+        if stream.is_none() {
+            let connection = UnixStream::connect(&self.path).map_err(|e| {
+                HolochainError::ErrorGeneric(format!(
+                    "Could not connect to keystore service at '{}': {}",
+                    self.path, e
+                ))
+            })?;
+            *stream = Some(BufReader::new(connection));
+        }
+
+        let result = Self::send(stream.as_mut().unwrap(), &request);
+        if result.is_err() {
+            // Start over with a new connection next time
+            *stream = None;
+        }
+        match result? {
+            KeystoreResponse::Ok(value) => Ok(value),
+            KeystoreResponse::Error(message) => Err(HolochainError::ErrorGeneric(message)),
+        }
+    }
+
+    fn send(
+        stream: &mut BufReader<UnixStream>,
+        request: &KeystoreRequest,
+    ) -> HcResult<KeystoreResponse> {
+        writeln!(stream.get_mut(), "{}", serde_json::to_string(request)?)?;
+        let mut line = String::new();
+        if stream.read_line(&mut line)? == 0 {
+            return Err(HolochainError::ErrorGeneric(
+                "Keystore service closed the connection".to_string(),
+            ));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::key_loaders::test_keystore;
+
+    fn test_service() -> KeystoreService {
+        KeystoreService::new(test_keystore(&"bob".to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_requests_are_line_protocol_json() {
+        assert_eq!(
+            serde_json::to_string(&KeystoreRequest::AgentSign {
+                payload: "abc".to_string()
+            })
+            .unwrap(),
+            r#"{"method":"agent_sign","params":{"payload":"abc"}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&KeystoreRequest::List).unwrap(),
+            r#"{"method":"list"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<KeystoreRequest>(r#"{"method":"agent_id"}"#).unwrap(),
+            KeystoreRequest::AgentId
+        );
+    }
+
+    #[test]
+    fn test_service_signs_like_the_keystore() {
+        let service = test_service();
+        let mut keystore = test_keystore(&"bob".to_string());
+        let mut keybundle = keystore.get_keybundle(PRIMARY_KEYBUNDLE_ID).unwrap();
+
+        let agent_id = service.handle(KeystoreRequest::AgentId).unwrap();
+        assert_eq!(agent_id, json!({ "agent_id": keybundle.get_id() }));
+
+        let result = service
+            .handle(KeystoreRequest::AgentSign {
+                payload: "test payload".to_string(),
+            })
+            .unwrap();
+        let mut message = SecBuf::with_insecure_from_string("test payload".to_string());
+        let mut expected = keybundle.sign(&mut message).unwrap();
+        let expected = base64::encode(&**expected.read_lock());
+        assert_eq!(result, json!({ "signature": expected }));
+    }
+
+    #[test]
+    fn test_service_encrypts_and_decrypts() {
+        let service = test_service();
+        let encrypted = service
+            .handle(KeystoreRequest::AgentEncrypt {
+                payload: "secret".to_string(),
+            })
+            .unwrap();
+        let decrypted = service
+            .handle(KeystoreRequest::AgentDecrypt {
+                payload: encrypted["message"].as_str().unwrap().to_string(),
+            })
+            .unwrap();
+        assert_eq!(decrypted, json!({ "message": "secret" }));
+    }
+
+    #[test]
+    fn test_service_derives_keys() {
+        let service = test_service();
+        service
+            .handle(KeystoreRequest::AddSeedFromSeed {
+                src_id: "root_seed".to_string(),
+                dst_id: "app_seed".to_string(),
+                context: "SOMECTXT".to_string(),
+                index: 1,
+            })
+            .unwrap();
+        let added = service
+            .handle(KeystoreRequest::AddKeyFromSeed {
+                src_id: "app_seed".to_string(),
+                dst_id: "app_key".to_string(),
+                key_type: "signing".to_string(),
+            })
+            .unwrap();
+        let public = service
+            .handle(KeystoreRequest::GetPublicKey {
+                src_id: "app_key".to_string(),
+            })
+            .unwrap();
+        assert_eq!(added, public);
+        assert!(service
+            .handle(KeystoreRequest::List)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .contains(&json!("app_key")));
+    }
+
+    #[test]
+    fn test_service_answers_errors_as_lines() {
+        let service = test_service();
+        assert_eq!(
+            service.handle_line(r#"{"method":"get_public_key","params":{"src_id":"root_seed"}}"#),
+            r#"{"error":"\"root_seed\" must be a signing or encrypting key"}"#
+        );
+        assert!(service
+            .handle_line("not json")
+            .starts_with(r#"{"error":"Invalid request"#));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_client_talks_to_service_over_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keystore.sock");
+        let path = path.to_str().unwrap().to_string();
+        let service = test_service();
+        let expected = service.handle(KeystoreRequest::AgentId).unwrap();
+
+        let listener_path = path.clone();
+        thread::spawn(move || service.serve(&listener_path));
+
+        let client = KeystoreClient::new(path);
+        let mut result = client.request(KeystoreRequest::AgentId);
+        for _ in 0..50 {
+            if result.is_ok() {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(100));
+            result = client.request(KeystoreRequest::AgentId);
+        }
+        assert_eq!(result.unwrap(), expected);
+        assert!(client
+            .request(KeystoreRequest::Sign {
+                src_id: "nothing".to_string(),
+                payload: "abc".to_string(),
+            })
+            .is_err());
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stale_socket_gets_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keystore.sock");
+        let path = path.to_str().unwrap().to_string();
+
+        // no socket yet
+        assert!(remove_stale_socket(&path).is_ok());
+
+        let listener = UnixListener::bind(&path).unwrap();
+        assert!(remove_stale_socket(&path).is_err());
+
+        // a crashed service leaves its socket file behind
+        drop(listener);
+        assert!(Path::new(&path).exists());
+        assert!(remove_stale_socket(&path).is_ok());
+        assert!(!Path::new(&path).exists());
+
+        let file_path = dir.path().join("not-a-socket");
+        fs::write(&file_path, "data").unwrap();
+        assert!(remove_stale_socket(file_path.to_str().unwrap()).is_err());
+        assert!(file_path.exists());
+    }
+}
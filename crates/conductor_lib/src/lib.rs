@@ -122,6 +122,7 @@ pub mod interface;
 pub mod interface_impls;
 pub mod key_loaders;
 pub mod keystore;
+pub mod keystore_service;
 pub mod logger;
 pub mod port_utils;
 pub mod signal_wrapper;
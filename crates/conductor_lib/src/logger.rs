@@ -1,9 +1,19 @@
-use chrono::{DateTime, Local};
+use crate::config::{LogFileConfiguration, LogFormat, LoggerConfiguration};
+use chrono::{DateTime, Local, Utc};
 use colored::*;
-use holochain_core::logger::{ChannelLogger, Sender};
+use holochain_core::logger::{current_log_context, ChannelLogger, LogContext, Sender};
 use holochain_core_types::error::HolochainError;
+use holochain_logging::{rule::RuleFilter, FastLogger, FastLoggerBuilder};
+use log::{LevelFilter, Log, Metadata, Record};
 use regex::Regex;
-use std::thread;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Mutex, RwLock},
+    thread,
+};
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct LogRule {
@@ -13,6 +23,30 @@ pub struct LogRule {
     pub exclude: bool,
     #[serde(default)]
     pub color: Option<String>,
+    /// Only applies to records logged for this instance
+    #[serde(default)]
+    pub instance_id: Option<String>,
+    /// Only applies to records logged while running a function of this zome
+    #[serde(default)]
+    pub zome: Option<String>,
+    /// Most verbose level this rule lets through, instead of the logger's level
+    #[serde(default)]
+    pub level: Option<String>,
+}
+
+impl LogRule {
+    // whether the rule applies to a record with the given target, logged for the given instance and zome
+    fn applies_to(&self, target: &str, instance_id: Option<&str>, zome: Option<&str>) -> bool {
+        self.pattern.is_match(target)
+            && self
+                .instance_id
+                .as_ref()
+                .map_or(true, |id| Some(id.as_str()) == instance_id)
+            && self
+                .zome
+                .as_ref()
+                .map_or(true, |name| Some(name.as_str()) == zome)
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -74,6 +108,9 @@ impl LogRules {
             pattern: regex,
             exclude,
             color,
+            instance_id: None,
+            zome: None,
+            level: None,
         });
         Ok(())
     }
@@ -150,19 +187,22 @@ fn pick_color(text: &str) -> &str {
 
 // renders a log message, using the id color if no color specified for the message.
 pub fn render(msg: LogMessage) {
+    println!("{}", format_message(msg));
+}
+
+fn format_message(msg: LogMessage) -> String {
     let id_color = pick_color(&msg.id);
     let msg_color = match msg.color {
         None => id_color.to_string(),
         Some(color) => color,
     };
-    let x = format!(
+    format!(
         "{} {:?}:{}: {}",
         msg.date.format("%Y-%m-%d %H:%M:%S"),
         std::thread::current().id(),
         msg.id.color(id_color),
         msg.msg.color(msg_color)
-    );
-    println!("{}", x);
+    )
 }
 
 #[derive(Debug, PartialEq)]
@@ -173,6 +213,396 @@ pub struct LogMessage {
     color: Option<String>,
 }
 
+fn parse_level(level: &str) -> Result<LevelFilter, HolochainError> {
+    LevelFilter::from_str(level)
+        .map_err(|_| HolochainError::ConfigError(format!("Invalid log level: {}", level)))
+}
+
+// instances log with their name as target, see holochain_core's log macros
+fn instance_of(target: &str, log_context: &LogContext) -> Option<String> {
+    log_context.instance_id.clone().or_else(|| {
+        if target.starts_with("holochain::") {
+            Some(target["holochain::".len()..].to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Decides which records get logged: the first rule that applies to a record decides,
+/// records no rule applies to get logged up to the logger's level.
+struct LogFilter {
+    level: LevelFilter,
+    rules: LogRules,
+    // the rules' levels, parsed once when the rules get set
+    rule_levels: Vec<Option<LevelFilter>>,
+}
+
+impl LogFilter {
+    fn new(level: LevelFilter, rules: LogRules) -> Result<Self, HolochainError> {
+        let rule_levels = rules
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.level
+                    .as_ref()
+                    .map(|level| parse_level(level))
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(LogFilter {
+            level,
+            rules,
+            rule_levels,
+        })
+    }
+
+    /// The most verbose level any record can get logged with
+    fn max_level(&self) -> LevelFilter {
+        self.rule_levels
+            .iter()
+            .filter_map(|level| *level)
+            .fold(self.level, |max, level| max.max(level))
+    }
+
+    /// The first rule that applies to a record, with the level it lets through
+    fn rule_for(&self, target: &str, log_context: &LogContext) -> Option<(&LogRule, LevelFilter)> {
+        let instance_id = instance_of(target, log_context);
+        self.rules
+            .rules
+            .iter()
+            .zip(self.rule_levels.iter())
+            .find(|(rule, _)| {
+                rule.applies_to(
+                    target,
+                    instance_id.as_ref().map(String::as_str),
+                    log_context.zome.as_ref().map(String::as_str),
+                )
+            })
+            .map(|(rule, level)| (rule, level.unwrap_or(self.level)))
+    }
+
+    /// Whether a record gets logged and, if so, the color its rule gives it
+    fn check(&self, record: &Record, log_context: &LogContext) -> Option<Option<String>> {
+        match self.rule_for(record.target(), log_context) {
+            Some((rule, level)) if !rule.exclude && record.level() <= level => {
+                Some(rule.color.clone())
+            }
+            Some(_) => None,
+            None if record.level() <= self.level => Some(None),
+            None => None,
+        }
+    }
+}
+
+lazy_static! {
+    // Global like the registered logger itself, so the rules can be swapped while it runs
+    static ref LOG_FILTER: RwLock<LogFilter> = RwLock::new(LogFilter {
+        level: LevelFilter::Debug,
+        rules: LogRules::new(),
+        rule_levels: Vec::new(),
+    });
+    // Writes the text output to stdout on its own thread. Gets built after the conductor's
+    // logger is registered, so that it can't register itself as the global logger instead.
+    static ref FAST_LOGGER: RwLock<Option<FastLogger>> = RwLock::new(None);
+    // Whether a conductor registered its logger already
+    static ref REGISTERED: Mutex<bool> = Mutex::new(false);
+}
+
+fn set_log_filter(filter: LogFilter) -> Result<(), HolochainError> {
+    let max_level = filter.max_level();
+    *LOG_FILTER
+        .write()
+        .map_err(|_| HolochainError::new("Log filter lock is poisoned"))? = filter;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Handle to the global logger the conductor registers.
+/// Several conductors in one process (i.e. in tests) share the first one's output,
+/// while the last one's level and rules apply.
+pub struct ConductorLogger;
+
+impl ConductorLogger {
+    /// Registers the logger described by the given config as the global logger.
+    /// Fails if the config is invalid or a logger other than a conductor's is registered already.
+    pub fn init(config: &LoggerConfiguration) -> Result<Self, HolochainError> {
+        let level = parse_level(&config.logger_level)?;
+        let filter = LogFilter::new(level, config.rules.clone())?;
+        let mut registered = REGISTERED
+            .lock()
+            .map_err(|_| HolochainError::new("Logger registration lock is poisoned"))?;
+        if !*registered {
+            let uses_fast_logger = config.format == LogFormat::Text && config.file.is_none();
+            let output = if uses_fast_logger {
+                LogOutput::Fast
+            } else {
+                let writer: Box<dyn Write + Send> = match &config.file {
+                    Some(file_config) => Box::new(RotatingFile::open(file_config)?),
+                    None => Box::new(io::stdout()),
+                };
+                LogOutput::Lines {
+                    format: config.format.clone(),
+                    output: Mutex::new(writer),
+                }
+            };
+            log::set_boxed_logger(Box::new(RuleLogger { output })).map_err(|error| {
+                HolochainError::ErrorGeneric(format!("Could not register the logger: {}", error))
+            })?;
+            if uses_fast_logger {
+                // the rules get applied before records reach it, so it lets everything through
+                let mut logger_builder = FastLoggerBuilder::new();
+                logger_builder.set_level_from_str("Trace");
+                logger_builder.add_rule_filter(RuleFilter::new(".*", false, ""));
+                let fast_logger = logger_builder
+                    .build()
+                    .map_err(|e| HolochainError::ErrorGeneric(e.to_string()))?;
+                *FAST_LOGGER
+                    .write()
+                    .map_err(|_| HolochainError::new("Fast logger lock is poisoned"))? =
+                    Some(fast_logger);
+            }
+            *registered = true;
+        }
+        set_log_filter(filter)?;
+        Ok(ConductorLogger)
+    }
+
+    /// The rules records currently get filtered with
+    pub fn rules(&self) -> Result<LogRules, HolochainError> {
+        Ok(LOG_FILTER
+            .read()
+            .map_err(|_| HolochainError::new("Log filter lock is poisoned"))?
+            .rules
+            .clone())
+    }
+
+    /// Swaps the rules records get filtered with, keeping the logger's level
+    pub fn set_rules(&self, rules: LogRules) -> Result<(), HolochainError> {
+        let level = LOG_FILTER
+            .read()
+            .map_err(|_| HolochainError::new("Log filter lock is poisoned"))?
+            .level;
+        set_log_filter(LogFilter::new(level, rules)?)
+    }
+
+    pub fn flush(&self) {
+        log::logger().flush()
+    }
+}
+
+/// One line of JSON log output
+#[derive(Serialize)]
+struct JsonLogRecord<'a> {
+    timestamp: String,
+    level: String,
+    target: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zome: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    action_id: Option<String>,
+    message: String,
+}
+
+impl<'a> JsonLogRecord<'a> {
+    fn new(record: &'a Record, log_context: LogContext) -> Self {
+        JsonLogRecord {
+            timestamp: Utc::now().to_rfc3339(),
+            level: record.level().to_string(),
+            target: record.target(),
+            instance_id: instance_of(record.target(), &log_context),
+            agent: log_context.agent,
+            zome: log_context.zome,
+            function: log_context.function,
+            action_id: log_context.action_id,
+            message: record.args().to_string(),
+        }
+    }
+}
+
+/// Where the records that pass the log filter go
+enum LogOutput {
+    /// Colored text on stdout, written by holochain_logging's FastLogger
+    Fast,
+    /// Lines of plain text or JSON that log pipelines can index without parsing text,
+    /// written to stdout or a rotated file
+    Lines {
+        format: LogFormat,
+        // std's Mutex because locksmith's locks log themselves
+        output: Mutex<Box<dyn Write + Send>>,
+    },
+}
+
+/// One line of plain text log output
+fn format_line(record: &Record) -> String {
+    format!(
+        "{} {} {}: {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        record.level(),
+        record.target(),
+        record.args()
+    )
+}
+
+/// Passes a record on to the FastLogger, in the color of the rule that let it through
+fn log_to_fast_logger(record: &Record, color: Option<String>) {
+    let fast_logger = match FAST_LOGGER.read() {
+        Ok(fast_logger) => fast_logger,
+        Err(_) => return,
+    };
+    let fast_logger = match fast_logger.as_ref() {
+        Some(fast_logger) => fast_logger,
+        None => return,
+    };
+    match color {
+        Some(color) => {
+            let message = record.args().to_string().as_str().color(color.as_str());
+            fast_logger.log(
+                &Record::builder()
+                    .args(format_args!("{}", message))
+                    .metadata(record.metadata().clone())
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .build(),
+            )
+        }
+        None => fast_logger.log(record),
+    }
+}
+
+/// The global logger, which applies the current log rules to every record
+/// and hands the ones that pass to its output.
+struct RuleLogger {
+    output: LogOutput,
+}
+
+impl Log for RuleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // the rules need the record's log context, so they get applied in log()
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        let log_context = current_log_context();
+        let color = match LOG_FILTER.read() {
+            Ok(filter) => match filter.check(record, &log_context) {
+                Some(color) => color,
+                None => return,
+            },
+            Err(_) => return,
+        };
+        let (format, output) = match &self.output {
+            LogOutput::Fast => return log_to_fast_logger(record, color),
+            LogOutput::Lines { format, output } => (format, output),
+        };
+        let line = match format {
+            LogFormat::Text => format_line(record),
+            LogFormat::Json => {
+                match serde_json::to_string(&JsonLogRecord::new(record, log_context)) {
+                    Ok(line) => line,
+                    Err(_) => return,
+                }
+            }
+        };
+        // in one write so a rotation can't split the line
+        if let Ok(mut output) = output.lock() {
+            let _ = output.write_all(format!("{}\n", line).as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        match &self.output {
+            LogOutput::Fast => {
+                if let Ok(fast_logger) = FAST_LOGGER.read() {
+                    if let Some(fast_logger) = fast_logger.as_ref() {
+                        fast_logger.flush();
+                    }
+                }
+            }
+            LogOutput::Lines { output, .. } => {
+                if let Ok(mut output) = output.lock() {
+                    let _ = output.flush();
+                }
+            }
+        }
+    }
+}
+
+/// A log file that starts over once it gets too big, see
+/// [LogFileConfiguration](../config/struct.LogFileConfiguration.html)
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: Option<u64>,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    pub fn open(config: &LogFileConfiguration) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: config.path.clone(),
+            max_size: config.max_size,
+            max_files: config.max_files,
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = File::create(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(max_size) = self.max_size {
+            if self.size > 0 && self.size + buf.len() as u64 > max_size {
+                self.rotate()?;
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -193,6 +623,148 @@ pub mod tests {
         assert_eq!(m.msg, "xboy");
     }
 
+    fn test_record_passes(filter: &LogFilter, level: log::Level, target: &str) -> bool {
+        filter
+            .check(
+                &Record::builder()
+                    .args(format_args!("hello"))
+                    .level(level)
+                    .target(target)
+                    .build(),
+                &current_log_context(),
+            )
+            .is_some()
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let filter = LogFilter::new(LevelFilter::Info, LogRules::default()).unwrap();
+        assert!(test_record_passes(
+            &filter,
+            log::Level::Info,
+            "holochain::my-instance"
+        ));
+        assert!(!test_record_passes(
+            &filter,
+            log::Level::Debug,
+            "holochain::my-instance"
+        ));
+        assert!(!test_record_passes(
+            &filter,
+            log::Level::Error,
+            "tokio_reactor"
+        ));
+        assert!(test_record_passes(
+            &filter,
+            log::Level::Info,
+            "some_other_crate"
+        ));
+        assert_eq!(LevelFilter::Info, filter.max_level());
+    }
+
+    #[test]
+    fn test_log_filter_with_instance_and_zome_levels() {
+        let mut rules = LogRules::new();
+        rules.add_rule("^holochain", false, None).unwrap();
+        rules.rules[0].instance_id = Some("noisy".to_string());
+        rules.rules[0].level = Some("trace".to_string());
+        rules.add_rule(".*", false, None).unwrap();
+        rules.rules[1].zome = Some("blog".to_string());
+        rules.rules[1].level = Some("debug".to_string());
+        let filter = LogFilter::new(LevelFilter::Warn, rules).unwrap();
+        assert_eq!(LevelFilter::Trace, filter.max_level());
+
+        assert!(test_record_passes(
+            &filter,
+            log::Level::Trace,
+            "holochain::noisy"
+        ));
+        assert!(!test_record_passes(
+            &filter,
+            log::Level::Info,
+            "holochain::quiet"
+        ));
+        {
+            let _log_context = holochain_core::logger::enter_log_context(|log_context| {
+                log_context.zome = Some("blog".to_string());
+            });
+            assert!(test_record_passes(
+                &filter,
+                log::Level::Debug,
+                "holochain::quiet"
+            ));
+            assert!(!test_record_passes(
+                &filter,
+                log::Level::Trace,
+                "holochain::quiet"
+            ));
+        }
+
+        let mut rules = LogRules::new();
+        rules.add_rule(".*", false, None).unwrap();
+        rules.rules[0].level = Some("chatty".to_string());
+        assert!(LogFilter::new(LevelFilter::Warn, rules).is_err());
+    }
+
+    #[test]
+    fn test_invalid_logger_level_is_an_error() {
+        let config = LoggerConfiguration {
+            logger_level: "chatty".to_string(),
+            ..LoggerConfiguration::default()
+        };
+        assert_eq!(
+            ConductorLogger::init(&config).err(),
+            Some(HolochainError::ConfigError(
+                "Invalid log level: chatty".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_json_log_record() {
+        let _log_context = holochain_core::logger::enter_log_context(|log_context| {
+            log_context.zome = Some("blog".to_string());
+            log_context.function = Some("create_post".to_string());
+        });
+        let json = serde_json::to_value(JsonLogRecord::new(
+            &Record::builder()
+                .args(format_args!("hello"))
+                .level(log::Level::Info)
+                .target("holochain::my-instance")
+                .build(),
+            current_log_context(),
+        ))
+        .unwrap();
+        assert_eq!(json["level"], "INFO");
+        assert_eq!(json["instance_id"], "my-instance");
+        assert_eq!(json["zome"], "blog");
+        assert_eq!(json["function"], "create_post");
+        assert_eq!(json["message"], "hello");
+        assert!(json.get("action_id").is_none());
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("conductor.log");
+        let mut file = RotatingFile::open(&LogFileConfiguration {
+            path: path.clone(),
+            max_size: Some(10),
+            max_files: 2,
+        })
+        .unwrap();
+        for line in &["first....\n", "second...\n", "third....\n", "fourth...\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("conductor.log"), "fourth...\n");
+        assert_eq!(read("conductor.log.1"), "third....\n");
+        assert_eq!(read("conductor.log.2"), "second...\n");
+        assert!(!dir.path().join("conductor.log.3").exists());
+    }
+
     #[test]
     fn test_bad_log_rules() {
         let mut rules = LogRules::new();
@@ -36,7 +36,7 @@ use std::{
 /// The standard approach is to drop the ActionWrapper into the key of a state history HashMap and
 /// use the convenience unwrap_to! macro to extract the action data in a reducer.
 /// All reducer functions must accept an ActionWrapper so all dispatchers take an ActionWrapper.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ActionWrapper {
     action: Action,
     id: snowflake::ProcessUniqueId,
@@ -62,6 +62,15 @@ impl ActionWrapper {
     pub fn id(&self) -> &snowflake::ProcessUniqueId {
         &self.id
     }
+
+    /// the same action (as far as reducers tell) with different data,
+    /// used to adapt recorded actions for replay
+    pub(crate) fn with_action(&self, action: Action) -> Self {
+        ActionWrapper {
+            action,
+            id: self.id,
+        }
+    }
 }
 
 impl PartialEq for ActionWrapper {
@@ -82,21 +91,21 @@ impl Hash for ActionWrapper {
 }
 
 ///This describes a key for the actions
-#[derive(Clone, PartialEq, Debug, Serialize, Eq, Hash)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize, Eq, Hash)]
 pub enum QueryKey {
     Entry(GetEntryKey),
     Links(GetLinksKey),
 }
 
 ///This is a payload for the Get Method
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum QueryPayload {
     Entry,
     Links((Option<CrudStatus>, GetLinksNetworkQuery)),
 }
 
 /// All Actions for the Holochain Instance Store, according to Redux pattern.
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(tag = "action_type", content = "data")]
 #[allow(clippy::large_enum_variant)]
 pub enum Action {
@@ -122,6 +131,10 @@ pub enum Action {
     /// Removes the given item from the holding queue.
     RemoveQueuedHoldingWorkflow(PendingValidation),
 
+    /// Marks the aspect of a validation that got given up on as rejected,
+    /// so it won't get queued again.
+    RejectPendingValidation(PendingValidation),
+
     /// Adds an entry aspect to the local DHT shard.
     /// Does not validate, assumes referenced entry is valid.
     HoldAspect(EntryAspect),
@@ -183,6 +196,11 @@ pub enum Action {
     /// Triggered when we got an answer to our initial DM.
     ResolveDirectConnection(String),
 
+    /// Makes the network module stop waiting for an answer to the store-and-forward
+    /// direct message with the given ID, which the network holds for its offline recipient,
+    /// but remember it so that the answer can be recognized as delivery receipt.
+    DirectMessageQueued(String),
+
     /// Makes the network module DM the source of the given entry
     /// and prepare for receiveing an answer
     GetValidationPackage(ChainHeader),
@@ -253,7 +271,7 @@ pub type ReduceFn<S> = fn(&mut S, &State, &ActionWrapper);
 
 /// The unique key that represents a GetLinks request, used to associate the eventual
 /// response with this GetLinks request
-#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub struct GetLinksKey {
     /// The address of the Link base
     pub base_address: Address,
@@ -270,7 +288,7 @@ pub struct GetLinksKey {
 
 /// The unique key that represents a Get request, used to associate the eventual
 /// response with this Get request
-#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub struct GetEntryKey {
     /// The address of the entry to get
     pub address: Address,
@@ -281,7 +299,7 @@ pub struct GetEntryKey {
 
 /// Everything the network module needs to know in order to send a
 /// direct message.
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct DirectMessageData {
     /// The address of the node to send a message to
     pub address: Address,
@@ -296,10 +314,14 @@ pub struct DirectMessageData {
     /// Should be true if we are responding to a previous message with this message.
     /// msg_id should then be the same as the in the message that we received.
     pub is_response: bool,
+
+    /// Should be true if the network should hold the message for its recipient
+    /// if the recipient is offline.
+    pub store_and_forward: bool,
 }
 
 /// Everything the network needs to initialize
-#[derive(Clone, PartialEq, Debug, Serialize)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct NetworkSettings {
     /// P2pConfig that gets passed to [P2pNetwork](struct.P2pNetwork.html)
     /// determines how to connect to the network module.
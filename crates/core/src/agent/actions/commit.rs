@@ -6,30 +6,35 @@ use crate::{
 };
 use futures::{future::Future, task::Poll};
 use holochain_core_types::{entry::Entry, error::HolochainError};
+use holochain_metrics::publish_latency;
 use holochain_persistence_api::cas::content::Address;
-use std::{pin::Pin, sync::Arc};
+use std::{pin::Pin, sync::Arc, time::SystemTime};
 
 /// Commit Action Creator
 /// This is the high-level commit function that wraps the whole commit process and is what should
 /// be called from zome api functions and other contexts that don't care about implementation details.
 ///
 /// Returns a future that resolves to an ActionResponse.
+/// Publishes `commit_entry` latency and count metrics.
 pub async fn commit_entry(
     entry: Entry,
     maybe_link_update_delete: Option<Address>,
     context: &Arc<Context>,
 ) -> Result<Address, HolochainError> {
+    let clock = SystemTime::now();
     let action_wrapper = ActionWrapper::new(Action::Commit((
         entry.clone(),
         maybe_link_update_delete,
         vec![],
     )));
     dispatch_action(context.action_channel(), action_wrapper.clone());
-    CommitFuture {
+    let result = CommitFuture {
         context: context.clone(),
         action: action_wrapper,
     }
-    .await
+    .await;
+    publish_latency(&context.metric_publisher, "commit_entry", clock);
+    result
 }
 
 /// CommitFuture resolves to ActionResponse
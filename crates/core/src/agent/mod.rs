@@ -2,6 +2,7 @@
 ///
 pub mod actions;
 pub mod chain_store;
+pub mod revocations;
 pub mod state;
 
 use crate::state::StateWrapper;
@@ -0,0 +1,83 @@
+//! Agent keys that have been revoked, e.g. because the device holding them got compromised.
+//! Entries whose headers are signed by a revoked key and time-stamped at or after its
+//! revocation fail validation, while everything committed before stays valid.
+
+use holochain_core_types::{error::HolochainError, time::Iso8601};
+use holochain_persistence_api::cas::content::Address;
+use std::collections::HashMap;
+
+/// Where the revocations that agents published for their keys can be looked up,
+/// e.g. the DPKI instance of a conductor.
+pub trait KeyRevocationSource: Send + Sync {
+    /// When `key` got revoked, if the source knows of its revocation
+    fn revoked_at(&self, key: &Address) -> Result<Option<Iso8601>, HolochainError>;
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyRevocations {
+    revoked: HashMap<Address, Iso8601>,
+}
+
+impl KeyRevocations {
+    pub fn new() -> Self {
+        KeyRevocations::default()
+    }
+
+    /// Records that `key` is revoked from `revoked_at` on.
+    /// Revoking an already revoked key keeps the earlier revocation.
+    pub fn revoke(&mut self, key: Address, revoked_at: Iso8601) {
+        let entry = self
+            .revoked
+            .entry(key)
+            .or_insert_with(|| revoked_at.clone());
+        if revoked_at < *entry {
+            *entry = revoked_at;
+        }
+    }
+
+    /// When `key` got revoked, if it did
+    pub fn revoked_at(&self, key: &Address) -> Option<&Iso8601> {
+        self.revoked.get(key)
+    }
+
+    pub fn is_revoked(&self, key: &Address) -> bool {
+        self.revoked.contains_key(key)
+    }
+
+    /// Whether something signed by `key` at `time` was signed after the key got revoked
+    pub fn is_revoked_at(&self, key: &Address, time: &Iso8601) -> bool {
+        self.revoked_at(key)
+            .map(|revoked_at| time >= revoked_at)
+            .unwrap_or(false)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Address, &Iso8601)> {
+        self.revoked.iter()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn revocation_keeps_earliest_time() {
+        let key = Address::from("HcScKey");
+        let mut revocations = KeyRevocations::new();
+        assert!(!revocations.is_revoked(&key));
+
+        revocations.revoke(key.clone(), Iso8601::from(100));
+        assert!(revocations.is_revoked(&key));
+        assert!(!revocations.is_revoked(&Address::from("HcScOther")));
+        assert!(!revocations.is_revoked_at(&key, &Iso8601::from(99)));
+        assert!(revocations.is_revoked_at(&key, &Iso8601::from(100)));
+        assert!(revocations.is_revoked_at(&key, &Iso8601::from(101)));
+        assert!(!revocations.is_revoked_at(&Address::from("HcScOther"), &Iso8601::from(101)));
+
+        // a later revocation of the same key does not move it
+        revocations.revoke(key.clone(), Iso8601::from(200));
+        assert_eq!(revocations.revoked_at(&key), Some(&Iso8601::from(100)));
+        revocations.revoke(key.clone(), Iso8601::from(50));
+        assert_eq!(revocations.revoked_at(&key), Some(&Iso8601::from(50)));
+    }
+}
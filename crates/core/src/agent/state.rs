@@ -354,6 +354,31 @@ pub fn reduce(
     }
 }
 
+/// Reduces a commit that is being replayed from a recording, using the chain header
+/// that was created when the commit was first reduced instead of creating a new one,
+/// which would get a new timestamp and signature.
+pub(crate) fn replay_commit(
+    old_state: Arc<AgentState>,
+    action_wrapper: &ActionWrapper,
+    chain_header: &ChainHeader,
+) -> Arc<AgentState> {
+    let mut new_state: AgentState = (*old_state).clone();
+    let (entry, _, _) = unwrap_to!(action_wrapper.action() => Action::Commit);
+    let result = new_state
+        .chain_store
+        .add(entry)
+        .and_then(|_| new_state.chain_store.add(chain_header))
+        .map(|_| {
+            new_state.top_chain_header = Some(chain_header.clone());
+            entry.address()
+        });
+    new_state.actions.insert(
+        action_wrapper.clone(),
+        Response::from(AgentActionResponse::Commit(result)),
+    );
+    Arc::new(new_state)
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
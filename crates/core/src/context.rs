@@ -1,11 +1,15 @@
 use crate::{
     action::{Action, ActionWrapper},
+    agent::revocations::{KeyRevocationSource, KeyRevocations},
     content_store::GetContent,
+    dht::pending_validations::ValidationRetryPolicy,
     instance::Observer,
     network::state::NetworkState,
     persister::Persister,
+    recording::ActionRecorder,
     signal::{Signal, SignalSender},
     state::StateWrapper,
+    state_history::StateHistory,
 };
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use futures::{
@@ -101,6 +105,16 @@ pub struct Context {
     thread_pool: ThreadPool,
     pub redux_wants_write: Arc<AtomicBool>,
     pub metric_publisher: Arc<RwLock<dyn MetricPublisher>>,
+    /// Writes every reduced action to a file if set, see [recording](../recording/index.html)
+    pub action_recorder: Option<Arc<ActionRecorder>>,
+    /// Keeps the most recent states with the actions that led to them if set
+    pub state_history: Option<Arc<Mutex<StateHistory>>>,
+    /// How validations that can't be run yet get retried
+    pub validation_retry_policy: ValidationRetryPolicy,
+    /// Revoked agent keys, shared between all instances of a conductor
+    pub key_revocations: Arc<RwLock<KeyRevocations>>,
+    /// Where revocations published by other agents get looked up during validation
+    pub key_revocation_source: Option<Arc<dyn KeyRevocationSource>>,
 }
 
 impl Context {
@@ -165,6 +179,11 @@ impl Context {
             thread_pool: ThreadPool::new().expect("Could not create thread pool for futures"),
             redux_wants_write: Arc::new(AtomicBool::new(false)),
             metric_publisher,
+            action_recorder: None,
+            state_history: None,
+            validation_retry_policy: ValidationRetryPolicy::default(),
+            key_revocations: Arc::new(RwLock::new(KeyRevocations::new())),
+            key_revocation_source: None,
         }
     }
 
@@ -200,6 +219,11 @@ impl Context {
             thread_pool: ThreadPool::new().expect("Could not create thread pool for futures"),
             redux_wants_write: Arc::new(AtomicBool::new(false)),
             metric_publisher,
+            action_recorder: None,
+            state_history: None,
+            validation_retry_policy: ValidationRetryPolicy::default(),
+            key_revocations: Arc::new(RwLock::new(KeyRevocations::new())),
+            key_revocation_source: None,
         })
     }
 
@@ -1,3 +1,4 @@
 pub mod hold_aspect;
 pub mod queue_holding_workflow;
+pub mod reject_pending_validation;
 pub mod remove_queued_holding_workflow;
@@ -0,0 +1,14 @@
+use crate::{
+    action::{Action, ActionWrapper},
+    context::Context,
+    dht::pending_validations::PendingValidation,
+    instance::dispatch_action,
+};
+use std::sync::Arc;
+
+/// Removes the given validation from the holding queue and makes sure
+/// its aspect won't get queued again.
+pub fn dispatch_reject_pending_validation(pending: PendingValidation, context: &Arc<Context>) {
+    let action_wrapper = ActionWrapper::new(Action::RejectPendingValidation(pending));
+    dispatch_action(context.action_channel(), action_wrapper);
+}
@@ -45,6 +45,7 @@ fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<DhtReducer> {
         Action::HoldAspect(_) => Some(reduce_hold_aspect),
         Action::QueueHoldingWorkflow(_) => Some(reduce_queue_holding_workflow),
         Action::RemoveQueuedHoldingWorkflow(_) => Some(reduce_remove_queued_holding_workflow),
+        Action::RejectPendingValidation(_) => Some(reduce_reject_pending_validation),
         Action::Prune => Some(reduce_prune),
         _ => None,
     }
@@ -167,6 +168,9 @@ pub fn reduce_queue_holding_workflow(
     if old_store.get_holding_map().contains(&entry_aspect) {
         error!("Tried to add pending validation to queue which is already held!");
         None
+    } else if old_store.is_rejected(&entry_aspect) {
+        warn!("Tried to add pending validation to queue which got rejected before!");
+        None
     } else {
         if old_store.has_same_queued_holding_worfkow(pending) {
             warn!("Tried to add pending validation to queue which is already queued!");
@@ -236,6 +240,20 @@ pub fn reduce_remove_queued_holding_workflow(
     Some(new_store)
 }
 
+pub fn reduce_reject_pending_validation(
+    old_store: &DhtStore,
+    action_wrapper: &ActionWrapper,
+) -> Option<DhtStore> {
+    let pending = unwrap_to!(action_wrapper.action() => Action::RejectPendingValidation);
+    let aspect_address = EntryAspect::from((**pending).clone()).address();
+    let mut new_store = (*old_store).clone();
+    new_store.add_rejected_aspect(aspect_address);
+    new_store
+        .queued_holding_workflows
+        .retain(|PendingValidationWithTimeout { pending: item, .. }| item != pending);
+    Some(new_store)
+}
+
 #[cfg(test)]
 pub mod tests {
 
@@ -617,4 +635,32 @@ pub mod tests {
         let (next_pending, _) = store.next_queued_holding_workflow().unwrap();
         assert_eq!(update, next_pending);
     }
+
+    #[test]
+    pub fn test_stuck_and_rejected_validations() {
+        let context = test_context("test", None);
+        let store = DhtStore::new(context.dht_storage.clone(), context.eav_storage.clone());
+
+        let hold = create_pending_validation(test_entry(), ValidatingWorkflow::HoldEntry);
+        let retried = Arc::new(hold.failed_attempt().failed_attempt());
+        let action = ActionWrapper::new(Action::QueueHoldingWorkflow((
+            retried.clone(),
+            Some((SystemTime::now(), Duration::from_secs(10000))),
+        )));
+        let store = reduce_queue_holding_workflow(&store, &action).unwrap();
+
+        let stuck = store.stuck_validations(1);
+        assert_eq!(1, stuck.len());
+        assert_eq!(2, stuck[0].attempts);
+        assert_eq!(ValidatingWorkflow::HoldEntry, stuck[0].workflow);
+        assert!(stuck[0].retry_in.is_some());
+        assert!(store.stuck_validations(3).is_empty());
+
+        let action = ActionWrapper::new(Action::RejectPendingValidation(retried.clone()));
+        let store = reduce_reject_pending_validation(&store, &action).unwrap();
+        assert!(!store.has_exact_queued_holding_workflow(&retried));
+
+        let action = ActionWrapper::new(Action::QueueHoldingWorkflow((hold.clone(), None)));
+        assert!(reduce_queue_holding_workflow(&store, &action).is_none());
+    }
 }
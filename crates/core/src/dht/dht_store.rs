@@ -2,7 +2,7 @@ use crate::{
     content_store::{AddContent, GetContent},
     dht::{
         aspect_map::{AspectMap, AspectMapBare},
-        pending_validations::{PendingValidationWithTimeout, ValidationTimeout},
+        pending_validations::{PendingValidationWithTimeout, StuckValidation, ValidationTimeout},
     },
 };
 use holochain_core_types::{
@@ -34,6 +34,58 @@ use std::{
     time::Duration,
 };
 
+/// How many rejected aspects are remembered, the oldest get forgotten first
+/// and would be validated again if the network sends them again
+pub const MAX_REJECTED_ASPECTS: usize = 10_000;
+
+/// Addresses of the rejected aspects. The set answers membership checks while the vector
+/// keeps the order to forget them in, both are cheap to clone with the rest of the state.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct RejectedAspects {
+    addresses: im::HashSet<Address>,
+    oldest_first: im::Vector<Address>,
+}
+
+impl RejectedAspects {
+    pub fn contains(&self, address: &Address) -> bool {
+        self.addresses.contains(address)
+    }
+
+    pub fn len(&self) -> usize {
+        self.oldest_first.len()
+    }
+
+    /// Adds the address, forgetting the oldest one if there are `MAX_REJECTED_ASPECTS` already
+    pub fn insert(&mut self, address: Address) {
+        if self.contains(&address) {
+            return;
+        }
+        if self.len() >= MAX_REJECTED_ASPECTS {
+            if let Some(oldest) = self.oldest_first.pop_front() {
+                self.addresses.remove(&oldest);
+            }
+        }
+        self.addresses.insert(address.clone());
+        self.oldest_first.push_back(address);
+    }
+}
+
+impl From<VecDeque<Address>> for RejectedAspects {
+    fn from(addresses: VecDeque<Address>) -> Self {
+        let mut rejected_aspects = RejectedAspects::default();
+        for address in addresses {
+            rejected_aspects.insert(address);
+        }
+        rejected_aspects
+    }
+}
+
+impl From<&RejectedAspects> for VecDeque<Address> {
+    fn from(rejected_aspects: &RejectedAspects) -> Self {
+        rejected_aspects.oldest_first.iter().cloned().collect()
+    }
+}
+
 /// The state-slice for the DHT.
 /// Holds the CAS and EAVi that's used for the agent's local shard
 /// as well as the holding list, i.e. list of all entries held for the DHT.
@@ -47,6 +99,10 @@ pub struct DhtStore {
     holding_map: AspectMap,
 
     pub(crate) queued_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+
+    /// Addresses of the aspects whose validation got given up on with
+    /// `ValidationGiveUp::Reject`, which won't get queued again
+    rejected_aspects: RejectedAspects,
 }
 
 impl PartialEq for DhtStore {
@@ -66,6 +122,8 @@ impl PartialEq for DhtStore {
 pub struct DhtStoreSnapshot {
     pub holding_map: AspectMapBare,
     pub queued_holding_workflows: VecDeque<PendingValidationWithTimeout>,
+    #[serde(default)]
+    pub rejected_aspects: VecDeque<Address>,
 }
 
 impl From<&StateWrapper> for DhtStoreSnapshot {
@@ -73,6 +131,7 @@ impl From<&StateWrapper> for DhtStoreSnapshot {
         DhtStoreSnapshot {
             holding_map: state.dht().get_holding_map().bare().clone(),
             queued_holding_workflows: state.dht().queued_holding_workflows.clone(),
+            rejected_aspects: (&state.dht().rejected_aspects).into(),
         }
     }
 }
@@ -128,6 +187,7 @@ impl DhtStore {
             meta_storage,
             holding_map: AspectMap::new(),
             queued_holding_workflows: VecDeque::new(),
+            rejected_aspects: RejectedAspects::default(),
         }
     }
 
@@ -139,6 +199,7 @@ impl DhtStore {
         let mut new_dht_store = Self::new(content_storage, meta_storage);
         new_dht_store.holding_map = snapshot.holding_map.into();
         new_dht_store.queued_holding_workflows = snapshot.queued_holding_workflows;
+        new_dht_store.rejected_aspects = snapshot.rejected_aspects.into();
         new_dht_store
     }
 
@@ -315,6 +376,46 @@ impl DhtStore {
     pub(crate) fn queued_holding_workflows(&self) -> &VecDeque<PendingValidationWithTimeout> {
         &self.queued_holding_workflows
     }
+
+    pub(crate) fn is_rejected(&self, aspect: &EntryAspect) -> bool {
+        self.rejected_aspects.contains(&aspect.address())
+    }
+
+    /// Remembers that the aspect got rejected, forgetting the oldest rejected aspect
+    /// if there are `MAX_REJECTED_ASPECTS` already
+    pub(crate) fn add_rejected_aspect(&mut self, aspect_address: Address) {
+        self.rejected_aspects.insert(aspect_address);
+    }
+
+    /// The dependencies of a validation that are not in the local shard yet
+    pub fn missing_dependencies(&self, pending: &PendingValidation) -> Vec<Address> {
+        pending
+            .dependencies
+            .iter()
+            .filter(|dependency| !self.contains(dependency).unwrap_or(false))
+            .cloned()
+            .collect()
+    }
+
+    /// The queued validations that could not be run `min_attempts` times or more
+    pub fn stuck_validations(&self, min_attempts: u32) -> Vec<StuckValidation> {
+        self.queued_holding_workflows
+            .iter()
+            .filter(|queued| queued.pending.attempts >= min_attempts)
+            .map(
+                |PendingValidationWithTimeout { pending, timeout }| StuckValidation {
+                    entry_address: pending.entry_with_header.entry.address(),
+                    workflow: pending.workflow.clone(),
+                    attempts: pending.attempts,
+                    waiting_on: self.missing_dependencies(pending),
+                    retry_in: timeout.as_ref().and_then(|timeout| {
+                        let elapsed = timeout.time_of_dispatch.elapsed().unwrap_or_default();
+                        timeout.delay.checked_sub(elapsed)
+                    }),
+                },
+            )
+            .collect()
+    }
 }
 
 use im::HashSet;
@@ -368,6 +469,32 @@ pub mod tests {
         cas::storage::ExampleContentAddressableStorage, eav::ExampleEntityAttributeValueStorage,
     };
 
+    #[test]
+    fn rejected_aspects_are_capped_and_survive_snapshots() {
+        let content_storage = Arc::new(RwLock::new(
+            ExampleContentAddressableStorage::new().unwrap(),
+        ));
+        let meta_storage = Arc::new(RwLock::new(ExampleEntityAttributeValueStorage::new()));
+        let mut store = DhtStore::new(content_storage.clone(), meta_storage.clone());
+        let aspect = |i: usize| Address::from(format!("aspect{}", i));
+        for i in 0..=MAX_REJECTED_ASPECTS {
+            store.add_rejected_aspect(aspect(i));
+        }
+        store.add_rejected_aspect(aspect(MAX_REJECTED_ASPECTS));
+        assert_eq!(MAX_REJECTED_ASPECTS, store.rejected_aspects.len());
+        assert!(!store.rejected_aspects.contains(&aspect(0)));
+
+        let snapshot = DhtStoreSnapshot {
+            holding_map: store.get_holding_map().bare().clone(),
+            queued_holding_workflows: store.queued_holding_workflows.clone(),
+            rejected_aspects: (&store.rejected_aspects).into(),
+        };
+        let json: JsonString = snapshot.into();
+        let snapshot = DhtStoreSnapshot::try_from(json).unwrap();
+        let restored = DhtStore::new_from_snapshot(content_storage, meta_storage, snapshot);
+        assert_eq!(store.rejected_aspects, restored.rejected_aspects);
+    }
+
     #[test]
     fn get_headers_roundtrip() {
         let mut store = DhtStore::new(
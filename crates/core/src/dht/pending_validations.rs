@@ -1,5 +1,6 @@
 use crate::{
     entry::validation_dependencies::ValidationDependencies,
+    instance::{RETRY_VALIDATION_DURATION_MAX, RETRY_VALIDATION_DURATION_MIN},
     network::entry_with_header::EntryWithHeader,
 };
 use holochain_core_types::{
@@ -74,6 +75,9 @@ pub struct PendingValidationStruct {
     pub dependencies: Vec<Address>,
     pub workflow: ValidatingWorkflow,
     uuid: ProcessUniqueId,
+    /// How often this validation could not be run because something was missing
+    #[serde(default)]
+    pub attempts: u32,
 }
 
 impl PendingValidationStruct {
@@ -84,6 +88,7 @@ impl PendingValidationStruct {
             dependencies,
             workflow,
             uuid: ProcessUniqueId::new(),
+            attempts: 0,
         }
     }
 
@@ -92,6 +97,13 @@ impl PendingValidationStruct {
         clone.uuid = ProcessUniqueId::new();
         clone
     }
+
+    /// The same validation, counting one more attempt that could not be run
+    pub fn failed_attempt(&self) -> Self {
+        let mut same = self.same();
+        same.attempts += 1;
+        same
+    }
 }
 
 impl TryFrom<EntryAspect> for PendingValidationStruct {
@@ -199,3 +211,107 @@ impl PendingValidationWithTimeout {
         Self { pending, timeout }
     }
 }
+
+/// What happens to a pending validation after its last attempt
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationGiveUp {
+    /// Drop it, so it gets validated again if the aspect is sent to us again
+    Abandon,
+    /// Drop it and refuse to queue the same aspect again, as if it were invalid
+    Reject,
+}
+
+/// How often, and how far apart, validations that can't be run yet get retried
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationRetryPolicy {
+    /// Delay before the first retry
+    pub initial_delay: Duration,
+    /// Every further retry waits this many times as long as the one before
+    pub multiplier: u32,
+    pub max_delay: Duration,
+    /// Retries forever if not set
+    pub max_attempts: Option<u32>,
+    pub give_up: ValidationGiveUp,
+}
+
+impl Default for ValidationRetryPolicy {
+    fn default() -> Self {
+        ValidationRetryPolicy {
+            initial_delay: RETRY_VALIDATION_DURATION_MIN,
+            multiplier: 2,
+            max_delay: RETRY_VALIDATION_DURATION_MAX,
+            max_attempts: None,
+            give_up: ValidationGiveUp::Abandon,
+        }
+    }
+}
+
+impl ValidationRetryPolicy {
+    /// How long to wait after the given number of failed attempts,
+    /// None if the validation should be given up on
+    pub fn delay_after(&self, attempts: u32) -> Option<Duration> {
+        if self.max_attempts.map_or(false, |max| attempts >= max) {
+            return None;
+        }
+        let mut delay = self.initial_delay;
+        for _ in 1..attempts {
+            if delay >= self.max_delay {
+                break;
+            }
+            delay *= self.multiplier;
+        }
+        Some(delay.min(self.max_delay))
+    }
+}
+
+/// A queued validation that could not be run at least once
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct StuckValidation {
+    pub entry_address: Address,
+    pub workflow: ValidatingWorkflow,
+    pub attempts: u32,
+    /// The dependencies that are not in the local shard yet
+    pub waiting_on: Vec<Address>,
+    /// Time until the next attempt, None if it can be run right away
+    pub retry_in: Option<Duration>,
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_policy_backs_off_exponentially() {
+        let policy = ValidationRetryPolicy {
+            initial_delay: Duration::from_millis(100),
+            multiplier: 3,
+            max_delay: Duration::from_secs(1),
+            max_attempts: Some(5),
+            give_up: ValidationGiveUp::Reject,
+        };
+        let delays: Vec<_> = (1..=5)
+            .map(|attempts| policy.delay_after(attempts))
+            .collect();
+        assert_eq!(
+            vec![
+                Some(Duration::from_millis(100)),
+                Some(Duration::from_millis(300)),
+                Some(Duration::from_millis(900)),
+                Some(Duration::from_secs(1)),
+                None,
+            ],
+            delays
+        );
+    }
+
+    #[test]
+    fn default_retry_policy_retries_forever() {
+        let policy = ValidationRetryPolicy::default();
+        assert_eq!(Some(RETRY_VALIDATION_DURATION_MIN), policy.delay_after(1));
+        assert_eq!(
+            Some(RETRY_VALIDATION_DURATION_MAX),
+            policy.delay_after(1000)
+        );
+    }
+}
@@ -2,14 +2,20 @@ use crate::{
     action::{Action, ActionWrapper},
     consistency::ConsistencyModel,
     context::Context,
-    dht::actions::{
-        queue_holding_workflow::queue_holding_workflow,
-        remove_queued_holding_workflow::remove_queued_holding_workflow,
+    dht::{
+        actions::{
+            queue_holding_workflow::queue_holding_workflow,
+            reject_pending_validation::dispatch_reject_pending_validation,
+            remove_queued_holding_workflow::remove_queued_holding_workflow,
+        },
+        pending_validations::{PendingValidation, StuckValidation, ValidationGiveUp},
     },
+    logger::enter_log_context,
     network,
     persister::Persister,
+    recording::committed_header,
     scheduled_jobs,
-    signal::Signal,
+    signal::{Signal, ValidationGivenUp},
     state::{State, StateWrapper},
     workflows::{application, run_holding_workflow},
 };
@@ -26,8 +32,10 @@ use holochain_core_types::{
     ugly::lax_send_sync,
 };
 use holochain_locksmith::RwLock;
+use holochain_metrics::with_latency_publishing;
 #[cfg(test)]
 use holochain_persistence_api::cas::content::Address;
+use holochain_persistence_api::cas::content::AddressableContent;
 use snowflake::ProcessUniqueId;
 use std::{
     sync::{
@@ -247,6 +255,11 @@ impl Instance {
         action_wrapper: &ActionWrapper,
         context: &Arc<Context>,
     ) -> Result<(), HolochainError> {
+        let _log_context = enter_log_context(|log_context| {
+            log_context.instance_id = Some(context.get_instance_name());
+            log_context.agent = Some(context.agent_id.pub_sign_key.clone());
+            log_context.action_id = Some(action_wrapper.id().to_string());
+        });
         context.redux_wants_write.store(true, Relaxed);
         // Mutate state
         {
@@ -260,10 +273,38 @@ impl Instance {
 
             new_state = state.reduce(action_wrapper.clone());
 
+            if let Some(recorder) = &context.action_recorder {
+                let header = committed_header(action_wrapper, &state.agent(), &new_state.agent());
+                if let Err(e) = recorder.record_action(action_wrapper, header) {
+                    log_error!(
+                        context,
+                        "instance/process_action: could not record action: {:?}",
+                        e
+                    );
+                }
+            }
+
+            if let Some(history) = &context.state_history {
+                match history.lock() {
+                    Ok(mut history) => history.push(action_wrapper.clone(), new_state.clone()),
+                    Err(e) => log_error!(
+                        context,
+                        "instance/process_action: could not add to state history: {:?}",
+                        e
+                    ),
+                }
+            }
+
             // Change the state
             *state = new_state;
 
-            if let Err(e) = self.save(&state) {
+            let saved = with_latency_publishing!(
+                "save_state",
+                context.metric_publisher,
+                |()| self.save(&state),
+                ()
+            );
+            if let Err(e) = saved {
                 log_error!(
                     context,
                     "instance/process_action: could not save state: {:?}",
@@ -300,7 +341,7 @@ impl Instance {
                             .expect("Couldn't get state in run_pending_validations")
                             .dht();
                         let maybe_holding_workflow = dht_store.next_queued_holding_workflow();
-                        if let Some((pending, maybe_delay)) = maybe_holding_workflow {
+                        if let Some((pending, _)) = maybe_holding_workflow {
                             log_debug!(context, "Found queued validation: {:?}", pending);
                             // NB: If for whatever reason we pop_next_holding_workflow anywhere else other than here,
                             // we can run into a race condition.
@@ -316,26 +357,23 @@ impl Instance {
                                     // If we couldn't run the validation due to unresolved dependencies,
                                     // we have to re-add this entry at the end of the queue:
                                     Err(HolochainError::ValidationPending) => {
-                                        // And with a delay so we are not trying to re-validate many times per second.
-                                        let mut delay = maybe_delay
-                                            .map(|old_delay| {
-                                                // Exponential back-off:
-                                                // If this was delayed before we double the delay.
-                                                old_delay * 2
-                                            })
-                                            .unwrap_or(RETRY_VALIDATION_DURATION_MIN);
-
-                                        // Cap delay with max duration
-                                        if delay > RETRY_VALIDATION_DURATION_MAX {
-                                            delay = RETRY_VALIDATION_DURATION_MAX
+                                        let pending = Arc::new(pending.failed_attempt());
+                                        // And with a delay, growing with every attempt according to the
+                                        // retry policy, so we are not trying to re-validate many times per second.
+                                        match c
+                                            .validation_retry_policy
+                                            .delay_after(pending.attempts)
+                                        {
+                                            Some(delay) => {
+                                                queue_holding_workflow(
+                                                    pending,
+                                                    Some(delay),
+                                                    c.clone(),
+                                                )
+                                                .await
+                                            }
+                                            None => give_up_validation(pending, &c),
                                         }
-
-                                        queue_holding_workflow(
-                                            Arc::new(pending.same()),
-                                            Some(delay),
-                                            c.clone(),
-                                        )
-                                        .await
                                     }
                                     Err(e) => log_error!(
                                         c,
@@ -456,6 +494,46 @@ impl Drop for Instance {
     }
 }*/
 
+/// Drops a validation the retry policy ran out of attempts for, rejecting its aspect
+/// if the policy says so, and lets the conductor know with a signal.
+fn give_up_validation(pending: PendingValidation, context: &Arc<Context>) {
+    let waiting_on = context
+        .state()
+        .map(|state| state.dht().missing_dependencies(&pending))
+        .unwrap_or_default();
+    let give_up = context.validation_retry_policy.give_up.clone();
+    log_warn!(
+        context,
+        "Giving up on validation ({:?}) after {} attempts, still waiting on {:?}: {:?}",
+        give_up,
+        pending.attempts,
+        waiting_on,
+        pending
+    );
+    if give_up == ValidationGiveUp::Reject {
+        dispatch_reject_pending_validation(pending.clone(), context);
+    }
+    if let Some(tx) = context.signal_tx() {
+        let signal = Signal::ValidationGivenUp(ValidationGivenUp {
+            validation: StuckValidation {
+                entry_address: pending.entry_with_header.entry.address(),
+                workflow: pending.workflow.clone(),
+                attempts: pending.attempts,
+                waiting_on,
+                retry_in: None,
+            },
+            give_up,
+        });
+        tx.send(signal).unwrap_or_else(|e| {
+            log_warn!(
+                context,
+                "give_up_validation: Signal channel is closed! No signals can be sent ({:?}).",
+                e
+            );
+        });
+    }
+}
+
 /// Send Action to the Event Queue
 ///
 /// # Panics
@@ -40,8 +40,10 @@ pub mod logger;
 pub mod network;
 pub mod nucleus;
 pub mod persister;
+pub mod recording;
 pub mod scheduled_jobs;
 pub mod signal;
 pub mod state;
 pub mod state_dump;
+pub mod state_history;
 pub mod workflows;
@@ -4,7 +4,7 @@
 use chrono::Local;
 use crossbeam_channel;
 use holochain_locksmith::Mutex;
-use std::sync::Arc;
+use std::{cell::RefCell, sync::Arc};
 
 /// trait that defines the logging functionality that holochain_core requires
 pub trait Logger: Send {
@@ -59,6 +59,52 @@ impl ChannelLogger {
         crossbeam_channel::unbounded()
     }
 }
+/// What the current thread is doing on behalf of an instance, so loggers can add it
+/// to every record as structured fields.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogContext {
+    pub instance_id: Option<String>,
+    pub agent: Option<String>,
+    pub zome: Option<String>,
+    pub function: Option<String>,
+    pub action_id: Option<String>,
+}
+
+thread_local! {
+    static LOG_CONTEXT: RefCell<LogContext> = RefCell::new(LogContext::default());
+}
+
+/// The log context of the current thread
+pub fn current_log_context() -> LogContext {
+    LOG_CONTEXT.with(|log_context| log_context.borrow().clone())
+}
+
+/// Restores the log context it replaced when dropped
+pub struct LogContextGuard {
+    previous: Option<LogContext>,
+}
+
+impl Drop for LogContextGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            LOG_CONTEXT.with(|log_context| *log_context.borrow_mut() = previous);
+        }
+    }
+}
+
+/// Changes the log context of the current thread until the returned guard gets dropped.
+/// Fields not touched by `update` are kept, so nested scopes only need to set what they add.
+pub fn enter_log_context<F: FnOnce(&mut LogContext)>(update: F) -> LogContextGuard {
+    LOG_CONTEXT.with(|log_context| {
+        let mut log_context = log_context.borrow_mut();
+        let previous = log_context.clone();
+        update(&mut log_context);
+        LogContextGuard {
+            previous: Some(previous),
+        }
+    })
+}
+
 pub fn default_handler(msg: String) {
     let date = Local::now();
     println!(
@@ -80,6 +126,24 @@ pub mod tests {
         assert_eq!(logger.dump(), "[\"test\"]".to_string());
     }
 
+    #[test]
+    fn test_log_context_is_restored() {
+        let _outer = enter_log_context(|log_context| {
+            log_context.instance_id = Some("instance".to_string());
+        });
+        {
+            let _inner = enter_log_context(|log_context| {
+                log_context.zome = Some("zome".to_string());
+            });
+            let inner = current_log_context();
+            assert_eq!(Some("instance".to_string()), inner.instance_id);
+            assert_eq!(Some("zome".to_string()), inner.zome);
+        }
+        let outer = current_log_context();
+        assert_eq!(Some("instance".to_string()), outer.instance_id);
+        assert_eq!(None, outer.zome);
+    }
+
     #[test]
     fn test_channel_logger() {
         let (tx, rx) = ChannelLogger::setup();
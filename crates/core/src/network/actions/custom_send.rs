@@ -6,6 +6,7 @@ use crate::{
 };
 use futures::{future::Future, task::Poll};
 use holochain_core_types::{error::HolochainError, time::Timeout};
+use holochain_metrics::publish_latency;
 use holochain_persistence_api::cas::content::Address;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use snowflake::ProcessUniqueId;
@@ -14,12 +15,16 @@ use std::{pin::Pin, sync::Arc, time::SystemTime};
 /// SendDirectMessage Action Creator for custom (=app) messages
 /// This triggers the network module to open a synchronous node-to-node connection
 /// by sending the given CustomDirectMessage and preparing to receive a response.
+/// With `store_and_forward` the network holds the message if the recipient is offline,
+/// which makes this return early with a DIRECT_MESSAGE_QUEUED error.
 pub async fn custom_send(
     to_agent: Address,
     custom_direct_message: CustomDirectMessage,
     timeout: Timeout,
+    store_and_forward: bool,
     context: Arc<Context>,
 ) -> Result<String, HolochainError> {
+    let clock = SystemTime::now();
     let rand_string: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
     let id = format!("{}-{}", ProcessUniqueId::new().to_string(), rand_string);
     let direct_message = DirectMessage::Custom(custom_direct_message);
@@ -28,6 +33,7 @@ pub async fn custom_send(
         message: direct_message,
         msg_id: id.clone(),
         is_response: false,
+        store_and_forward,
     };
     let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
         direct_message_data,
@@ -35,11 +41,13 @@ pub async fn custom_send(
     )));
     dispatch_action(context.action_channel(), action_wrapper);
 
-    SendResponseFuture {
+    let result = SendResponseFuture {
         context: context.clone(),
         id,
     }
-    .await
+    .await;
+    publish_latency(&context.metric_publisher, "custom_send", clock);
+    result
 }
 
 /// SendResponseFuture waits for a result to show up in NetworkState::custom_direct_message_replys
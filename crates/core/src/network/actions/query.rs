@@ -9,6 +9,7 @@ use futures::{future::Future, task::Poll};
 use holochain_persistence_api::cas::content::Address;
 
 use holochain_core_types::{crud_status::CrudStatus, error::HcResult, time::Timeout};
+use holochain_metrics::publish_latency;
 
 use std::{pin::Pin, sync::Arc};
 
@@ -34,6 +35,7 @@ pub async fn query(
     method: QueryMethod,
     timeout: Timeout,
 ) -> HcResult<NetworkQueryResult> {
+    let clock = SystemTime::now();
     let (key, payload) = match method {
         QueryMethod::Entry(address) => {
             let key = GetEntryKey {
@@ -69,11 +71,17 @@ pub async fn query(
     let action_wrapper = ActionWrapper::new(entry);
     dispatch_action(context.action_channel(), action_wrapper.clone());
 
-    QueryFuture {
+    let metric_prefix = match key {
+        QueryKey::Entry(_) => "query.entry",
+        QueryKey::Links(_) => "query.links",
+    };
+    let result = QueryFuture {
         context: context.clone(),
         key: key.clone(),
     }
-    .await
+    .await;
+    publish_latency(&context.metric_publisher, metric_prefix, clock);
+    result
 }
 
 /// GetEntryFuture resolves to a HcResult<Entry>.
@@ -25,7 +25,8 @@ use crate::{
 };
 use boolinator::Boolinator;
 use holochain_core_types::{
-    chain_header::ChainHeader, eav::Attribute, entry::Entry, error::HolochainError, time::Timeout,
+    chain_header::ChainHeader, eav::Attribute, entry::Entry, error::HolochainError,
+    network::DIRECT_MESSAGE_QUEUED, time::Timeout,
 };
 use holochain_json_api::json::JsonString;
 use holochain_net::connection::net_connection::NetHandler;
@@ -111,6 +112,12 @@ fn handle_failure_result(
     context: &Arc<Context>,
     failure_data: GenericResultData,
 ) -> Result<(), HolochainError> {
+    // only the queued notice, real delivery errors of store-and-forward
+    // messages still have to time out like any other direct message
+    if std::str::from_utf8(&*failure_data.result_info) == Ok(DIRECT_MESSAGE_QUEUED) {
+        handle_direct_message_queued(failure_data.request_id, context.clone());
+        return Ok(());
+    }
     log_warn!(
         context,
         "handle_failure_result: unhandle failure={:?}",
@@ -133,6 +140,11 @@ pub fn create_handler(c: &Arc<Context>, my_dna_address: String) -> NetHandler {
             );
             return Ok(());
         }
+        if let (Some(recorder), Ok(message)) = (&context.action_recorder, &message) {
+            if let Err(e) = recorder.record_network_input(message) {
+                log_error!(context, "net/handle: could not record message: {:?}", e);
+            }
+        }
         match message.unwrap() {
             Lib3hServerProtocol::FailureResult(failure_data) => {
                 if !is_my_dna(&my_dna_address, &failure_data.space_address.to_string()) {
@@ -2,7 +2,8 @@ use crate::{
     action::{Action, ActionWrapper},
     context::Context,
     instance::dispatch_action,
-    network::direct_message::DirectMessage,
+    network::direct_message::{CustomDirectMessage, DirectMessage},
+    signal::{DirectMessageReceipt, Signal},
     workflows::{
         handle_custom_direct_message::handle_custom_direct_message,
         respond_validation_package_request::respond_validation_package_request,
@@ -11,6 +12,7 @@ use crate::{
 use std::sync::Arc;
 
 use holochain_json_api::{error::JsonError, json::JsonString};
+use holochain_wasm_utils::api_serialization::send::DIRECT_MESSAGE_QUEUED;
 use lib3h_protocol::data_types::DirectMessageData;
 use std::convert::TryFrom;
 
@@ -92,16 +94,34 @@ pub fn handle_send_message_result(message_data: DirectMessageData, context: Arc<
         }
     };
 
-    let initial_message = context
+    let network_state = context
         .network_state()
-        .expect("network state not initialized")
+        .expect("network state not initialized");
+    let initial_message = network_state
         .direct_message_connections
         .get(&message_data.request_id)
         .cloned();
+    let was_queued = network_state
+        .queued_direct_messages
+        .contains(&message_data.request_id);
 
     match response {
         DirectMessage::Custom(custom_direct_message) => {
             if initial_message.is_none() {
+                // the send already returned because the message got queued,
+                // this answer is its delivery receipt
+                if was_queued {
+                    let action_wrapper = ActionWrapper::new(Action::ResolveDirectConnection(
+                        message_data.request_id.clone(),
+                    ));
+                    emit_direct_message_receipt(
+                        message_data,
+                        custom_direct_message,
+                        context.clone(),
+                    );
+                    dispatch_action(context.action_channel(), action_wrapper);
+                    return;
+                }
                 log_error!(context, "net: Received a custom direct message response but could not find message ID in history. Not able to process.");
                 return;
             }
@@ -140,3 +160,167 @@ pub fn handle_send_message_result(message_data: DirectMessageData, context: Arc<
         }
     };
 }
+
+/// We got a FailureResult for a store-and-forward direct message we sent.
+/// This means the recipient is offline and the network holds the message for them,
+/// so we stop waiting for an answer and let the send return.
+pub fn handle_direct_message_queued(request_id: String, context: Arc<Context>) {
+    let is_pending = context
+        .network_state()
+        .expect("network state not initialized")
+        .direct_message_connections
+        .contains_key(&request_id);
+    if !is_pending {
+        return;
+    }
+
+    log_debug!(
+        context,
+        "net: Direct message {} queued, recipient is offline",
+        request_id
+    );
+    let action_wrapper = ActionWrapper::new(Action::HandleCustomSendResponse((
+        request_id.clone(),
+        Err(DIRECT_MESSAGE_QUEUED.to_string()),
+    )));
+    dispatch_action(context.action_channel(), action_wrapper);
+
+    let action_wrapper = ActionWrapper::new(Action::DirectMessageQueued(request_id));
+    dispatch_action(context.action_channel(), action_wrapper);
+}
+
+fn emit_direct_message_receipt(
+    message_data: DirectMessageData,
+    custom_direct_message: CustomDirectMessage,
+    context: Arc<Context>,
+) {
+    let receipt = DirectMessageReceipt {
+        msg_id: message_data.request_id,
+        from_agent: message_data.from_agent_id.into(),
+        zome: custom_direct_message.zome,
+        response: custom_direct_message.payload,
+    };
+    if let Some(sender) = context.signal_tx() {
+        if let Err(error) = sender.send(Signal::DirectMessageReceipt(receipt)) {
+            log_error!(
+                context,
+                "net: Could not send direct message receipt signal: {:?}",
+                error
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        instance::tests::test_context_with_state,
+        network::state::NetworkState,
+        signal::{signal_channel, SignalReceiver},
+        state::test_store,
+    };
+    use crossbeam_channel::{unbounded, Receiver};
+    use holochain_locksmith::RwLock;
+    use holochain_persistence_api::cas::content::Address;
+
+    const REQUEST_ID: &str = "1234";
+
+    /// a context that records dispatched actions and signals, with `pending` as the
+    /// direct messages still waiting for an answer and `queued` as the IDs of those
+    /// the network holds for their offline recipients
+    fn test_context_with_pending(
+        pending: Vec<(String, DirectMessage)>,
+        queued: Vec<String>,
+    ) -> (Arc<Context>, Receiver<ActionWrapper>, SignalReceiver) {
+        let mut context = (*test_context_with_state(None)).clone();
+        let (action_tx, action_rx) = unbounded();
+        let (signal_tx, signal_rx) = signal_channel();
+        context.action_channel = Some(action_tx);
+        context.signal_tx = Some(signal_tx);
+
+        let mut network = NetworkState::new();
+        network.direct_message_connections.extend(pending);
+        network.queued_direct_messages.extend(queued);
+        let state = test_store(Arc::new(context.clone())).with_network(network);
+        context.set_state(Arc::new(RwLock::new(state.into())));
+        (Arc::new(context), action_rx, signal_rx)
+    }
+
+    fn custom_message(payload: Result<String, String>) -> CustomDirectMessage {
+        CustomDirectMessage {
+            zome: "test_zome".to_string(),
+            payload,
+        }
+    }
+
+    fn response_data(payload: Result<String, String>) -> DirectMessageData {
+        let content: JsonString = DirectMessage::Custom(custom_message(payload)).into();
+        DirectMessageData {
+            request_id: REQUEST_ID.to_string(),
+            space_address: "test_dna".to_string().into(),
+            to_agent_id: "alice".to_string().into(),
+            from_agent_id: "bob".to_string().into(),
+            content: content.to_bytes().into(),
+        }
+    }
+
+    #[test]
+    fn answer_to_queued_message_emits_receipt() {
+        // the send returned with DIRECT_MESSAGE_QUEUED, nothing is pending anymore
+        let (context, action_rx, signal_rx) =
+            test_context_with_pending(Vec::new(), vec![REQUEST_ID.to_string()]);
+
+        handle_send_message_result(response_data(Ok("pong".to_string())), context);
+
+        match signal_rx.try_recv() {
+            Ok(Signal::DirectMessageReceipt(receipt)) => assert_eq!(
+                receipt,
+                DirectMessageReceipt {
+                    msg_id: REQUEST_ID.to_string(),
+                    from_agent: Address::from("bob"),
+                    zome: "test_zome".to_string(),
+                    response: Ok("pong".to_string()),
+                }
+            ),
+            other => panic!("expected a direct message receipt, got {:?}", other),
+        }
+        assert_eq!(
+            action_rx.try_recv().unwrap().action(),
+            &Action::ResolveDirectConnection(REQUEST_ID.to_string())
+        );
+    }
+
+    #[test]
+    fn late_answer_to_unqueued_message_is_dropped() {
+        // the send timed out, but the message was never held for an offline recipient
+        let (context, action_rx, signal_rx) = test_context_with_pending(Vec::new(), Vec::new());
+
+        handle_send_message_result(response_data(Ok("pong".to_string())), context);
+
+        assert!(signal_rx.try_recv().is_err());
+        assert!(action_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn answer_to_delivered_message_resolves_send() {
+        // the recipient was online, the send is still waiting for this answer
+        let pending = vec![(
+            REQUEST_ID.to_string(),
+            DirectMessage::Custom(custom_message(Ok("ping".to_string()))),
+        )];
+        let (context, action_rx, signal_rx) = test_context_with_pending(pending, Vec::new());
+
+        handle_send_message_result(response_data(Ok("pong".to_string())), context);
+
+        assert_eq!(
+            action_rx.try_recv().unwrap().action(),
+            &Action::HandleCustomSendResponse((REQUEST_ID.to_string(), Ok("pong".to_string())))
+        );
+        assert_eq!(
+            action_rx.try_recv().unwrap().action(),
+            &Action::ResolveDirectConnection(REQUEST_ID.to_string())
+        );
+        assert!(signal_rx.try_recv().is_err());
+    }
+}
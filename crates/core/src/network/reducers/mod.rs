@@ -34,7 +34,9 @@ use crate::{
             publish::reduce_publish,
             publish_header_entry::reduce_publish_header_entry,
             query::{reduce_query, reduce_query_timeout},
-            resolve_direct_connection::reduce_resolve_direct_connection,
+            resolve_direct_connection::{
+                reduce_direct_message_queued, reduce_resolve_direct_connection,
+            },
             respond_authoring_list::reduce_respond_authoring_list,
             respond_fetch::reduce_respond_fetch_data,
             respond_gossip_list::reduce_respond_gossip_list,
@@ -68,6 +70,7 @@ fn resolve_reducer(action_wrapper: &ActionWrapper) -> Option<NetworkReduceFn> {
         Action::ClearQueryResult(_) => Some(reduce_clear_query_result),
         Action::ClearValidationPackageResult(_) => Some(reduce_clear_validation_package_result),
         Action::ClearCustomSendResponse(_) => Some(reduce_clear_custom_send_response),
+        Action::DirectMessageQueued(_) => Some(reduce_direct_message_queued),
         Action::Query(_) => Some(reduce_query),
         Action::QueryTimeout(_) => Some(reduce_query_timeout),
         Action::GetValidationPackage(_) => Some(reduce_get_validation_package),
@@ -124,6 +127,22 @@ pub fn send(
         .ok_or_else(|| HolochainError::ErrorGeneric("Network not initialized".to_string()))?
 }
 
+/// Sends a direct message that the network holds for its recipient if they are offline.
+pub fn send_store_and_forward(
+    network_state: &mut NetworkState,
+    data: DirectMessageData,
+) -> Result<(), HolochainError> {
+    network_state
+        .network
+        .as_mut()
+        .map(|network| {
+            network
+                .send_store_and_forward_direct_message(data)
+                .map_err(|error| HolochainError::IoError(error.to_string()))
+        })
+        .ok_or_else(|| HolochainError::ErrorGeneric("Network not initialized".to_string()))?
+}
+
 /// Sends the given DirectMessage to the node given by to_agent_id.
 /// This creates a transient connection as every node-to-node communication follows a
 /// request-response pattern. This function therefore logs the open connection
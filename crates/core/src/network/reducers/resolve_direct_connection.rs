@@ -10,4 +10,18 @@ pub fn reduce_resolve_direct_connection(
 
     network_state.direct_message_connections.remove(id);
     network_state.direct_message_timeouts.remove(id);
+    network_state.queued_direct_messages.remove(id);
+}
+
+pub fn reduce_direct_message_queued(
+    network_state: &mut NetworkState,
+    _root_state: &State,
+    action_wrapper: &ActionWrapper,
+) {
+    let action = action_wrapper.action();
+    let id = unwrap_to!(action => crate::action::Action::DirectMessageQueued);
+
+    network_state.direct_message_connections.remove(id);
+    network_state.direct_message_timeouts.remove(id);
+    network_state.queued_direct_messages.insert(id.clone());
 }
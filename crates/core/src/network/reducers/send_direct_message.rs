@@ -1,6 +1,9 @@
 use crate::{
     action::{ActionWrapper, DirectMessageData},
-    network::{reducers::send, state::NetworkState},
+    network::{
+        reducers::{send, send_store_and_forward},
+        state::NetworkState,
+    },
     state::State,
 };
 use holochain_core_types::error::HolochainError;
@@ -31,6 +34,9 @@ fn inner(
         network_state
             .direct_message_connections
             .insert(data.request_id.clone(), direct_message_data.message.clone());
+        if direct_message_data.store_and_forward {
+            return send_store_and_forward(network_state, data);
+        }
         Lib3hClientProtocol::SendDirectMessage(data)
     };
 
@@ -118,6 +124,7 @@ mod tests {
             message: custom_direct_message,
             msg_id: msg_id.clone(),
             is_response: false,
+            store_and_forward: false,
         };
         let action_wrapper =
             ActionWrapper::new(Action::SendDirectMessage((direct_message_data, None)));
@@ -6,7 +6,7 @@ use boolinator::*;
 use holochain_core_types::{error::HolochainError, validation::ValidationPackage};
 use holochain_net::p2p_network::P2pNetwork;
 use holochain_persistence_api::cas::content::Address;
-use im::HashMap;
+use im::{HashMap, HashSet};
 use snowflake;
 use std::time::{Duration, SystemTime};
 
@@ -46,6 +46,11 @@ pub struct NetworkState {
     pub direct_message_connections: HashMap<String, DirectMessage>,
     pub direct_message_timeouts: HashMap<String, (SystemTime, Duration)>,
 
+    /// Store-and-forward direct messages that the network holds for their offline recipients.
+    /// Their answers arrive as delivery receipts after the send already returned.
+    /// Entries get removed through Action::ResolveDirectConnection once the receipt arrived.
+    pub queued_direct_messages: HashSet<String>,
+
     pub custom_direct_message_replys: HashMap<String, Result<String, HolochainError>>,
 
     id: snowflake::ProcessUniqueId,
@@ -70,6 +75,7 @@ impl NetworkState {
             get_validation_package_timeouts: HashMap::new(),
             direct_message_connections: HashMap::new(),
             direct_message_timeouts: HashMap::new(),
+            queued_direct_messages: HashSet::new(),
             custom_direct_message_replys: HashMap::new(),
 
             id: snowflake::ProcessUniqueId::new(),
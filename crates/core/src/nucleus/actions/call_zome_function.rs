@@ -23,14 +23,15 @@ use holochain_persistence_api::cas::content::{Address, AddressableContent};
 use holochain_json_api::json::JsonString;
 
 use holochain_dpki::utils::Verify;
+use holochain_metrics::{publish_latency, Metric};
 
 use crate::instance::dispatch_action;
 use base64;
 use futures::{future::Future, task::Poll};
 use holochain_wasm_utils::api_serialization::crypto::CryptoMethod;
-use std::{pin::Pin, sync::Arc};
+use std::{pin::Pin, sync::Arc, time::SystemTime};
 
-#[derive(Clone, Debug, PartialEq, Hash, Serialize)]
+#[derive(Clone, Debug, PartialEq, Hash, Serialize, Deserialize)]
 pub struct ExecuteZomeFnResponse {
     call: ZomeFnCall,
     result: ZomeFnResult,
@@ -276,15 +277,35 @@ pub fn verify_grant(context: Arc<Context>, grant: &CapTokenGrant, fn_call: &Zome
     }
 }
 
+/// Runs the zome function in a new thread and publishes its latency and count,
+/// plus an error count if it failed, as `call_zome_function.{zome}.{fn}` metrics.
 pub fn spawn_zome_function(context: Arc<Context>, zome_call: ZomeFnCall) {
     std::thread::Builder::new()
         .name(format!("{:?}", zome_call))
         .spawn(move || {
+            let clock = SystemTime::now();
             // Have Ribosome spin up DNA and call the zome function
             let call_result = ribosome::run_dna(
                 Some(zome_call.clone().parameters.to_bytes()),
                 WasmCallData::new_zome_call(context.clone(), zome_call.clone()),
             );
+            let metric_prefix = format!(
+                "call_zome_function.{}.{}",
+                zome_call.zome_name, zome_call.fn_name
+            );
+            publish_latency(&context.metric_publisher, &metric_prefix, clock);
+            if call_result.is_err() {
+                context
+                    .metric_publisher
+                    .write()
+                    .unwrap()
+                    .publish(&Metric::new(
+                        format!("{}.error.count", metric_prefix),
+                        None,
+                        Some(clock.into()),
+                        1.0,
+                    ));
+            }
             log_debug!(
                 context,
                 "actions/call_zome_fn: got call_result from ribosome::run_dna."
@@ -23,7 +23,7 @@ use snowflake;
 use std::sync::Arc;
 
 /// Struct holding data for tracing the call of an HDK function from a zome function
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct HdkFnCall {
     pub function: ZomeApiFunction,
     pub parameters: JsonString,
@@ -32,7 +32,7 @@ pub struct HdkFnCall {
 pub type HdkFnCallResult = Result<JsonString, String>;
 
 /// Struct holding data for requesting the execution of a Zome function (QueueZomeFunctionCall Action)
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ZomeFnCall {
     id: snowflake::ProcessUniqueId,
     pub zome_name: String,
@@ -136,6 +136,8 @@ pub mod tests {
         signature::Signature,
     };
     use holochain_json_api::json::{JsonString, RawString};
+    use holochain_locksmith::RwLock;
+    use holochain_metrics::{Metric, MetricPublisher};
     use holochain_persistence_api::cas::content::AddressableContent;
 
     /// dummy zome name compatible with ZomeFnCall
@@ -287,6 +289,49 @@ pub mod tests {
         assert_eq!(JsonString::from(RawString::from(1337)), result.unwrap());
     }
 
+    #[derive(Default)]
+    struct CollectingPublisher(Vec<Metric>);
+
+    impl MetricPublisher for CollectingPublisher {
+        fn publish(&mut self, metric: &Metric) {
+            self.0.push(metric.clone());
+        }
+    }
+
+    #[test]
+    /// tests that zome calls publish latency and count metrics named after the zome function
+    fn test_call_zome_function_publishes_metrics() {
+        let dna = test_utils::create_test_dna_with_wat("test_zome", None);
+        let test_setup = setup_test(dna, "test_call_zome_function_publishes_metrics");
+        let collected = Arc::new(RwLock::new(CollectingPublisher::default()));
+        let mut context = (*test_setup.context).clone();
+        context.metric_publisher = collected.clone();
+        let context = Arc::new(context);
+        let token = context.get_public_token().unwrap();
+
+        let zome_call =
+            ZomeFnCall::create(context.clone(), "test_zome", token, "public_test_fn", "");
+        assert!(context
+            .block_on(call_zome_function(zome_call, context.clone()))
+            .is_ok());
+
+        let names: Vec<String> = collected
+            .read()
+            .unwrap()
+            .0
+            .iter()
+            .map(|metric| metric.name.clone())
+            .filter(|name| name.starts_with("call_zome_function."))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "call_zome_function.test_zome.public_test_fn.latency",
+                "call_zome_function.test_zome.public_test_fn.count",
+            ]
+        );
+    }
+
     #[test]
     /// tests that calling an invalid DNA returns the correct error
     fn call_ribosome_wrong_dna() {
@@ -17,7 +17,7 @@ macro_rules! link_zome_api {
         /// Enumeration of all the Zome Functions known and usable in Zomes.
         /// Enumeration can convert to str.
         #[repr(usize)]
-        #[derive(FromPrimitive, Clone, Hash, Debug, PartialEq, Eq, Serialize)]
+        #[derive(FromPrimitive, Clone, Hash, Debug, PartialEq, Eq, Serialize, Deserialize)]
         pub enum ZomeApiFunction {
             /// Error index for unimplemented functions
             MissingNo = 0,
@@ -31,6 +31,7 @@ pub fn invoke_send(runtime: &mut Runtime, args: &RuntimeArgs) -> ZomeApiResult {
             args.to_agent,
             message,
             args.options.0,
+            args.store_and_forward,
             call_data.context.clone(),
         ))
         .map(|s| JsonString::from_json(&s));
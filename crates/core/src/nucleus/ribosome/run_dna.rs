@@ -1,10 +1,13 @@
-use crate::nucleus::{
-    ribosome::{
-        factories::{wasm_instance_factory, wasm_module_factory},
-        memory::WasmPageManager,
-        runtime::{Runtime, WasmCallData},
+use crate::{
+    logger::enter_log_context,
+    nucleus::{
+        ribosome::{
+            factories::{wasm_instance_factory, wasm_module_factory},
+            memory::WasmPageManager,
+            runtime::{Runtime, WasmCallData},
+        },
+        ZomeFnResult,
     },
-    ZomeFnResult,
 };
 use holochain_core_types::{
     dna::wasm::ModuleArc,
@@ -13,6 +16,7 @@ use holochain_core_types::{
     },
 };
 use holochain_json_api::json::JsonString;
+use holochain_metrics::with_latency_publishing;
 
 use holochain_wasm_utils::memory::allocation::{AllocationError, WasmAllocation};
 use std::convert::TryFrom;
@@ -57,11 +61,27 @@ fn get_module(data: WasmCallData) -> Result<ModuleArc, HolochainError> {
 /// panics if wasm binary isn't valid.
 pub fn run_dna(parameters: Option<Vec<u8>>, data: WasmCallData) -> ZomeFnResult {
     let wasm_module = get_module(data.clone())?;
-    let wasm_instance = wasm_instance_factory(&wasm_module)?;
+    let wasm_instance = match data.context() {
+        Some(context) => with_latency_publishing!(
+            "wasm_instance_factory",
+            context.metric_publisher,
+            wasm_instance_factory,
+            &wasm_module
+        ),
+        None => wasm_instance_factory(&wasm_module),
+    }?;
     // write input arguments for module call in memory Buffer
     let input_parameters: Vec<_> = parameters.unwrap_or_default();
 
     let fn_name = data.fn_name();
+    let _log_context = enter_log_context(|log_context| {
+        if let Some(context) = data.context() {
+            log_context.instance_id = Some(context.get_instance_name());
+            log_context.agent = Some(context.agent_id.pub_sign_key.clone());
+        }
+        log_context.zome = data.zome_name();
+        log_context.function = Some(fn_name.clone());
+    });
     // instantiate runtime struct for passing external state data over wasm but not to wasm
     let mut runtime = Runtime {
         memory_manager: WasmPageManager::new(&wasm_instance),
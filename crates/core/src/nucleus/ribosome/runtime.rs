@@ -69,6 +69,24 @@ impl WasmCallData {
             WasmCallData::DirectCall(name, _) => name.to_string(),
         }
     }
+
+    /// The zome the called function belongs to, direct calls don't have one.
+    pub fn zome_name(&self) -> Option<String> {
+        match self {
+            WasmCallData::ZomeCall(data) => Some(data.call.zome_name.clone()),
+            WasmCallData::CallbackCall(data) => Some(data.call.zome_name.clone()),
+            WasmCallData::DirectCall(_, _) => None,
+        }
+    }
+
+    /// The context of the instance the call runs in, direct calls don't have one.
+    pub fn context(&self) -> Option<Arc<Context>> {
+        match self {
+            WasmCallData::ZomeCall(data) => Some(data.context.clone()),
+            WasmCallData::CallbackCall(data) => Some(data.context.clone()),
+            WasmCallData::DirectCall(_, _) => None,
+        }
+    }
 }
 
 impl fmt::Display for WasmCallData {
@@ -67,7 +67,8 @@ impl From<ValidationError> for HolochainError {
 /// 1. Checks if the entry's address matches the address in given header provided by
 ///    the validation package.
 /// 2. Validates provenances given in the header by verifying the cryptographic signatures
-///    against the source agent addresses.
+///    against the source agent addresses, and makes sure none of them was made with a key
+///    that had already been revoked.
 /// 3. Finally spawns a thread to run the type specific validation callback in a Ribosome.
 ///
 /// All of this actually happens in the functions of the sub modules. This function is the
@@ -82,6 +83,11 @@ pub async fn validate_entry(
     //check_entry_type(entry.entry_type(), context)?;
     header_address::validate_header_address(&entry, &validation_data.package.chain_header)?;
     provenances::validate_provenances(&validation_data)?;
+    provenances::lookup_published_revocations(&validation_data, context)?;
+    provenances::validate_provenances_not_revoked(
+        &validation_data,
+        &context.key_revocations.read().unwrap(),
+    )?;
 
     match entry.entry_type() {
         // DNA entries are not validated currently and always valid
@@ -1,4 +1,8 @@
-use crate::nucleus::validation::{ValidationError, ValidationResult};
+use crate::{
+    agent::revocations::KeyRevocations,
+    context::Context,
+    nucleus::validation::{ValidationError, ValidationResult},
+};
 use boolinator::Boolinator;
 use holochain_core_types::validation::ValidationData;
 use holochain_dpki::utils::Verify;
@@ -30,3 +34,165 @@ pub fn validate_provenances(validation_data: &ValidationData) -> ValidationResul
         .collect::<Result<Vec<()>, ValidationError>>()?;
     Ok(())
 }
+
+/// Asks the context's key revocation source, if it has one, whether the keys of the header's
+/// provenances got revoked and adds the revocations it knows of to the context's revocations.
+/// Revocations can't be undone, so keys already known to be revoked are not looked up again.
+/// If the source can't be reached, the key counts as an unresolved dependency so that the
+/// validation gets retried later instead of passing without the revocation check.
+pub fn lookup_published_revocations(
+    validation_data: &ValidationData,
+    context: &Context,
+) -> ValidationResult {
+    let source = match context.key_revocation_source {
+        Some(ref source) => source,
+        None => return Ok(()),
+    };
+    for provenance in validation_data.package.chain_header.provenances().iter() {
+        let key = provenance.source();
+        if context.key_revocations.read().unwrap().is_revoked(&key) {
+            continue;
+        }
+        match source.revoked_at(&key) {
+            Ok(Some(revoked_at)) => context
+                .key_revocations
+                .write()
+                .unwrap()
+                .revoke(key, revoked_at),
+            Ok(None) => (),
+            Err(_) => return Err(ValidationError::UnresolvedDependencies(vec![key])),
+        }
+    }
+    Ok(())
+}
+
+/// Fails if any of the header's provenances was signed by a key that was already revoked
+/// at the time stamped into the header, so that history committed before the revocation
+/// stays valid.
+pub fn validate_provenances_not_revoked(
+    validation_data: &ValidationData,
+    revocations: &KeyRevocations,
+) -> ValidationResult {
+    let header = &validation_data.package.chain_header;
+    header
+        .provenances()
+        .iter()
+        .find(|provenance| revocations.is_revoked_at(&provenance.source(), header.timestamp()))
+        .map(|provenance| {
+            Err(ValidationError::Fail(format!(
+                "Entry {} was signed at {} by key {} which was revoked at {}",
+                header.entry_address(),
+                header.timestamp(),
+                provenance.source(),
+                revocations
+                    .revoked_at(&provenance.source())
+                    .expect("revocation to exist for revoked key"),
+            )))
+        })
+        .unwrap_or(Ok(()))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{agent::revocations::KeyRevocationSource, instance::tests::test_context};
+    use holochain_core_types::{
+        chain_header::{test_provenances, ChainHeader},
+        entry::{entry_type::test_entry_type, test_entry},
+        error::HolochainError,
+        time::Iso8601,
+        validation::{EntryLifecycle, ValidationPackage},
+    };
+    use holochain_persistence_api::cas::content::{Address, AddressableContent};
+    use std::sync::Arc;
+
+    struct TestRevocationSource(Result<Option<Iso8601>, HolochainError>);
+
+    impl KeyRevocationSource for TestRevocationSource {
+        fn revoked_at(&self, _key: &Address) -> Result<Option<Iso8601>, HolochainError> {
+            self.0.clone()
+        }
+    }
+
+    fn context_with_source(source: TestRevocationSource) -> Context {
+        let mut context = (*test_context("alice", None)).clone();
+        context.key_revocation_source = Some(Arc::new(source));
+        context
+    }
+
+    fn validation_data_at(timestamp: Iso8601) -> ValidationData {
+        let header = ChainHeader::new(
+            &test_entry_type(),
+            &test_entry().address(),
+            &test_provenances("sig"),
+            &None,
+            &None,
+            &None,
+            &timestamp,
+        );
+        ValidationData {
+            package: ValidationPackage::only_header(header),
+            lifecycle: EntryLifecycle::Chain,
+        }
+    }
+
+    #[test]
+    fn header_before_revocation_stays_valid() {
+        let validation_data = validation_data_at(Iso8601::from(999));
+        let author = validation_data.package.chain_header.provenances()[0].source();
+
+        let mut revocations = KeyRevocations::new();
+        revocations.revoke(author, Iso8601::from(1000));
+        assert_eq!(
+            validate_provenances_not_revoked(&validation_data, &revocations),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn header_at_or_after_revocation_is_rejected() {
+        let mut revocations = KeyRevocations::new();
+        for timestamp in vec![1000, 1001] {
+            let validation_data = validation_data_at(Iso8601::from(timestamp));
+            let author = validation_data.package.chain_header.provenances()[0].source();
+            assert_eq!(
+                validate_provenances_not_revoked(&validation_data, &revocations),
+                Ok(())
+            );
+            revocations.revoke(author, Iso8601::from(1000));
+            assert!(validate_provenances_not_revoked(&validation_data, &revocations).is_err());
+        }
+    }
+
+    #[test]
+    fn published_revocations_get_recorded() {
+        let revoked_at = Iso8601::from(1000);
+        let context = context_with_source(TestRevocationSource(Ok(Some(revoked_at.clone()))));
+        let validation_data = validation_data_at(Iso8601::from(1001));
+        assert_eq!(
+            lookup_published_revocations(&validation_data, &context),
+            Ok(())
+        );
+        let key = validation_data.package.chain_header.provenances()[0].source();
+        assert_eq!(
+            context.key_revocations.read().unwrap().revoked_at(&key),
+            Some(&revoked_at)
+        );
+        assert!(validate_provenances_not_revoked(
+            &validation_data,
+            &context.key_revocations.read().unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn unreachable_revocation_source_defers_validation() {
+        let context = context_with_source(TestRevocationSource(Err(HolochainError::Timeout)));
+        let validation_data = validation_data_at(Iso8601::from(1001));
+        let key = validation_data.package.chain_header.provenances()[0].source();
+        assert_eq!(
+            lookup_published_revocations(&validation_data, &context),
+            Err(ValidationError::UnresolvedDependencies(vec![key]))
+        );
+    }
+}
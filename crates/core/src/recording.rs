@@ -0,0 +1,352 @@
+//! Record-and-replay of an instance's action stream.
+//!
+//! Every state change of an instance is the result of reducing an action, so the
+//! sequence of reduced actions is all it takes to rebuild the state of an instance
+//! somewhere else, e.g. on a developer's machine to reproduce a reported bug.
+//! An [ActionRecorder](struct.ActionRecorder.html) set on the context writes every
+//! action the instance reduces, together with all messages the network module
+//! hands to the instance, to a file of JSON lines. [Recording](struct.Recording.html)
+//! reads such a file back and replays its actions against fresh memory storage.
+//!
+//! Replaying only reproduces the original state if the recording covers the whole
+//! life of the instance, i.e. it was started with an empty chain. A restarted
+//! instance continues its recording instead of starting a new one.
+use crate::{
+    action::{Action, ActionWrapper, NetworkSettings},
+    agent::state::AgentState,
+    context::Context,
+    persister::SimplePersister,
+    state::State,
+};
+use holochain_core_types::{agent::AgentId, chain_header::ChainHeader, error::HolochainError};
+use holochain_locksmith::{Mutex, RwLock};
+use holochain_metrics::DefaultMetricPublisher;
+use holochain_net::p2p_config::P2pConfig;
+use holochain_persistence_mem::{cas::memory::MemoryStorage, eav::memory::EavMemoryStorage};
+use jsonrpc_core::IoHandler;
+use lib3h_protocol::protocol_server::Lib3hServerProtocol;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, LineWriter, Lines, Write},
+    path::Path,
+    sync::Arc,
+    time::SystemTime,
+};
+
+/// Bumped whenever recordings written by older versions can't be replayed anymore
+pub const RECORDING_FORMAT_VERSION: u32 = 1;
+
+/// The first line of a recording, everything needed to set up a context for replay
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub format_version: u32,
+    pub instance_name: String,
+    pub agent_id: AgentId,
+    pub started: SystemTime,
+}
+
+/// Something that happened to the recorded instance
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum RecordedEvent {
+    /// An action got reduced, in the order of reduction.
+    /// Commits come with the chain header they added, as creating a header
+    /// takes a timestamp and a signature which would differ on replay.
+    Action {
+        action_wrapper: ActionWrapper,
+        committed_header: Option<ChainHeader>,
+    },
+    /// The network module handed a message to the instance.
+    /// Only recorded for inspection, replay uses the actions dispatched in response.
+    NetworkInput(Lib3hServerProtocol),
+}
+
+/// One line of a recording after the header
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedLine {
+    pub time: SystemTime,
+    pub event: RecordedEvent,
+}
+
+/// Writes the action stream of an instance to a file.
+/// Every line gets flushed right away so a recording survives a crash of the conductor.
+pub struct ActionRecorder {
+    writer: Mutex<LineWriter<File>>,
+}
+
+impl ActionRecorder {
+    /// Opens the recording at `path` to add to it. An existing recording gets continued,
+    /// so restarting the instance doesn't lose what got recorded before, but only if it
+    /// was recorded for the same agent. A new recording starts with the recording header.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        instance_name: &str,
+        agent_id: &AgentId,
+    ) -> Result<Self, HolochainError> {
+        let path = path.as_ref();
+        let is_new = !path.exists() || fs::metadata(path)?.len() == 0;
+        if !is_new {
+            let header = Recording::load_header(path)?;
+            if header.agent_id != *agent_id {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Recording {} was made for another agent",
+                    path.display()
+                )));
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let recorder = ActionRecorder {
+            writer: Mutex::new(LineWriter::new(file)),
+        };
+        if is_new {
+            recorder.write_line(&RecordingHeader {
+                format_version: RECORDING_FORMAT_VERSION,
+                instance_name: instance_name.to_string(),
+                agent_id: agent_id.clone(),
+                started: SystemTime::now(),
+            })?;
+        }
+        Ok(recorder)
+    }
+
+    pub fn record_action(
+        &self,
+        action_wrapper: &ActionWrapper,
+        committed_header: Option<ChainHeader>,
+    ) -> Result<(), HolochainError> {
+        self.record(RecordedEvent::Action {
+            action_wrapper: action_wrapper.clone(),
+            committed_header,
+        })
+    }
+
+    pub fn record_network_input(
+        &self,
+        message: &Lib3hServerProtocol,
+    ) -> Result<(), HolochainError> {
+        self.record(RecordedEvent::NetworkInput(message.clone()))
+    }
+
+    fn record(&self, event: RecordedEvent) -> Result<(), HolochainError> {
+        self.write_line(&RecordedLine {
+            time: SystemTime::now(),
+            event,
+        })
+    }
+
+    fn write_line<T: serde::Serialize>(&self, line: &T) -> Result<(), HolochainError> {
+        let json = serde_json::to_string(line)
+            .map_err(|e| HolochainError::SerializationError(e.to_string()))?;
+        let mut writer = self.writer.lock()?;
+        writeln!(writer, "{}", json)?;
+        Ok(())
+    }
+}
+
+fn parse_error(number: usize, error: serde_json::Error) -> HolochainError {
+    HolochainError::SerializationError(format!(
+        "Could not parse line {} of recording: {}",
+        number, error
+    ))
+}
+
+/// A recording read back from a file
+#[derive(Clone, Debug)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub lines: Vec<RecordedLine>,
+}
+
+impl Recording {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, HolochainError> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = Self::read_header(&mut lines)?;
+        let lines = lines
+            .enumerate()
+            .map(|(index, line)| {
+                serde_json::from_str(&line?).map_err(|e| parse_error(index + 2, e))
+            })
+            .collect::<Result<Vec<RecordedLine>, HolochainError>>()?;
+        Ok(Recording { header, lines })
+    }
+
+    fn load_header(path: &Path) -> Result<RecordingHeader, HolochainError> {
+        Self::read_header(&mut BufReader::new(File::open(path)?).lines())
+    }
+
+    fn read_header(lines: &mut Lines<BufReader<File>>) -> Result<RecordingHeader, HolochainError> {
+        let header: RecordingHeader = match lines.next() {
+            Some(line) => serde_json::from_str(&line?).map_err(|e| parse_error(1, e))?,
+            None => return Err(HolochainError::ErrorGeneric("Recording is empty".into())),
+        };
+        if header.format_version != RECORDING_FORMAT_VERSION {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Recording has format version {}, can only replay version {}",
+                header.format_version, RECORDING_FORMAT_VERSION
+            )));
+        }
+        Ok(header)
+    }
+
+    /// The recorded actions, in the order they got reduced
+    pub fn actions(&self) -> impl Iterator<Item = &ActionWrapper> {
+        self.recorded_actions()
+            .map(|(action_wrapper, _)| action_wrapper)
+    }
+
+    fn recorded_actions(&self) -> impl Iterator<Item = (&ActionWrapper, Option<&ChainHeader>)> {
+        self.lines.iter().filter_map(|line| match &line.event {
+            RecordedEvent::Action {
+                action_wrapper,
+                committed_header,
+            } => Some((action_wrapper, committed_header.as_ref())),
+            RecordedEvent::NetworkInput(_) => None,
+        })
+    }
+
+    /// A context for the recorded agent with empty memory storages and an unconnected
+    /// memory network, which is what replaying starts from.
+    pub fn replay_context(&self) -> Arc<Context> {
+        let cas = Arc::new(RwLock::new(MemoryStorage::new()));
+        Arc::new(Context::new(
+            &self.header.instance_name,
+            self.header.agent_id.clone(),
+            Arc::new(RwLock::new(SimplePersister::new(cas.clone()))),
+            cas.clone(),
+            cas,
+            Arc::new(RwLock::new(EavMemoryStorage::new())),
+            P2pConfig::new_with_unique_memory_backend(),
+            Some(Arc::new(RwLock::new(IoHandler::new()))),
+            None,
+            false,
+            Arc::new(RwLock::new(DefaultMetricPublisher::default())),
+        ))
+    }
+
+    /// Rebuilds the state of the recorded instance by reducing the first `until`
+    /// recorded actions, or all of them, in the order they were reduced originally.
+    /// The network gets initialized with the context's network config instead of
+    /// the recorded one so replaying never talks to the original network, and
+    /// messages it receives get ignored.
+    pub fn replay(&self, context: Arc<Context>, until: Option<usize>) -> State {
+        self.recorded_actions()
+            .take(until.unwrap_or(std::usize::MAX))
+            .fold(
+                State::new(context.clone()),
+                |state, (action_wrapper, committed_header)| {
+                    state.replay(replayable(action_wrapper, &context), committed_header)
+                },
+            )
+    }
+}
+
+/// The chain header a reduced commit added to the source chain, if it succeeded
+pub(crate) fn committed_header(
+    action_wrapper: &ActionWrapper,
+    old_agent: &AgentState,
+    new_agent: &AgentState,
+) -> Option<ChainHeader> {
+    match action_wrapper.action() {
+        Action::Commit(_) => new_agent
+            .top_chain_header()
+            .filter(|header| Some(header) != old_agent.top_chain_header().as_ref()),
+        _ => None,
+    }
+}
+
+fn replayable(action_wrapper: &ActionWrapper, context: &Context) -> ActionWrapper {
+    match action_wrapper.action() {
+        Action::InitNetwork(settings) => {
+            action_wrapper.with_action(Action::InitNetwork(NetworkSettings {
+                p2p_config: context.p2p_config.clone(),
+                ..settings.clone()
+            }))
+        }
+        _ => action_wrapper.clone(),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        action::tests::test_action_wrapper_commit, instance::tests::test_context,
+        nucleus::actions::tests::test_dna,
+    };
+    use holochain_core_types::entry::{test_entry, Entry};
+    use tempfile::tempdir;
+
+    #[test]
+    fn recording_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("recording.jsonl");
+        let context = test_context("alice", None);
+        let commit = test_action_wrapper_commit();
+
+        let recorder = ActionRecorder::open(&path, "alice-instance", &context.agent_id).unwrap();
+        recorder.record_action(&commit, None).unwrap();
+        recorder
+            .record_network_input(&Lib3hServerProtocol::Terminated)
+            .unwrap();
+
+        let recording = Recording::load(&path).unwrap();
+        assert_eq!("alice-instance", recording.header.instance_name);
+        assert_eq!(context.agent_id, recording.header.agent_id);
+        assert_eq!(2, recording.lines.len());
+        let actions: Vec<_> = recording.actions().collect();
+        assert_eq!(vec![&commit], actions);
+        assert_eq!(commit.action(), actions[0].action());
+    }
+
+    #[test]
+    fn reopened_recording_gets_continued() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("recording.jsonl");
+        let context = test_context("alice", None);
+        let commit = test_action_wrapper_commit();
+
+        let recorder = ActionRecorder::open(&path, "alice-instance", &context.agent_id).unwrap();
+        recorder.record_action(&commit, None).unwrap();
+        drop(recorder);
+        let recorder = ActionRecorder::open(&path, "alice-instance", &context.agent_id).unwrap();
+        recorder.record_action(&commit, None).unwrap();
+
+        let recording = Recording::load(&path).unwrap();
+        assert_eq!(2, recording.actions().count());
+
+        let bob = test_context("bob", None);
+        assert!(ActionRecorder::open(&path, "alice-instance", &bob.agent_id).is_err());
+    }
+
+    #[test]
+    fn replay_rebuilds_the_same_source_chain() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("recording.jsonl");
+        let context = test_context("alice", None);
+        let recorder = ActionRecorder::open(&path, "alice-instance", &context.agent_id).unwrap();
+
+        let dna = test_dna();
+        let mut state = State::new(context.clone());
+        for action in vec![
+            Action::InitializeChain(dna.clone()),
+            Action::Commit((Entry::Dna(Box::new(dna)), None, vec![])),
+            Action::Commit((test_entry(), None, vec![])),
+        ] {
+            let action_wrapper = ActionWrapper::new(action);
+            let new_state = state.reduce(action_wrapper.clone());
+            let header = committed_header(&action_wrapper, &state.agent(), &new_state.agent());
+            recorder.record_action(&action_wrapper, header).unwrap();
+            state = new_state;
+        }
+        let original_chain: Vec<ChainHeader> = state.agent().iter_chain().collect();
+        assert_eq!(2, original_chain.len());
+
+        let recording = Recording::load(&path).unwrap();
+        let replayed = recording.replay(recording.replay_context(), None);
+        let replayed_chain: Vec<ChainHeader> = replayed.agent().iter_chain().collect();
+        assert_eq!(original_chain, replayed_chain);
+
+        let replayed = recording.replay(recording.replay_context(), Some(2));
+        assert_eq!(1, replayed.agent().iter_chain().count());
+    }
+}
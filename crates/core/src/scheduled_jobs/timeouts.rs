@@ -73,6 +73,7 @@ mod tests {
             message: custom_direct_message,
             msg_id: msg_id.clone(),
             is_response: false,
+            store_and_forward: false,
         };
         let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((
             direct_message_data,
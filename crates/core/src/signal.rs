@@ -1,6 +1,11 @@
-use crate::{action::ActionWrapper, consistency::ConsistencySignal};
+use crate::{
+    action::ActionWrapper,
+    consistency::ConsistencySignal,
+    dht::pending_validations::{StuckValidation, ValidationGiveUp},
+};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use holochain_json_api::{error::JsonError, json::JsonString};
+use holochain_persistence_api::cas::content::Address;
 use holochain_wasm_utils::api_serialization::emit_signal::EmitSignalArgs;
 use serde::{Deserialize, Deserializer};
 use snowflake::ProcessUniqueId;
@@ -13,6 +18,8 @@ pub enum Signal {
     Trace(ActionWrapper),
     Consistency(ConsistencySignal<String>),
     User(UserSignal),
+    DirectMessageReceipt(DirectMessageReceipt),
+    ValidationGivenUp(ValidationGivenUp),
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, DefaultJson, PartialEq)]
@@ -21,6 +28,27 @@ pub struct UserSignal {
     pub arguments: JsonString,
 }
 
+/// Emitted when the answer to a store-and-forward direct message arrives after
+/// `hdk::send_store_and_forward` already returned because the recipient was offline.
+/// The answer proves the message got delivered.
+#[derive(Clone, Debug, Serialize, Deserialize, DefaultJson, PartialEq)]
+pub struct DirectMessageReceipt {
+    pub msg_id: String,
+    /// the agent the message was sent to, who answered it
+    pub from_agent: Address,
+    pub zome: String,
+    /// what the `receive` callback of the recipient returned
+    pub response: Result<String, String>,
+}
+
+/// Emitted when a validation that could not be run is dropped from the holding queue
+/// because the instance's validation retry policy ran out of attempts.
+#[derive(Clone, Debug, Serialize, Deserialize, DefaultJson, PartialEq)]
+pub struct ValidationGivenUp {
+    pub validation: StuckValidation,
+    pub give_up: ValidationGiveUp,
+}
+
 impl From<EmitSignalArgs> for UserSignal {
     fn from(args: EmitSignalArgs) -> UserSignal {
         UserSignal {
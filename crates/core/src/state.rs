@@ -119,6 +119,16 @@ impl State {
         }
     }
 
+    /// the same state with the given network state, so tests can set up
+    /// pending direct messages without a running network
+    #[cfg(test)]
+    pub fn with_network(self, network: NetworkState) -> Self {
+        State {
+            network: Arc::new(network),
+            ..self
+        }
+    }
+
     fn get_dna(
         agent_state: &AgentState,
         cas: Arc<RwLock<dyn ContentAddressableStorage>>,
@@ -161,6 +171,40 @@ impl State {
         }
     }
 
+    /// Like `reduce`, but a replayed commit gets the chain header it got when it was
+    /// first reduced, so replaying a recording rebuilds the exact same source chain.
+    pub(crate) fn replay(
+        &self,
+        action_wrapper: ActionWrapper,
+        chain_header: Option<&ChainHeader>,
+    ) -> Self {
+        match chain_header {
+            Some(chain_header) => State {
+                agent: crate::agent::state::replay_commit(
+                    Arc::clone(&self.agent),
+                    &action_wrapper,
+                    chain_header,
+                ),
+                ..self.reduce_without_agent(&action_wrapper)
+            },
+            None => self.reduce(action_wrapper),
+        }
+    }
+
+    fn reduce_without_agent(&self, action_wrapper: &ActionWrapper) -> Self {
+        State {
+            nucleus: crate::nucleus::reduce(Arc::clone(&self.nucleus), &self, action_wrapper),
+            agent: Arc::clone(&self.agent),
+            dht: crate::dht::dht_reducers::reduce(Arc::clone(&self.dht), action_wrapper),
+            network: crate::network::reducers::reduce(
+                Arc::clone(&self.network),
+                &self,
+                action_wrapper,
+            ),
+            conductor_api: self.conductor_api.clone(),
+        }
+    }
+
     pub fn nucleus(&self) -> Arc<NucleusState> {
         Arc::clone(&self.nucleus)
     }
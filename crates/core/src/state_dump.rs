@@ -4,6 +4,7 @@ use crate::{
     dht::{aspect_map::AspectMapBare, pending_validations::PendingValidationWithTimeout},
     network::direct_message::DirectMessage,
     nucleus::{ZomeFnCall, ZomeFnCallState},
+    state::StateWrapper,
 };
 use holochain_core_types::{chain_header::ChainHeader, entry::Entry, error::HolochainError};
 use holochain_json_api::json::JsonString;
@@ -25,15 +26,18 @@ pub struct StateDump {
 
 impl From<Arc<Context>> for StateDump {
     fn from(context: Arc<Context>) -> StateDump {
-        let (agent, nucleus, network, dht) = {
-            let state_lock = context.state().expect("No state?!");
-            (
-                (*state_lock.agent()).clone(),
-                (*state_lock.nucleus()).clone(),
-                (*state_lock.network()).clone(),
-                (*state_lock.dht()).clone(),
-            )
-        };
+        StateDump::from(&context.state().expect("No state?!"))
+    }
+}
+
+impl From<&StateWrapper> for StateDump {
+    fn from(state: &StateWrapper) -> StateDump {
+        let (agent, nucleus, network, dht) = (
+            (*state.agent()).clone(),
+            (*state.nucleus()).clone(),
+            (*state.network()).clone(),
+            (*state.dht()).clone(),
+        );
 
         let source_chain: Vec<ChainHeader> = agent.iter_chain().collect();
         let source_chain: Vec<ChainHeader> = source_chain.into_iter().rev().collect();
@@ -0,0 +1,134 @@
+//! Bounded history of an instance's most recent states, each with the action that led
+//! to it, to see how an instance got into the state it is in.
+//! States share all unchanged data with their predecessors, so keeping them around is
+//! cheap compared to dumping them, which only happens when the history gets inspected.
+use crate::{action::ActionWrapper, state::StateWrapper, state_dump::StateDump};
+use std::{collections::VecDeque, time::SystemTime};
+
+/// A state together with the action that got reduced to reach it
+#[derive(Clone, Debug)]
+pub struct StateTransition {
+    pub action: ActionWrapper,
+    pub time: SystemTime,
+    pub state: StateWrapper,
+}
+
+/// Serializable form of a [StateTransition](struct.StateTransition.html)
+#[derive(Serialize)]
+pub struct StateTransitionDump {
+    pub action: ActionWrapper,
+    /// Milliseconds since the UNIX epoch
+    pub time: u64,
+    pub state: StateDump,
+}
+
+impl From<&StateTransition> for StateTransitionDump {
+    fn from(transition: &StateTransition) -> Self {
+        StateTransitionDump {
+            action: transition.action.clone(),
+            time: transition
+                .time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|since_epoch| since_epoch.as_millis() as u64)
+                .unwrap_or_default(),
+            state: StateDump::from(&transition.state),
+        }
+    }
+}
+
+/// Ring buffer of the most recent state transitions, oldest first
+#[derive(Clone, Debug)]
+pub struct StateHistory {
+    capacity: usize,
+    transitions: VecDeque<StateTransition>,
+}
+
+impl StateHistory {
+    pub fn new(capacity: usize) -> Self {
+        StateHistory {
+            capacity,
+            transitions: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Adds a transition, dropping the oldest one if the history is full
+    pub fn push(&mut self, action: ActionWrapper, state: StateWrapper) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.transitions.len() == self.capacity {
+            self.transitions.pop_front();
+        }
+        self.transitions.push_back(StateTransition {
+            action,
+            time: SystemTime::now(),
+            state,
+        });
+    }
+
+    pub fn transitions(&self) -> impl Iterator<Item = &StateTransition> {
+        self.transitions.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    /// Dumps the `count` most recent transitions, or all of them, oldest first
+    pub fn dump(&self, count: Option<usize>) -> Vec<StateTransitionDump> {
+        let skip = count
+            .map(|count| self.len().saturating_sub(count))
+            .unwrap_or(0);
+        self.transitions
+            .iter()
+            .skip(skip)
+            .map(StateTransitionDump::from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        action::{tests::test_action_wrapper_commit, Action},
+        instance::tests::test_context,
+    };
+
+    #[test]
+    fn history_keeps_the_most_recent_transitions() {
+        let context = test_context("alice", None);
+        let mut history = StateHistory::new(2);
+        let mut state = StateWrapper::new(context);
+        let actions = vec![
+            ActionWrapper::new(Action::Prune),
+            test_action_wrapper_commit(),
+            ActionWrapper::new(Action::Prune),
+        ];
+        for action in actions.iter() {
+            state = state.reduce(action.clone());
+            history.push(action.clone(), state.clone());
+        }
+
+        assert_eq!(2, history.len());
+        let kept: Vec<_> = history.transitions().map(|t| t.action.clone()).collect();
+        assert_eq!(actions[1..].to_vec(), kept);
+
+        let dump = history.dump(Some(1));
+        assert_eq!(1, dump.len());
+        assert_eq!(actions[2], dump[0].action);
+        assert_eq!(2, history.dump(None).len());
+    }
+
+    #[test]
+    fn history_of_size_zero_keeps_nothing() {
+        let context = test_context("alice", None);
+        let mut history = StateHistory::new(0);
+        history.push(test_action_wrapper_commit(), StateWrapper::new(context));
+        assert!(history.is_empty());
+    }
+}
@@ -7,6 +7,7 @@ use crate::{
 };
 
 use holochain_core_types::error::HolochainError;
+use holochain_metrics::with_latency_publishing;
 use holochain_persistence_api::cas::content::Address;
 use holochain_wasm_utils::api_serialization::receive::ReceiveParams;
 use std::sync::Arc;
@@ -25,13 +26,16 @@ pub async fn handle_custom_direct_message(
         .payload
         .map_err(|error| format!("Got error in initial custom direct message: {}", error))?;
 
-    let result = receive(
+    let result = with_latency_publishing!(
+        format!("handle_custom_direct_message.{}", zome),
+        context.metric_publisher,
+        receive,
         context.clone(),
         &zome,
         &CallbackParams::Receive(ReceiveParams {
             from: from_agent_id.clone(),
             payload,
-        }),
+        })
     );
     let response = match result {
         CallbackResult::ReceiveResult(response) => Ok(response),
@@ -48,6 +52,7 @@ pub async fn handle_custom_direct_message(
         message: direct_message,
         msg_id,
         is_response: true,
+        store_and_forward: false,
     };
 
     let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((direct_message_data, None)));
@@ -34,8 +34,9 @@ use holochain_core_types::{
     error::HolochainError,
     validation::{ValidationPackage, ValidationPackageDefinition},
 };
+use holochain_metrics::publish_latency;
 use holochain_persistence_api::cas::content::AddressableContent;
-use std::sync::Arc;
+use std::{sync::Arc, time::SystemTime};
 
 /// Try to create a ValidationPackage for the given entry without calling out to some other node.
 /// I.e. either create it just from/with the header if `ValidationPackageDefinition` is `Entry`,
@@ -236,11 +237,14 @@ pub mod tests {
 
 /// Runs the given pending validation using the right holding workflow
 /// as specified by PendingValidationStruct::workflow.
+/// Publishes latency and count metrics per workflow.
 pub async fn run_holding_workflow(
     pending: PendingValidation,
     context: Arc<Context>,
 ) -> Result<(), HolochainError> {
-    match pending.workflow {
+    let clock = SystemTime::now();
+    let workflow: String = pending.workflow.clone().into();
+    let result = match pending.workflow {
         ValidatingWorkflow::HoldLink => {
             hold_link_workflow(&pending.entry_with_header, context.clone()).await
         }
@@ -256,5 +260,11 @@ pub async fn run_holding_workflow(
         ValidatingWorkflow::RemoveEntry => {
             hold_remove_workflow(&pending.entry_with_header, context.clone()).await
         }
-    }
+    };
+    publish_latency(
+        &context.metric_publisher,
+        &format!("run_holding_workflow.{}", workflow),
+        clock,
+    );
+    result
 }
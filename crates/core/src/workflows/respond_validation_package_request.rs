@@ -44,6 +44,7 @@ pub fn respond_validation_package_request(
         message: direct_message,
         msg_id,
         is_response: true,
+        store_and_forward: false,
     };
 
     let action_wrapper = ActionWrapper::new(Action::SendDirectMessage((direct_message_data, None)));
@@ -1,2 +1,7 @@
 pub mod entry_aspect;
 pub mod query;
+
+/// error of a store-and-forward direct message whose recipient is offline,
+/// the network sends it in the `result_info` of a `FailureResult`
+/// and the zome gets it back from `send()`
+pub const DIRECT_MESSAGE_QUEUED: &str = "Direct message queued, the recipient is offline";
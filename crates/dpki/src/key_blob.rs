@@ -6,6 +6,7 @@ use crate::{
     keypair::*,
     password_encryption::{self, pw_dec, pw_enc, pw_hash, EncryptedData, PwHashConfig},
     seed::*,
+    seed_share::SeedShare,
     utils, SEED_SIZE,
 };
 use holochain_core_types::{
@@ -34,6 +35,7 @@ pub enum BlobType {
     KeyBundle,
     SigningKey,
     EncryptingKey,
+    SeedShare,
     // TODO futur blobbables?
     // Key,
 }
@@ -461,6 +463,99 @@ impl Blobbable for EncryptingKeyPair {
     }
 }
 
+//--------------------------------------------------------------------------------------------------
+// SeedShare
+//--------------------------------------------------------------------------------------------------
+
+const SEED_SHARE_BLOB_FORMAT_VERSION: u8 = 1;
+
+const SEED_SHARE_BLOB_SIZE: usize = 1 // version byte
+    + 1 // threshold
+    + 1 // index
+    + SEED_SIZE;
+
+pub const SEED_SHARE_BLOB_SIZE_ALIGNED: usize = ((SEED_SHARE_BLOB_SIZE + 8 - 1) / 8) * 8;
+
+impl Blobbable for SeedShare {
+    fn blob_type() -> BlobType {
+        BlobType::SeedShare
+    }
+
+    fn blob_size() -> usize {
+        SEED_SHARE_BLOB_SIZE_ALIGNED
+    }
+
+    /// Generate an encrypted blob of a seed share to hand to a trustee
+    /// @param {SecBuf} passphrase - the encryption passphrase
+    /// @param {string} hint - additional info / description for the share
+    /// @param {Option<PwHashConfig>} config - Settings for pwhash
+    fn as_blob(
+        &mut self,
+        passphrase: &mut SecBuf,
+        hint: String,
+        config: Option<PwHashConfig>,
+    ) -> HcResult<KeyBlob> {
+        // Initialize buffer
+        let mut data_buf = SecBuf::with_secure(SEED_SHARE_BLOB_SIZE_ALIGNED);
+        // Write version, threshold and index
+        data_buf
+            .write(
+                0,
+                &[SEED_SHARE_BLOB_FORMAT_VERSION, self.threshold, self.index],
+            )
+            .unwrap();
+        // Write share
+        data_buf
+            .write(3, &**self.buf.read_lock())
+            .expect("Failed blobbing seed share");
+
+        // Finalize
+        let encoded_blob = Self::finalize_blobbing(&mut data_buf, passphrase, config)?;
+
+        // Done
+        Ok(KeyBlob {
+            seed_type: self.kind.clone(),
+            blob_type: BlobType::SeedShare,
+            hint,
+            data: encoded_blob,
+        })
+    }
+
+    /// Get the seed share back from an encrypted blob
+    /// @param {object} blob - the seed share blob to unblob
+    /// @param {SecBuf} passphrase - decryption passphrase
+    /// @param {Option<PwHashConfig>} config - Settings for pwhash
+    fn from_blob(
+        blob: &KeyBlob,
+        passphrase: &mut SecBuf,
+        config: Option<PwHashConfig>,
+    ) -> HcResult<SeedShare> {
+        // Retrieve data buf from blob
+        let mut share_blob = Self::unblob(blob, passphrase, config)?;
+
+        // Deserialize manually
+        let mut share_buf = SecBuf::with_secure(SEED_SIZE);
+        let (threshold, index) = {
+            let share_blob = share_blob.read_lock();
+            if share_blob[0] != SEED_SHARE_BLOB_FORMAT_VERSION {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Invalid SeedShare Blob Format: v{:?} != v{:?}",
+                    share_blob[0], SEED_SHARE_BLOB_FORMAT_VERSION
+                )));
+            }
+            share_buf.write(0, &share_blob[3..SEED_SHARE_BLOB_SIZE])?;
+            (share_blob[1], share_blob[2])
+        };
+        // Done
+        Ok(SeedShare::new(
+            blob.seed_type.clone(),
+            threshold,
+            index,
+            share_buf,
+        ))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -565,6 +660,39 @@ mod tests {
         assert_eq!(0, root_seed.buf.compare(&mut initial_seed.buf));
     }
 
+    #[test]
+    fn it_should_blob_seed_shares() {
+        let mut passphrase = generate_random_seed_buf();
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        let shares = root_seed.split(2, 3).unwrap();
+
+        let mut unblobbed: Vec<SeedShare> = shares
+            .into_iter()
+            .take(2)
+            .map(|mut share| {
+                let blob = share
+                    .as_blob(&mut passphrase, "hint".to_string(), TEST_CONFIG)
+                    .unwrap();
+                assert_eq!(BlobType::SeedShare, blob.blob_type);
+                assert_eq!(SeedType::Root, blob.seed_type);
+                let mut unblob = SeedShare::from_blob(&blob, &mut passphrase, TEST_CONFIG).unwrap();
+                assert_eq!(share.threshold, unblob.threshold);
+                assert_eq!(share.index, unblob.index);
+                assert_eq!(0, unblob.buf.compare(&mut share.buf));
+                unblob
+            })
+            .collect();
+
+        let mut rebuilt = RootSeed::combine(&mut unblobbed).unwrap();
+        assert_eq!(
+            0,
+            rebuilt
+                .seed_mut()
+                .buf
+                .compare(&mut root_seed.seed_mut().buf)
+        );
+    }
+
     #[test]
     fn it_should_blob_device_pin_seed() {
         let mut passphrase = generate_random_seed_buf();
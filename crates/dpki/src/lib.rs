@@ -23,4 +23,5 @@ pub mod key_bundle;
 pub mod keypair;
 pub mod password_encryption;
 pub mod seed;
+pub mod seed_share;
 pub mod utils;
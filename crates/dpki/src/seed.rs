@@ -77,6 +77,16 @@ where
     fn get_mnemonic(&mut self) -> HcResult<String>;
 }
 
+/// Collapses any whitespace (several spaces, line breaks from copying a phrase off
+/// paper) between the words of a mnemonic phrase into single spaces.
+fn normalize_phrase(phrase: &str) -> String {
+    phrase
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
 //--------------------------------------------------------------------------------------------------
 // Seed
 //--------------------------------------------------------------------------------------------------
@@ -123,12 +133,20 @@ impl Seed {
 impl MnemonicableSeed for Seed {
     // TODO: We need some way of zeroing the internal memory used by mnemonic
     fn new_with_mnemonic(phrase: String, seed_type: SeedType) -> HcResult<Self> {
+        let phrase = normalize_phrase(&phrase);
+        let word_count = phrase.split(' ').count();
         let mnemonic = Mnemonic::from_phrase(phrase, Language::English).map_err(|e| {
             HolochainError::ErrorGeneric(format!("Error loading Mnemonic phrase: {}", e))
         })?;
 
         let entropy = mnemonic.entropy().to_owned();
-        assert_eq!(entropy.len(), SEED_SIZE);
+        if entropy.len() != SEED_SIZE {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Mnemonic phrase has {} words but a seed needs {}",
+                word_count,
+                MnemonicType::Words24.word_count()
+            )));
+        }
         let mut seed_buf = SecBuf::with_secure(entropy.len());
         seed_buf.from_array(entropy.as_slice())?;
         // Done
@@ -176,6 +194,16 @@ impl RootSeed {
         }
     }
 
+    /// Take the typed seed out of a seed of unknown type, failing if it is not a root seed
+    fn from_seed(seed: Seed) -> HcResult<Self> {
+        match seed.into_typed()? {
+            TypedSeed::Root(root_seed) => Ok(root_seed),
+            _ => Err(HolochainError::ErrorGeneric(
+                "Seed is not a root seed".to_string(),
+            )),
+        }
+    }
+
     /// Generate Device Seed
     /// @param {number} index - the index number in this seed group, must not be zero
     pub fn generate_device_seed(
@@ -189,6 +217,18 @@ impl RootSeed {
     }
 }
 
+/// Root seeds are backed up as the 24 word mnemonic of their 32 bytes so that an agent
+/// identity can be written down and restored on another machine.
+impl MnemonicableSeed for RootSeed {
+    fn new_with_mnemonic(phrase: String, seed_type: SeedType) -> HcResult<Self> {
+        RootSeed::from_seed(Seed::new_with_mnemonic(phrase, seed_type)?)
+    }
+
+    fn get_mnemonic(&mut self) -> HcResult<String> {
+        self.inner.get_mnemonic()
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // DeviceSeed
 //--------------------------------------------------------------------------------------------------
@@ -215,6 +255,16 @@ impl DeviceSeed {
         }
     }
 
+    /// Take the typed seed out of a seed of unknown type, failing if it is not a device seed
+    fn from_seed(seed: Seed) -> HcResult<Self> {
+        match seed.into_typed()? {
+            TypedSeed::Device(device_seed) => Ok(device_seed),
+            _ => Err(HolochainError::ErrorGeneric(
+                "Seed is not a device seed".to_string(),
+            )),
+        }
+    }
+
     /// generate a device pin seed by applying pwhash of pin with this seed as the salt
     /// @param {string} pin - should be >= 4 characters 1-9
     /// @return {DevicePinSeed} Resulting Device Pin Seed
@@ -229,6 +279,16 @@ impl DeviceSeed {
     }
 }
 
+impl MnemonicableSeed for DeviceSeed {
+    fn new_with_mnemonic(phrase: String, seed_type: SeedType) -> HcResult<Self> {
+        DeviceSeed::from_seed(Seed::new_with_mnemonic(phrase, seed_type)?)
+    }
+
+    fn get_mnemonic(&mut self) -> HcResult<String> {
+        self.inner.get_mnemonic()
+    }
+}
+
 //--------------------------------------------------------------------------------------------------
 // DevicePinSeed
 //--------------------------------------------------------------------------------------------------
@@ -303,20 +363,23 @@ impl EncryptedSeed {
 impl MnemonicableSeed for EncryptedSeed {
     fn new_with_mnemonic(phrase: String, seed_type: SeedType) -> HcResult<Self> {
         // split out the two phrases, decode then combine the bytes
-        let entropy: Vec<u8> = phrase
-            .split(' ')
-            .collect::<Vec<&str>>()
-            .chunks(MnemonicType::Words24.word_count())
-            .map(|chunk| {
-                Mnemonic::from_phrase(chunk.join(" "), Language::English)
-                    .unwrap()
-                    .entropy()
-                    .to_owned()
-            })
-            .flatten()
-            .collect();
-
-        assert_eq!(entropy.len(), SEED_SIZE + ABYTES + SALTBYTES);
+        let phrase = normalize_phrase(&phrase);
+        let words = phrase.split(' ').collect::<Vec<&str>>();
+        if words.len() != MnemonicType::Words24.word_count() * 2 {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Mnemonic phrase has {} words but an encrypted seed needs {}",
+                words.len(),
+                MnemonicType::Words24.word_count() * 2
+            )));
+        }
+        let mut entropy: Vec<u8> = Vec::with_capacity(SEED_SIZE + ABYTES + SALTBYTES);
+        for chunk in words.chunks(MnemonicType::Words24.word_count()) {
+            let mnemonic =
+                Mnemonic::from_phrase(chunk.join(" "), Language::English).map_err(|e| {
+                    HolochainError::ErrorGeneric(format!("Error loading Mnemonic phrase: {}", e))
+                })?;
+            entropy.extend_from_slice(mnemonic.entropy());
+        }
 
         let enc_data = EncryptedData {
             nonce: [0; NONCEBYTES].to_vec(), // zero nonce
@@ -469,6 +532,44 @@ mod tests {
         assert_eq!(0, seed.buf.compare(&mut seed_2.buf));
     }
 
+    #[test]
+    fn it_should_roundtrip_root_and_device_seed_mnemonics() {
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        let mnemonic = root_seed.get_mnemonic().unwrap();
+        // whitespace and case are not significant when typing a phrase back in
+        let retyped = format!(" {}\n", mnemonic.to_uppercase().replace(' ', "  "));
+        let mut restored = RootSeed::new_with_mnemonic(retyped, SeedType::Root).unwrap();
+        assert_eq!(
+            0,
+            root_seed
+                .seed_mut()
+                .buf
+                .compare(&mut restored.seed_mut().buf)
+        );
+
+        let context = SeedContext::new(*b"HCDEVICE");
+        let mut device_seed = root_seed.generate_device_seed(&context, 1).unwrap();
+        let mnemonic = device_seed.get_mnemonic().unwrap();
+        let mut restored =
+            DeviceSeed::new_with_mnemonic(mnemonic.clone(), SeedType::Device).unwrap();
+        assert_eq!(
+            0,
+            device_seed
+                .seed_mut()
+                .buf
+                .compare(&mut restored.seed_mut().buf)
+        );
+
+        assert!(RootSeed::new_with_mnemonic(mnemonic.clone(), SeedType::Device).is_err());
+        let twelve_words = mnemonic
+            .split(' ')
+            .take(12)
+            .collect::<Vec<&str>>()
+            .join(" ");
+        assert!(RootSeed::new_with_mnemonic(twelve_words, SeedType::Root).is_err());
+        assert!(EncryptedSeed::new_with_mnemonic(mnemonic, SeedType::Root).is_err());
+    }
+
     #[test]
     fn it_should_change_into_typed() {
         // Root
@@ -0,0 +1,338 @@
+//! Shamir secret sharing of seeds.
+//!
+//! A seed gets split into `share_count` shares of which any `threshold` are enough to
+//! rebuild it, while fewer reveal nothing about it. This lets the holder of a [RootSeed]
+//! hand shares to trusted parties so they can jointly recover the seed if its passphrase
+//! is lost. Each byte of the seed is shared separately over GF(256).
+
+use crate::{
+    seed::{MnemonicableSeed, RootSeed, Seed, SeedTrait, SeedType},
+    utils::generate_random_buf,
+    SEED_SIZE,
+};
+use bip39::{Language, Mnemonic};
+use holochain_core_types::error::{HcResult, HolochainError};
+use lib3h_sodium::secbuf::SecBuf;
+
+//--------------------------------------------------------------------------------------------------
+// GF(256) arithmetic
+//--------------------------------------------------------------------------------------------------
+
+/// Multiplication in GF(256) with the reducing polynomial x^8 + x^4 + x^3 + x + 1.
+/// Runs the same instructions for all operands, as they are bytes of secrets:
+/// instead of branching on a bit, it gets spread into an all-ones or all-zeros mask.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Multiplicative inverse in GF(256), i.e. a^254. Only defined for a != 0.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1;
+    for _ in 0..254 {
+        result = gf_mul(result, a);
+    }
+    result
+}
+
+//--------------------------------------------------------------------------------------------------
+// SeedShare
+//--------------------------------------------------------------------------------------------------
+
+/// One share of a seed split with [Seed::split]
+#[derive(Debug)]
+pub struct SeedShare {
+    /// Type of the seed this is a share of
+    pub kind: SeedType,
+    /// Number of shares needed to rebuild the seed
+    pub threshold: u8,
+    /// Position of this share, from 1 to the number of shares created
+    pub index: u8,
+    pub buf: SecBuf,
+}
+
+impl SeedShare {
+    pub fn new(seed_type: SeedType, threshold: u8, index: u8, share_buf: SecBuf) -> Self {
+        assert_eq!(share_buf.len(), SEED_SIZE);
+        SeedShare {
+            kind: seed_type,
+            threshold,
+            index,
+            buf: share_buf,
+        }
+    }
+}
+
+/// A share's mnemonic starts with `<threshold>-<index>`, followed by the 24 words of its
+/// share of the seed, e.g. `2-3 abandon ability ...` for the third share of a seed that
+/// needs two shares to be rebuilt.
+impl MnemonicableSeed for SeedShare {
+    fn new_with_mnemonic(phrase: String, seed_type: SeedType) -> HcResult<Self> {
+        let mut words = phrase.split_whitespace();
+        let header = words.next().unwrap_or_default();
+        let invalid_header = || {
+            HolochainError::ErrorGeneric(format!(
+                "Invalid seed share header '{}', expected <threshold>-<index>",
+                header
+            ))
+        };
+        let mut numbers = header.splitn(2, '-').map(|n| n.parse::<u8>());
+        let threshold = numbers
+            .next()
+            .and_then(|n| n.ok())
+            .ok_or_else(invalid_header)?;
+        let index = numbers
+            .next()
+            .and_then(|n| n.ok())
+            .ok_or_else(invalid_header)?;
+
+        let seed = Seed::new_with_mnemonic(words.collect::<Vec<&str>>().join(" "), seed_type)?;
+        Ok(SeedShare::new(seed.kind, threshold, index, seed.buf))
+    }
+
+    fn get_mnemonic(&mut self) -> HcResult<String> {
+        let entropy = self.buf.read_lock();
+        let mnemonic = Mnemonic::from_entropy(&*entropy, Language::English).map_err(|e| {
+            HolochainError::ErrorGeneric(format!("Error generating Mnemonic phrase: {}", e))
+        })?;
+        Ok(format!(
+            "{}-{} {}",
+            self.threshold,
+            self.index,
+            mnemonic.into_phrase()
+        ))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Splitting and combining
+//--------------------------------------------------------------------------------------------------
+
+impl Seed {
+    /// Split this seed into shares of which any `threshold` can rebuild it with [Seed::combine]
+    /// @param {u8} threshold - number of shares needed to rebuild the seed, at least 2
+    /// @param {u8} share_count - number of shares to create, at least `threshold`
+    /// @return {Vec<SeedShare>} the shares, with indexes 1 to `share_count`
+    pub fn split(&mut self, threshold: u8, share_count: u8) -> HcResult<Vec<SeedShare>> {
+        if threshold < 2 || share_count < threshold {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "Invalid seed sharing scheme: {} of {} shares",
+                threshold, share_count
+            )));
+        }
+        // Every byte of the seed is the constant term of its own random polynomial
+        // of degree threshold - 1.
+        let mut coefficients = generate_random_buf(SEED_SIZE * (threshold as usize - 1));
+        let coefficients = coefficients.read_lock();
+        let seed = self.buf.read_lock();
+
+        let mut shares = Vec::with_capacity(share_count as usize);
+        for index in 1..=share_count {
+            let mut share_buf = SecBuf::with_secure(SEED_SIZE);
+            {
+                let mut share = share_buf.write_lock();
+                for byte in 0..SEED_SIZE {
+                    // Horner's method, starting from the highest coefficient
+                    let mut y = 0;
+                    for degree in (1..threshold as usize).rev() {
+                        y = gf_mul(y, index) ^ coefficients[(degree - 1) * SEED_SIZE + byte];
+                    }
+                    share[byte] = gf_mul(y, index) ^ seed[byte];
+                }
+            }
+            shares.push(SeedShare::new(
+                self.kind.clone(),
+                threshold,
+                index,
+                share_buf,
+            ));
+        }
+        Ok(shares)
+    }
+
+    /// Rebuild a seed from at least `threshold` of the shares created by [Seed::split].
+    /// Shares of different seeds can not be told apart from shares of the same seed, so
+    /// combining them does not fail but produces a wrong seed.
+    pub fn combine(shares: &mut [SeedShare]) -> HcResult<Self> {
+        let (kind, threshold) = match shares.first() {
+            Some(share) => (share.kind.clone(), share.threshold),
+            None => {
+                return Err(HolochainError::ErrorGeneric(
+                    "No seed shares given".to_string(),
+                ))
+            }
+        };
+        if shares
+            .iter()
+            .any(|share| share.kind != kind || share.threshold != threshold)
+        {
+            return Err(HolochainError::ErrorGeneric(
+                "Seed shares are not from the same seed".to_string(),
+            ));
+        }
+        if shares.len() < threshold as usize {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "{} seed shares are needed but only {} were given",
+                threshold,
+                shares.len()
+            )));
+        }
+        let shares = &mut shares[..threshold as usize];
+        let indexes: Vec<u8> = shares.iter().map(|share| share.index).collect();
+        for (i, index) in indexes.iter().enumerate() {
+            if *index == 0 || indexes[..i].contains(index) {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "Invalid or duplicate seed share index {}",
+                    index
+                )));
+            }
+        }
+
+        // Lagrange interpolation at x = 0; subtraction is addition (xor) in GF(256)
+        let mut seed_buf = SecBuf::with_secure(SEED_SIZE);
+        {
+            let mut seed = seed_buf.write_lock();
+            for (i, share) in shares.iter_mut().enumerate() {
+                let mut basis = 1;
+                for (j, other_index) in indexes.iter().enumerate() {
+                    if i != j {
+                        basis = gf_mul(
+                            basis,
+                            gf_mul(*other_index, gf_inv(other_index ^ indexes[i])),
+                        );
+                    }
+                }
+                let share = share.buf.read_lock();
+                for byte in 0..SEED_SIZE {
+                    seed[byte] ^= gf_mul(share[byte], basis);
+                }
+            }
+        }
+        Ok(Seed::new(seed_buf, kind))
+    }
+}
+
+impl RootSeed {
+    /// Split this root seed into shares of which any `threshold` can rebuild it,
+    /// for escrowing its recovery with trusted parties
+    pub fn split(&mut self, threshold: u8, share_count: u8) -> HcResult<Vec<SeedShare>> {
+        self.seed_mut().split(threshold, share_count)
+    }
+
+    /// Rebuild a root seed from at least `threshold` of its shares
+    pub fn combine(shares: &mut [SeedShare]) -> HcResult<Self> {
+        if shares.iter().any(|share| share.kind != SeedType::Root) {
+            return Err(HolochainError::ErrorGeneric(
+                "Seed shares are not shares of a root seed".to_string(),
+            ));
+        }
+        Ok(RootSeed::new(Seed::combine(shares)?.buf))
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Tests
+//--------------------------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::generate_random_seed_buf;
+
+    #[test]
+    fn it_should_do_gf256_arithmetic() {
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn it_should_split_and_combine_root_seed() {
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        let mut shares = root_seed.split(3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert_eq!(
+            shares.iter().map(|share| share.index).collect::<Vec<u8>>(),
+            vec![1, 2, 3, 4, 5]
+        );
+
+        // any three shares rebuild the seed
+        for picked in &[[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let mut subset: Vec<SeedShare> = picked
+                .iter()
+                .map(|i| {
+                    let share = &mut shares[*i];
+                    let mut buf = SecBuf::with_secure(SEED_SIZE);
+                    buf.from_array(&*share.buf.read_lock()).unwrap();
+                    SeedShare::new(share.kind.clone(), share.threshold, share.index, buf)
+                })
+                .collect();
+            let mut rebuilt = RootSeed::combine(&mut subset).unwrap();
+            assert_eq!(
+                0,
+                rebuilt
+                    .seed_mut()
+                    .buf
+                    .compare(&mut root_seed.seed_mut().buf)
+            );
+        }
+
+        // two are not enough
+        assert!(RootSeed::combine(&mut shares[..2]).is_err());
+        // and the same share twice does not count
+        let mut duplicate = SecBuf::with_secure(SEED_SIZE);
+        duplicate.from_array(&*shares[0].buf.read_lock()).unwrap();
+        let mut with_duplicate = vec![
+            SeedShare::new(SeedType::Root, 3, 1, duplicate),
+            SeedShare::new(SeedType::Root, 3, 2, generate_random_seed_buf()),
+            SeedShare::new(SeedType::Root, 3, 1, generate_random_seed_buf()),
+        ];
+        assert!(RootSeed::combine(&mut with_duplicate).is_err());
+    }
+
+    #[test]
+    fn it_should_reject_invalid_sharing_schemes() {
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        assert!(root_seed.split(0, 3).is_err());
+        // a single share would just be a copy of the seed
+        assert!(root_seed.split(1, 3).is_err());
+        assert!(root_seed.split(4, 3).is_err());
+        assert_eq!(root_seed.split(2, 2).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn it_should_roundtrip_share_mnemonics() {
+        let mut root_seed = RootSeed::new(generate_random_seed_buf());
+        let shares = root_seed.split(2, 3).unwrap();
+
+        let mut restored: Vec<SeedShare> = shares
+            .into_iter()
+            .skip(1)
+            .map(|mut share| {
+                let mnemonic = share.get_mnemonic().unwrap();
+                assert!(mnemonic.starts_with(&format!("2-{} ", share.index)));
+                assert_eq!(mnemonic.split(' ').count(), 25);
+                SeedShare::new_with_mnemonic(mnemonic, SeedType::Root).unwrap()
+            })
+            .collect();
+        let mut rebuilt = RootSeed::combine(&mut restored).unwrap();
+        assert_eq!(
+            0,
+            rebuilt
+                .seed_mut()
+                .buf
+                .compare(&mut root_seed.seed_mut().buf)
+        );
+
+        assert!(SeedShare::new_with_mnemonic("abandon".to_string(), SeedType::Root).is_err());
+        assert!(SeedShare::new_with_mnemonic("2x abandon".to_string(), SeedType::Root).is_err());
+    }
+}
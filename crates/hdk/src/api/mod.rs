@@ -66,7 +66,7 @@ pub use self::{
     property::property,
     query::{query, query_result},
     remove_link::remove_link,
-    send::send,
+    send::{send, send_store_and_forward},
     sign::{sign, sign_one_time, verify_signature},
     sleep::sleep,
     update_remove::{remove_entry, update_agent, update_entry},
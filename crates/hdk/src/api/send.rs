@@ -132,5 +132,30 @@ pub fn send(to_agent: Address, payload: String, timeout: Timeout) -> ZomeApiResu
         to_agent,
         payload,
         options: SendOptions(timeout),
+        store_and_forward: false,
+    })
+}
+
+/// Like [send](fn.send.html), but if the receiving agent is offline the message is held
+/// by the network and delivered as soon as they come back online.
+///
+/// If the message could be delivered right away this returns the string returned by the
+/// `receive` callback of the other node, just like `send`. If the receiver is offline this
+/// returns an error with the message [DIRECT_MESSAGE_QUEUED](../../holochain_wasm_utils/api_serialization/send/constant.DIRECT_MESSAGE_QUEUED.html)
+/// and the answer of the `receive` callback is emitted later as a `DirectMessageReceipt`
+/// signal of the sending instance, which doubles as delivery receipt.
+///
+/// Holding messages is currently only supported by the sim2h network backend,
+/// with other backends this behaves exactly like `send`.
+pub fn send_store_and_forward(
+    to_agent: Address,
+    payload: String,
+    timeout: Timeout,
+) -> ZomeApiResult<String> {
+    Dispatch::Send.with_input(SendArgs {
+        to_agent,
+        payload,
+        options: SendOptions(timeout),
+        store_and_forward: true,
     })
 }
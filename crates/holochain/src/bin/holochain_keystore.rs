@@ -0,0 +1,81 @@
+#![warn(unused_extern_crates)]
+/// Holochain keystore service executable
+///
+/// Holds an agent's keystore outside of the conductor process and answers the conductor's
+/// crypto requests (sign, encrypt, decrypt, derive) over a unix domain socket.
+/// Point an agent's `keystore_service` in the conductor config to the same socket path
+/// to have the conductor use this service instead of loading the agent's keystore file.
+///
+/// The keystore's passphrase is asked for on the command line when the service starts.
+extern crate holochain_conductor_lib;
+extern crate holochain_core_types;
+extern crate holochain_locksmith;
+extern crate lib3h_sodium;
+#[cfg(unix)]
+extern crate signal_hook;
+extern crate structopt;
+
+use holochain_conductor_lib::{
+    conductor::passphrase_manager::{PassphraseManager, PassphraseServiceCmd},
+    keystore::Keystore,
+    keystore_service::KeystoreService,
+};
+use holochain_core_types::error::HolochainError;
+use holochain_locksmith::Mutex;
+#[cfg(unix)]
+use signal_hook::{iterator::Signals, SIGINT, SIGTERM};
+use std::{path::PathBuf, sync::Arc};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "holochain_keystore")]
+struct Opt {
+    /// Path to the keystore file of the agent
+    #[structopt(short = "k", long = "keystore", parse(from_os_str))]
+    keystore: PathBuf,
+    /// Path of the unix domain socket to listen on
+    #[structopt(short = "s", long = "socket")]
+    socket: String,
+}
+
+#[cfg_attr(tarpaulin, skip)]
+fn main() {
+    lib3h_sodium::check_init();
+    let opt = Opt::from_args();
+
+    if let Err(error) = run(opt) {
+        eprintln!("Error while running keystore service: {}", error);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(unix)]
+#[cfg_attr(tarpaulin, skip)]
+fn run(opt: Opt) -> Result<(), HolochainError> {
+    let passphrase_manager = Arc::new(PassphraseManager::new(Arc::new(Mutex::new(
+        PassphraseServiceCmd {},
+    ))));
+    let keystore = Keystore::new_from_file(opt.keystore, passphrase_manager, None)?;
+    let service = KeystoreService::new(keystore)?;
+
+    // Clean up the socket file when we get shut down
+    let socket = opt.socket.clone();
+    let termination_signals = Signals::new(&[SIGINT, SIGTERM])?;
+    std::thread::spawn(move || {
+        let _sig = termination_signals.forever().next();
+        let _ = std::fs::remove_file(&socket);
+        std::process::exit(0);
+    });
+
+    println!("Keystore service listening on {}", opt.socket);
+    service.serve(&opt.socket)
+}
+
+#[cfg(not(unix))]
+#[cfg_attr(tarpaulin, skip)]
+fn run(_opt: Opt) -> Result<(), HolochainError> {
+    Err(HolochainError::ErrorGeneric(
+        "The keystore service needs unix domain sockets which are not available on this system"
+            .to_string(),
+    ))
+}
@@ -1,31 +1,86 @@
 use crate::tracker::GuardTracker;
-use parking_lot::{Mutex, MutexGuard};
+use parking_lot::{Mutex, MutexGuard, RwLock};
 use snowflake::ProcessUniqueId;
 use std::{collections::HashMap, time::Duration};
 
-lazy_static! {
-
+/// The thresholds locksmith works with, changeable at runtime through `configure`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocksmithConfig {
     /// if a lock guard lives this long, it is assumed it will never die
-    pub(crate) static ref IMMORTAL_TIMEOUT: Duration = Duration::from_secs(60);
+    pub immortal_timeout: Duration,
 
-    /// this should be a bit longer than IMMORTAL_TIMEOUT, so that locks don't timeout
+    /// this should be a bit longer than immortal_timeout, so that locks don't timeout
     /// before all long-running guards are detected, in the case of a deadlock.
     /// (But NOT longer than try-o-rama's conductor timeout)
-    pub(crate) static ref LOCK_TIMEOUT: Duration = Duration::from_secs(120);
+    pub lock_timeout: Duration,
 
     /// This is how often we check the elapsed time of guards
-    pub(crate) static ref GUARD_WATCHER_POLL_INTERVAL: Duration = Duration::from_millis(1000);
+    pub guard_watcher_poll_interval: Duration,
 
     /// We filter out any guards alive less than this long
-    pub(crate) static ref ACTIVE_GUARD_MIN_ELAPSED: Duration = Duration::from_millis(1000);
+    pub active_guard_min_elapsed: Duration,
 
     /// Only report about no activity if this much time has passed
-    pub(crate) static ref ACTIVE_GUARD_NO_ACTIVITY_INTERVAL: Duration = Duration::from_secs(10);
+    pub active_guard_no_activity_interval: Duration,
+
+    /// Track which thread waits on which lock and fail a lock call right away
+    /// if waiting would close a cycle in the wait-for graph.
+    /// Off by default, as every lock call that has to wait then captures a
+    /// backtrace and searches the graph while holding two process-wide mutexes.
+    pub deadlock_detection: bool,
+
+    /// Gather per-site wait and hold times of all guards, see `lock_site_stats`.
+    /// Off by default, as it records every guard that gets dropped.
+    pub contention_profiling: bool,
+}
 
+impl Default for LocksmithConfig {
+    fn default() -> Self {
+        Self {
+            immortal_timeout: Duration::from_secs(60),
+            lock_timeout: Duration::from_secs(120),
+            guard_watcher_poll_interval: Duration::from_millis(1000),
+            active_guard_min_elapsed: Duration::from_millis(1000),
+            active_guard_no_activity_interval: Duration::from_secs(10),
+            deadlock_detection: false,
+            contention_profiling: false,
+        }
+    }
+}
+
+lazy_static! {
+    static ref CONFIG: RwLock<LocksmithConfig> = RwLock::new(LocksmithConfig::default());
     static ref GUARDS: Mutex<GuardsMap> = Mutex::new(HashMap::new());
 }
 
-type GuardsMap = HashMap<ProcessUniqueId, GuardTracker>;
+/// Replaces the thresholds for all locks, taking effect with the next lock call.
+pub fn configure(config: LocksmithConfig) {
+    *CONFIG.write() = config;
+}
+
+/// The thresholds currently in effect.
+pub fn locksmith_config() -> LocksmithConfig {
+    CONFIG.read().clone()
+}
+
+/// Changes the config in place, so that tests turning on different settings
+/// concurrently don't undo each other.
+#[cfg(test)]
+pub(crate) fn update_config<F: FnOnce(&mut LocksmithConfig)>(update: F) {
+    update(&mut CONFIG.write())
+}
+
+/// Identifies a lock by its address, which can't change while it is locked or waited on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct LockId(pub(crate) usize);
+
+impl std::fmt::Display for LockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+pub(crate) type GuardsMap = HashMap<ProcessUniqueId, GuardTracker>;
 
 pub(crate) fn guards_guard<'a>() -> MutexGuard<'a, GuardsMap> {
     GUARDS
@@ -0,0 +1,280 @@
+//! Per-site lock contention statistics. Every guard gets recorded when it is
+//! dropped, under its annotation if it has one, or else under the call site
+//! that created it.
+use crate::{common::locksmith_config, tracker::GuardTracker};
+use backtrace::Backtrace;
+use parking_lot::Mutex;
+use std::{collections::HashMap, time::Duration};
+
+/// How many frames of the guard's backtrace tell call sites apart
+const SITE_FRAMES: usize = 16;
+
+/// How many sites get stats of their own, guards of any further sites
+/// are summed up under `OTHER_SITES`
+const MAX_SITES: usize = 1000;
+
+/// The site guards are recorded under once `MAX_SITES` is reached
+pub const OTHER_SITES: &str = "<other sites>";
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum SiteKey {
+    Annotation(String),
+    Frames(Vec<usize>),
+}
+
+#[derive(Clone)]
+struct SiteStats {
+    acquisitions: u64,
+    total_wait: Duration,
+    total_hold: Duration,
+    max_hold: Duration,
+    backtrace: Backtrace,
+}
+
+lazy_static! {
+    static ref SITES: Mutex<HashMap<SiteKey, SiteStats>> = Mutex::new(HashMap::new());
+}
+
+/// What the guards created at one site have cost, summed up since the last reset.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LockSiteStats {
+    /// The annotation of the guards, or the function, file and line they got created at
+    pub site: String,
+    pub acquisitions: u64,
+    /// Time spent blocking before the lock was acquired
+    pub total_wait: Duration,
+    pub total_hold: Duration,
+    pub max_hold: Duration,
+}
+
+impl LockSiteStats {
+    fn add(&mut self, other: &LockSiteStats) {
+        self.acquisitions += other.acquisitions;
+        self.total_wait += other.total_wait;
+        self.total_hold += other.total_hold;
+        self.max_hold = self.max_hold.max(other.max_hold);
+    }
+}
+
+/// Adds a dropped guard, which was held for `held`, to the stats of its site.
+pub(crate) fn record(tracker: GuardTracker, held: Duration) {
+    if !locksmith_config().contention_profiling {
+        return;
+    }
+    let key = match tracker.annotation {
+        Some(annotation) => SiteKey::Annotation(annotation),
+        None => SiteKey::Frames(
+            tracker
+                .backtrace
+                .frames()
+                .iter()
+                .take(SITE_FRAMES)
+                .map(|frame| frame.ip() as usize)
+                .collect(),
+        ),
+    };
+    let mut sites = SITES.lock();
+    let key = capped_key(&sites, key);
+    let stats = sites.entry(key).or_insert_with(|| SiteStats {
+        acquisitions: 0,
+        total_wait: Duration::default(),
+        total_hold: Duration::default(),
+        max_hold: Duration::default(),
+        backtrace: tracker.backtrace,
+    });
+    stats.acquisitions += 1;
+    stats.total_wait += tracker.waited;
+    stats.total_hold += held;
+    stats.max_hold = stats.max_hold.max(held);
+}
+
+/// The key to record a guard under, `OTHER_SITES` if it would be a new site
+/// but there are already `MAX_SITES`.
+fn capped_key(sites: &HashMap<SiteKey, SiteStats>, key: SiteKey) -> SiteKey {
+    if sites.len() >= MAX_SITES && !sites.contains_key(&key) {
+        SiteKey::Annotation(OTHER_SITES.to_string())
+    } else {
+        key
+    }
+}
+
+/// The modules whose frames are part of creating a guard, rather than the call site
+const INTERNAL_MODULES: [&str; 5] = [
+    "backtrace::",
+    "parking_lot::",
+    "holochain_locksmith::guard::",
+    "holochain_locksmith::mutex::",
+    "holochain_locksmith::tracker::",
+];
+
+/// The first frame of the backtrace outside of the locking machinery.
+fn call_site(backtrace: &Backtrace) -> String {
+    let mut backtrace = backtrace.clone();
+    backtrace.resolve();
+    backtrace
+        .frames()
+        .iter()
+        .flat_map(|frame| frame.symbols())
+        .filter_map(|symbol| symbol.name().map(|name| (name.to_string(), symbol)))
+        .find(|(name, _)| {
+            !INTERNAL_MODULES
+                .iter()
+                .any(|internal| name.contains(internal))
+        })
+        .map(
+            |(name, symbol)| match (symbol.filename(), symbol.lineno()) {
+                (Some(file), Some(line)) => format!("{} ({}:{})", name, file.display(), line),
+                _ => name,
+            },
+        )
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+/// The stats of every site a guard has been dropped at, the sites which held
+/// their locks the longest first.
+pub fn lock_site_stats() -> Vec<LockSiteStats> {
+    // resolving backtraces is slow, so it happens on a copy
+    // to not block the guards that get dropped meanwhile
+    let recorded: Vec<(SiteKey, SiteStats)> = SITES
+        .lock()
+        .iter()
+        .map(|(key, stats)| (key.clone(), stats.clone()))
+        .collect();
+    report(recorded)
+}
+
+/// The same as `lock_site_stats`, but also forgets the stats reported, in one
+/// step so that no guard dropped meanwhile goes missing from both reports.
+pub fn take_lock_site_stats() -> Vec<LockSiteStats> {
+    let recorded: Vec<(SiteKey, SiteStats)> = std::mem::replace(&mut *SITES.lock(), HashMap::new())
+        .into_iter()
+        .collect();
+    report(recorded)
+}
+
+fn report(recorded: Vec<(SiteKey, SiteStats)>) -> Vec<LockSiteStats> {
+    let mut by_site: HashMap<String, LockSiteStats> = HashMap::new();
+    for (key, stats) in recorded {
+        let stats = LockSiteStats {
+            site: match key {
+                SiteKey::Annotation(annotation) => annotation,
+                SiteKey::Frames(_) => call_site(&stats.backtrace),
+            },
+            acquisitions: stats.acquisitions,
+            total_wait: stats.total_wait,
+            total_hold: stats.total_hold,
+            max_hold: stats.max_hold,
+        };
+        // different paths into the same call site are the same site
+        match by_site.get_mut(&stats.site) {
+            Some(existing) => existing.add(&stats),
+            None => {
+                by_site.insert(stats.site.clone(), stats);
+            }
+        }
+    }
+    let mut sites: Vec<_> = by_site.into_iter().map(|(_, stats)| stats).collect();
+    sites.sort_by(|a, b| b.total_hold.cmp(&a.total_hold));
+    sites
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{common::update_config, Mutex, RwLock};
+    use std::{
+        sync::{Arc, Barrier},
+        thread,
+    };
+
+    fn enable_profiling() {
+        update_config(|config| config.contention_profiling = true);
+    }
+
+    fn stats_for(site: &str) -> LockSiteStats {
+        lock_site_stats()
+            .into_iter()
+            .find(|stats| stats.site == site)
+            .expect("stats for site")
+    }
+
+    #[test]
+    pub fn test_annotated_guards_are_aggregated() {
+        enable_profiling();
+        let lock = RwLock::new(0);
+        for _ in 0..3 {
+            let _guard = lock.read().unwrap().annotate("test_annotated_guards");
+        }
+        let guard = lock.write().unwrap().annotate("test_annotated_guards");
+        drop(guard);
+
+        let stats = stats_for("test_annotated_guards");
+        assert_eq!(4, stats.acquisitions);
+        assert!(stats.max_hold <= stats.total_hold);
+    }
+
+    #[test]
+    pub fn test_unannotated_guards_are_recorded_at_their_call_site() {
+        enable_profiling();
+        let mutex = Mutex::new(0);
+        for _ in 0..2 {
+            *mutex.lock().unwrap() += 1;
+        }
+        let site = lock_site_stats()
+            .into_iter()
+            .find(|stats| {
+                stats
+                    .site
+                    .contains("test_unannotated_guards_are_recorded_at_their_call_site")
+            })
+            .expect("stats for the test function");
+        assert!(site.acquisitions >= 2);
+    }
+
+    #[test]
+    pub fn test_new_sites_are_capped() {
+        let stats = SiteStats {
+            acquisitions: 1,
+            total_wait: Duration::default(),
+            total_hold: Duration::default(),
+            max_hold: Duration::default(),
+            backtrace: Backtrace::new_unresolved(),
+        };
+        let site = |i: usize| SiteKey::Annotation(format!("site{}", i));
+        let mut sites = HashMap::new();
+        for i in 0..MAX_SITES {
+            assert_eq!(site(i), capped_key(&sites, site(i)));
+            sites.insert(site(i), stats.clone());
+        }
+        assert_eq!(site(0), capped_key(&sites, site(0)));
+        assert_eq!(
+            SiteKey::Annotation(OTHER_SITES.to_string()),
+            capped_key(&sites, site(MAX_SITES))
+        );
+    }
+
+    #[test]
+    pub fn test_wait_and_hold_times_are_measured() {
+        enable_profiling();
+        let mutex = Arc::new(Mutex::new(0));
+        let barrier = Arc::new(Barrier::new(2));
+        let holder = {
+            let mutex = mutex.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                let _guard = mutex.lock().unwrap().annotate("test_wait_and_hold_times");
+                barrier.wait();
+                thread::sleep(Duration::from_millis(50));
+            })
+        };
+        barrier.wait();
+        let guard = mutex.lock().unwrap().annotate("test_wait_and_hold_times");
+        drop(guard);
+        holder.join().unwrap();
+
+        let stats = stats_for("test_wait_and_hold_times");
+        assert_eq!(2, stats.acquisitions);
+        assert!(stats.max_hold >= Duration::from_millis(50));
+        assert!(stats.total_wait >= Duration::from_millis(20));
+    }
+}
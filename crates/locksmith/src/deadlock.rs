@@ -0,0 +1,209 @@
+//! Wait-for graph of the threads blocked on locks. A thread waits for the threads
+//! holding guards on the lock it wants, so a cycle in this graph is a deadlock.
+//! Cycles are looked for whenever a thread starts waiting, which is the moment
+//! the last edge of any cycle gets added.
+use crate::{
+    common::{guards_guard, GuardsMap, LockId},
+    error::LockType,
+    tracker::GuardTracker,
+};
+use backtrace::Backtrace;
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, HashSet},
+    thread::{self, ThreadId},
+};
+
+struct Waiter {
+    lock_id: LockId,
+    lock_type: LockType,
+    thread_name: String,
+    backtrace: Backtrace,
+}
+
+type WaitersMap = HashMap<ThreadId, Waiter>;
+
+lazy_static! {
+    static ref WAITERS: Mutex<WaitersMap> = Mutex::new(HashMap::new());
+}
+
+pub(crate) fn thread_name(thread: &thread::Thread) -> String {
+    thread
+        .name()
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("{:?}", thread.id()))
+}
+
+/// Takes the current thread out of the wait-for graph when dropped.
+pub(crate) struct Waiting;
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        WAITERS.lock().remove(&thread::current().id());
+    }
+}
+
+/// Adds the current thread to the wait-for graph as waiting for the given lock.
+/// Fails with a report of the cycle if waiting would deadlock.
+pub(crate) fn wait_for(lock_id: LockId, lock_type: LockType) -> Result<Waiting, String> {
+    let current = thread::current();
+    let mut waiters = WAITERS.lock();
+    waiters.insert(
+        current.id(),
+        Waiter {
+            lock_id,
+            lock_type,
+            thread_name: thread_name(&current),
+            backtrace: Backtrace::new_unresolved(),
+        },
+    );
+    let guards = guards_guard();
+    match find_cycle(current.id(), &waiters, &guards) {
+        Some(cycle) => {
+            let report = report_cycle(&cycle, &waiters, &guards);
+            waiters.remove(&current.id());
+            Err(report)
+        }
+        None => Ok(Waiting),
+    }
+}
+
+/// The guards a waiter has to wait for, readers don't block readers.
+fn blockers<'a>(waiter: &Waiter, guards: &'a GuardsMap) -> Vec<&'a GuardTracker> {
+    guards
+        .values()
+        .filter(|guard| {
+            guard.lock_id == waiter.lock_id
+                && !(guard.lock_type == LockType::Read && waiter.lock_type == LockType::Read)
+        })
+        .collect()
+}
+
+/// The threads of a cycle starting at `start`, each waiting for a guard
+/// held by the next one, the last one waiting for `start`.
+fn find_cycle(start: ThreadId, waiters: &WaitersMap, guards: &GuardsMap) -> Option<Vec<ThreadId>> {
+    let mut path = vec![start];
+    let mut visited = HashSet::new();
+    if extend_path(&mut path, &mut visited, waiters, guards) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn extend_path(
+    path: &mut Vec<ThreadId>,
+    visited: &mut HashSet<ThreadId>,
+    waiters: &WaitersMap,
+    guards: &GuardsMap,
+) -> bool {
+    let current = path[path.len() - 1];
+    let waiter = match waiters.get(&current) {
+        Some(waiter) => waiter,
+        None => return false,
+    };
+    for holder in blockers(waiter, guards) {
+        if holder.thread == path[0] {
+            return true;
+        }
+        if visited.insert(holder.thread) {
+            path.push(holder.thread);
+            if extend_path(path, visited, waiters, guards) {
+                return true;
+            }
+            path.pop();
+        }
+    }
+    false
+}
+
+fn report_cycle(cycle: &[ThreadId], waiters: &WaitersMap, guards: &GuardsMap) -> String {
+    let mut report = format!(
+        r"
+
+        !!!!!!!!!!!!!!!!!!!!!!!!!!!!!
+        !!!   DEADLOCK DETECTED   !!!
+        !!!!!!!!!!!!!!!!!!!!!!!!!!!!!
+
+{} thread(s) wait for each other's locks:
+",
+        cycle.len()
+    );
+    for (index, thread) in cycle.iter().enumerate() {
+        let waiter = &waiters[thread];
+        let next = cycle[(index + 1) % cycle.len()];
+        let mut backtrace = waiter.backtrace.clone();
+        backtrace.resolve();
+        report.push_str(&format!(
+            "\nThread '{}' waits for {:?} on lock {} at:\n{:?}\n",
+            waiter.thread_name, waiter.lock_type, waiter.lock_id, backtrace
+        ));
+        for holder in blockers(waiter, guards)
+            .into_iter()
+            .filter(|holder| holder.thread == next)
+        {
+            let mut backtrace = holder.backtrace.clone();
+            backtrace.resolve();
+            let annotation = holder
+                .annotation
+                .as_ref()
+                .map(|a| format!(" ({})", a))
+                .unwrap_or_default();
+            report.push_str(&format!(
+                "\nwhich thread '{}' holds with {:?} guard {}{}, created at:\n{:?}\n",
+                holder.thread_name, holder.lock_type, holder.puid, annotation, backtrace
+            ));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::{common::update_config, error::LocksmithErrorKind, Mutex, RwLock};
+    use std::sync::{Arc, Barrier};
+
+    fn enable_deadlock_detection() {
+        update_config(|config| config.deadlock_detection = true);
+    }
+
+    #[test]
+    pub fn test_relocking_a_mutex_is_a_deadlock() {
+        enable_deadlock_detection();
+        let mutex = Mutex::new(0);
+        let _guard = mutex.lock().unwrap();
+        let error = mutex.lock().err().expect("second lock to fail");
+        assert_eq!(&LocksmithErrorKind::LocksmithDeadlock, error.kind());
+    }
+
+    #[test]
+    pub fn test_read_locks_do_not_wait_for_each_other() {
+        enable_deadlock_detection();
+        let lock = RwLock::new(0);
+        let _first = lock.read().unwrap();
+        assert!(lock.read().is_ok());
+    }
+
+    #[test]
+    pub fn test_lock_order_inversion_is_a_deadlock() {
+        enable_deadlock_detection();
+        let a = Arc::new(Mutex::new(0));
+        let b = Arc::new(Mutex::new(0));
+        let barrier = Arc::new(Barrier::new(2));
+
+        let spawn = |first: Arc<Mutex<i32>>, second: Arc<Mutex<i32>>, barrier: Arc<Barrier>| {
+            std::thread::spawn(move || {
+                let _first = first.lock().unwrap();
+                barrier.wait();
+                second.lock().map(|_| ()).map_err(|e| e.kind().clone())
+            })
+        };
+        let one = spawn(a.clone(), b.clone(), barrier.clone());
+        let two = spawn(b, a, barrier);
+
+        let mut results = vec![one.join().unwrap(), two.join().unwrap()];
+        results.sort_by_key(|result| result.is_ok());
+        assert_eq!(Err(LocksmithErrorKind::LocksmithDeadlock), results[0]);
+        assert_eq!(Ok(()), results[1]);
+    }
+}
@@ -1,11 +1,13 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LocksmithErrorKind {
     LocksmithTimeout,
     LocksmithPoisonError,
     LocksmithWouldBlock,
+    /// waiting for the lock would have closed a cycle in the wait-for graph
+    LocksmithDeadlock,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LockType {
     Lock,
     Read,
@@ -22,11 +24,15 @@ impl LocksmithError {
     pub fn new(lock_type: LockType, kind: LocksmithErrorKind) -> Self {
         Self { lock_type, kind }
     }
+
+    pub fn kind(&self) -> &LocksmithErrorKind {
+        &self.kind
+    }
 }
 
 impl std::fmt::Display for LocksmithError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{:?}", self)
     }
 }
 
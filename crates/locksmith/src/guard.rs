@@ -1,9 +1,15 @@
-use crate::{common::guards_guard, error::LockType, tracker::GuardTracker};
+use crate::{
+    common::{guards_guard, LockId},
+    contention,
+    error::LockType,
+    tracker::GuardTracker,
+};
 use parking_lot::{MutexGuard, RwLockReadGuard, RwLockWriteGuard};
 use snowflake::ProcessUniqueId;
 use std::{
     borrow::{Borrow, BorrowMut},
     ops::{Deref, DerefMut},
+    time::{Duration, Instant},
 };
 
 macro_rules! guard_struct {
@@ -15,9 +21,12 @@ macro_rules! guard_struct {
         }
 
         impl<'a, T: ?Sized> $HcGuard<'a, T> {
-            pub fn new(inner: $Guard<'a, T>) -> Self {
+            pub(crate) fn new(inner: $Guard<'a, T>, lock_id: LockId, waited: Duration) -> Self {
                 let puid = ProcessUniqueId::new();
-                guards_guard().insert(puid, GuardTracker::new(puid, LockType::$lock_type));
+                guards_guard().insert(
+                    puid,
+                    GuardTracker::new(puid, LockType::$lock_type, lock_id, waited),
+                );
                 Self {
                     puid,
                     inner: Some(inner),
@@ -56,9 +65,17 @@ macro_rules! guard_struct {
 
         impl<'a, T: ?Sized> Drop for $HcGuard<'a, T> {
             fn drop(&mut self) {
-                guards_guard().remove(&self.puid);
+                let released = Instant::now();
+                let tracker = guards_guard().remove(&self.puid);
                 if self.fair_unlocking {
                     self._unlock_fair();
+                } else {
+                    self.inner.take();
+                }
+                // only record once the lock is free again, to not add to its contention
+                if let Some(tracker) = tracker {
+                    let held = released.duration_since(tracker.created);
+                    contention::record(tracker, held);
                 }
             }
         }
@@ -4,12 +4,16 @@ extern crate lazy_static;
 extern crate log;
 
 mod common;
+mod contention;
+mod deadlock;
 mod error;
 mod guard;
 mod mutex;
 mod tracker;
 
-pub use error::LocksmithError;
+pub use common::{configure, locksmith_config, LocksmithConfig};
+pub use contention::{lock_site_stats, take_lock_site_stats, LockSiteStats};
+pub use error::{LocksmithError, LocksmithErrorKind};
 pub use guard::{
     HcMutexGuard as MutexGuard, HcRwLockReadGuard as RwLockReadGuard,
     HcRwLockWriteGuard as RwLockWriteGuard,
@@ -1,5 +1,6 @@
 use crate::{
-    common::LOCK_TIMEOUT,
+    common::{locksmith_config, LockId},
+    deadlock::wait_for,
     error::{LockType, LocksmithError, LocksmithErrorKind, LocksmithResult},
     guard::{HcMutexGuard, HcRwLockReadGuard, HcRwLockWriteGuard},
 };
@@ -33,6 +34,12 @@ impl<T> HcMutex<T> {
     }
 }
 
+impl<T: ?Sized> HcMutex<T> {
+    fn lock_id(&self) -> LockId {
+        LockId(&self.inner as *const Mutex<T> as *const () as usize)
+    }
+}
+
 #[derive(Debug)]
 pub struct HcRwLock<T: ?Sized> {
     fair_unlocking: bool,
@@ -60,36 +67,68 @@ impl<T> HcRwLock<T> {
     }
 }
 
+impl<T: ?Sized> HcRwLock<T> {
+    fn lock_id(&self) -> LockId {
+        LockId(&self.inner as *const RwLock<T> as *const () as usize)
+    }
+}
+
 macro_rules! mutex_impl {
-    ($HcMutex: ident, $Mutex: ident, $HcGuard:ident, $Guard:ident, $lock_type:ident, $lock_fn:ident, $try_lock_fn:ident, $try_lock_for_fn:ident, $try_lock_until_fn:ident, $new_guard_fn:ident) => {
+    ($HcMutex: ident, $Mutex: ident, $HcGuard:ident, $Guard:ident, $lock_type:ident, $lock_fn:ident, $try_lock_fn:ident, $try_lock_for_fn:ident, $try_lock_until_fn:ident, $wait_fn:ident, $new_guard_fn:ident) => {
         impl<T: ?Sized> $HcMutex<T> {
             pub fn $lock_fn(&self) -> LocksmithResult<$HcGuard<T>> {
-                self.$try_lock_for_fn(*LOCK_TIMEOUT).ok_or_else(|| {
-                    LocksmithError::new(LockType::$lock_type, LocksmithErrorKind::LocksmithTimeout)
-                })
+                self.$wait_fn(locksmith_config().lock_timeout)
+                    .map_err(|kind| LocksmithError::new(LockType::$lock_type, kind))
             }
 
             pub fn $try_lock_for_fn(&self, duration: Duration) -> Option<$HcGuard<T>> {
-                self.inner
-                    .$try_lock_for_fn(duration)
-                    .map(|g| self.$new_guard_fn(g))
+                self.$wait_fn(duration).ok()
             }
 
             pub fn $try_lock_until_fn(&self, instant: Instant) -> Option<$HcGuard<T>> {
-                self.inner
-                    .$try_lock_until_fn(instant)
-                    .map(|g| self.$new_guard_fn(g))
+                let now = Instant::now();
+                if instant > now {
+                    self.$wait_fn(instant - now).ok()
+                } else {
+                    self.$try_lock_fn()
+                }
             }
 
             pub fn $try_lock_fn(&self) -> Option<$HcGuard<T>> {
-                (*self).inner.$try_lock_fn().map(|g| self.$new_guard_fn(g))
+                (*self)
+                    .inner
+                    .$try_lock_fn()
+                    .map(|g| self.$new_guard_fn(g, Duration::default()))
+            }
+
+            /// Blocks until the lock is acquired or `timeout` has passed,
+            /// unless blocking would deadlock, in which case the cycle gets
+            /// logged and this fails right away.
+            fn $wait_fn(&self, timeout: Duration) -> Result<$HcGuard<T>, LocksmithErrorKind> {
+                if let Some(guard) = self.$try_lock_fn() {
+                    return Ok(guard);
+                }
+                let started = Instant::now();
+                let inner = if locksmith_config().deadlock_detection {
+                    let _waiting =
+                        wait_for(self.lock_id(), LockType::$lock_type).map_err(|report| {
+                            error!("{}", report);
+                            LocksmithErrorKind::LocksmithDeadlock
+                        })?;
+                    self.inner.$try_lock_for_fn(timeout)
+                } else {
+                    self.inner.$try_lock_for_fn(timeout)
+                };
+                inner
+                    .map(|g| self.$new_guard_fn(g, started.elapsed()))
+                    .ok_or(LocksmithErrorKind::LocksmithTimeout)
             }
 
-            fn $new_guard_fn<'a>(&self, inner: $Guard<'a, T>) -> $HcGuard<'a, T> {
+            fn $new_guard_fn<'a>(&self, inner: $Guard<'a, T>, waited: Duration) -> $HcGuard<'a, T> {
                 if self.fair_unlocking {
-                    $HcGuard::new(inner).use_fair_unlocking()
+                    $HcGuard::new(inner, self.lock_id(), waited).use_fair_unlocking()
                 } else {
-                    $HcGuard::new(inner)
+                    $HcGuard::new(inner, self.lock_id(), waited)
                 }
             }
         }
@@ -106,6 +145,7 @@ mutex_impl!(
     try_lock,
     try_lock_for,
     try_lock_until,
+    wait_for_lock,
     new_guard
 );
 mutex_impl!(
@@ -118,6 +158,7 @@ mutex_impl!(
     try_read,
     try_read_for,
     try_read_until,
+    wait_for_read,
     new_guard_read
 );
 mutex_impl!(
@@ -130,5 +171,6 @@ mutex_impl!(
     try_write,
     try_write_for,
     try_write_until,
+    wait_for_write,
     new_guard_write
 );
@@ -1,14 +1,12 @@
 use crate::{
-    common::{
-        guards_guard, ACTIVE_GUARD_MIN_ELAPSED, ACTIVE_GUARD_NO_ACTIVITY_INTERVAL,
-        GUARD_WATCHER_POLL_INTERVAL, IMMORTAL_TIMEOUT,
-    },
+    common::{guards_guard, locksmith_config, LockId, LocksmithConfig},
+    deadlock::thread_name,
     error::LockType,
 };
 use backtrace::Backtrace;
 use snowflake::ProcessUniqueId;
 use std::{
-    thread,
+    thread::{self, ThreadId},
     time::{Duration, Instant},
 };
 
@@ -19,10 +17,21 @@ pub(crate) struct GuardTracker {
     pub(crate) lock_type: LockType,
     pub(crate) immortal: bool,
     pub(crate) annotation: Option<String>,
+    pub(crate) lock_id: LockId,
+    pub(crate) thread: ThreadId,
+    pub(crate) thread_name: String,
+    /// how long it took to acquire the lock
+    pub(crate) waited: Duration,
 }
 
 impl GuardTracker {
-    pub fn new(puid: ProcessUniqueId, lock_type: LockType) -> Self {
+    pub fn new(
+        puid: ProcessUniqueId,
+        lock_type: LockType,
+        lock_id: LockId,
+        waited: Duration,
+    ) -> Self {
+        let thread = thread::current();
         Self {
             puid,
             lock_type,
@@ -30,15 +39,19 @@ impl GuardTracker {
             backtrace: Backtrace::new_unresolved(),
             immortal: false,
             annotation: None,
+            lock_id,
+            thread: thread.id(),
+            thread_name: thread_name(&thread),
+            waited,
         }
     }
 
-    pub fn report_and_update(&mut self) -> Option<(i64, String)> {
+    pub fn report_and_update(&mut self, config: &LocksmithConfig) -> Option<(i64, String)> {
         let elapsed = Instant::now().duration_since(self.created);
-        if elapsed > *ACTIVE_GUARD_MIN_ELAPSED {
+        if elapsed > config.active_guard_min_elapsed {
             let elapsed_ms = elapsed.as_millis() as i64;
-            if !self.immortal && elapsed > *IMMORTAL_TIMEOUT {
-                self.immortalize();
+            if !self.immortal && elapsed > config.immortal_timeout {
+                self.immortalize(config);
             }
             let lock_type_str = format!("{:?}", self.lock_type);
             let report = if self.immortal {
@@ -59,7 +72,7 @@ impl GuardTracker {
         format!("{:6} {:^13} {:>12}", "KIND", "PUID", "ELAPSED (ms)")
     }
 
-    fn immortalize(&mut self) {
+    fn immortalize(&mut self, config: &LocksmithConfig) {
         if self.immortal {
             return;
         }
@@ -77,13 +90,15 @@ impl GuardTracker {
         !!! IMMORTAL LOCK GUARD FOUND !!!
         !!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!
 
-{type:?} guard {puid} lived for > {time} seconds.{annotation}
+{type:?} guard {puid} on lock {lock_id} held by thread '{thread}' lived for > {time} seconds.{annotation}
 Backtrace at the moment of guard creation follows:
 
 {backtrace:?}",
             type=self.lock_type,
             puid=self.puid,
-            time=IMMORTAL_TIMEOUT.as_secs(),
+            lock_id=self.lock_id,
+            thread=self.thread_name,
+            time=config.immortal_timeout.as_secs(),
             annotation=annotation,
             backtrace=self.backtrace
         );
@@ -100,10 +115,11 @@ pub fn spawn_locksmith_guard_watcher() {
         .spawn(move || {
             let mut inactive_for = Duration::from_millis(0);
             loop {
+                let config = locksmith_config();
                 let mut reports: Vec<(i64, String)> = {
                     guards_guard()
                         .values_mut()
-                        .filter_map(|gt| gt.report_and_update())
+                        .filter_map(|gt| gt.report_and_update(&config))
                         .collect()
                 };
                 if reports.len() > 0 {
@@ -116,23 +132,23 @@ pub fn spawn_locksmith_guard_watcher() {
                     debug!(
                         "tracking {} active guard(s) alive for > {}ms:\n{}\n{}",
                         num_active,
-                        ACTIVE_GUARD_MIN_ELAPSED.as_millis(),
+                        config.active_guard_min_elapsed.as_millis(),
                         GuardTracker::report_header(),
                         output
                     );
                 } else {
-                    inactive_for += *GUARD_WATCHER_POLL_INTERVAL;
-                    if inactive_for > *ACTIVE_GUARD_NO_ACTIVITY_INTERVAL {
+                    inactive_for += config.guard_watcher_poll_interval;
+                    if inactive_for > config.active_guard_no_activity_interval {
                         debug!(
                             "no active guards alive > {:?}ms for the last {:?} seconds",
-                            ACTIVE_GUARD_MIN_ELAPSED.as_millis(),
-                            ACTIVE_GUARD_NO_ACTIVITY_INTERVAL.as_secs(),
+                            config.active_guard_min_elapsed.as_millis(),
+                            config.active_guard_no_activity_interval.as_secs(),
                         );
                         inactive_for = Duration::from_millis(0);
                     }
                 }
 
-                thread::sleep(*GUARD_WATCHER_POLL_INTERVAL);
+                thread::sleep(config.guard_watcher_poll_interval);
             }
         });
 }
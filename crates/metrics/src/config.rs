@@ -1,4 +1,9 @@
-use crate::{cloudwatch::CloudWatchLogger, logger::LoggerMetricPublisher, MetricPublisher};
+use crate::{
+    cloudwatch::CloudWatchLogger,
+    logger::LoggerMetricPublisher,
+    prometheus::{PrometheusConfig, PrometheusMetricPublisher},
+    MetricPublisher,
+};
 use holochain_locksmith::RwLock;
 use std::sync::Arc;
 
@@ -9,6 +14,7 @@ use std::sync::Arc;
 pub enum MetricPublisherConfig {
     Logger,
     CloudWatchLogs(CloudWatchLogsConfig),
+    Prometheus(PrometheusConfig),
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
@@ -57,6 +63,9 @@ impl MetricPublisherConfig {
                     ))),
                 }
             }
+            Self::Prometheus(config) => {
+                Arc::new(RwLock::new(PrometheusMetricPublisher::new(config)))
+            }
         };
         publisher
     }
@@ -75,4 +84,9 @@ impl MetricPublisherConfig {
             assume_role_arn: None,
         })
     }
+
+    /// The default prometheus publisher configuration, serving on the given local port.
+    pub fn default_prometheus(port: u16) -> Self {
+        Self::Prometheus(PrometheusConfig::new(port))
+    }
 }
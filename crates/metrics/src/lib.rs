@@ -18,6 +18,7 @@ pub mod cloudwatch;
 pub mod config;
 pub mod logger;
 pub mod metrics;
+pub mod prometheus;
 pub mod stats;
 
 pub use cloudwatch::*;
@@ -77,9 +77,35 @@ impl MetricPublisher for ChannelPublisher {
 /// The default metric publisher trait implementation
 pub type DefaultMetricPublisher = crate::logger::LoggerMetricPublisher;
 
+/// Publishes the milliseconds elapsed since `start` as "$metric_prefix.latency"
+/// and a sample of one as "$metric_prefix.count" to `publisher`.
+/// Use this directly where the timed work spans `.await` points and can't be
+/// wrapped by `with_latency_publishing!`.
+pub fn publish_latency(
+    publisher: &Arc<RwLock<dyn MetricPublisher>>,
+    metric_prefix: &str,
+    start: std::time::SystemTime,
+) {
+    let latency = start.elapsed().unwrap_or_default().as_millis();
+    let mut publisher = publisher.write().unwrap();
+    publisher.publish(&Metric::new(
+        format!("{}.latency", metric_prefix),
+        None,
+        Some(start.into()),
+        latency as f64,
+    ));
+    publisher.publish(&Metric::new(
+        format!("{}.count", metric_prefix),
+        None,
+        Some(start.into()),
+        1.0,
+    ));
+}
+
 /// Wraps a standard rust function with latency timing that is published to
 /// $publisher upon completion of $f($args,*). The latency metric name will
-/// be "$metric_prefix.latency".
+/// be "$metric_prefix.latency", next to it a "$metric_prefix.count" of one
+/// is published to count the calls.
 #[macro_export]
 macro_rules! with_latency_publishing {
     ($metric_prefix:expr, $publisher:expr, $f:expr, $($args:expr),* ) => {{
@@ -88,12 +114,15 @@ macro_rules! with_latency_publishing {
         let ret = ($f)($($args),*);
         let latency = clock.elapsed().unwrap().as_millis();
 
-        let metric_name = format!("{}.latency", $metric_prefix);
+        let metric_prefix = format!("{}", $metric_prefix);
 
         // TODO pass in stream id or not?
-        let metric = $crate::Metric::new(metric_name.as_str(), None,
+        let metric = $crate::Metric::new(format!("{}.latency", metric_prefix), None,
             Some(clock.into()), latency as f64);
         $publisher.write().unwrap().publish(&metric);
+        let metric = $crate::Metric::new(format!("{}.count", metric_prefix), None,
+            Some(clock.into()), 1.0);
+        $publisher.write().unwrap().publish(&metric);
         ret
     }}
 }
@@ -157,4 +186,32 @@ mod test {
 
         assert!(ret)
     }
+
+    #[derive(Default)]
+    struct CollectingPublisher(Vec<Metric>);
+
+    impl MetricPublisher for CollectingPublisher {
+        fn publish(&mut self, metric: &Metric) {
+            self.0.push(metric.clone());
+        }
+    }
+
+    #[test]
+    fn publishes_latency_and_count() {
+        let collected = Arc::new(holochain_locksmith::RwLock::new(
+            CollectingPublisher::default(),
+        ));
+        let publisher: Arc<holochain_locksmith::RwLock<dyn MetricPublisher>> = collected.clone();
+
+        publish_latency(&publisher, "workflow", std::time::SystemTime::now());
+
+        let names: Vec<String> = collected
+            .read()
+            .unwrap()
+            .0
+            .iter()
+            .map(|metric| metric.name.clone())
+            .collect();
+        assert_eq!(vec!["workflow.latency", "workflow.count"], names);
+    }
 }
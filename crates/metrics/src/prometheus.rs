@@ -0,0 +1,414 @@
+//! Publishes metrics in the prometheus text exposition format.
+//! Metric samples are aggregated in memory and served over http on a
+//! local port for a prometheus server to scrape.
+use crate::{
+    stats::{Histogram, HISTOGRAM_BUCKETS},
+    Metric, MetricPublisher,
+};
+use holochain_locksmith::Mutex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as FmtWrite,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Metric names ending with this suffix are aggregated into counters,
+/// all other metrics are aggregated into histograms.
+pub const COUNTER_SUFFIX: &str = ".count";
+
+/// The content type of the text exposition format.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// How long a scrape connection may take to send its request or receive the response
+/// before it gets dropped.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct PrometheusConfig {
+    /// The port the metrics are served on.
+    pub port: u16,
+    /// The address to bind to, defaults to the loopback interface.
+    #[serde(default = "default_bind_address")]
+    pub bind_address: String,
+}
+
+fn default_bind_address() -> String {
+    "127.0.0.1".to_string()
+}
+
+impl PrometheusConfig {
+    pub fn new(port: u16) -> Self {
+        Self {
+            port,
+            bind_address: default_bind_address(),
+        }
+    }
+
+    fn address(&self) -> String {
+        format!("{}:{}", self.bind_address, self.port)
+    }
+}
+
+type Labels = Vec<(String, String)>;
+
+/// A histogram over `HISTOGRAM_BUCKETS` plus the sum of all samples.
+#[derive(Debug, Clone, Default)]
+struct HistogramSeries {
+    histogram: Histogram,
+    sum: f64,
+}
+
+impl HistogramSeries {
+    fn observe(&mut self, value: f64) {
+        self.histogram.add(value);
+        self.sum += value;
+    }
+}
+
+#[derive(Default)]
+struct Families {
+    counters: BTreeMap<String, BTreeMap<Labels, f64>>,
+    gauges: BTreeMap<String, BTreeMap<Labels, f64>>,
+    histograms: BTreeMap<String, BTreeMap<Labels, HistogramSeries>>,
+}
+
+/// Aggregated metric state served on one port. Publishers and gauge
+/// sources sharing a port share the same registry.
+#[derive(Default)]
+pub struct PrometheusRegistry {
+    families: Mutex<Families>,
+}
+
+/// Replaces everything prometheus does not allow in metric names with underscores.
+pub fn sanitize_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if sanitized
+        .chars()
+        .next()
+        .map_or(true, |c| c.is_ascii_digit())
+    {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_labels(labels: &[(String, String)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect();
+    format!("{{{}}}", labels.join(","))
+}
+
+fn with_label(labels: &[(String, String)], name: &str, value: String) -> Labels {
+    let mut labels = labels.to_vec();
+    labels.push((name.to_string(), value));
+    labels
+}
+
+impl PrometheusRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Aggregates a metric sample. The stream id, if any, becomes the `stream` label.
+    pub fn observe(&self, metric: &Metric) {
+        let labels: Labels = metric
+            .stream_id
+            .iter()
+            .map(|stream_id| ("stream".to_string(), stream_id.clone()))
+            .collect();
+        let mut families = self.families.lock().unwrap();
+        if metric.name.ends_with(COUNTER_SUFFIX) {
+            let name = sanitize_name(metric.name.trim_end_matches(COUNTER_SUFFIX)) + "_total";
+            *families
+                .counters
+                .entry(name)
+                .or_insert_with(BTreeMap::new)
+                .entry(labels)
+                .or_insert(0.0) += metric.value;
+        } else {
+            families
+                .histograms
+                .entry(sanitize_name(&metric.name))
+                .or_insert_with(BTreeMap::new)
+                .entry(labels)
+                .or_insert_with(HistogramSeries::default)
+                .observe(metric.value);
+        }
+    }
+
+    /// Sets the current value of a gauge, replacing any previous value.
+    pub fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        self.families
+            .lock()
+            .unwrap()
+            .gauges
+            .entry(sanitize_name(name))
+            .or_insert_with(BTreeMap::new)
+            .insert(labels, value);
+    }
+
+    /// Renders all aggregated metrics in the text exposition format.
+    pub fn render(&self) -> String {
+        let families = self.families.lock().unwrap();
+        let mut out = String::new();
+        for (name, series) in families.counters.iter() {
+            writeln!(out, "# TYPE {} counter", name).unwrap();
+            for (labels, value) in series.iter() {
+                writeln!(out, "{}{} {}", name, format_labels(labels), value).unwrap();
+            }
+        }
+        for (name, series) in families.gauges.iter() {
+            writeln!(out, "# TYPE {} gauge", name).unwrap();
+            for (labels, value) in series.iter() {
+                writeln!(out, "{}{} {}", name, format_labels(labels), value).unwrap();
+            }
+        }
+        for (name, series) in families.histograms.iter() {
+            writeln!(out, "# TYPE {} histogram", name).unwrap();
+            for (labels, HistogramSeries { histogram, sum }) in series.iter() {
+                let count = histogram.count();
+                let mut cumulative = 0;
+                for (bound, bucket_count) in HISTOGRAM_BUCKETS.iter().zip(histogram.counts()) {
+                    cumulative += bucket_count;
+                    let labels = with_label(labels, "le", bound.to_string());
+                    writeln!(
+                        out,
+                        "{}_bucket{} {}",
+                        name,
+                        format_labels(&labels),
+                        cumulative
+                    )
+                    .unwrap();
+                }
+                let labels_inf = with_label(labels, "le", "+Inf".to_string());
+                writeln!(
+                    out,
+                    "{}_bucket{} {}",
+                    name,
+                    format_labels(&labels_inf),
+                    count
+                )
+                .unwrap();
+                writeln!(out, "{}_sum{} {}", name, format_labels(labels), sum).unwrap();
+                writeln!(out, "{}_count{} {}", name, format_labels(labels), count).unwrap();
+            }
+        }
+        out
+    }
+}
+
+lazy_static! {
+    static ref REGISTRIES: Mutex<HashMap<String, Arc<PrometheusRegistry>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Returns the registry served on the configured address, starting
+/// the http server thread the first time the address is asked for.
+pub fn prometheus_registry(config: &PrometheusConfig) -> Arc<PrometheusRegistry> {
+    let address = config.address();
+    let mut registries = REGISTRIES.lock().unwrap();
+    registries
+        .entry(address.clone())
+        .or_insert_with(|| {
+            let registry = Arc::new(PrometheusRegistry::new());
+            match TcpListener::bind(&address) {
+                Ok(listener) => {
+                    spawn_server(listener, registry.clone());
+                    info!("Serving prometheus metrics on http://{}/metrics", address);
+                }
+                Err(err) => error!(
+                    "Could not bind prometheus metrics to {}, metrics will not be served: {:?}",
+                    address, err
+                ),
+            }
+            registry
+        })
+        .clone()
+}
+
+fn spawn_server(listener: TcpListener, registry: Arc<PrometheusRegistry>) {
+    let address = listener
+        .local_addr()
+        .map(|address| address.to_string())
+        .unwrap_or_default();
+    let _join_handle = std::thread::Builder::new()
+        .name(format!("prometheus_metrics/{}", address))
+        .spawn(move || serve(listener, registry))
+        .expect("Could not spawn prometheus metrics thread");
+}
+
+/// Answers every connection on its own thread so that a slow or stalled client
+/// can't hold up the scrapes of others.
+fn serve(listener: TcpListener, registry: Arc<PrometheusRegistry>) {
+    for stream in listener.incoming() {
+        let registry = registry.clone();
+        let result = stream.and_then(|mut stream| {
+            std::thread::Builder::new()
+                .name("prometheus_metrics/connection".to_string())
+                .spawn(move || {
+                    if let Err(err) = respond(&mut stream, &registry) {
+                        warn!("Error serving prometheus metrics: {:?}", err);
+                    }
+                })
+                .map(|_join_handle| ())
+        });
+        if let Err(err) = result {
+            warn!("Error accepting prometheus metrics connection: {:?}", err);
+        }
+    }
+}
+
+/// Every request gets the full exposition, whatever the path.
+fn respond(stream: &mut TcpStream, registry: &PrometheusRegistry) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+    let mut request = [0; 1024];
+    let _ = stream.read(&mut request)?;
+    let body = registry.render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        CONTENT_TYPE,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// A metric publisher aggregating metrics into a prometheus registry.
+#[derive(Clone)]
+pub struct PrometheusMetricPublisher {
+    registry: Arc<PrometheusRegistry>,
+}
+
+impl PrometheusMetricPublisher {
+    pub fn new(config: &PrometheusConfig) -> Self {
+        Self {
+            registry: prometheus_registry(config),
+        }
+    }
+
+    /// The registry backing this publisher, used to export gauges next to the metrics.
+    pub fn registry(&self) -> Arc<PrometheusRegistry> {
+        self.registry.clone()
+    }
+}
+
+impl MetricPublisher for PrometheusMetricPublisher {
+    fn publish(&mut self, metric: &Metric) {
+        self.registry.observe(metric);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_metric_names() {
+        assert_eq!(
+            "sim2h_handle_message",
+            sanitize_name("sim2h.handle-message")
+        );
+        assert_eq!("_1st", sanitize_name("1st"));
+    }
+
+    #[test]
+    fn renders_counters_and_histograms() {
+        let registry = PrometheusRegistry::new();
+        registry.observe(&Metric::new("calls.count", None, None, 2.0));
+        registry.observe(&Metric::new("calls.count", None, None, 3.0));
+        registry.observe(&Metric::new(
+            "zome_call.latency",
+            Some("app".to_string()),
+            None,
+            7.0,
+        ));
+        registry.observe(&Metric::new(
+            "zome_call.latency",
+            Some("app".to_string()),
+            None,
+            20000.0,
+        ));
+        let rendered = registry.render();
+
+        assert!(rendered.contains("# TYPE calls_total counter\ncalls_total 5\n"));
+        assert!(rendered.contains("# TYPE zome_call_latency histogram\n"));
+        assert!(rendered.contains("zome_call_latency_bucket{stream=\"app\",le=\"5\"} 0\n"));
+        assert!(rendered.contains("zome_call_latency_bucket{stream=\"app\",le=\"10\"} 1\n"));
+        assert!(rendered.contains("zome_call_latency_bucket{stream=\"app\",le=\"10000\"} 1\n"));
+        assert!(rendered.contains("zome_call_latency_bucket{stream=\"app\",le=\"+Inf\"} 2\n"));
+        assert!(rendered.contains("zome_call_latency_sum{stream=\"app\"} 20007\n"));
+        assert!(rendered.contains("zome_call_latency_count{stream=\"app\"} 2\n"));
+    }
+
+    #[test]
+    fn gauges_keep_the_latest_value() {
+        let registry = PrometheusRegistry::new();
+        registry.set_gauge("instance.held_entries", &[("instance", "app")], 3.0);
+        registry.set_gauge("instance.held_entries", &[("instance", "app")], 4.0);
+        registry.set_gauge("instance.held_entries", &[("instance", "a\"b")], 1.0);
+        let rendered = registry.render();
+
+        assert!(rendered.contains("# TYPE instance_held_entries gauge\n"));
+        assert!(rendered.contains("instance_held_entries{instance=\"app\"} 4\n"));
+        assert!(rendered.contains("instance_held_entries{instance=\"a\\\"b\"} 1\n"));
+        assert!(!rendered.contains("instance_held_entries{instance=\"app\"} 3\n"));
+    }
+
+    #[test]
+    fn deserializes_publisher_config() {
+        let config: crate::MetricPublisherConfig =
+            toml::from_str("type = \"prometheus\"\nport = 9100\n").unwrap();
+        assert_eq!(
+            crate::MetricPublisherConfig::default_prometheus(9100),
+            config
+        );
+    }
+
+    #[test]
+    fn serves_the_exposition_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let registry = Arc::new(PrometheusRegistry::new());
+        spawn_server(listener, registry.clone());
+        registry.observe(&Metric::new("served.count", None, None, 1.0));
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.contains("served_total 1\n"));
+    }
+}
@@ -4,15 +4,18 @@ use crate::metrics::Metric;
 use num_traits::float::Float;
 use stats::Commute;
 use std::{
+    cmp::Ordering,
     collections::HashMap,
     error::Error,
     fmt,
     fmt::{Display, Formatter},
     io,
     iter::FromIterator,
+    str::FromStr,
 };
 
 use regex::Regex;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 /// Generic representation of descriptive statistics.
 pub trait DescriptiveStats {
@@ -23,6 +26,26 @@ pub trait DescriptiveStats {
     fn stddev(&self) -> f64;
     fn variance(&self) -> f64;
 
+    /// The estimated 50th percentile, if tracked.
+    fn p50(&self) -> Option<f64> {
+        None
+    }
+
+    /// The estimated 90th percentile, if tracked.
+    fn p90(&self) -> Option<f64> {
+        None
+    }
+
+    /// The estimated 99th percentile, if tracked.
+    fn p99(&self) -> Option<f64> {
+        None
+    }
+
+    /// The distribution of samples over `HISTOGRAM_BUCKETS`, if tracked.
+    fn histogram(&self) -> Option<Histogram> {
+        None
+    }
+
     /// Computes percent change between two descriptive statistics
     fn percent_change(&self, other: &dyn DescriptiveStats) -> StatsRecord {
         StatsRecord {
@@ -32,11 +55,300 @@ pub trait DescriptiveStats {
             cnt: percent_change(self.cnt(), other.cnt()),
             stddev: percent_change(self.stddev(), other.stddev()),
             variance: percent_change(self.variance(), other.variance()),
+            p50: optional_percent_change(self.p50(), other.p50()),
+            p90: optional_percent_change(self.p90(), other.p90()),
+            p99: optional_percent_change(self.p99(), other.p99()),
             ..Default::default()
         }
     }
 }
 
+/// Upper bounds of the fixed histogram buckets, tuned for latencies in milliseconds.
+/// Samples above the last bound fall into a final overflow bucket.
+pub const HISTOGRAM_BUCKETS: [f64; 12] = [
+    1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+];
+
+const NUM_BUCKETS: usize = 13;
+
+/// Sample counts per bucket of `HISTOGRAM_BUCKETS`, plus the overflow bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Histogram {
+    counts: [u64; NUM_BUCKETS],
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self {
+            counts: [0; NUM_BUCKETS],
+        }
+    }
+
+    /// Counts a sample in the first bucket whose upper bound is not below it.
+    pub fn add(&mut self, value: f64) {
+        let index = HISTOGRAM_BUCKETS
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(NUM_BUCKETS - 1);
+        self.counts[index] += 1;
+    }
+
+    /// The (non cumulative) sample count of each bucket, the overflow bucket last.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// The number of samples counted over all buckets.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Commute for Histogram {
+    fn merge(&mut self, rhs: Self) {
+        for (count, rhs_count) in self.counts.iter_mut().zip(rhs.counts.iter()) {
+            *count += rhs_count;
+        }
+    }
+}
+
+/// Renders as `<bound>=<count>` pairs separated by `;`, the overflow bucket as `+Inf`.
+impl Display for Histogram {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let bounds = HISTOGRAM_BUCKETS
+            .iter()
+            .map(|bound| bound.to_string())
+            .chain(std::iter::once("+Inf".to_string()));
+        let buckets: Vec<String> = bounds
+            .zip(self.counts.iter())
+            .map(|(bound, count)| format!("{}={}", bound, count))
+            .collect();
+        write!(f, "{}", buckets.join(";"))
+    }
+}
+
+impl FromStr for Histogram {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut histogram = Histogram::new();
+        let buckets: Vec<&str> = s.split(';').collect();
+        if buckets.len() != NUM_BUCKETS {
+            return Err(format!(
+                "Expected {} histogram buckets, got {}",
+                NUM_BUCKETS,
+                buckets.len()
+            ));
+        }
+        for (count, bucket) in histogram.counts.iter_mut().zip(buckets) {
+            *count = bucket
+                .rsplit('=')
+                .next()
+                .and_then(|count| count.parse().ok())
+                .ok_or_else(|| format!("Invalid histogram bucket: {}", bucket))?;
+        }
+        Ok(histogram)
+    }
+}
+
+impl Serialize for Histogram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Histogram {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Streaming estimate of a single quantile using the P² algorithm
+/// (Jain & Chlamtac, 1985), which keeps five markers instead of the samples.
+#[derive(Debug, Clone, Copy)]
+pub struct QuantileEstimator {
+    quantile: f64,
+    cnt: u64,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl QuantileEstimator {
+    /// Estimates the `quantile`, which must lie in [0, 1].
+    pub fn new(quantile: f64) -> Self {
+        Self {
+            quantile,
+            cnt: 0,
+            heights: [0.0; 5],
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [
+                1.0,
+                1.0 + 2.0 * quantile,
+                1.0 + 4.0 * quantile,
+                3.0 + 2.0 * quantile,
+                5.0,
+            ],
+            increments: [0.0, quantile / 2.0, quantile, (1.0 + quantile) / 2.0, 1.0],
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        if self.cnt < 5 {
+            self.heights[self.cnt as usize] = value;
+            self.cnt += 1;
+            if self.cnt == 5 {
+                self.heights
+                    .sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            }
+            return;
+        }
+        self.cnt += 1;
+
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (1..5).find(|i| value < self.heights[*i]).unwrap_or(4) - 1
+        };
+        for position in self.positions.iter_mut().skip(cell + 1) {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments.iter()) {
+            *desired += increment;
+        }
+
+        for i in 1..4 {
+            let delta = self.desired[i] - self.positions[i];
+            if (delta >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0)
+                || (delta <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0)
+            {
+                let delta = delta.signum();
+                let parabolic = self.parabolic(i, delta);
+                self.heights[i] =
+                    if self.heights[i - 1] < parabolic && parabolic < self.heights[i + 1] {
+                        parabolic
+                    } else {
+                        self.linear(i, delta)
+                    };
+                self.positions[i] += delta;
+            }
+        }
+    }
+
+    fn parabolic(&self, i: usize, delta: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + delta / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + delta) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - delta) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, delta: f64) -> f64 {
+        let j = if delta > 0.0 { i + 1 } else { i - 1 };
+        self.heights[i]
+            + delta * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+
+    /// The current estimate, `None` before any sample was added.
+    pub fn estimate(&self) -> Option<f64> {
+        match self.cnt {
+            0 => None,
+            cnt if cnt < 5 => {
+                let mut samples = self.heights[..cnt as usize].to_vec();
+                samples.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let index = (self.quantile * (cnt - 1) as f64).round() as usize;
+                Some(samples[index])
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+}
+
+/// Merging is approximate: while either side has fewer than five samples they
+/// are replayed into the other, after that the markers of both sides are read as
+/// piecewise linear distributions which are summed and sampled at the merged
+/// marker positions.
+impl Commute for QuantileEstimator {
+    fn merge(&mut self, rhs: Self) {
+        if rhs.cnt < 5 {
+            for value in rhs.heights.iter().take(rhs.cnt as usize) {
+                self.add(*value);
+            }
+        } else if self.cnt < 5 {
+            let lhs = *self;
+            *self = rhs;
+            for value in lhs.heights.iter().take(lhs.cnt as usize) {
+                self.add(*value);
+            }
+        } else {
+            let mut points: Vec<f64> = self
+                .heights
+                .iter()
+                .chain(rhs.heights.iter())
+                .cloned()
+                .collect();
+            points.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            let cumulative: Vec<f64> = points
+                .iter()
+                .map(|point| self.cumulative_count(*point) + rhs.cumulative_count(*point))
+                .collect();
+
+            self.cnt += rhs.cnt;
+            let cnt = self.cnt as f64;
+            for i in 0..5 {
+                self.desired[i] = 1.0 + (cnt - 1.0) * self.increments[i];
+                let position = self.desired[i].round();
+                self.positions[i] = if i == 0 {
+                    position
+                } else {
+                    f64::max(position, self.positions[i - 1] + 1.0)
+                };
+                self.heights[i] = match cumulative.iter().position(|c| *c >= self.positions[i]) {
+                    Some(0) => points[0],
+                    Some(k) => {
+                        points[k - 1]
+                            + (self.positions[i] - cumulative[k - 1])
+                                / (cumulative[k] - cumulative[k - 1])
+                                * (points[k] - points[k - 1])
+                    }
+                    None => points[points.len() - 1],
+                };
+            }
+        }
+    }
+}
+
+impl QuantileEstimator {
+    /// Approximate number of samples not above `value`, interpolated between the markers.
+    fn cumulative_count(&self, value: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        if value < q[0] {
+            return 0.0;
+        }
+        if value >= q[4] {
+            return n[4];
+        }
+        let i = (1..5).find(|i| value < q[*i]).unwrap_or(4) - 1;
+        if q[i + 1] > q[i] {
+            n[i] + (value - q[i]) / (q[i + 1] - q[i]) * (n[i + 1] - n[i])
+        } else {
+            n[i]
+        }
+    }
+}
+
 /// An extension of `stats::OnlineStats` that also incrementally tracks
 /// max and min values.
 #[derive(Debug, Clone, Shrinkwrap)]
@@ -46,6 +358,10 @@ pub struct OnlineStats {
     max: f64,
     min: f64,
     cnt: u64,
+    p50: QuantileEstimator,
+    p90: QuantileEstimator,
+    p99: QuantileEstimator,
+    histogram: Histogram,
 }
 
 /// A statistical record, useful for serialization and display purposes.
@@ -59,6 +375,14 @@ pub struct StatsRecord {
     pub mean: f64,
     pub variance: f64,
     pub stddev: f64,
+    #[serde(default)]
+    pub p50: Option<f64>,
+    #[serde(default)]
+    pub p90: Option<f64>,
+    #[serde(default)]
+    pub p99: Option<f64>,
+    #[serde(default)]
+    pub histogram: Option<Histogram>,
 }
 
 /// A checked statistical record to indicate differences between two statistics.
@@ -72,18 +396,27 @@ pub struct CheckedStatsRecord {
     pub expected_mean: f64,
     pub expected_variance: f64,
     pub expected_stddev: f64,
+    pub expected_p50: Option<f64>,
+    pub expected_p90: Option<f64>,
+    pub expected_p99: Option<f64>,
     pub actual_max: f64,
     pub actual_min: f64,
     pub actual_cnt: f64,
     pub actual_mean: f64,
     pub actual_variance: f64,
     pub actual_stddev: f64,
+    pub actual_p50: Option<f64>,
+    pub actual_p90: Option<f64>,
+    pub actual_p99: Option<f64>,
     pub percent_change_max: f64,
     pub percent_change_min: f64,
     pub percent_change_cnt: f64,
     pub percent_change_mean: f64,
     pub percent_change_variance: f64,
     pub percent_change_stddev: f64,
+    pub percent_change_p50: Option<f64>,
+    pub percent_change_p90: Option<f64>,
+    pub percent_change_p99: Option<f64>,
     pub percent_change_allowed: f64,
     pub passed: bool,
 }
@@ -107,18 +440,27 @@ impl CheckedStatsRecord {
             expected_mean: expected.mean(),
             expected_variance: expected.variance(),
             expected_stddev: expected.stddev(),
+            expected_p50: expected.p50(),
+            expected_p90: expected.p90(),
+            expected_p99: expected.p99(),
             actual_max: actual.max(),
             actual_min: actual.min(),
             actual_cnt: actual.cnt(),
             actual_mean: actual.mean(),
             actual_variance: actual.variance(),
             actual_stddev: actual.stddev(),
+            actual_p50: actual.p50(),
+            actual_p90: actual.p90(),
+            actual_p99: actual.p99(),
             percent_change_max: percent_change.max(),
             percent_change_min: percent_change.min(),
             percent_change_cnt: percent_change.cnt(),
             percent_change_mean: percent_change.mean(),
             percent_change_variance: percent_change.variance(),
             percent_change_stddev: percent_change.stddev(),
+            percent_change_p50: percent_change.p50(),
+            percent_change_p90: percent_change.p90(),
+            percent_change_p99: percent_change.p99(),
             percent_change_allowed,
             passed,
         }
@@ -136,6 +478,10 @@ impl Default for StatsRecord {
             variance: 0.0,
             stddev: 0.0,
             cnt: 0.,
+            p50: None,
+            p90: None,
+            p99: None,
+            histogram: None,
         }
     }
 }
@@ -157,6 +503,10 @@ impl StatsRecord {
             cnt: desc.cnt() as f64,
             stddev: desc.stddev(),
             variance: desc.variance(),
+            p50: desc.p50(),
+            p90: desc.p90(),
+            p99: desc.p99(),
+            histogram: desc.histogram(),
         }
     }
 
@@ -192,6 +542,10 @@ impl From<OnlineStats> for StatsRecord {
             mean: desc_stats.mean(),
             variance: desc_stats.variance(),
             cnt: desc_stats.cnt() as f64,
+            p50: desc_stats.p50(),
+            p90: desc_stats.p90(),
+            p99: desc_stats.p99(),
+            histogram: desc_stats.histogram(),
         }
     }
 }
@@ -215,6 +569,18 @@ impl DescriptiveStats for StatsRecord {
     fn mean(&self) -> f64 {
         self.mean
     }
+    fn p50(&self) -> Option<f64> {
+        self.p50
+    }
+    fn p90(&self) -> Option<f64> {
+        self.p90
+    }
+    fn p99(&self) -> Option<f64> {
+        self.p99
+    }
+    fn histogram(&self) -> Option<Histogram> {
+        self.histogram
+    }
 }
 
 impl Copy for OnlineStats {}
@@ -226,6 +592,9 @@ pub enum DescriptiveStatType {
     Min,
     StdDev,
     Count,
+    P50,
+    P90,
+    P99,
 }
 
 impl Display for DescriptiveStatType {
@@ -260,6 +629,10 @@ impl OnlineStats {
             max: f64::min_value(),
             min: f64::max_value(),
             cnt: 0,
+            p50: QuantileEstimator::new(0.5),
+            p90: QuantileEstimator::new(0.9),
+            p99: QuantileEstimator::new(0.99),
+            histogram: Histogram::new(),
         }
     }
 
@@ -273,6 +646,10 @@ impl OnlineStats {
             self.min = value
         }
         self.cnt += 1;
+        self.p50.add(value);
+        self.p90.add(value);
+        self.p99.add(value);
+        self.histogram.add(value);
     }
 }
 
@@ -306,6 +683,26 @@ impl DescriptiveStats for OnlineStats {
     fn cnt(&self) -> f64 {
         self.cnt as f64
     }
+
+    /// The estimated median of the running statistic.
+    fn p50(&self) -> Option<f64> {
+        self.p50.estimate()
+    }
+
+    /// The estimated 90th percentile of the running statistic.
+    fn p90(&self) -> Option<f64> {
+        self.p90.estimate()
+    }
+
+    /// The estimated 99th percentile of the running statistic.
+    fn p99(&self) -> Option<f64> {
+        self.p99.estimate()
+    }
+
+    /// The distribution of samples over the fixed buckets.
+    fn histogram(&self) -> Option<Histogram> {
+        Some(self.histogram)
+    }
 }
 
 pub trait StatCheck {
@@ -341,6 +738,15 @@ pub fn percent_change<N: Into<f64>>(expected: N, actual: N) -> f64 {
     f64::abs(e - a) / e
 }
 
+/// Percentage change between two statistics which may not be tracked,
+/// `None` unless both are present.
+pub fn optional_percent_change(expected: Option<f64>, actual: Option<f64>) -> Option<f64> {
+    match (expected, actual) {
+        (Some(expected), Some(actual)) => Some(percent_change(expected, actual)),
+        _ => None,
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LessThanStatCheck {
     percent_change_allowed: f64,
@@ -408,6 +814,40 @@ impl StatCheck for LessThanStatCheck {
             })
         }
 
+        // Quantiles are only checked if both sides tracked them,
+        // so stat files written before they existed still compare.
+        let quantiles = [
+            (
+                percent_change.p50(),
+                expected.p50(),
+                actual.p50(),
+                DescriptiveStatType::P50,
+            ),
+            (
+                percent_change.p90(),
+                expected.p90(),
+                actual.p90(),
+                DescriptiveStatType::P90,
+            ),
+            (
+                percent_change.p99(),
+                expected.p99(),
+                actual.p99(),
+                DescriptiveStatType::P99,
+            ),
+        ];
+        for (change, expected, actual, stat_type) in quantiles.iter() {
+            if let (Some(change), Some(expected), Some(actual)) = (change, expected, actual) {
+                if *change > self.percent_change_allowed {
+                    failures.push(StatFailure {
+                        expected: *expected,
+                        actual: *actual,
+                        stat_type: stat_type.clone(),
+                    })
+                }
+            }
+        }
+
         if failures.is_empty() {
             checked_stats_record.passed = true;
             Ok(checked_stats_record)
@@ -451,6 +891,10 @@ impl Commute for OnlineStats {
             self.min = rhs.min
         }
         self.cnt += rhs.cnt;
+        self.p50.merge(rhs.p50);
+        self.p90.merge(rhs.p90);
+        self.p99.merge(rhs.p99);
+        self.histogram.merge(rhs.histogram);
     }
 }
 
@@ -685,6 +1129,126 @@ mod tests {
         assert!(f64::is_infinite(percent_change(0., 10.0)));
     }
 
+    #[test]
+    fn can_estimate_quantiles() {
+        let metrics = (1..=1000).map(|x| Metric::new("latency", None, None, x as f64));
+        let stats = StatsByMetric::from_iter_with_stream_id(metrics, "test");
+        let latency_stats = stats
+            .get(&GroupingKey::new("test", "latency"))
+            .expect("latency stats to be present");
+
+        let p50 = latency_stats.p50().unwrap();
+        let p90 = latency_stats.p90().unwrap();
+        let p99 = latency_stats.p99().unwrap();
+        assert!((p50 - 500.0).abs() < 10.0, "p50 was {}", p50);
+        assert!((p90 - 900.0).abs() < 10.0, "p90 was {}", p90);
+        assert!((p99 - 990.0).abs() < 10.0, "p99 was {}", p99);
+    }
+
+    #[test]
+    fn quantiles_of_few_samples_are_exact() {
+        let mut estimator = QuantileEstimator::new(0.5);
+        assert_eq!(None, estimator.estimate());
+        for value in &[30.0, 10.0, 20.0] {
+            estimator.add(*value);
+        }
+        assert_eq!(Some(20.0), estimator.estimate());
+    }
+
+    #[test]
+    fn can_merge_quantiles_and_histograms() {
+        let mut lhs = OnlineStats::empty();
+        let mut rhs = OnlineStats::empty();
+        for x in 1..=500 {
+            lhs.add(x as f64);
+            rhs.add((x + 500) as f64);
+        }
+        lhs.merge(rhs);
+
+        let p50 = lhs.p50().unwrap();
+        let p90 = lhs.p90().unwrap();
+        assert!((p50 - 500.0).abs() < 10.0, "p50 was {}", p50);
+        assert!((p90 - 900.0).abs() < 10.0, "p90 was {}", p90);
+        assert_eq!(1000, lhs.histogram().unwrap().count());
+    }
+
+    #[test]
+    fn histogram_buckets_samples() {
+        let mut histogram = Histogram::new();
+        for value in &[0.5, 1.0, 3.0, 700.0, 20000.0] {
+            histogram.add(*value);
+        }
+        assert_eq!(
+            &[2u64, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1],
+            histogram.counts()
+        );
+        assert_eq!(
+            "1=2;5=1;10=0;25=0;50=0;100=0;250=0;500=0;1000=1;2500=0;5000=0;10000=0;+Inf=1",
+            histogram.to_string()
+        );
+        assert_eq!(Ok(histogram), histogram.to_string().parse());
+        assert!("1=2;5=1".parse::<Histogram>().is_err());
+    }
+
+    #[test]
+    fn quantiles_and_histograms_roundtrip_through_csv() {
+        let metrics = (1..=100).map(|x| Metric::new("latency", None, None, x as f64));
+        let stats = StatsByMetric::from_iter_with_stream_id(metrics, "test");
+        let mut csv = Vec::new();
+        stats.write_csv(&mut csv).unwrap();
+
+        let read = StatsByMetric::<StatsRecord>::from_reader(&mut csv.as_slice()).unwrap();
+        let record = read
+            .get(&GroupingKey::new("test", "latency"))
+            .expect("latency stats to be present");
+        let online = stats.get(&GroupingKey::new("test", "latency")).unwrap();
+        assert_eq!(online.p50(), record.p50());
+        assert_eq!(online.p99(), record.p99());
+        assert_eq!(online.histogram(), record.histogram());
+    }
+
+    #[test]
+    fn can_read_stats_without_quantiles() {
+        let csv = "metric,stream_id,max,min,cnt,mean,variance,stddev\nlatency,test,3,1,3,2,1,1\n";
+        let read = StatsByMetric::<StatsRecord>::from_reader(&mut csv.as_bytes()).unwrap();
+        let record = read.get(&GroupingKey::new("test", "latency")).unwrap();
+        assert_eq!(2.0, record.mean());
+        assert_eq!(None, record.p99());
+        assert_eq!(None, record.histogram());
+    }
+
+    #[test]
+    fn stat_check_compares_tail_latency() {
+        let expected = StatsRecord {
+            mean: 50.0,
+            max: 100.0,
+            min: 25.0,
+            cnt: 100.,
+            stddev: 10.0,
+            variance: 5.0,
+            p99: Some(90.0),
+            ..Default::default()
+        };
+        let actual = StatsRecord {
+            p99: Some(99.0),
+            ..expected.clone()
+        };
+
+        let (checked, failures) = LessThanStatCheck::default()
+            .check(&expected, &actual)
+            .unwrap_err();
+        assert_eq!(
+            Some(0.1),
+            checked
+                .percent_change_p99
+                .map(|x| (x * 100.).round() / 100.)
+        );
+        assert_eq!(
+            vec!["P99: Expected 90, Actual was 99".to_string()],
+            failures.iter().map(|f| f.to_string()).collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     fn checked_stats_can_serialize() {
         let expected = StatsRecord {
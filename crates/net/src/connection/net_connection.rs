@@ -1,16 +1,25 @@
 use super::NetResult;
-use lib3h_protocol::{protocol_client::Lib3hClientProtocol, protocol_server::Lib3hServerProtocol};
+use lib3h_protocol::{
+    data_types::DirectMessageData, protocol_client::Lib3hClientProtocol,
+    protocol_server::Lib3hServerProtocol,
+};
 use parking_lot::RwLock;
 use std::{fmt, sync::Arc};
 
 /// closure for processing a Protocol message received from the network
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct NetHandler {
-    #[serde(skip)]
+    #[serde(skip, default = "ignore_messages")]
     closure:
         Arc<RwLock<Box<dyn FnMut(NetResult<Lib3hServerProtocol>) -> NetResult<()> + Send + Sync>>>,
 }
 
+/// the closure a deserialized handler gets, as closures can't be serialized
+fn ignore_messages(
+) -> Arc<RwLock<Box<dyn FnMut(NetResult<Lib3hServerProtocol>) -> NetResult<()> + Send + Sync>>> {
+    Arc::new(RwLock::new(Box::new(|_| Ok(()))))
+}
+
 impl NetHandler {
     pub fn new(
         c: Box<dyn FnMut(NetResult<Lib3hServerProtocol>) -> NetResult<()> + Send + Sync>,
@@ -44,6 +53,10 @@ pub type NetShutdown = Option<Box<dyn FnMut() + Send + Sync>>;
 ///  Trait for sending a Protocol message to the network
 pub trait NetSend {
     fn send(&mut self, data: Lib3hClientProtocol) -> NetResult<()>;
+
+    /// Sends a direct message that the network should hold for its recipient
+    /// if the recipient is offline, instead of failing
+    fn send_store_and_forward_direct_message(&mut self, data: DirectMessageData) -> NetResult<()>;
 }
 
 /// Trait that represents a worker thread that relays incoming and outgoing protocol messages
@@ -55,6 +68,16 @@ pub trait NetWorker {
         Ok(())
     }
 
+    /// The receiving method when NetSend's `send_store_and_forward_direct_message()` is called.
+    /// P2p modules that can't hold messages for offline agents send it like any other
+    /// direct message.
+    fn receive_store_and_forward_direct_message(
+        &mut self,
+        data: DirectMessageData,
+    ) -> NetResult<()> {
+        self.receive(Lib3hClientProtocol::SendDirectMessage(data))
+    }
+
     /// Polls the p2p module for Protocol messages received from the network,
     /// and perform any other upkeep.
     /// It should realy those messages back to the handler closure.
@@ -14,18 +14,24 @@ use std::{
     thread, time,
 };
 
-use lib3h_protocol::protocol_client::Lib3hClientProtocol;
+use lib3h_protocol::{data_types::DirectMessageData, protocol_client::Lib3hClientProtocol};
 
 const TICK_SLEEP_MIN_US: u64 = 100;
 const TICK_SLEEP_MAX_US: u64 = 10_000;
 const TICK_SLEEP_STARTUP_RETRY_MS: u64 = 3_000;
 
+/// What gets passed on to the worker when NetSend gets called
+enum WorkerInput {
+    Protocol(Lib3hClientProtocol),
+    StoreAndForwardDirectMessage(DirectMessageData),
+}
+
 /// Struct for holding a network connection running on a separate thread.
 /// It is itself a NetSend, and spawns a NetWorker.
 #[derive(Clone)]
 pub struct NetConnectionThread {
     can_keep_running: Arc<AtomicBool>,
-    send_channel: crossbeam_channel::Sender<Lib3hClientProtocol>,
+    send_channel: crossbeam_channel::Sender<WorkerInput>,
     thread: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
     pub endpoint: String,
     pub p2p_endpoint: url::Url,
@@ -34,7 +40,13 @@ pub struct NetConnectionThread {
 impl NetSend for NetConnectionThread {
     /// send a message to the worker within NetConnectionThread's child thread.
     fn send(&mut self, data: Lib3hClientProtocol) -> NetResult<()> {
-        self.send_channel.send(data)?;
+        self.send_channel.send(WorkerInput::Protocol(data))?;
+        Ok(())
+    }
+
+    fn send_store_and_forward_direct_message(&mut self, data: DirectMessageData) -> NetResult<()> {
+        self.send_channel
+            .send(WorkerInput::StoreAndForwardDirectMessage(data))?;
         Ok(())
     }
 }
@@ -90,7 +102,13 @@ impl NetConnectionThread {
                             // Received data from parent
                             // Have the worker handle it
                             did_something = true;
-                            worker.receive(data).unwrap_or_else(|e| {
+                            match data {
+                                WorkerInput::Protocol(data) => worker.receive(data),
+                                WorkerInput::StoreAndForwardDirectMessage(data) => {
+                                    worker.receive_store_and_forward_direct_message(data)
+                                }
+                            }
+                            .unwrap_or_else(|e| {
                                 debug!("Error occured in p2p network module, on receive: {:?}", e)
                             });
                             Ok(())
@@ -0,0 +1,320 @@
+//! configurable network faults for the in-memory network.
+//! Lets scenario tests simulate latency, lossy links, network partitions and agents
+//! going offline on a single machine. All randomness comes from a seeded generator
+//! so a given seed always drops and delays the same messages, and delays are measured
+//! on a [Clock](enum.Clock.html) that tests can advance by hand instead of sleeping.
+
+use lib3h_protocol::types::AgentPubKey;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+/// the time delayed messages become due by
+#[derive(Clone, Debug, PartialEq)]
+pub enum Clock {
+    /// the system's monotonic clock
+    System,
+    /// a clock that stands still until it gets advanced
+    Manual(Instant),
+}
+
+impl Clock {
+    pub fn now(&self) -> Instant {
+        match self {
+            Clock::System => Instant::now(),
+            Clock::Manual(now) => *now,
+        }
+    }
+}
+
+/// faults applied to every message travelling over a link between two agents
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkFaults {
+    /// fixed delay before a message is delivered
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// additional random delay between 0 and this value
+    #[serde(default)]
+    pub jitter_ms: u64,
+    /// probability between 0.0 and 1.0 that a message gets lost
+    #[serde(default)]
+    pub drop_rate: f64,
+}
+
+/// initial fault model of an in-memory network, read from the `faultModel` field
+/// of the in-memory backend config
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FaultModelConfig {
+    /// seed for the generator deciding about drops and jitter
+    #[serde(default)]
+    pub seed: u64,
+    /// faults applied to all links that don't have their own
+    #[serde(default)]
+    pub default_link: LinkFaults,
+}
+
+/// what should happen to a message
+#[derive(Clone, Debug, PartialEq)]
+pub enum Delivery {
+    Drop,
+    After(Duration),
+}
+
+pub struct FaultModel {
+    default_link: LinkFaults,
+    /// faults of specific links, keyed by (from, to)
+    links: HashMap<(AgentPubKey, AgentPubKey), LinkFaults>,
+    /// agents are only reachable from agents in the same partition,
+    /// agents not in this map are in the default partition 0
+    partitions: HashMap<AgentPubKey, usize>,
+    /// agents that are simulated to be offline
+    clocked_out: HashSet<AgentPubKey>,
+    rng_state: u64,
+    clock: Clock,
+}
+
+impl Default for FaultModel {
+    fn default() -> Self {
+        FaultModel::new(FaultModelConfig::default())
+    }
+}
+
+impl FaultModel {
+    pub fn new(config: FaultModelConfig) -> Self {
+        FaultModel {
+            default_link: config.default_link,
+            links: HashMap::new(),
+            partitions: HashMap::new(),
+            clocked_out: HashSet::new(),
+            rng_state: config.seed,
+            clock: Clock::System,
+        }
+    }
+
+    /// the current time of the clock delays are measured on
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// stop the clock delays are measured on, from now on time only passes
+    /// through `advance_clock`
+    pub fn use_manual_clock(&mut self) {
+        self.clock = Clock::Manual(self.clock.now());
+    }
+
+    /// let `by` pass on the manual clock, does nothing on the system clock
+    pub fn advance_clock(&mut self, by: Duration) {
+        if let Clock::Manual(now) = &mut self.clock {
+            *now += by;
+        }
+    }
+
+    /// set the faults of all links that don't have their own
+    pub fn set_default_link(&mut self, faults: LinkFaults) {
+        self.default_link = faults;
+    }
+
+    /// set the faults of messages sent from `from` to `to`
+    pub fn set_link(&mut self, from: AgentPubKey, to: AgentPubKey, faults: LinkFaults) {
+        self.links.insert((from, to), faults);
+    }
+
+    /// go back to the default faults for messages sent from `from` to `to`
+    pub fn reset_link(&mut self, from: &AgentPubKey, to: &AgentPubKey) {
+        self.links.remove(&(from.clone(), to.clone()));
+    }
+
+    /// split the network so agents can only reach agents in the same group.
+    /// Agents not mentioned in any group form one more group of their own.
+    pub fn partition(&mut self, groups: Vec<Vec<AgentPubKey>>) {
+        self.partitions.clear();
+        for (index, group) in groups.into_iter().enumerate() {
+            for agent in group {
+                self.partitions.insert(agent, index + 1);
+            }
+        }
+    }
+
+    /// make every agent reachable again
+    pub fn heal_partitions(&mut self) {
+        self.partitions.clear();
+    }
+
+    /// simulate the agent going offline, all messages from and to it get lost
+    pub fn clock_out_agent(&mut self, agent: AgentPubKey) {
+        self.clocked_out.insert(agent);
+    }
+
+    /// bring a clocked out agent back online
+    pub fn clock_in_agent(&mut self, agent: &AgentPubKey) {
+        self.clocked_out.remove(agent);
+    }
+
+    pub fn is_clocked_out(&self, agent: &AgentPubKey) -> bool {
+        self.clocked_out.contains(agent)
+    }
+
+    /// can a message sent by `from` reach `to` at all?
+    pub fn is_reachable(&self, from: &AgentPubKey, to: &AgentPubKey) -> bool {
+        let partition_of = |agent: &AgentPubKey| self.partitions.get(agent).cloned().unwrap_or(0);
+        !self.is_clocked_out(from)
+            && !self.is_clocked_out(to)
+            && partition_of(from) == partition_of(to)
+    }
+
+    /// decide the fate of a message sent to `to`.
+    /// `from` is None for messages the network itself sends.
+    pub fn delivery(&mut self, from: Option<&AgentPubKey>, to: &AgentPubKey) -> Delivery {
+        let from = match from {
+            Some(from) => from,
+            None if self.is_clocked_out(to) => return Delivery::Drop,
+            None => return Delivery::After(Duration::from_millis(0)),
+        };
+        if !self.is_reachable(from, to) {
+            return Delivery::Drop;
+        }
+        // an agent talking to itself doesn't go over the network
+        if from == to {
+            return Delivery::After(Duration::from_millis(0));
+        }
+        let faults = self
+            .links
+            .get(&(from.clone(), to.clone()))
+            .unwrap_or(&self.default_link)
+            .clone();
+        if faults.drop_rate > 0.0 && self.next_f64() < faults.drop_rate {
+            return Delivery::Drop;
+        }
+        let jitter_ms = if faults.jitter_ms > 0 {
+            self.next_u64() % (faults.jitter_ms + 1)
+        } else {
+            0
+        };
+        Delivery::After(Duration::from_millis(faults.latency_ms + jitter_ms))
+    }
+
+    /// splitmix64, good enough for simulating faults and fully reproducible
+    fn next_u64(&mut self) -> u64 {
+        self.rng_state = self.rng_state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// uniformly distributed in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn agent(name: &str) -> AgentPubKey {
+        AgentPubKey::from(name)
+    }
+
+    fn lossy_model(seed: u64) -> FaultModel {
+        FaultModel::new(FaultModelConfig {
+            seed,
+            default_link: LinkFaults {
+                latency_ms: 100,
+                jitter_ms: 50,
+                drop_rate: 0.3,
+            },
+        })
+    }
+
+    #[test]
+    fn default_fault_model_delivers_everything_instantly() {
+        let mut model = FaultModel::default();
+        for _ in 0..100 {
+            assert_eq!(
+                model.delivery(Some(&agent("alice")), &agent("bob")),
+                Delivery::After(Duration::from_millis(0))
+            );
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_faults() {
+        let mut model_1 = lossy_model(42);
+        let mut model_2 = lossy_model(42);
+        let deliveries_1 = (0..100)
+            .map(|_| model_1.delivery(Some(&agent("alice")), &agent("bob")))
+            .collect::<Vec<_>>();
+        let deliveries_2 = (0..100)
+            .map(|_| model_2.delivery(Some(&agent("alice")), &agent("bob")))
+            .collect::<Vec<_>>();
+        assert_eq!(deliveries_1, deliveries_2);
+
+        let dropped = deliveries_1
+            .iter()
+            .filter(|delivery| **delivery == Delivery::Drop)
+            .count();
+        assert!(dropped > 10 && dropped < 50);
+        for delivery in deliveries_1 {
+            if let Delivery::After(delay) = delivery {
+                assert!(delay >= Duration::from_millis(100));
+                assert!(delay <= Duration::from_millis(150));
+            }
+        }
+    }
+
+    #[test]
+    fn link_faults_override_default() {
+        let mut model = lossy_model(0);
+        model.set_link(agent("alice"), agent("bob"), LinkFaults::default());
+        assert_eq!(
+            model.delivery(Some(&agent("alice")), &agent("bob")),
+            Delivery::After(Duration::from_millis(0))
+        );
+        model.reset_link(&agent("alice"), &agent("bob"));
+        assert_ne!(
+            model.delivery(Some(&agent("alice")), &agent("bob")),
+            Delivery::After(Duration::from_millis(0))
+        );
+    }
+
+    #[test]
+    fn partitions_can_be_set_and_healed() {
+        let mut model = FaultModel::default();
+        model.partition(vec![vec![agent("alice")], vec![agent("bob")]]);
+        assert!(!model.is_reachable(&agent("alice"), &agent("bob")));
+        assert!(!model.is_reachable(&agent("carol"), &agent("bob")));
+        assert!(model.is_reachable(&agent("alice"), &agent("alice")));
+        assert_eq!(
+            model.delivery(Some(&agent("bob")), &agent("alice")),
+            Delivery::Drop
+        );
+        // the network itself can still reach everyone
+        assert_eq!(
+            model.delivery(None, &agent("alice")),
+            Delivery::After(Duration::from_millis(0))
+        );
+
+        model.heal_partitions();
+        assert!(model.is_reachable(&agent("alice"), &agent("bob")));
+    }
+
+    #[test]
+    fn clocked_out_agents_are_unreachable() {
+        let mut model = FaultModel::default();
+        model.clock_out_agent(agent("alice"));
+        assert_eq!(model.delivery(None, &agent("alice")), Delivery::Drop);
+        assert_eq!(
+            model.delivery(Some(&agent("alice")), &agent("alice")),
+            Delivery::Drop
+        );
+        assert!(!model.is_reachable(&agent("bob"), &agent("alice")));
+        assert!(!model.is_reachable(&agent("alice"), &agent("bob")));
+
+        model.clock_in_agent(&agent("alice"));
+        assert!(model.is_reachable(&agent("bob"), &agent("alice")));
+    }
+}
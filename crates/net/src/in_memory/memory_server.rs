@@ -1,10 +1,11 @@
 //! provides in-memory p2p "server" for use in scenario testing
 //! the server connects all the memory_workers together, so there is no real gossiping going around.
 //! Could have pluggable DHT strategy. Full-sync currently hard-coded: #fullsync
+//! Messages are routed through a FaultModel, which can delay or drop them.
 
 #![allow(non_snake_case)]
 
-use super::memory_book::*;
+use super::{fault_model::*, memory_book::*};
 use crate::{connection::NetResult, error::NetworkError, tweetlog::*};
 
 use lib3h_protocol::{
@@ -20,10 +21,20 @@ use lib3h_protocol::{
 use holochain_locksmith::{Mutex, RwLock};
 use holochain_persistence_api::cas::content::Address;
 use lib3h_protocol::types::SpaceHash;
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    time::Instant,
+};
 
 type RequestId = String;
 
+/// a message held back by the fault model until it is due
+struct DelayedMessage {
+    deliver_at: Instant,
+    chain_id: ChainId,
+    data: Lib3hServerProtocol,
+}
+
 /// Type for holding a map of 'network_name -> InMemoryServer'
 type InMemoryServerMap = HashMap<String, Mutex<InMemoryServer>>;
 
@@ -58,6 +69,13 @@ pub(crate) struct InMemoryServer {
     // used for making unique request ids
     request_count: usize,
 
+    // decides which messages get delayed or lost
+    fault_model: FaultModel,
+    // the agent that sent the message currently being served
+    current_sender: Option<AgentPubKey>,
+    // messages waiting for their simulated latency, in order of sending
+    delayed_messages: Vec<DelayedMessage>,
+
     // Logger
     log: TweetProxy,
 }
@@ -138,10 +156,37 @@ impl InMemoryServer {
             stored_book: HashMap::new(),
             request_count: 0,
             trackdna_book: HashSet::new(),
+            fault_model: FaultModel::default(),
+            current_sender: None,
+            delayed_messages: Vec::new(),
             log: TweetProxy::new("memory_server"),
         }
     }
 
+    /// access the fault model to change it at runtime
+    pub fn fault_model_mut(&mut self) -> &mut FaultModel {
+        &mut self.fault_model
+    }
+
+    /// deliver all delayed messages whose simulated latency has passed
+    pub fn flush_delayed_messages(&mut self) -> NetResult<()> {
+        if self.delayed_messages.is_empty() {
+            return Ok(());
+        }
+        let now = self.fault_model.now();
+        let (mut due, pending): (Vec<_>, Vec<_>) = self
+            .delayed_messages
+            .drain(..)
+            .partition(|message| message.deliver_at <= now);
+        self.delayed_messages = pending;
+        // stable sort keeps the sending order for messages due at the same time
+        due.sort_by_key(|message| message.deliver_at);
+        for message in due {
+            self.priv_deliver_now(&message.chain_id, message.data)?;
+        }
+        Ok(())
+    }
+
     /// A client clocks in on this server
     pub fn clock_in(&mut self) {
         self.log
@@ -207,6 +252,24 @@ impl InMemoryServer {
     pub fn serve(&mut self, data: Lib3hClientProtocol) -> NetResult<()> {
         self.log
             .d(&format!(">>>> '{}' recv: {:?}", self.name.clone(), data));
+        self.current_sender = client_message_sender(&data);
+        if let Some(sender) = &self.current_sender {
+            if self.fault_model.is_clocked_out(sender) {
+                self.log.d(&format!(
+                    "---- '{}' dropping message of clocked out agent {}",
+                    self.name.clone(),
+                    sender
+                ));
+                self.current_sender = None;
+                return Ok(());
+            }
+        }
+        let result = self.priv_serve(data);
+        self.current_sender = None;
+        result
+    }
+
+    fn priv_serve(&mut self, data: Lib3hClientProtocol) -> NetResult<()> {
         // serve only Lib3hClientProtocol
         match data {
             Lib3hClientProtocol::SuccessResult(msg) => {
@@ -404,8 +467,7 @@ impl InMemoryServer {
         chain_id: &str,
         data: Lib3hServerProtocol,
     ) -> NetResult<()> {
-        let maybe_sender = self.senders.get_mut(chain_id);
-        if maybe_sender.is_none() {
+        if !self.senders.contains_key(chain_id) {
             self.log.e(&format!(
                 "#### ({}) error: No sender channel found for {}",
                 self.name.clone(),
@@ -417,11 +479,57 @@ impl InMemoryServer {
                 chain_id,
             ));
         }
-        let sender = maybe_sender.unwrap();
-        self.log
-            .d(&format!("<<<< '{}' send: {:?}", self.name.clone(), data));
-        sender.send(data)?;
-        Ok(())
+        self.priv_deliver(chain_id, data)
+    }
+
+    /// route a message through the fault model to the channel of chain_id
+    fn priv_deliver(&mut self, chain_id: &str, data: Lib3hServerProtocol) -> NetResult<()> {
+        let to_agent_id = undo_chain_id(&chain_id.to_string()).1;
+        match self
+            .fault_model
+            .delivery(self.current_sender.as_ref(), &to_agent_id)
+        {
+            Delivery::Drop => {
+                self.log.d(&format!(
+                    "<<<< '{}' fault model dropped: {:?}",
+                    self.name.clone(),
+                    data
+                ));
+                Ok(())
+            }
+            Delivery::After(delay) if delay.as_millis() == 0 => {
+                self.priv_deliver_now(chain_id, data)
+            }
+            Delivery::After(delay) => {
+                self.delayed_messages.push(DelayedMessage {
+                    deliver_at: self.fault_model.now() + delay,
+                    chain_id: chain_id.to_string(),
+                    data,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn priv_deliver_now(&mut self, chain_id: &str, data: Lib3hServerProtocol) -> NetResult<()> {
+        match self.senders.get_mut(chain_id) {
+            None => {
+                // the agent might have left while the message was delayed
+                self.log.w(&format!(
+                    "#### ({}) No sender channel found for {}, dropping {:?}",
+                    self.name.clone(),
+                    chain_id,
+                    data
+                ));
+                Ok(())
+            }
+            Some(sender) => {
+                self.log
+                    .d(&format!("<<<< '{}' send: {:?}", self.name.clone(), data));
+                sender.send(data)?;
+                Ok(())
+            }
+        }
     }
     /// send a message to the appropriate channel based on chain_id (dna_address::to_agent_id)
     /// If chain_id is unknown, send back FailureResult to `maybe_sender_info`
@@ -437,16 +545,18 @@ impl InMemoryServer {
 
     /// send a message to all nodes connected with this dna address
     fn priv_send_all(&mut self, dna_address: &Address, data: Lib3hServerProtocol) -> NetResult<()> {
-        if let Some(arr) = self.senders_by_dna.get_mut(dna_address) {
-            self.log.d(&format!(
-                "<<<< '{}' send all: {:?} ({})",
-                self.name.clone(),
-                data.clone(),
-                dna_address.clone()
-            ));
-            for (_k, val) in arr.iter_mut() {
-                (*val).send(data.clone())?;
-            }
+        let agent_ids = match self.senders_by_dna.get(dna_address) {
+            Some(arr) => arr.keys().cloned().collect::<Vec<_>>(),
+            None => return Ok(()),
+        };
+        self.log.d(&format!(
+            "<<<< '{}' send all: {:?} ({})",
+            self.name.clone(),
+            data.clone(),
+            dna_address.clone()
+        ));
+        for agent_id in agent_ids {
+            self.priv_deliver(&into_chain_id(dna_address, &agent_id), data.clone())?;
         }
         Ok(())
     }
@@ -780,3 +890,41 @@ impl InMemoryServer {
         }
     }
 }
+
+/// the agent that sent a message to the network, if the message says so
+fn client_message_sender(data: &Lib3hClientProtocol) -> Option<AgentPubKey> {
+    match data {
+        Lib3hClientProtocol::JoinSpace(msg) | Lib3hClientProtocol::LeaveSpace(msg) => {
+            Some(msg.agent_id.clone())
+        }
+        Lib3hClientProtocol::SendDirectMessage(msg)
+        | Lib3hClientProtocol::HandleSendDirectMessageResult(msg) => {
+            Some(msg.from_agent_id.clone())
+        }
+        Lib3hClientProtocol::PublishEntry(msg) => Some(msg.provider_agent_id.clone()),
+        Lib3hClientProtocol::HandleFetchEntryResult(msg) => Some(msg.provider_agent_id.clone()),
+        Lib3hClientProtocol::QueryEntry(msg) => Some(msg.requester_agent_id.clone()),
+        Lib3hClientProtocol::HandleQueryEntryResult(msg) => Some(msg.responder_agent_id.clone()),
+        Lib3hClientProtocol::HandleGetAuthoringEntryListResult(msg)
+        | Lib3hClientProtocol::HandleGetGossipingEntryListResult(msg) => {
+            Some(msg.provider_agent_id.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Change the fault model of the running in-memory network with the given name,
+/// e.g. to partition it, heal it again or clock out an agent.
+pub fn update_fault_model<F>(server_name: &str, update: F) -> NetResult<()>
+where
+    F: FnOnce(&mut FaultModel),
+{
+    let server_map = MEMORY_SERVER_MAP.read().unwrap();
+    let mut server = server_map
+        .get(server_name)
+        .ok_or_else(|| format_err!("No in-memory network named {}", server_name))?
+        .lock()
+        .unwrap();
+    update(server.fault_model_mut());
+    Ok(())
+}
@@ -1,6 +1,6 @@
 //! provides fake in-memory p2p worker for use in scenario testing
 
-use super::memory_server::*;
+use super::{fault_model::*, memory_server::*};
 use crate::connection::{
     net_connection::{NetHandler, NetWorker},
     NetResult,
@@ -78,6 +78,16 @@ impl NetWorker for InMemoryWorker {
             self.can_send_P2pReady = false;
             self.handler.handle(Ok(Lib3hServerProtocol::P2pReady))?;
         }
+        // release messages the fault model has held back
+        {
+            let server_map = MEMORY_SERVER_MAP.read().unwrap();
+            let mut server = server_map
+                .get(&self.server_name)
+                .expect("InMemoryServer should have been initialized by now")
+                .lock()
+                .unwrap();
+            server.flush_delayed_messages()?;
+        }
         // check for messages from our InMemoryServer
         let mut did_something = false;
         for (_, receiver) in self.receiver_per_dna.iter_mut() {
@@ -118,10 +128,13 @@ impl InMemoryWorker {
         // Create server with that name if it doesn't already exist
         let mut server_map = MEMORY_SERVER_MAP.write().unwrap();
         if !server_map.contains_key(&server_name) {
-            server_map.insert(
-                server_name.clone(),
-                Mutex::new(InMemoryServer::new(server_name.clone())),
-            );
+            let mut server = InMemoryServer::new(server_name.clone());
+            if !config["faultModel"].is_null() {
+                let fault_config: FaultModelConfig =
+                    serde_json::from_value(config["faultModel"].clone())?;
+                *server.fault_model_mut() = FaultModel::new(fault_config);
+            }
+            server_map.insert(server_name.clone(), Mutex::new(server));
         }
         let mut server = server_map
             .get(&server_name)
@@ -158,13 +171,17 @@ mod tests {
     use crate::p2p_config::P2pConfig;
     use crossbeam_channel::unbounded;
     use holochain_persistence_api::cas::content::Address;
-    use lib3h_protocol::{data_types::SpaceData, types::AgentPubKey};
+    use lib3h_protocol::{
+        data_types::{DirectMessageData, SpaceData},
+        types::AgentPubKey,
+    };
 
     fn example_dna_address() -> Address {
         "QmYsFu7QGaVeUUac1E4BWST7BR38cYvzRaaTc3YS9WqsTu".into()
     }
 
     static AGENT_ID_1: &'static str = "QmY6MfiuhHnQ1kg7RwNZJNUQhwDxTFL45AAPnpJMNPEoxk";
+    static AGENT_ID_2: &'static str = "QmQJxUSfJe2QoxTyEwKQX9ypbkcNv3cw1vasGTx1CUpJFm";
     // TODO - AgentIds need to be HcSyada base32 format
     //        currently HashString try_into Vec<u8> is doing only base58
     //static AGENT_ID_1: &'static str = "HcScIkRaAaaaaaaaaaAaaaAAAAaaaaaaaaAaaaaAaaaaaaaaAaaAAAAatzu4aqa";
@@ -218,4 +235,132 @@ mod tests {
 
         memory_worker_1.tick().unwrap();
     }
+
+    fn joined_worker(
+        server_name: &str,
+        agent_id: &str,
+    ) -> (
+        Box<InMemoryWorker>,
+        crossbeam_channel::Receiver<Lib3hServerProtocol>,
+    ) {
+        let memory_config = &JsonString::from(P2pConfig::memory_backend_json(server_name));
+        let (handler_send, handler_recv) = unbounded::<Lib3hServerProtocol>();
+        let mut memory_worker = Box::new(
+            InMemoryWorker::new(
+                NetHandler::new(Box::new(move |r| {
+                    handler_send.send(r?)?;
+                    Ok(())
+                })),
+                memory_config,
+            )
+            .unwrap(),
+        );
+        memory_worker
+            .receive(Lib3hClientProtocol::JoinSpace(SpaceData {
+                request_id: format!("join_{}", agent_id),
+                space_address: example_dna_address().into(),
+                agent_id: AgentPubKey::from(agent_id),
+            }))
+            .unwrap();
+        while memory_worker.tick().unwrap() {}
+        while handler_recv.try_recv().is_ok() {}
+        (memory_worker, handler_recv)
+    }
+
+    fn direct_message(request_id: &str) -> Lib3hClientProtocol {
+        Lib3hClientProtocol::SendDirectMessage(DirectMessageData {
+            request_id: request_id.to_string(),
+            space_address: example_dna_address().into(),
+            to_agent_id: AgentPubKey::from(AGENT_ID_2),
+            from_agent_id: AgentPubKey::from(AGENT_ID_1),
+            content: b"hello".to_vec().into(),
+        })
+    }
+
+    fn received_direct_messages(
+        handler_recv: &crossbeam_channel::Receiver<Lib3hServerProtocol>,
+    ) -> Vec<String> {
+        handler_recv
+            .try_iter()
+            .filter_map(|message| match message {
+                Lib3hServerProtocol::HandleSendDirectMessage(dm) => Some(dm.request_id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg_attr(tarpaulin, skip)]
+    fn can_memory_worker_partition_and_heal() {
+        let server_name = format!(
+            "memory-faults-{}",
+            snowflake::ProcessUniqueId::new().to_string()
+        );
+        let (mut memory_worker_1, _handler_recv_1) = joined_worker(&server_name, AGENT_ID_1);
+        let (mut memory_worker_2, handler_recv_2) = joined_worker(&server_name, AGENT_ID_2);
+
+        update_fault_model(&server_name, |fault_model| {
+            fault_model.partition(vec![
+                vec![AgentPubKey::from(AGENT_ID_1)],
+                vec![AgentPubKey::from(AGENT_ID_2)],
+            ])
+        })
+        .unwrap();
+
+        // the message gets lost in the partition
+        memory_worker_1.receive(direct_message("dm_1")).unwrap();
+        memory_worker_2.tick().unwrap();
+        assert!(received_direct_messages(&handler_recv_2).is_empty());
+
+        update_fault_model(&server_name, |fault_model| fault_model.heal_partitions()).unwrap();
+
+        memory_worker_1.receive(direct_message("dm_2")).unwrap();
+        memory_worker_2.tick().unwrap();
+        assert_eq!(
+            received_direct_messages(&handler_recv_2),
+            vec!["dm_2".to_string()]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(tarpaulin, skip)]
+    fn can_memory_worker_delay_messages() {
+        let server_name = format!(
+            "memory-faults-{}",
+            snowflake::ProcessUniqueId::new().to_string()
+        );
+        let (mut memory_worker_1, _handler_recv_1) = joined_worker(&server_name, AGENT_ID_1);
+        let (mut memory_worker_2, handler_recv_2) = joined_worker(&server_name, AGENT_ID_2);
+
+        update_fault_model(&server_name, |fault_model| {
+            fault_model.use_manual_clock();
+            fault_model.set_default_link(LinkFaults {
+                latency_ms: 200,
+                jitter_ms: 0,
+                drop_rate: 0.0,
+            })
+        })
+        .unwrap();
+
+        memory_worker_1.receive(direct_message("dm_1")).unwrap();
+        memory_worker_2.tick().unwrap();
+        assert!(received_direct_messages(&handler_recv_2).is_empty());
+
+        update_fault_model(&server_name, |fault_model| {
+            fault_model.advance_clock(std::time::Duration::from_millis(199))
+        })
+        .unwrap();
+        memory_worker_2.tick().unwrap();
+        assert!(received_direct_messages(&handler_recv_2).is_empty());
+
+        update_fault_model(&server_name, |fault_model| {
+            fault_model.advance_clock(std::time::Duration::from_millis(1))
+        })
+        .unwrap();
+        memory_worker_2.tick().unwrap();
+        assert_eq!(
+            received_direct_messages(&handler_recv_2),
+            vec!["dm_1".to_string()]
+        );
+    }
 }
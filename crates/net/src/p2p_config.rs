@@ -1,4 +1,6 @@
-use crate::{sim1h_worker::Sim1hConfig, sim2h_worker::Sim2hConfig};
+use crate::{
+    in_memory::fault_model::FaultModelConfig, sim1h_worker::Sim1hConfig, sim2h_worker::Sim2hConfig,
+};
 use holochain_json_api::{error::JsonError, json::JsonString};
 use lib3h::engine::{EngineConfig, GatewayId, TransportConfig};
 use lib3h_protocol::uri::Lib3hUri;
@@ -171,6 +173,22 @@ impl P2pConfig {
         )
     }
 
+    /// an in-memory network that delays, drops and partitions messages as configured,
+    /// see `in_memory::memory_server::update_fault_model` for changing faults at runtime
+    pub fn new_with_faulty_memory_backend(
+        server_name: &str,
+        fault_model: FaultModelConfig,
+    ) -> Self {
+        P2pConfig::new(
+            P2pBackendKind::LegacyInMemory,
+            BackendConfig::Json(json!({
+                "serverName": server_name,
+                "faultModel": fault_model,
+            })),
+            None,
+        )
+    }
+
     pub fn new_with_sim1h_backend(dynamo_path: &str) -> Self {
         P2pConfig::new(
             P2pBackendKind::SIM1H,
@@ -15,7 +15,8 @@ use crate::{
     tweetlog::*,
 };
 use lib3h_protocol::{
-    protocol_client::Lib3hClientProtocol, protocol_server::Lib3hServerProtocol, Address,
+    data_types::DirectMessageData, protocol_client::Lib3hClientProtocol,
+    protocol_server::Lib3hServerProtocol, Address,
 };
 
 use crate::{sim1h_worker::Sim1hWorker, sim2h_worker::Sim2hWorker};
@@ -222,6 +223,10 @@ impl NetSend for P2pNetwork {
     fn send(&mut self, data: Lib3hClientProtocol) -> NetResult<()> {
         self.connection.send(data)
     }
+
+    fn send_store_and_forward_direct_message(&mut self, data: DirectMessageData) -> NetResult<()> {
+        self.connection.send_store_and_forward_direct_message(data)
+    }
 }
 
 #[cfg(test)]
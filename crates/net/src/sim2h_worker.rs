@@ -6,12 +6,14 @@ use crate::connection::{
 };
 use failure::_core::time::Duration;
 use holochain_conductor_lib_api::{ConductorApi, CryptoMethod};
+use holochain_core_types::network::DIRECT_MESSAGE_QUEUED;
 use holochain_json_api::{error::JsonError, json::JsonString};
 use holochain_metrics::{DefaultMetricPublisher, MetricPublisher};
 use in_stream::*;
 use lib3h_protocol::{
     data_types::{
-        EntryListData, FetchEntryData, GenericResultData, Opaque, SpaceData, StoreEntryAspectData,
+        DirectMessageData, EntryListData, FetchEntryData, GenericResultData, Opaque, SpaceData,
+        StoreEntryAspectData,
     },
     protocol::*,
     protocol_client::Lib3hClientProtocol,
@@ -22,8 +24,10 @@ use lib3h_protocol::{
 };
 use log::*;
 use sim2h::{
+    compression::{compress_frame, decompress_frame, WireCompression},
     crypto::{Provenance, SignedWireMessage},
-    WireError, WireMessage,
+    wss::Sim2hWss,
+    WireError, WireMessage, MAX_BATCH_SIZE,
 };
 use std::{convert::TryFrom, time::Instant};
 use url::Url;
@@ -32,9 +36,10 @@ use url2::prelude::*;
 const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
 const SIM2H_WORKER_INTERNAL_REQUEST_ID: &str = "SIM2H_WORKER";
 
-fn connect(url: Lib3hUri) -> NetResult<InStreamWss<InStreamTls<InStreamTcp>>> {
-    let config = WssConnectConfig::new(TlsConnectConfig::new(TcpConnectConfig::default()));
-    Ok(InStreamWss::connect(&url::Url::from(url).into(), config)?)
+/// connects over tcp + tls, or over the in-process memory transport
+/// if sim2h was started with `Sim2h::new_in_memory` and gave us a mem:// url
+fn connect(url: Lib3hUri) -> NetResult<Sim2hWss> {
+    Ok(Sim2hWss::connect(&url::Url::from(url).into())?)
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, DefaultJson, PartialEq)]
@@ -46,7 +51,7 @@ pub struct Sim2hConfig {
 #[allow(non_snake_case, dead_code)]
 pub struct Sim2hWorker {
     handler: NetHandler,
-    connection: Option<InStreamWss<InStreamTls<InStreamTcp>>>,
+    connection: Option<Sim2hWss>,
     inbox: Vec<Lib3hClientProtocol>,
     to_core: Vec<Lib3hServerProtocol>,
     server_url: Lib3hUri,
@@ -60,6 +65,11 @@ pub struct Sim2hWorker {
     initial_authoring_list: Option<EntryListData>,
     initial_gossiping_list: Option<EntryListData>,
     has_self_stored_authored_aspects: bool,
+    /// set from the `Hello` sim2h sends after joining, in a sharded DHT
+    /// queries have to go through sim2h because we might not hold the entry
+    is_full_sync_dht: bool,
+    /// frame compression agreed on with sim2h for the current connection
+    compression: WireCompression,
 }
 
 impl Sim2hWorker {
@@ -94,6 +104,8 @@ impl Sim2hWorker {
             initial_authoring_list: None,
             initial_gossiping_list: None,
             has_self_stored_authored_aspects: false,
+            is_full_sync_dht: true,
+            compression: WireCompression::None,
         };
 
         instance.check_reconnect();
@@ -114,8 +126,15 @@ impl Sim2hWorker {
 
         self.time_of_last_connection_attempt = Instant::now();
         self.connection = None;
+        self.compression = WireCompression::None;
         if let Ok(connection) = connect(self.server_url.clone()) {
             self.connection = Some(connection);
+            // every new connection starts uncompressed,
+            // make negotiating compression the first thing we send
+            self.outgoing_message_buffer.insert(
+                0,
+                WireMessage::NegotiateCompression(WireCompression::supported()),
+            );
         }
     }
 
@@ -143,7 +162,7 @@ impl Sim2hWorker {
                 return did_something;
             }
             did_something = true;
-            let message = self.outgoing_message_buffer.get(0).unwrap();
+            let (message, message_count) = self.next_outgoing_frame();
             let payload: String = message.clone().into();
             let signature = self
                 .conductor_api
@@ -159,13 +178,9 @@ impl Sim2hWorker {
                 Provenance::new(self.agent_id.clone(), signature.into()),
             );
             let to_send: Opaque = signed_wire_message.into();
+            let frame = compress_frame(self.compression, &to_send);
             // safe to unwrap because we check connection_ready() above
-            if let Err(e) = self
-                .connection
-                .as_mut()
-                .unwrap()
-                .write(to_send.to_vec().into())
-            {
+            if let Err(e) = self.connection.as_mut().unwrap().write(frame.into()) {
                 error!(
                     "TransportError trying to send message to sim2h server: {:?}",
                     e
@@ -174,9 +189,28 @@ impl Sim2hWorker {
                 self.check_reconnect();
                 return did_something;
             }
-            // if we made it here, we successfully sent the first message
-            // we can remove it from the outgoing buffer queue
-            self.outgoing_message_buffer.remove(0);
+            // if we made it here, we successfully sent the first message(s)
+            // we can remove them from the outgoing buffer queue
+            self.outgoing_message_buffer.drain(..message_count);
+        }
+    }
+
+    /// the next message to send from the outgoing buffer and how many buffered messages it covers,
+    /// consecutive store/fetch messages are sent together as one batch
+    fn next_outgoing_frame(&self) -> (WireMessage, usize) {
+        let batchable_count = self
+            .outgoing_message_buffer
+            .iter()
+            .take(MAX_BATCH_SIZE)
+            .take_while(|message| message.is_batchable())
+            .count();
+        if batchable_count > 1 {
+            (
+                WireMessage::Batch(self.outgoing_message_buffer[..batchable_count].to_vec()),
+                batchable_count,
+            )
+        } else {
+            (self.outgoing_message_buffer[0].clone(), 1)
         }
     }
 
@@ -301,22 +335,35 @@ impl Sim2hWorker {
             }
             // Request some info / data from a Entry
             Lib3hClientProtocol::QueryEntry(query_entry_data) => {
-                // For now, sim2h implements a full-sync mirror DHT
-                // which means queries should always be handled locally.
-                // Thus, we don't even need to ask the central sim2h instance
-                // to handle a query - we just send it back to core directly.
-                self.to_core
-                    .push(Lib3hServerProtocol::HandleQueryEntry(query_entry_data));
-                Ok(())
+                if self.is_full_sync_dht {
+                    // In a full-sync mirror DHT queries should always be handled locally.
+                    // Thus, we don't even need to ask the central sim2h instance
+                    // to handle a query - we just send it back to core directly.
+                    self.to_core
+                        .push(Lib3hServerProtocol::HandleQueryEntry(query_entry_data));
+                    Ok(())
+                } else {
+                    // With sharding we might not hold the entry,
+                    // sim2h forwards the query to an agent that does.
+                    self.send_wire_message(WireMessage::ClientToLib3h(ClientToLib3h::QueryEntry(
+                        query_entry_data,
+                    )))
+                }
             }
             // Response to a `HandleQueryEntry` request
             Lib3hClientProtocol::HandleQueryEntryResult(query_entry_result_data) => {
-                // See above QueryEntry implementation.
-                // All queries are handled locally - we just reflect them back to core:
-                self.to_core.push(Lib3hServerProtocol::QueryEntryResult(
-                    query_entry_result_data,
-                ));
-                Ok(())
+                if self.is_full_sync_dht {
+                    // See above QueryEntry implementation.
+                    // All queries are handled locally - we just reflect them back to core:
+                    self.to_core.push(Lib3hServerProtocol::QueryEntryResult(
+                        query_entry_result_data,
+                    ));
+                    Ok(())
+                } else {
+                    self.send_wire_message(WireMessage::Lib3hToClientResponse(
+                        Lib3hToClientResponse::HandleQueryEntryResult(query_entry_result_data),
+                    ))
+                }
             }
 
             // -- Entry lists -- //
@@ -385,6 +432,33 @@ impl Sim2hWorker {
         match message {
             WireMessage::Ping => self.send_wire_message(WireMessage::Pong)?,
             WireMessage::Pong => {}
+            WireMessage::CompressionAgreed(compression) => {
+                debug!("Using {:?} compression for sim2h connection", compression);
+                self.compression = compression;
+            }
+            WireMessage::Batch(messages) => {
+                for message in messages {
+                    self.handle_server_message(message)?;
+                }
+            }
+            WireMessage::NegotiateCompression(_) => {
+                error!("Got a NegotiateCompression from the Sim2h server, weird! Ignoring.")
+            }
+            WireMessage::Hello(redundant_count) => {
+                debug!("Sim2h DHT redundancy factor is {}", redundant_count);
+                self.is_full_sync_dht = redundant_count == 0;
+            }
+            // the recipient of a store-and-forward direct message is offline,
+            // let core know it won't get an answer right away
+            WireMessage::DirectMessageQueued(dm_data) => {
+                self.to_core
+                    .push(Lib3hServerProtocol::FailureResult(GenericResultData {
+                        request_id: dm_data.request_id,
+                        space_address: dm_data.space_address,
+                        to_agent_id: dm_data.from_agent_id,
+                        result_info: DIRECT_MESSAGE_QUEUED.as_bytes().to_vec().into(),
+                    }))
+            }
             WireMessage::Lib3hToClient(m) => self.to_core.push(Lib3hServerProtocol::from(m)),
             WireMessage::ClientToLib3hResponse(m) => {
                 self.to_core.push(Lib3hServerProtocol::from(m))
@@ -430,6 +504,15 @@ impl NetWorker for Sim2hWorker {
         Ok(())
     }
 
+    /// sim2h holds these in the recipient's mailbox if the recipient is offline
+    fn receive_store_and_forward_direct_message(
+        &mut self,
+        data: DirectMessageData,
+    ) -> NetResult<()> {
+        debug!("CORE >> Sim2h: store-and-forward {:?}", data);
+        self.send_wire_message(WireMessage::StoreAndForwardDirectMessage(data))
+    }
+
     /// Check for messages from our NetworkEngine
     fn tick(&mut self) -> NetResult<bool> {
         let clock = std::time::SystemTime::now();
@@ -455,19 +538,26 @@ impl NetWorker for Sim2hWorker {
                 Ok(_) => {
                     did_something = true;
                     let frame = self.ws_frame.take().unwrap();
-                    if let WsFrame::Binary(payload) = frame {
-                        let payload: Opaque = payload.into();
-                        match WireMessage::try_from(&payload) {
-                            Ok(wire_message) =>
-                                if let Err(error) = self.handle_server_message(wire_message) {
-                                    error!("Error handling server message in Sim2hWorker: {:?}", error);
-                                },
-                            Err(error) =>
-                                error!(
-                                    "Could not deserialize received payload into WireMessage!\nError: {:?}\nPayload was: {:?}",
-                                    error,
-                                    payload
-                                )
+                    if let WsFrame::Binary(bytes) = frame {
+                        match decompress_frame(self.compression, &bytes) {
+                            Ok(payload) => {
+                                let payload: Opaque = payload.into();
+                                match WireMessage::try_from(&payload) {
+                                    Ok(wire_message) =>
+                                        if let Err(error) = self.handle_server_message(wire_message) {
+                                            error!("Error handling server message in Sim2hWorker: {:?}", error);
+                                        },
+                                    Err(error) =>
+                                        error!(
+                                            "Could not deserialize received payload into WireMessage!\nError: {:?}\nPayload was: {:?}",
+                                            error,
+                                            payload
+                                        )
+                                }
+                            }
+                            Err(error) => {
+                                error!("Could not decompress frame from sim2h server: {:?}", error)
+                            }
                         }
                     } else {
                         trace!("unhandled websocket message type: {:?}", frame);
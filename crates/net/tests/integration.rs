@@ -14,11 +14,34 @@ use lib3h_protocol::{
     uri::Lib3hUri,
 };
 use lib3h_sodium::SodiumCryptoSystem;
-use sim2h::Sim2h;
+use sim2h::{DhtAlgorithm, Sim2h};
 use std::sync::Arc;
 
 #[test]
 fn sim2h_worker_talks_to_sim2h() {
+    sim2h_worker_round_trip(|| {
+        let url = url2!("wss://127.0.0.1:0");
+        Sim2h::new(
+            Box::new(SodiumCryptoSystem::new()),
+            Lib3hUri(url.into()),
+            DhtAlgorithm::FullSync,
+        )
+    });
+}
+
+#[test]
+fn sim2h_worker_talks_to_in_memory_sim2h() {
+    sim2h_worker_round_trip(|| {
+        Sim2h::new_in_memory(Box::new(SodiumCryptoSystem::new()), DhtAlgorithm::FullSync)
+    });
+}
+
+/// runs the sim2h created by `new_sim2h` on its own thread and
+/// checks a worker can join, publish and direct message through it
+fn sim2h_worker_round_trip<F>(new_sim2h: F)
+where
+    F: FnOnce() -> Sim2h + Send + 'static,
+{
     let crypto = Box::new(SodiumCryptoSystem::new());
 
     let mut pub_key = crypto.buf_new_insecure(crypto.sign_public_key_bytes());
@@ -33,8 +56,7 @@ fn sim2h_worker_talks_to_sim2h() {
 
     let srv_cont = cont.clone();
     let sim2h_join = std::thread::spawn(move || {
-        let url = url2!("wss://127.0.0.1:0");
-        let mut sim2h = Sim2h::new(Box::new(SodiumCryptoSystem::new()), Lib3hUri(url.into()));
+        let mut sim2h = new_sim2h();
 
         snd.send(sim2h.bound_uri.clone().unwrap()).unwrap();
         drop(snd);
@@ -1,5 +1,5 @@
 //! implements caching structures for spaces and aspects
-use crate::{error::*, AgentId};
+use crate::{error::*, naive_sharding::*, AgentId, DhtAlgorithm};
 use lib3h::rrdht_util::*;
 use lib3h_crypto_api::CryptoSystem;
 use lib3h_protocol::{
@@ -13,6 +13,8 @@ use std::collections::{HashMap, HashSet};
 pub(crate) struct AgentInfo {
     pub uri: Lib3hUri,
     pub location: Location,
+    /// the radius of the storage arc centered on this agent's location
+    pub storage_arc_radius: u32,
 }
 
 pub struct Space {
@@ -20,19 +22,17 @@ pub struct Space {
     agents: HashMap<AgentId, AgentInfo>,
     all_aspects_hashes: AspectList,
     missing_aspects: HashMap<AgentId, HashMap<EntryHash, HashSet<AspectHash>>>,
-    /// sim2h currently uses the same radius for all connections
-    rrdht_arc_radius: u32,
+    dht_algorithm: DhtAlgorithm,
 }
 
 impl Space {
-    pub fn new(crypto: Box<dyn CryptoSystem>) -> Self {
+    pub fn new(crypto: Box<dyn CryptoSystem>, dht_algorithm: DhtAlgorithm) -> Self {
         Space {
             crypto,
             agents: HashMap::new(),
             all_aspects_hashes: AspectList::from(HashMap::new()),
             missing_aspects: HashMap::new(),
-            // default to max radius
-            rrdht_arc_radius: ARC_RADIUS_MAX,
+            dht_algorithm,
         }
     }
 
@@ -109,41 +109,107 @@ impl Space {
         true
     }
 
-    pub(crate) fn recalc_rrdht_arc_radius(&mut self) {
+    /// the storage arc radius a freshly joined agent starts out with,
+    /// before the next rrdht recalculation
+    fn initial_arc_radius(&self) -> u32 {
+        match self.dht_algorithm {
+            DhtAlgorithm::FullSync => ARC_RADIUS_MAX,
+            DhtAlgorithm::NaiveSharding { redundant_count } => {
+                naive_sharding_arc_radius(self.agents.len() as u64 + 1, redundant_count)
+            }
+        }
+    }
+
+    /// Recalculates the storage arcs of all agents. Agents whose arc grew are not holding
+    /// the aspects that just entered it, so those get recorded as missing at the agent
+    /// and returned per agent, for them to be fetched.
+    pub(crate) fn recalc_rrdht_arc_radius(&mut self) -> Vec<(AgentId, AspectList)> {
+        let redundant_count = match self.dht_algorithm {
+            // everybody holds everything, nothing to recalculate
+            DhtAlgorithm::FullSync => return Vec::new(),
+            DhtAlgorithm::NaiveSharding { redundant_count } => redundant_count,
+        };
+
         let mut peer_record_set = RValuePeerRecordSet::default()
             // sim2h is currently omniscient
             .arc_of_included_peer_records(Arc::new(0.into(), ARC_LENGTH_MAX));
         for (_id, info) in self.agents.iter() {
             peer_record_set = peer_record_set.push_peer_record(
                 RValuePeerRecord::default()
-                    // since sim2h uses the same storage arc for all nodes
-                    // we just put that same value in here for all nodes
-                    .storage_arc(Arc::new_radius(info.location, self.rrdht_arc_radius))
+                    .storage_arc(Arc::new_radius(info.location, info.storage_arc_radius))
                     // we do not yet have the metrics infrastructure to track
                     // uptime, let's pretend all nodes are up exactly 1/2 the time
                     .uptime_0_to_1(0.5),
             );
         }
 
-        let mut new_arc_radius = get_recommended_storage_arc_radius(
-            &peer_record_set,
-            25.0, // target_minimum_r_value
-            50.0, // target_maximum_r_value
-            Some(self.rrdht_arc_radius),
-        );
-
-        if new_arc_radius != ARC_RADIUS_MAX {
-            let pct = 100 * new_arc_radius / ARC_RADIUS_MAX;
-            warn!("rrdht-r-value recommends shrinking arc radius to {} %, sim2h is not yet set up to do this, but, yay sharding!", pct);
-            new_arc_radius = ARC_RADIUS_MAX;
+        // with every node up 1/2 the time, an r-value of redundant_count / 2
+        // means on average redundant_count nodes are holding each aspect
+        let target_minimum_r_value = redundant_count as f64 / 2.0;
+        let target_maximum_r_value = redundant_count as f64;
+
+        // never shrink an arc below what naive sharding would need
+        // to reach our redundancy factor
+        let min_arc_radius = naive_sharding_arc_radius(self.agents.len() as u64, redundant_count);
+
+        let mut grown_arcs = Vec::new();
+        for (agent_id, info) in self.agents.iter_mut() {
+            let recommended_arc_radius = get_recommended_storage_arc_radius(
+                &peer_record_set,
+                target_minimum_r_value,
+                target_maximum_r_value,
+                Some(info.storage_arc_radius),
+            );
+            let new_arc_radius = std::cmp::max(recommended_arc_radius, min_arc_radius);
+            if new_arc_radius != info.storage_arc_radius {
+                debug!(
+                    "rrdht arc radius for {} changed to {} %",
+                    agent_id,
+                    100 * u64::from(new_arc_radius) / u64::from(ARC_RADIUS_MAX)
+                );
+                if new_arc_radius > info.storage_arc_radius {
+                    grown_arcs.push((
+                        agent_id.clone(),
+                        info.location,
+                        info.storage_arc_radius,
+                        new_arc_radius,
+                    ));
+                }
+                info.storage_arc_radius = new_arc_radius;
+            }
         }
 
-        self.rrdht_arc_radius = new_arc_radius;
+        let mut entered_aspects = Vec::new();
+        for (agent_id, location, old_arc_radius, new_arc_radius) in grown_arcs {
+            let crypto = &self.crypto;
+            let aspects = self.all_aspects_hashes.filter_by_entry_hash(|entry_hash| {
+                let entry_loc = entry_location(crypto, entry_hash);
+                arc_radius_contains(location, new_arc_radius, entry_loc)
+                    && !arc_radius_contains(location, old_arc_radius, entry_loc)
+            });
+            let aspect_hashes: HashSet<(EntryHash, AspectHash)> = (&aspects).into();
+            if aspect_hashes.is_empty() {
+                continue;
+            }
+            for (entry_hash, aspect_hash) in aspect_hashes {
+                self.add_missing_aspect(agent_id.clone(), entry_hash, aspect_hash);
+            }
+            entered_aspects.push((agent_id, aspects));
+        }
+        entered_aspects
     }
 
     pub fn join_agent(&mut self, agent_id: AgentId, uri: Lib3hUri) -> Sim2hResult<()> {
         let location = calc_location_for_id(&self.crypto, &agent_id.to_string())?;
-        self.agents.insert(agent_id, AgentInfo { uri, location });
+        let storage_arc_radius = self.initial_arc_radius();
+        self.agents.insert(
+            agent_id,
+            AgentInfo {
+                uri,
+                location,
+                storage_arc_radius,
+            },
+        );
         Ok(())
     }
 
@@ -153,6 +219,10 @@ impl Space {
         self.agents.len()
     }
 
+    pub fn agent_count(&self) -> usize {
+        self.agents.len()
+    }
+
     pub fn agent_id_to_uri(&self, agent_id: &AgentId) -> Option<Lib3hUri> {
         for (found_agent, info) in self.agents.iter() {
             if found_agent == agent_id {
@@ -166,6 +236,41 @@ impl Space {
         &self.agents
     }
 
+    /// Returns all agents whose storage arc covers the location of the given entry.
+    pub(crate) fn agents_supposed_to_hold_entry(
+        &self,
+        entry_hash: &EntryHash,
+    ) -> HashMap<AgentId, AgentInfo> {
+        if let DhtAlgorithm::FullSync = self.dht_algorithm {
+            return self.agents.clone();
+        }
+        let entry_loc = entry_location(&self.crypto, entry_hash);
+        self.agents
+            .iter()
+            .filter(|(_, info)| {
+                arc_radius_contains(info.location, info.storage_arc_radius, entry_loc)
+            })
+            .map(|(agent_id, info)| (agent_id.clone(), info.clone()))
+            .collect()
+    }
+
+    /// Returns true if the storage arc of the given agent covers the location of the given entry.
+    pub fn agent_is_supposed_to_hold_entry(
+        &self,
+        agent_id: &AgentId,
+        entry_hash: &EntryHash,
+    ) -> bool {
+        match self.agents.get(agent_id) {
+            None => false,
+            Some(_) if self.dht_algorithm == DhtAlgorithm::FullSync => true,
+            Some(info) => arc_radius_contains(
+                info.location,
+                info.storage_arc_radius,
+                entry_location(&self.crypto, entry_hash),
+            ),
+        }
+    }
+
     pub fn all_aspects(&self) -> &AspectList {
         &self.all_aspects_hashes
     }
@@ -199,6 +304,21 @@ impl AspectList {
             .push(aspect_address);
     }
 
+    /// Returns an AspectList that only contains the entries of self
+    /// for which the given predicate returns true.
+    pub fn filter_by_entry_hash<F>(&self, predicate: F) -> AspectList
+    where
+        F: Fn(&EntryHash) -> bool,
+    {
+        AspectList::from(
+            self.0
+                .iter()
+                .filter(|(entry_address, _)| predicate(entry_address))
+                .map(|(entry_address, aspects)| (entry_address.clone(), aspects.clone()))
+                .collect::<HashMap<EntryHash, Vec<AspectHash>>>(),
+        )
+    }
+
     pub fn entry_addresses(&self) -> impl Iterator<Item = &EntryHash> {
         self.0.keys()
     }
@@ -279,7 +399,7 @@ mod tests {
 
     #[test]
     fn space_can_add_and_remove_agents() {
-        let mut space = Space::new(Box::new(SodiumCryptoSystem::new()));
+        let mut space = Space::new(Box::new(SodiumCryptoSystem::new()), DhtAlgorithm::FullSync);
         let agent =
             AgentId::from("HcSCJCqoIY3uwiw34acyvNmJMyzkk4y9groHdYKBekqp7y48mvwfVTQQkzcjnfz");
         assert_eq!(space.agents.len(), 0);
@@ -303,7 +423,7 @@ mod tests {
 
     #[test]
     fn space_can_add_and_remove_missing_aspects() {
-        let mut space = Space::new(Box::new(SodiumCryptoSystem::new()));
+        let mut space = Space::new(Box::new(SodiumCryptoSystem::new()), DhtAlgorithm::FullSync);
         let agent = AgentId::from("test-agent");
 
         assert!(space.agents_with_missing_aspects().is_empty());
@@ -345,7 +465,7 @@ mod tests {
 
     #[test]
     fn space_can_tell_if_agent_is_missing_all_aspects() {
-        let mut space = Space::new(Box::new(SodiumCryptoSystem::new()));
+        let mut space = Space::new(Box::new(SodiumCryptoSystem::new()), DhtAlgorithm::FullSync);
         let agent = AgentId::from("test-agent");
         let entry_hash_1 = EntryHash::from("entry_hash_1");
         let entry_hash_2 = EntryHash::from("entry_hash_2");
@@ -385,4 +505,101 @@ mod tests {
             &vec![aspect_hash_1_1.clone(), aspect_hash_1_2.clone()]
         ));
     }
+
+    #[test]
+    fn space_only_asks_agents_within_their_arc_to_hold_entries() {
+        let crypto: Box<dyn CryptoSystem> = Box::new(SodiumCryptoSystem::new());
+        let enc = hcid::HcidEncoding::with_kind("hcs0").unwrap();
+        let mut full_sync_space = Space::new(crypto.box_clone(), DhtAlgorithm::FullSync);
+        let mut sharded_space = Space::new(
+            crypto.box_clone(),
+            DhtAlgorithm::NaiveSharding { redundant_count: 5 },
+        );
+
+        for i in 0..50 {
+            let mut key = crypto.buf_new_insecure(32);
+            crypto.randombytes_buf(&mut key).unwrap();
+            let agent = AgentId::from(enc.encode(&key).unwrap());
+            let uri = Lib3hUri::try_from(format!("ws://agent{}.com:9000", i).as_str()).unwrap();
+            full_sync_space
+                .join_agent(agent.clone(), uri.clone())
+                .expect("should work");
+            sharded_space.join_agent(agent, uri).expect("should work");
+        }
+        let entry_hash = EntryHash::from("entry_hash_1");
+
+        // agents joining a big space start out with a shrunk arc
+        let holders = sharded_space.agents_supposed_to_hold_entry(&entry_hash);
+        assert!(!holders.is_empty());
+        assert!(holders.len() < 50);
+
+        full_sync_space.recalc_rrdht_arc_radius();
+        sharded_space.recalc_rrdht_arc_radius();
+
+        assert_eq!(
+            full_sync_space
+                .agents_supposed_to_hold_entry(&entry_hash)
+                .len(),
+            50
+        );
+        let holders = sharded_space.agents_supposed_to_hold_entry(&entry_hash);
+        assert!(!holders.is_empty());
+        for agent in sharded_space.all_agents().keys() {
+            assert_eq!(
+                holders.contains_key(agent),
+                sharded_space.agent_is_supposed_to_hold_entry(agent, &entry_hash)
+            );
+        }
+    }
+
+    #[test]
+    fn space_marks_aspects_entering_a_grown_arc_as_missing() {
+        let crypto: Box<dyn CryptoSystem> = Box::new(SodiumCryptoSystem::new());
+        let enc = hcid::HcidEncoding::with_kind("hcs0").unwrap();
+        let mut space = Space::new(
+            crypto.box_clone(),
+            DhtAlgorithm::NaiveSharding { redundant_count: 1 },
+        );
+        let mut agents = Vec::new();
+        for i in 0..50 {
+            let mut key = crypto.buf_new_insecure(32);
+            crypto.randombytes_buf(&mut key).unwrap();
+            let agent = AgentId::from(enc.encode(&key).unwrap());
+            let uri = Lib3hUri::try_from(format!("ws://agent{}.com:9000", i).as_str()).unwrap();
+            space.join_agent(agent.clone(), uri).expect("should work");
+            agents.push(agent);
+        }
+        for i in 0..500 {
+            space.add_aspect(
+                EntryHash::from(format!("entry_hash_{}", i)),
+                AspectHash::from(format!("aspect_hash_{}", i)),
+            );
+        }
+
+        // shrink one agent's arc to nothing, the recalculation grows it back
+        let agent = agents[0].clone();
+        space.agents.get_mut(&agent).unwrap().storage_arc_radius = 0;
+        let entered = space.recalc_rrdht_arc_radius();
+
+        let (_, aspects) = entered
+            .into_iter()
+            .find(|(agent_id, _)| *agent_id == agent)
+            .expect("the grown arc should cover some entries");
+        let entered_entries: HashSet<EntryHash> = aspects.entry_addresses().cloned().collect();
+        let held_entries: HashSet<EntryHash> = space
+            .all_aspects()
+            .entry_addresses()
+            .filter(|entry_hash| space.agent_is_supposed_to_hold_entry(&agent, entry_hash))
+            .cloned()
+            .collect();
+        assert!(!entered_entries.is_empty());
+        assert_eq!(entered_entries, held_entries);
+        for entry_hash in entered_entries.iter() {
+            assert!(space.agent_is_missing_all_aspects(
+                &agent,
+                entry_hash,
+                aspects.per_entry(entry_hash).unwrap()
+            ));
+        }
+    }
 }
@@ -0,0 +1,133 @@
+//! negotiated compression of websocket frames between sim2h and its clients
+use crate::error::*;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder};
+use std::io::{Read, Write};
+
+/// frames smaller than this are not worth the cpu time of compressing them
+pub const COMPRESSION_THRESHOLD_BYTES: usize = 1024;
+
+/// compressed frames start with this byte, which can never start a json payload
+const COMPRESSED_FRAME_TAG: u8 = 0;
+
+/// compressed frames must not expand to more than this, the largest message our
+/// websockets accept uncompressed
+pub const MAX_FRAME_SIZE: usize = 64 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WireCompression {
+    None,
+    Zlib,
+}
+
+impl Default for WireCompression {
+    fn default() -> Self {
+        WireCompression::None
+    }
+}
+
+impl WireCompression {
+    /// all compression methods we understand, in order of preference
+    pub fn supported() -> Vec<WireCompression> {
+        vec![WireCompression::Zlib, WireCompression::None]
+    }
+
+    /// pick the compression both sides should use, given the methods the peer offered
+    pub fn negotiate(offered: &[WireCompression]) -> WireCompression {
+        WireCompression::supported()
+            .into_iter()
+            .find(|compression| offered.contains(compression))
+            .unwrap_or_default()
+    }
+}
+
+/// prepare an outgoing frame, compressing it if it's large enough
+pub fn compress_frame(compression: WireCompression, payload: &[u8]) -> Vec<u8> {
+    match compression {
+        WireCompression::Zlib if payload.len() >= COMPRESSION_THRESHOLD_BYTES => {
+            let mut encoder =
+                ZlibEncoder::new(vec![COMPRESSED_FRAME_TAG], flate2::Compression::default());
+            encoder
+                .write_all(payload)
+                .expect("writing to a vec cannot fail");
+            encoder.finish().expect("writing to a vec cannot fail")
+        }
+        _ => payload.to_vec(),
+    }
+}
+
+/// read an incoming frame, uncompressed frames are always accepted so peers can
+/// start compressing as soon as they have negotiated, compressed frames only if
+/// compression was negotiated with the peer
+pub fn decompress_frame(compression: WireCompression, frame: &[u8]) -> Sim2hResult<Vec<u8>> {
+    match frame.split_first() {
+        Some((&COMPRESSED_FRAME_TAG, compressed)) => {
+            if compression == WireCompression::None {
+                return Err("received compressed frame without negotiating compression".into());
+            }
+            let mut payload = Vec::new();
+            ZlibDecoder::new(compressed)
+                .take(MAX_FRAME_SIZE as u64 + 1)
+                .read_to_end(&mut payload)?;
+            if payload.len() > MAX_FRAME_SIZE {
+                return Err(format!(
+                    "compressed frame expands to more than {} bytes",
+                    MAX_FRAME_SIZE
+                )
+                .into());
+            }
+            Ok(payload)
+        }
+        _ => Ok(frame.to_vec()),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_negotiate_compression() {
+        assert_eq!(
+            WireCompression::Zlib,
+            WireCompression::negotiate(&WireCompression::supported())
+        );
+        assert_eq!(
+            WireCompression::None,
+            WireCompression::negotiate(&[WireCompression::None])
+        );
+        assert_eq!(WireCompression::None, WireCompression::negotiate(&[]));
+    }
+
+    #[test]
+    pub fn test_compress_frame_roundtrip() {
+        let small = b"{\"Ping\":null}".to_vec();
+        assert_eq!(small, compress_frame(WireCompression::Zlib, &small));
+        assert_eq!(
+            small,
+            decompress_frame(WireCompression::None, &small).unwrap()
+        );
+
+        let large = format!("\"{}\"", "aspect".repeat(1000)).into_bytes();
+        assert_eq!(large, compress_frame(WireCompression::None, &large));
+        let compressed = compress_frame(WireCompression::Zlib, &large);
+        assert!(compressed.len() < large.len());
+        assert_eq!(
+            large,
+            decompress_frame(WireCompression::Zlib, &compressed).unwrap()
+        );
+        assert!(decompress_frame(WireCompression::None, &compressed).is_err());
+    }
+
+    #[test]
+    pub fn test_decompress_frame_rejects_oversized_frames() {
+        let mut encoder =
+            ZlibEncoder::new(vec![COMPRESSED_FRAME_TAG], flate2::Compression::default());
+        let zeros = vec![0; 1 << 20];
+        for _ in 0..(MAX_FRAME_SIZE >> 20) + 1 {
+            encoder.write_all(&zeros).unwrap();
+        }
+        let bomb = encoder.finish().unwrap();
+        assert!(bomb.len() < 1 << 20);
+        assert!(decompress_frame(WireCompression::Zlib, &bomb).is_err());
+    }
+}
@@ -7,7 +7,7 @@ pub(crate) type FrameResult = Result<WsFrame, Sim2hError>;
 /// manages a websocket stream/socket - will periodically poll for data
 pub(crate) struct ConnectionJob {
     cont: bool,
-    wss: TcpWss,
+    wss: Sim2hWss,
     msg_send: crossbeam_channel::Sender<(Url2, FrameResult)>,
     frame: Option<WsFrame>,
     outgoing_recv: crossbeam_channel::Receiver<WsFrame>,
@@ -15,7 +15,7 @@ pub(crate) struct ConnectionJob {
 
 impl ConnectionJob {
     pub(crate) fn new(
-        wss: TcpWss,
+        wss: Sim2hWss,
         msg_send: crossbeam_channel::Sender<(Url2, FrameResult)>,
     ) -> (Self, crossbeam_channel::Sender<WsFrame>) {
         let (outgoing_send, outgoing_recv) = crossbeam_channel::unbounded();
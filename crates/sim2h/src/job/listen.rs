@@ -3,12 +3,15 @@ use crate::*;
 /// a job that manages the sim2h listening socket connection
 /// every iteration will `accept()` a single pending connection
 pub(crate) struct ListenJob {
-    listen: TcpWssServer,
-    wss_send: crossbeam_channel::Sender<TcpWss>,
+    listen: Sim2hWssServer,
+    wss_send: crossbeam_channel::Sender<Sim2hWss>,
 }
 
 impl ListenJob {
-    pub(crate) fn new(listen: TcpWssServer, wss_send: crossbeam_channel::Sender<TcpWss>) -> Self {
+    pub(crate) fn new(
+        listen: Sim2hWssServer,
+        wss_send: crossbeam_channel::Sender<Sim2hWss>,
+    ) -> Self {
         Self { listen, wss_send }
     }
 
@@ -8,27 +8,29 @@ extern crate serde;
 #[macro_use]
 extern crate lazy_static;
 
-#[allow(dead_code)]
 mod naive_sharding;
 
 pub mod cache;
+pub mod compression;
 pub mod connection_state;
 pub mod crypto;
 pub mod error;
+pub mod mailbox;
 use lib3h_protocol::types::{AgentPubKey, AspectHash, EntryHash};
 mod message_log;
 pub mod websocket;
 pub mod wire_message;
+pub mod wss;
 
 pub use crate::message_log::MESSAGE_LOGGER;
-use crate::{crypto::*, error::*};
+use crate::{compression::*, crypto::*, error::*, mailbox::*, wss::*};
 use cache::*;
 use connection_state::*;
 use lib3h_crypto_api::CryptoSystem;
 use lib3h_protocol::{
     data_types::{
-        EntryData, EntryListData, FetchEntryData, GetListData, Opaque, SpaceData,
-        StoreEntryAspectData,
+        DirectMessageData, EntryData, EntryListData, FetchEntryData, GetListData, Opaque,
+        SpaceData, StoreEntryAspectData,
     },
     protocol::*,
     types::SpaceHash,
@@ -36,7 +38,7 @@ use lib3h_protocol::{
 };
 use url2::prelude::*;
 
-pub use wire_message::{WireError, WireMessage};
+pub use wire_message::{WireError, WireMessage, MAX_BATCH_SIZE};
 
 use in_stream::*;
 use log::*;
@@ -83,12 +85,39 @@ impl<T> SendExt<T> for crossbeam_channel::Sender<T> {
     }
 }
 
+/// how sim2h decides which agents are asked to hold which entries
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DhtAlgorithm {
+    /// every agent holds every aspect in its space
+    FullSync,
+    /// agents only hold the aspects that fall into their rrdht storage arc,
+    /// which is sized so that on average `redundant_count` agents hold each entry
+    NaiveSharding { redundant_count: u64 },
+}
+
+impl DhtAlgorithm {
+    /// the value sent to clients in a `WireMessage::Hello`, 0 meaning full sync
+    pub fn redundant_count(&self) -> u64 {
+        match self {
+            DhtAlgorithm::FullSync => 0,
+            DhtAlgorithm::NaiveSharding { redundant_count } => *redundant_count,
+        }
+    }
+}
+
+impl From<u64> for DhtAlgorithm {
+    fn from(redundant_count: u64) -> Self {
+        if redundant_count == 0 {
+            DhtAlgorithm::FullSync
+        } else {
+            DhtAlgorithm::NaiveSharding { redundant_count }
+        }
+    }
+}
+
 const RECALC_RRDHT_ARC_RADIUS_INTERVAL_MS: u64 = 20000; // 20 seconds
 const RETRY_FETCH_MISSING_ASPECTS_INTERVAL_MS: u64 = 10000; // 10 seconds
 
-pub(crate) type TcpWssServer = InStreamListenerWss<InStreamListenerTls<InStreamListenerTcp>>;
-pub(crate) type TcpWss = InStreamWss<InStreamTls<InStreamTcp>>;
-
 mod job;
 use job::*;
 
@@ -98,7 +127,7 @@ pub struct Sim2h {
     connection_states: RwLock<HashMap<Lib3hUri, ConnectionState>>,
     spaces: HashMap<SpaceHash, RwLock<Space>>,
     pool: Pool,
-    wss_recv: crossbeam_channel::Receiver<TcpWss>,
+    wss_recv: crossbeam_channel::Receiver<Sim2hWss>,
     msg_send: crossbeam_channel::Sender<(Url2, FrameResult)>,
     msg_recv: crossbeam_channel::Receiver<(Url2, FrameResult)>,
     open_connections: HashMap<
@@ -113,10 +142,40 @@ pub struct Sim2h {
     rrdht_arc_radius_recalc: std::time::Instant,
     /// when should we try to resync nodes that are still missing aspect data
     missing_aspects_resync: std::time::Instant,
+    dht_algorithm: DhtAlgorithm,
+    /// frame compression negotiated with each connection
+    connection_compression: HashMap<Lib3hUri, WireCompression>,
+    /// store and fetch messages queued during this process() pass, sent as batches at its end
+    pending_batches: HashMap<Lib3hUri, Vec<WireMessage>>,
+    /// store-and-forward direct messages waiting for their recipients to join
+    mailbox: Mailbox,
 }
 
 impl Sim2h {
-    pub fn new(crypto: Box<dyn CryptoSystem>, bind_spec: Lib3hUri) -> Self {
+    /// create a sim2h server listening for tcp + tls websocket connections on `bind_spec`
+    pub fn new(
+        crypto: Box<dyn CryptoSystem>,
+        bind_spec: Lib3hUri,
+        dht_algorithm: DhtAlgorithm,
+    ) -> Self {
+        let url: Url2 = url::Url::from(bind_spec).into();
+        let listen = Sim2hWssServer::bind_tcp(&url).unwrap();
+        Sim2h::with_listener(crypto, listen, dht_algorithm)
+    }
+
+    /// create a sim2h server listening on the in-process memory transport,
+    /// no ports or certificates are involved, so a test can run sim2h and many
+    /// conductors in a single process. Clients connect to the mem:// `bound_uri`.
+    pub fn new_in_memory(crypto: Box<dyn CryptoSystem>, dht_algorithm: DhtAlgorithm) -> Self {
+        let listen = Sim2hWssServer::bind_mem().unwrap();
+        Sim2h::with_listener(crypto, listen, dht_algorithm)
+    }
+
+    fn with_listener(
+        crypto: Box<dyn CryptoSystem>,
+        listen: Sim2hWssServer,
+        dht_algorithm: DhtAlgorithm,
+    ) -> Self {
         let pool = Pool::new();
         pool.push_job(Box::new(Arc::new(Mutex::new(Tick::new()))));
 
@@ -136,23 +195,23 @@ impl Sim2h {
             num_ticks: 0,
             rrdht_arc_radius_recalc: std::time::Instant::now(),
             missing_aspects_resync: std::time::Instant::now(),
+            dht_algorithm,
+            connection_compression: HashMap::new(),
+            pending_batches: HashMap::new(),
+            mailbox: Mailbox::new(),
         };
 
-        sim2h.priv_bind_listening_socket(url::Url::from(bind_spec).into(), wss_send);
+        sim2h.priv_start_listen_job(listen, wss_send);
 
         sim2h
     }
 
-    /// bind a listening socket, and set up the polling job to accept connections
-    fn priv_bind_listening_socket(
+    /// set up the polling job to accept connections on our listening socket
+    fn priv_start_listen_job(
         &mut self,
-        url: Url2,
-        wss_send: crossbeam_channel::Sender<TcpWss>,
+        listen: Sim2hWssServer,
+        wss_send: crossbeam_channel::Sender<Sim2hWss>,
     ) {
-        let config = TcpBindConfig::default();
-        let config = TlsBindConfig::new(config).dev_certificate();
-        let config = WssBindConfig::new(config);
-        let listen: TcpWssServer = InStreamListenerWss::bind(&url, config).unwrap();
         self.bound_uri = Some(url::Url::from(listen.binding()).into());
         self.pool
             .push_job(Box::new(Arc::new(Mutex::new(ListenJob::new(
@@ -198,7 +257,18 @@ impl Sim2h {
                         format!("unexpected text message: {:?}", s).into(),
                     ),
                     WsFrame::Binary(b) => {
-                        let payload: Opaque = b.into();
+                        let compression = self
+                            .connection_compression
+                            .get(&url)
+                            .cloned()
+                            .unwrap_or_default();
+                        let payload: Opaque = match decompress_frame(compression, &b) {
+                            Ok(payload) => payload.into(),
+                            Err(error) => {
+                                self.priv_drop_connection_for_error(url, error);
+                                return;
+                            }
+                        };
                         match Sim2h::verify_payload(payload.clone()) {
                             Ok((source, wire_message)) => {
                                 if let Err(error) = self.handle_message(&url, wire_message, &source)
@@ -226,10 +296,25 @@ impl Sim2h {
         }
     }
 
-    /// recalculate arc radius for our connections
+    /// recalculate arc radius for our connections,
+    /// and fetch the aspects that entered grown arcs for their agents
     fn recalc_rrdht_arc_radius(&mut self) {
-        for (_, space) in self.spaces.iter_mut() {
-            space.write().recalc_rrdht_arc_radius();
+        let mut entered_aspects = Vec::new();
+        for (space_address, space) in self.spaces.iter_mut() {
+            let mut space = space.write();
+            let agent_pool = space.all_agents().keys().cloned().collect::<Vec<AgentId>>();
+            for (agent_id, aspects) in space.recalc_rrdht_arc_radius() {
+                entered_aspects.push((
+                    space_address.clone(),
+                    agent_id,
+                    aspects,
+                    agent_pool.clone(),
+                ));
+            }
+        }
+        for (space_address, agent_id, aspects, mut agent_pool) in entered_aspects {
+            agent_pool.shuffle(&mut thread_rng());
+            self.fetch_aspects_from_arbitrary_agent(aspects, agent_id, &agent_pool, space_address);
         }
     }
 
@@ -267,7 +352,7 @@ impl Sim2h {
         if !self.spaces.contains_key(space_address) {
             self.spaces.insert(
                 space_address.clone(),
-                RwLock::new(Space::new(self.crypto.box_clone())),
+                RwLock::new(Space::new(self.crypto.box_clone(), self.dht_algorithm)),
             );
             info!(
                 "\n\n+++++++++++++++\nNew Space: {}\n+++++++++++++++\n",
@@ -294,6 +379,11 @@ impl Sim2h {
                     "Agent {:?} joined space {:?}",
                     data.agent_id, data.space_address
                 );
+                self.send(
+                    data.agent_id.clone(),
+                    uri.clone(),
+                    &WireMessage::Hello(self.dht_algorithm.redundant_count()),
+                );
                 self.request_authoring_list(
                     uri.clone(),
                     data.space_address.clone(),
@@ -312,6 +402,10 @@ impl Sim2h {
                         );
                     }
                 }
+                for message in self.mailbox.take(&data.space_address, &data.agent_id) {
+                    debug!("Delivering {} from mailbox", message.message_type());
+                    self.send(data.agent_id.clone(), uri.clone(), &message);
+                }
                 Ok(())
             } else {
                 Err(format!("no agent found in limbo at {} ", uri).into())
@@ -341,6 +435,8 @@ impl Sim2h {
         if let Some((con, _outgoing_send)) = self.open_connections.remove(uri) {
             con.f_lock().stop();
         }
+        self.connection_compression.remove(uri);
+        self.pending_batches.remove(uri);
 
         if let Some(ConnectionState::Joined(space_address, agent_id)) =
             self.connection_states.write().remove(uri)
@@ -396,6 +492,26 @@ impl Sim2h {
             self.send(signer.clone(), uri.clone(), &WireMessage::Pong);
             return Ok(());
         }
+        if let WireMessage::NegotiateCompression(offered) = message {
+            let compression = WireCompression::negotiate(&offered);
+            debug!("Using {:?} compression for {}", compression, uri);
+            // the answer itself still goes out uncompressed
+            self.send(
+                signer.clone(),
+                uri.clone(),
+                &WireMessage::CompressionAgreed(compression),
+            );
+            self.connection_compression.insert(uri.clone(), compression);
+            return Ok(());
+        }
+        if let WireMessage::Batch(messages) = message {
+            for message in messages {
+                if let Err(error) = self.handle_message(uri, message, signer) {
+                    error!("Error handling batched message: {:?}", error);
+                }
+            }
+            return Ok(());
+        }
         MESSAGE_LOGGER
             .lock()
             .log_in(signer.clone(), uri.clone(), message.clone());
@@ -448,6 +564,24 @@ impl Sim2h {
         Ok((signed_message.provenance.source().into(), wire_message))
     }
 
+    /// number of open websocket connections, joined or not
+    pub fn connection_count(&self) -> usize {
+        self.open_connections.len()
+    }
+
+    /// number of spaces any agent has joined since startup
+    pub fn space_count(&self) -> usize {
+        self.spaces.len()
+    }
+
+    /// number of agents currently joined across all spaces
+    pub fn agent_count(&self) -> usize {
+        self.spaces
+            .values()
+            .map(|space| space.read().agent_count())
+            .sum()
+    }
+
     // process transport and  incoming messages from it
     pub fn process(&mut self) -> Sim2hResult<()> {
         trace!("process");
@@ -481,6 +615,8 @@ impl Sim2h {
             self.retry_sync_missing_aspects();
         }
 
+        self.priv_flush_pending_batches();
+
         trace!("process done");
         Ok(())
     }
@@ -510,6 +646,44 @@ impl Sim2h {
         }
     }
 
+    /// forwards a direct message to its recipient, or with `store_and_forward` holds it
+    /// in the recipient's mailbox if they are offline and tells the sender it got queued
+    fn handle_direct_message(
+        &mut self,
+        uri: &Lib3hUri,
+        space_address: &SpaceHash,
+        agent_id: &AgentId,
+        dm_data: DirectMessageData,
+        store_and_forward: bool,
+    ) -> Sim2hResult<()> {
+        if (dm_data.from_agent_id != *agent_id) || (dm_data.space_address != *space_address) {
+            return Err(SPACE_MISMATCH_ERR_STR.into());
+        }
+        let message =
+            WireMessage::Lib3hToClient(Lib3hToClient::HandleSendDirectMessage(dm_data.clone()));
+        let maybe_to_url = self.lookup_joined(space_address, &dm_data.to_agent_id);
+        if maybe_to_url.is_none() && !store_and_forward {
+            return Err(format!("unvalidated proxy agent {}", &dm_data.to_agent_id).into());
+        }
+        if store_and_forward {
+            self.mailbox
+                .await_receipt(space_address.clone(), dm_data.request_id.clone());
+        }
+        match maybe_to_url {
+            Some(to_url) => self.send(dm_data.to_agent_id.clone(), to_url, &message),
+            None => {
+                self.mailbox
+                    .store(space_address.clone(), dm_data.to_agent_id.clone(), message)?;
+                self.send(
+                    agent_id.clone(),
+                    uri.clone(),
+                    &WireMessage::DirectMessageQueued(dm_data),
+                );
+            }
+        }
+        Ok(())
+    }
+
     // given an incoming messages, prepare a proxy message and whether it's an publish or request
     fn handle_joined(
         &mut self,
@@ -542,35 +716,95 @@ impl Sim2h {
             // -- Direct Messaging -- //
             // Send a message directly to another agent on the network
             WireMessage::ClientToLib3h(ClientToLib3h::SendDirectMessage(dm_data)) => {
+                self.handle_direct_message(uri, space_address, agent_id, dm_data, false)
+            }
+            // Same, but hold the message in the recipient's mailbox if they are offline
+            WireMessage::StoreAndForwardDirectMessage(dm_data) => {
+                self.handle_direct_message(uri, space_address, agent_id, dm_data, true)
+            }
+            // Direct message response
+            WireMessage::Lib3hToClientResponse(Lib3hToClientResponse::HandleSendDirectMessageResult(
+                dm_data,
+            )) => {
                 if (dm_data.from_agent_id != *agent_id) || (dm_data.space_address != *space_address)
                 {
                     return Err(SPACE_MISMATCH_ERR_STR.into());
                 }
-                let to_url = self
-                    .lookup_joined(space_address, &dm_data.to_agent_id)
-                    .ok_or_else(|| format!("unvalidated proxy agent {}", &dm_data.to_agent_id))?;
+                let message = WireMessage::Lib3hToClient(
+                    Lib3hToClient::SendDirectMessageResult(dm_data.clone())
+                );
+                match self.lookup_joined(space_address, &dm_data.to_agent_id) {
+                    Some(to_url) => {
+                        self.send(dm_data.to_agent_id.clone(), to_url, &message);
+                        Ok(())
+                    }
+                    // the answer to a store-and-forward message is its delivery receipt,
+                    // hold it if the original sender went offline in the meantime
+                    None if self.mailbox.take_receipt(space_address, &dm_data.request_id) => {
+                        self.mailbox.store(
+                            space_address.clone(),
+                            dm_data.to_agent_id.clone(),
+                            message,
+                        )?;
+                        Ok(())
+                    }
+                    None => {
+                        Err(format!("unvalidated proxy agent {}", &dm_data.to_agent_id).into())
+                    }
+                }
+            }
+            // -- Queries -- //
+            // With a sharded DHT the querying agent might not hold the entry,
+            // so we forward the query to one of the agents that are supposed to hold it
+            WireMessage::ClientToLib3h(ClientToLib3h::QueryEntry(query_data)) => {
+                if (query_data.requester_agent_id != *agent_id)
+                    || (query_data.space_address != *space_address)
+                {
+                    return Err(SPACE_MISMATCH_ERR_STR.into());
+                }
+                let mut holders = self
+                    .get_or_create_space(space_address)
+                    .read()
+                    .agents_supposed_to_hold_entry(&query_data.entry_address)
+                    .into_iter()
+                    .collect::<Vec<(AgentId, AgentInfo)>>();
+                holders.shuffle(&mut thread_rng());
+                // if nobody is supposed to hold the entry, the requester is as good as anyone
+                let (responder_id, responder_uri) = holders
+                    .into_iter()
+                    .next()
+                    .map(|(holder_id, info)| (holder_id, info.uri))
+                    .unwrap_or_else(|| (agent_id.clone(), uri.clone()));
                 self.send(
-                    dm_data.to_agent_id.clone(),
-                    to_url,
-                    &WireMessage::Lib3hToClient(Lib3hToClient::HandleSendDirectMessage(dm_data))
+                    responder_id,
+                    responder_uri,
+                    &WireMessage::Lib3hToClient(Lib3hToClient::HandleQueryEntry(query_data)),
                 );
                 Ok(())
             }
-            // Direct message response
-            WireMessage::Lib3hToClientResponse(Lib3hToClientResponse::HandleSendDirectMessageResult(
-                dm_data,
+            // Query response, route it back to the agent that asked
+            WireMessage::Lib3hToClientResponse(Lib3hToClientResponse::HandleQueryEntryResult(
+                query_result,
             )) => {
-                if (dm_data.from_agent_id != *agent_id) || (dm_data.space_address != *space_address)
+                if (query_result.responder_agent_id != *agent_id)
+                    || (query_result.space_address != *space_address)
                 {
                     return Err(SPACE_MISMATCH_ERR_STR.into());
                 }
                 let to_url = self
-                    .lookup_joined(space_address, &dm_data.to_agent_id)
-                    .ok_or_else(|| format!("unvalidated proxy agent {}", &dm_data.to_agent_id))?;
+                    .lookup_joined(space_address, &query_result.requester_agent_id)
+                    .ok_or_else(|| {
+                        format!(
+                            "unvalidated proxy agent {}",
+                            &query_result.requester_agent_id
+                        )
+                    })?;
                 self.send(
-                    dm_data.to_agent_id.clone(),
+                    query_result.requester_agent_id.clone(),
                     to_url,
-                    &WireMessage::Lib3hToClient(Lib3hToClient::SendDirectMessageResult(dm_data))
+                    &WireMessage::ClientToLib3hResponse(ClientToLib3hResponse::QueryEntryResult(
+                        query_result,
+                    )),
                 );
                 Ok(())
             }
@@ -599,9 +833,13 @@ impl Sim2h {
                     let space = self
                         .get_or_create_space(&space_address)
                         .read();
+                    // only ask the node for aspects of entries that fall into its storage arc
                     let aspects_missing_at_node = space
                         .all_aspects()
-                        .diff(&AspectList::from(list_data.address_map));
+                        .diff(&AspectList::from(list_data.address_map))
+                        .filter_by_entry_hash(|entry_hash| {
+                            space.agent_is_supposed_to_hold_entry(agent_id, entry_hash)
+                        });
 
                     warn!("MISSING ASPECTS at {}:\n{}", agent_id, aspects_missing_at_node.pretty_string());
 
@@ -725,6 +963,8 @@ impl Sim2h {
     }
 
     /// Get an agent who has at least one of the aspects specified, and who is not the same as for_agent_id.
+    /// Agents whose storage arc covers the entry are preferred over agents that might
+    /// just still be holding it from before their arc shrunk.
     /// `agent_pool` is expected to be randomly shuffled, to ensure that no hotspots are created.
     fn get_agent_not_missing_aspects(
         &self,
@@ -735,14 +975,18 @@ impl Sim2h {
         space_address: &SpaceHash,
     ) -> Option<AgentId> {
         let space_lock = self.spaces.get(space_address)?.read();
-        agent_pool
+        let mut candidates = agent_pool
             .into_iter()
             // We ignore all agents that are missing all of the same aspects as well since
             // they can't help us.
-            .find(|a| {
+            .filter(|a| {
                 **a != *for_agent_id
                     && !space_lock.agent_is_missing_all_aspects(*a, entry_hash, aspects)
-            })
+            });
+        candidates
+            .clone()
+            .find(|a| space_lock.agent_is_supposed_to_hold_entry(*a, entry_hash))
+            .or_else(|| candidates.next())
             .cloned()
     }
 
@@ -763,6 +1007,14 @@ impl Sim2h {
         let aspect_list = AspectList::from(map);
         debug!("GOT NEW ASPECTS:\n{}", aspect_list.pretty_string());
 
+        let holders = self
+            .get_or_create_space(&space_address)
+            .read()
+            .agents_supposed_to_hold_entry(&entry_data.entry_address)
+            .into_iter()
+            .filter(|(agent_id, _)| *agent_id != provider)
+            .collect::<Vec<(AgentId, AgentInfo)>>();
+
         for aspect in entry_data.aspect_list {
             // 1. Add hashes to our global list of all aspects in this space:
             {
@@ -788,41 +1040,16 @@ impl Sim2h {
                     entry_aspect: aspect,
                 },
             ));
-            // 3. Send store message to everybody in this space
-            if let Err(e) = self.broadcast(space_address.clone(), &store_message, Some(&provider)) {
-                error!("Error during broadcast: {:?}", e);
-            }
+            // 3. Send store message to everybody in this space that is supposed to hold it
+            self.multi_send(&holders, &store_message);
         }
     }
 
-    fn broadcast(
-        &mut self,
-        space: SpaceHash,
-        msg: &WireMessage,
-        except: Option<&AgentId>,
-    ) -> Sim2hResult<()> {
-        debug!("Broadcast in space: {:?}", space);
-        let all_agents = self
-            .spaces
-            .get(&space)
-            .ok_or("No such space")?
-            .read()
-            .all_agents()
-            .clone()
-            .into_iter()
-            .filter(|(a, _)| {
-                if let Some(exception) = except {
-                    *a != *exception
-                } else {
-                    true
-                }
-            })
-            .collect::<Vec<(AgentId, AgentInfo)>>();
-        for (agent, info) in all_agents {
-            debug!("Broadcast: Sending to {:?}", info.uri);
-            self.send(agent, info.uri, msg);
+    fn multi_send(&mut self, agents: &[(AgentId, AgentInfo)], msg: &WireMessage) {
+        for (agent, info) in agents {
+            debug!("Multi send: Sending to {:?}", info.uri);
+            self.send(agent.clone(), info.uri.clone(), msg);
         }
-        Ok(())
     }
 
     fn send(&mut self, agent: AgentId, uri: Lib3hUri, msg: &WireMessage) {
@@ -836,24 +1063,59 @@ impl Sim2h {
             }
         }
 
+        if msg.is_batchable() {
+            self.pending_batches
+                .entry(uri)
+                .or_insert_with(Vec::new)
+                .push(msg.clone());
+            return;
+        }
+
+        // batched messages queued earlier have to go out first to keep the send order
+        self.priv_flush_pending_batch(&uri);
+        self.priv_send_frame(uri, msg);
+
+        match msg {
+            WireMessage::Ping | WireMessage::Pong => {}
+            _ => debug!("sent."),
+        }
+    }
+
+    /// send all batchable messages queued up by send()
+    fn priv_flush_pending_batches(&mut self) {
+        let uris = self.pending_batches.keys().cloned().collect::<Vec<_>>();
+        for uri in uris {
+            self.priv_flush_pending_batch(&uri);
+        }
+    }
+
+    /// send the batchable messages queued up by send() for one connection
+    fn priv_flush_pending_batch(&mut self, uri: &Lib3hUri) {
+        if let Some(messages) = self.pending_batches.remove(uri) {
+            for batch in WireMessage::into_batches(messages) {
+                self.priv_send_frame(uri.clone(), &batch);
+            }
+        }
+    }
+
+    fn priv_send_frame(&mut self, uri: Lib3hUri, msg: &WireMessage) {
         let payload: Opaque = msg.clone().into();
+        let compression = self
+            .connection_compression
+            .get(&uri)
+            .cloned()
+            .unwrap_or_default();
 
         match self.open_connections.get_mut(&uri) {
             None => {
                 error!("FAILED TO SEND, NO ROUTE: {}", uri);
-                return;
             }
             Some((_con, outgoing_send)) => {
-                if let Err(_) = outgoing_send.send(payload.as_bytes().into()) {
+                if let Err(_) = outgoing_send.send(compress_frame(compression, &payload).into()) {
                     self.disconnect(&uri);
                 }
             }
         }
-
-        match msg {
-            WireMessage::Ping | WireMessage::Pong => {}
-            _ => debug!("sent."),
-        }
     }
 
     fn retry_sync_missing_aspects(&mut self) {
@@ -0,0 +1,276 @@
+//! store-and-forward of direct messages to agents that are offline
+use crate::{wire_message::WireMessage, AgentId};
+use lib3h_protocol::types::SpaceHash;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// the number of messages held for a single agent, the oldest get dropped first
+pub const MAX_MAILBOX_SIZE: usize = 100;
+
+/// the number of messages held for all agents together,
+/// new messages get rejected once this is reached
+pub const MAX_MAILBOX_MESSAGES: usize = 10_000;
+
+/// how long a message is held before it gets dropped undelivered
+pub const MAILBOX_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// error of `Mailbox::store` if all mailboxes together are full
+pub const MAILBOX_FULL_ERR_STR: &str = "mailbox full, recipient has too many messages waiting";
+
+/// messages waiting for offline agents, kept outside of the spaces
+/// so they survive everyone in a space disconnecting
+#[derive(Default)]
+pub struct Mailbox {
+    messages: HashMap<(SpaceHash, AgentId), Vec<(Instant, WireMessage)>>,
+    count: usize,
+    /// request ids of the store-and-forward messages whose answers are delivery receipts,
+    /// which get held too if the sender went offline in the meantime
+    awaiting_receipt: HashMap<(SpaceHash, String), Instant>,
+}
+
+impl Mailbox {
+    pub fn new() -> Self {
+        Mailbox::default()
+    }
+
+    /// hold a message until `agent_id` joins `space_address`,
+    /// fails if the mailboxes of all agents together are full
+    pub fn store(
+        &mut self,
+        space_address: SpaceHash,
+        agent_id: AgentId,
+        message: WireMessage,
+    ) -> Result<(), &'static str> {
+        self.store_at(space_address, agent_id, message, Instant::now())
+    }
+
+    /// all messages held for the agent that have not expired, in the order they were stored
+    pub fn take(&mut self, space_address: &SpaceHash, agent_id: &AgentId) -> Vec<WireMessage> {
+        self.take_at(space_address, agent_id, Instant::now())
+    }
+
+    /// remember that the answer to this store-and-forward message is its delivery receipt,
+    /// receipts of at most `MAX_MAILBOX_MESSAGES` messages are awaited at a time
+    pub fn await_receipt(&mut self, space_address: SpaceHash, request_id: String) {
+        self.await_receipt_at(space_address, request_id, Instant::now())
+    }
+
+    /// is this answer the delivery receipt of a store-and-forward message?
+    /// a receipt is only recognized once
+    pub fn take_receipt(&mut self, space_address: &SpaceHash, request_id: &str) -> bool {
+        self.awaiting_receipt
+            .remove(&(space_address.clone(), request_id.to_string()))
+            .map(|sent_at| !is_expired(sent_at, Instant::now()))
+            .unwrap_or(false)
+    }
+
+    fn await_receipt_at(&mut self, space_address: SpaceHash, request_id: String, now: Instant) {
+        if self.awaiting_receipt.len() >= MAX_MAILBOX_MESSAGES {
+            self.awaiting_receipt
+                .retain(|_, sent_at| !is_expired(*sent_at, now));
+        }
+        if self.awaiting_receipt.len() < MAX_MAILBOX_MESSAGES {
+            self.awaiting_receipt
+                .insert((space_address, request_id), now);
+        }
+    }
+
+    fn store_at(
+        &mut self,
+        space_address: SpaceHash,
+        agent_id: AgentId,
+        message: WireMessage,
+        now: Instant,
+    ) -> Result<(), &'static str> {
+        if self.count >= MAX_MAILBOX_MESSAGES {
+            self.prune_expired(now);
+        }
+        let key = (space_address, agent_id);
+        let agent_is_full = self
+            .messages
+            .get(&key)
+            .map(|messages| messages.len() >= MAX_MAILBOX_SIZE)
+            .unwrap_or(false);
+        if self.count >= MAX_MAILBOX_MESSAGES && !agent_is_full {
+            return Err(MAILBOX_FULL_ERR_STR);
+        }
+        let messages = self.messages.entry(key).or_insert_with(Vec::new);
+        if agent_is_full {
+            messages.remove(0);
+            self.count -= 1;
+        }
+        messages.push((now, message));
+        self.count += 1;
+        Ok(())
+    }
+
+    fn take_at(
+        &mut self,
+        space_address: &SpaceHash,
+        agent_id: &AgentId,
+        now: Instant,
+    ) -> Vec<WireMessage> {
+        let messages = self
+            .messages
+            .remove(&(space_address.clone(), agent_id.clone()))
+            .unwrap_or_else(Vec::new);
+        self.count -= messages.len();
+        messages
+            .into_iter()
+            .filter(|(stored_at, _)| !is_expired(*stored_at, now))
+            .map(|(_, message)| message)
+            .collect()
+    }
+
+    /// drops the messages held longer than `MAILBOX_TTL`
+    fn prune_expired(&mut self, now: Instant) {
+        let mut count = 0;
+        self.messages.retain(|_, messages| {
+            messages.retain(|(stored_at, _)| !is_expired(*stored_at, now));
+            count += messages.len();
+            !messages.is_empty()
+        });
+        self.count = count;
+    }
+}
+
+fn is_expired(stored_at: Instant, now: Instant) -> bool {
+    now.duration_since(stored_at) >= MAILBOX_TTL
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_mailbox_holds_messages_per_agent() {
+        let space: SpaceHash = "space".to_string().into();
+        let alice: AgentId = "alice".into();
+        let bob: AgentId = "bob".into();
+        let mut mailbox = Mailbox::new();
+
+        mailbox
+            .store(space.clone(), alice.clone(), WireMessage::Ping)
+            .unwrap();
+        mailbox
+            .store(space.clone(), alice.clone(), WireMessage::Pong)
+            .unwrap();
+        assert_eq!(Vec::<WireMessage>::new(), mailbox.take(&space, &bob));
+        assert_eq!(
+            vec![WireMessage::Ping, WireMessage::Pong],
+            mailbox.take(&space, &alice)
+        );
+        assert_eq!(Vec::<WireMessage>::new(), mailbox.take(&space, &alice));
+    }
+
+    #[test]
+    pub fn test_mailbox_drops_oldest_messages() {
+        let space: SpaceHash = "space".to_string().into();
+        let alice: AgentId = "alice".into();
+        let mut mailbox = Mailbox::new();
+
+        mailbox
+            .store(space.clone(), alice.clone(), WireMessage::Ping)
+            .unwrap();
+        for _ in 0..MAX_MAILBOX_SIZE {
+            mailbox
+                .store(space.clone(), alice.clone(), WireMessage::Pong)
+                .unwrap();
+        }
+        let messages = mailbox.take(&space, &alice);
+        assert_eq!(MAX_MAILBOX_SIZE, messages.len());
+        assert!(!messages.contains(&WireMessage::Ping));
+    }
+
+    #[test]
+    pub fn test_mailbox_rejects_messages_if_all_mailboxes_are_full() {
+        let space: SpaceHash = "space".to_string().into();
+        let mut mailbox = Mailbox::new();
+        let now = Instant::now();
+
+        for i in 0..MAX_MAILBOX_MESSAGES / MAX_MAILBOX_SIZE {
+            let agent: AgentId = format!("agent{}", i).into();
+            for _ in 0..MAX_MAILBOX_SIZE {
+                mailbox
+                    .store_at(space.clone(), agent.clone(), WireMessage::Ping, now)
+                    .unwrap();
+            }
+        }
+        let alice: AgentId = "alice".into();
+        assert_eq!(
+            Err(MAILBOX_FULL_ERR_STR),
+            mailbox.store_at(space.clone(), alice, WireMessage::Ping, now)
+        );
+
+        // a full agent mailbox still makes room by dropping its oldest message
+        let agent0: AgentId = "agent0".into();
+        assert_eq!(
+            Ok(()),
+            mailbox.store_at(space.clone(), agent0, WireMessage::Pong, now)
+        );
+
+        // and space frees up once messages expire
+        let later = now + MAILBOX_TTL;
+        let bob: AgentId = "bob".into();
+        assert_eq!(
+            Ok(()),
+            mailbox.store_at(space.clone(), bob, WireMessage::Ping, later)
+        );
+        assert_eq!(1, mailbox.count);
+    }
+
+    #[test]
+    pub fn test_mailbox_drops_expired_messages() {
+        let space: SpaceHash = "space".to_string().into();
+        let alice: AgentId = "alice".into();
+        let mut mailbox = Mailbox::new();
+        let now = Instant::now();
+
+        mailbox
+            .store_at(space.clone(), alice.clone(), WireMessage::Ping, now)
+            .unwrap();
+        mailbox
+            .store_at(
+                space.clone(),
+                alice.clone(),
+                WireMessage::Pong,
+                now + MAILBOX_TTL / 2,
+            )
+            .unwrap();
+        assert_eq!(
+            vec![WireMessage::Pong],
+            mailbox.take_at(&space, &alice, now + MAILBOX_TTL)
+        );
+        assert_eq!(0, mailbox.count);
+    }
+
+    #[test]
+    pub fn test_mailbox_recognizes_receipts_once() {
+        let space: SpaceHash = "space".to_string().into();
+        let mut mailbox = Mailbox::new();
+
+        mailbox.await_receipt(space.clone(), "1234".to_string());
+        assert!(!mailbox.take_receipt(&space, "5678"));
+        assert!(!mailbox.take_receipt(&"other".to_string().into(), "1234"));
+        assert!(mailbox.take_receipt(&space, "1234"));
+        assert!(!mailbox.take_receipt(&space, "1234"));
+    }
+
+    #[test]
+    pub fn test_mailbox_forgets_expired_receipts_when_full() {
+        let space: SpaceHash = "space".to_string().into();
+        let mut mailbox = Mailbox::new();
+        let now = Instant::now();
+
+        for i in 0..MAX_MAILBOX_MESSAGES {
+            mailbox.await_receipt_at(space.clone(), i.to_string(), now);
+        }
+        mailbox.await_receipt_at(space.clone(), "late".to_string(), now);
+        assert!(!mailbox.take_receipt(&space, "late"));
+
+        mailbox.await_receipt_at(space.clone(), "later".to_string(), now + MAILBOX_TTL);
+        assert_eq!(1, mailbox.awaiting_receipt.len());
+    }
+}
@@ -1,7 +1,6 @@
 use lib3h::rrdht_util::*;
 use lib3h_crypto_api::CryptoSystem;
-
-const REDUNDANT_COUNT: u64 = 50;
+use lib3h_protocol::types::EntryHash;
 
 #[allow(clippy::borrowed_box)]
 /// ack - lib3h can only convert agent_ids to locations right now
@@ -25,30 +24,59 @@ pub fn anything_to_location(crypto: &Box<dyn CryptoSystem>, anything: &str) -> L
     }
 }
 
+#[allow(clippy::borrowed_box)]
+/// the location of an entry in the rrdht space
+pub fn entry_location(crypto: &Box<dyn CryptoSystem>, entry_hash: &EntryHash) -> Location {
+    anything_to_location(crypto, &entry_hash.to_string())
+}
+
 /// implement a super simple sharding algorithm
-/// to distribute data when node counts go > 50
+/// to distribute data when node counts go > redundant_count
+/// (only the tests still compare storage arcs against it)
+#[cfg(test)]
 pub fn naive_sharding_should_store(
     agent_loc: Location,
     data_addr_loc: Location,
     node_count: u64,
+    redundant_count: u64,
 ) -> bool {
-    // if there are < 50 nodes, everyone should store everything
-    if node_count <= REDUNDANT_COUNT {
+    // if there are < redundant_count nodes, everyone should store everything
+    if node_count <= redundant_count {
         return true;
     }
 
-    // divide up the space so on average data will be stored by 50 nodes
-    let dist: f64 = ARC_LENGTH_MAX as f64 / (node_count as f64 / REDUNDANT_COUNT as f64);
+    // divide up the space so on average data will be stored by redundant_count nodes
+    let dist: f64 = ARC_LENGTH_MAX as f64 / (node_count as f64 / redundant_count as f64);
 
     // determine if this specific piece of data should be stored by this node
     agent_loc.forward_distance_to(data_addr_loc) < dist as u32
 }
 
+/// the smallest storage arc radius that, given evenly distributed agent locations,
+/// will on average have redundant_count nodes storing every piece of data
+pub fn naive_sharding_arc_radius(node_count: u64, redundant_count: u64) -> u32 {
+    // if there are < redundant_count nodes, everyone should store everything
+    if node_count <= redundant_count {
+        return ARC_RADIUS_MAX;
+    }
+
+    (ARC_RADIUS_MAX as f64 * redundant_count as f64 / node_count as f64) as u32
+}
+
+/// does the storage arc centered on `center` with the given radius cover `location`?
+pub fn arc_radius_contains(center: Location, radius: u32, location: Location) -> bool {
+    radius >= ARC_RADIUS_MAX
+        || center.forward_distance_to(location) <= radius
+        || location.forward_distance_to(center) <= radius
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use lib3h_sodium::SodiumCryptoSystem;
 
+    const REDUNDANT_COUNT: u64 = 50;
+
     // generate a test agent id (HcS)
     fn gen_id(crypto: &Box<dyn CryptoSystem>) -> String {
         let enc = hcid::HcidEncoding::with_kind("hcs0").unwrap();
@@ -139,7 +167,12 @@ mod tests {
 
                 // go through all the nodes
                 for agent_loc in nodes.iter() {
-                    if naive_sharding_should_store(*agent_loc, data_loc, nodes.len() as u64) {
+                    if naive_sharding_should_store(
+                        *agent_loc,
+                        data_loc,
+                        nodes.len() as u64,
+                        REDUNDANT_COUNT,
+                    ) {
                         store_count += 1;
                     }
                 }
@@ -171,7 +204,8 @@ mod tests {
                                 naive_sharding_should_store(
                                     *agent_loc,
                                     data_loc,
-                                    nodes.len() as u64
+                                    nodes.len() as u64,
+                                    REDUNDANT_COUNT,
                                 )
                             );
                         }
@@ -197,7 +231,8 @@ mod tests {
                                 naive_sharding_should_store(
                                     *agent_loc,
                                     data_loc,
-                                    nodes.len() as u64
+                                    nodes.len() as u64,
+                                    REDUNDANT_COUNT,
                                 )
                             );
                         }
@@ -232,4 +267,38 @@ mod tests {
             count, min, max, mean
         );
     }
+
+    #[test]
+    fn it_should_cover_redundant_count_with_arc_radius() {
+        let crypto: Box<dyn CryptoSystem> =
+            Box::new(SodiumCryptoSystem::new().set_pwhash_interactive());
+
+        // small networks hold everything
+        assert_eq!(
+            ARC_RADIUS_MAX,
+            naive_sharding_arc_radius(10, REDUNDANT_COUNT)
+        );
+
+        let nodes = (0..1000)
+            .map(|_| anything_to_location(&crypto, &gen_id(&crypto)))
+            .collect::<Vec<_>>();
+        let radius = naive_sharding_arc_radius(nodes.len() as u64, REDUNDANT_COUNT);
+        assert!(radius < ARC_RADIUS_MAX);
+
+        let mut total = 0;
+        for _ in 0..100 {
+            let data_loc = anything_to_location(&crypto, &gen_data_addr(&crypto));
+            let store_count = nodes
+                .iter()
+                .filter(|agent_loc| arc_radius_contains(**agent_loc, radius, data_loc))
+                .count();
+            assert!(store_count >= 15, "store count < 15: {}", store_count);
+            assert!(store_count < 100, "store count >= 100: {}", store_count);
+            total += store_count;
+        }
+
+        let mean = total as f64 / 100.0;
+        println!("mean: {}", mean);
+        assert!(mean > 35.0 && mean < 65.0);
+    }
 }
@@ -1,6 +1,9 @@
 //! encapsulates lib3h ghostmessage for sim2h including security challenge
-use crate::error::Sim2hError;
-use lib3h_protocol::{data_types::Opaque, protocol::*};
+use crate::{compression::WireCompression, error::Sim2hError};
+use lib3h_protocol::{
+    data_types::{DirectMessageData, Opaque},
+    protocol::*,
+};
 use std::convert::TryFrom;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -18,13 +21,38 @@ pub enum WireMessage {
     Err(WireError),
     Ping,
     Pong,
+    /// sent by sim2h after a successful join, carrying the redundancy factor
+    /// of its DHT algorithm (0 meaning full sync)
+    Hello(u64),
+    /// sent by a client right after connecting, offering the compression methods it supports
+    NegotiateCompression(Vec<WireCompression>),
+    /// sim2h's answer to `NegotiateCompression`, frames may be compressed from now on
+    CompressionAgreed(WireCompression),
+    /// many messages sent in one frame, used for the store/fetch floods
+    /// caused by publishing and by (re)connecting agents
+    Batch(Vec<WireMessage>),
+    /// sent by a client instead of `ClientToLib3h::SendDirectMessage` for direct messages
+    /// that sim2h should hold in the recipient's mailbox if the recipient is offline
+    StoreAndForwardDirectMessage(DirectMessageData),
+    /// sent by sim2h to the sender of a store-and-forward direct message
+    /// whose recipient is offline, the message waits in the recipient's mailbox
+    DirectMessageQueued(DirectMessageData),
 }
 
+/// the maximum number of messages sent in one `WireMessage::Batch`
+pub const MAX_BATCH_SIZE: usize = 100;
+
 impl WireMessage {
     pub fn message_type(&self) -> String {
         String::from(match self {
             WireMessage::Ping => "Ping",
             WireMessage::Pong => "Pong",
+            WireMessage::Hello(_) => "Hello",
+            WireMessage::NegotiateCompression(_) => "NegotiateCompression",
+            WireMessage::CompressionAgreed(_) => "CompressionAgreed",
+            WireMessage::Batch(_) => "Batch",
+            WireMessage::StoreAndForwardDirectMessage(_) => "StoreAndForwardDirectMessage",
+            WireMessage::DirectMessageQueued(_) => "DirectMessageQueued",
             WireMessage::ClientToLib3h(ClientToLib3h::Bootstrap(_)) => "[C>L]Bootstrap",
             WireMessage::ClientToLib3h(ClientToLib3h::FetchEntry(_)) => "[C>L]FetchEntry",
             WireMessage::ClientToLib3h(ClientToLib3h::JoinSpace(_)) => "[C>L]JoinSpace",
@@ -100,6 +128,33 @@ impl WireMessage {
             WireMessage::Err(_) => "[Error] {:?}",
         })
     }
+
+    /// true for the store and fetch messages that come in floods and are worth batching
+    pub fn is_batchable(&self) -> bool {
+        match self {
+            WireMessage::Lib3hToClient(Lib3hToClient::HandleStoreEntryAspect(_))
+            | WireMessage::Lib3hToClient(Lib3hToClient::HandleFetchEntry(_))
+            | WireMessage::Lib3hToClientResponse(Lib3hToClientResponse::HandleFetchEntryResult(
+                _,
+            )) => true,
+            _ => false,
+        }
+    }
+
+    /// pack messages into as few frames as possible
+    pub fn into_batches(mut messages: Vec<WireMessage>) -> Vec<WireMessage> {
+        let mut batches = Vec::new();
+        while !messages.is_empty() {
+            let rest = messages.split_off(std::cmp::min(MAX_BATCH_SIZE, messages.len()));
+            if messages.len() == 1 {
+                batches.push(messages.remove(0));
+            } else {
+                batches.push(WireMessage::Batch(messages));
+            }
+            messages = rest;
+        }
+        batches
+    }
 }
 
 impl From<WireMessage> for Opaque {
@@ -166,4 +221,26 @@ pub mod tests {
         let roundtrip_msg = WireMessage::try_from(opaque_msg).expect("deserialize should work");
         assert_eq!(roundtrip_msg, msg);
     }
+
+    #[test]
+    pub fn test_into_batches() {
+        assert_eq!(WireMessage::into_batches(vec![]), vec![]);
+        assert_eq!(
+            WireMessage::into_batches(vec![WireMessage::Ping]),
+            vec![WireMessage::Ping]
+        );
+
+        let batches = WireMessage::into_batches(vec![WireMessage::Ping; MAX_BATCH_SIZE + 2]);
+        assert_eq!(
+            batches,
+            vec![
+                WireMessage::Batch(vec![WireMessage::Ping; MAX_BATCH_SIZE]),
+                WireMessage::Batch(vec![WireMessage::Ping; 2]),
+            ]
+        );
+
+        let opaque_msg: Opaque = batches[1].clone().into();
+        let roundtrip_msg = WireMessage::try_from(opaque_msg).expect("deserialize should work");
+        assert_eq!(roundtrip_msg, batches[1]);
+    }
 }
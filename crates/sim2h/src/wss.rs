@@ -0,0 +1,167 @@
+//! websocket streams sim2h and its clients talk over, either tcp + tls
+//! or the in-process memory transport used to run sim2h inside tests
+use in_stream::*;
+use std::io::Result;
+use url2::prelude::*;
+
+pub type TcpWssServer = InStreamListenerWss<InStreamListenerTls<InStreamListenerTcp>>;
+pub type TcpWss = InStreamWss<InStreamTls<InStreamTcp>>;
+pub type MemWssServer = InStreamListenerWss<InStreamListenerMem>;
+pub type MemWss = InStreamWss<InStreamMem>;
+
+/// url scheme clients use to reach an in-memory sim2h
+pub const MEM_SCHEME: &str = "mem";
+
+/// the websocket streams run on wss:// urls, the memory transport is told apart
+/// by the mem:// urls we hand out, so translate between the two
+fn convert_scheme(url: &Url2, scheme: &str) -> Url2 {
+    match url.port() {
+        Some(port) => Url2::parse(&format!(
+            "{}://{}:{}",
+            scheme,
+            url.host_str().unwrap_or_default(),
+            port
+        )),
+        None => Url2::parse(&format!(
+            "{}://{}",
+            scheme,
+            url.host_str().unwrap_or_default()
+        )),
+    }
+}
+
+/// listening socket of a sim2h server
+#[derive(Debug)]
+pub enum Sim2hWssServer {
+    Tcp(TcpWssServer),
+    Mem(MemWssServer),
+}
+
+impl Sim2hWssServer {
+    /// listen for tcp + tls websocket connections on `url`
+    pub fn bind_tcp(url: &Url2) -> Result<Self> {
+        let config = TcpBindConfig::default();
+        let config = TlsBindConfig::new(config).dev_certificate();
+        let config = WssBindConfig::new(config);
+        Ok(Sim2hWssServer::Tcp(TcpWssServer::bind(url, config)?))
+    }
+
+    /// listen for websocket connections on a new, unique in-memory interface
+    pub fn bind_mem() -> Result<Self> {
+        let url = convert_scheme(&in_stream_mem::random_url("sim2h"), "wss");
+        let config = WssBindConfig::new(MemBindConfig::default());
+        Ok(Sim2hWssServer::Mem(MemWssServer::bind(&url, config)?))
+    }
+
+    /// the url clients should connect to
+    pub fn binding(&self) -> Url2 {
+        match self {
+            Sim2hWssServer::Tcp(listen) => listen.binding(),
+            Sim2hWssServer::Mem(listen) => convert_scheme(&listen.binding(), MEM_SCHEME),
+        }
+    }
+
+    /// may return Err(ErrorKind::WouldBlock.into())
+    pub fn accept(&mut self) -> Result<Sim2hWss> {
+        match self {
+            Sim2hWssServer::Tcp(listen) => Ok(Sim2hWss::Tcp(listen.accept()?)),
+            Sim2hWssServer::Mem(listen) => Ok(Sim2hWss::Mem(listen.accept()?)),
+        }
+    }
+}
+
+/// a websocket connection between sim2h and one of its clients
+#[derive(Debug)]
+pub enum Sim2hWss {
+    Tcp(TcpWss),
+    Mem(MemWss),
+}
+
+impl Sim2hWss {
+    /// connect to a sim2h server, mem:// urls connect over the in-memory transport
+    pub fn connect(url: &Url2) -> Result<Self> {
+        if url.scheme() == MEM_SCHEME {
+            let config = WssConnectConfig::new(MemConnectConfig::default());
+            Ok(Sim2hWss::Mem(MemWss::connect(
+                &convert_scheme(url, "wss"),
+                config,
+            )?))
+        } else {
+            let config = WssConnectConfig::new(TlsConnectConfig::new(TcpConnectConfig::default()));
+            Ok(Sim2hWss::Tcp(TcpWss::connect(url, config)?))
+        }
+    }
+
+    pub fn remote_url(&self) -> Url2 {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.remote_url(),
+            Sim2hWss::Mem(wss) => wss.remote_url(),
+        }
+    }
+
+    pub fn check_ready(&mut self) -> Result<bool> {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.check_ready(),
+            Sim2hWss::Mem(wss) => wss.check_ready(),
+        }
+    }
+
+    pub fn read(&mut self, frame: &mut WsFrame) -> Result<usize> {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.read(frame),
+            Sim2hWss::Mem(wss) => wss.read(frame),
+        }
+    }
+
+    pub fn write(&mut self, frame: WsFrame) -> Result<usize> {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.write(frame),
+            Sim2hWss::Mem(wss) => wss.write(frame),
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        match self {
+            Sim2hWss::Tcp(wss) => wss.flush(),
+            Sim2hWss::Mem(wss) => wss.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_mem_wss_roundtrip() {
+        let mut listen = Sim2hWssServer::bind_mem().unwrap();
+        let binding = listen.binding();
+        assert_eq!(MEM_SCHEME, binding.scheme());
+
+        let mut client = Sim2hWss::connect(&binding).unwrap();
+        let mut server = loop {
+            match listen.accept() {
+                Ok(server) => break server,
+                Err(e) if e.would_block() => std::thread::yield_now(),
+                Err(e) => panic!("{:?}", e),
+            }
+        };
+
+        client.write(b"hello".to_vec().into()).unwrap();
+        let mut frame = WsFrame::default();
+        loop {
+            match server.read(&mut frame) {
+                Ok(_) => break,
+                Err(e) if e.would_block() => {
+                    client.check_ready().unwrap();
+                    std::thread::yield_now();
+                }
+                Err(e) => panic!("{:?}", e),
+            }
+        }
+        match frame {
+            WsFrame::Binary(data) => assert_eq!(b"hello".to_vec(), data),
+            _ => panic!("unexpected frame {:?}", frame),
+        }
+    }
+}
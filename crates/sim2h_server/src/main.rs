@@ -1,11 +1,21 @@
 extern crate lib3h_sodium;
 extern crate structopt;
+#[macro_use]
+extern crate holochain_metrics;
 
+use holochain_metrics::{
+    prometheus::{prometheus_registry, PrometheusConfig, PrometheusRegistry},
+    MetricPublisherConfig,
+};
 use lib3h_protocol::uri::Builder;
 use lib3h_sodium::SodiumCryptoSystem;
 use log::error;
-use sim2h::{Sim2h, MESSAGE_LOGGER};
-use std::{path::PathBuf, process::exit};
+use sim2h::{DhtAlgorithm, Sim2h, MESSAGE_LOGGER};
+use std::{
+    path::PathBuf,
+    process::exit,
+    time::{Duration, Instant},
+};
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
@@ -23,6 +33,27 @@ struct Cli {
         help = "CSV file to log all incoming and outgoing messages to"
     )]
     message_log_file: Option<PathBuf>,
+    #[structopt(
+        long,
+        short,
+        help = "Number of agents that should hold each entry, 0 makes every agent hold everything",
+        default_value = "0"
+    )]
+    sharding: u64,
+    #[structopt(
+        long,
+        help = "Serve prometheus metrics on this local port, metrics are off if not given"
+    )]
+    prometheus_port: Option<u16>,
+}
+
+/// how often the connection and space gauges get refreshed
+const GAUGE_INTERVAL: Duration = Duration::from_secs(1);
+
+fn export_gauges(registry: &PrometheusRegistry, sim2h: &Sim2h) {
+    registry.set_gauge("sim2h_connections", &[], sim2h.connection_count() as f64);
+    registry.set_gauge("sim2h_spaces", &[], sim2h.space_count() as f64);
+    registry.set_gauge("sim2h_agents", &[], sim2h.agent_count() as f64);
 }
 
 fn main() {
@@ -40,10 +71,37 @@ fn main() {
         MESSAGE_LOGGER.lock().start();
     }
 
-    let mut sim2h = Sim2h::new(Box::new(SodiumCryptoSystem::new()), uri);
+    let mut sim2h = Sim2h::new(
+        Box::new(SodiumCryptoSystem::new()),
+        uri,
+        DhtAlgorithm::from(args.sharding),
+    );
+
+    let metrics = args.prometheus_port.map(|port| {
+        let config = PrometheusConfig::new(port);
+        let registry = prometheus_registry(&config);
+        let publisher = MetricPublisherConfig::Prometheus(config).create_metric_publisher();
+        (publisher, registry)
+    });
+    let mut gauges_exported_at = Instant::now();
 
     loop {
-        let result = sim2h.process();
+        let result = match &metrics {
+            Some((publisher, registry)) => {
+                let result = with_latency_publishing!(
+                    "sim2h.process",
+                    publisher,
+                    |sim2h: &mut Sim2h| sim2h.process(),
+                    &mut sim2h
+                );
+                if gauges_exported_at.elapsed() >= GAUGE_INTERVAL {
+                    export_gauges(registry, &sim2h);
+                    gauges_exported_at = Instant::now();
+                }
+                result
+            }
+            None => sim2h.process(),
+        };
         if let Err(e) = result {
             if e.to_string().contains("Bind error:") {
                 println!("{:?}", e);
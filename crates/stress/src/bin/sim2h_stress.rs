@@ -14,7 +14,7 @@ use lib3h_protocol::{data_types::*, protocol::*, uri::Lib3hUri};
 use lib3h_sodium::SodiumCryptoSystem;
 use sim2h::{
     crypto::{Provenance, SignedWireMessage},
-    Sim2h, WireMessage,
+    DhtAlgorithm, Sim2h, WireMessage,
 };
 use std::{
     collections::{HashMap, VecDeque},
@@ -465,6 +465,12 @@ impl Job {
                 let res = res.unwrap();
                 logger.log("ping_recv_pong_in_ms", res.elapsed().as_millis() as f64);
             }
+            WireMessage::Hello(_) => {}
+            WireMessage::Batch(messages) => {
+                for msg in messages {
+                    self.priv_handle_msg(logger, msg);
+                }
+            }
             WireMessage::Lib3hToClient(Lib3hToClient::HandleGetAuthoringEntryList(_))
             | WireMessage::Lib3hToClient(Lib3hToClient::HandleGetGossipingEntryList(_)) => {}
             WireMessage::Lib3hToClient(Lib3hToClient::HandleStoreEntryAspect(aspect)) => {
@@ -572,7 +578,11 @@ impl Suite {
         let sim2h_join = Some(std::thread::spawn(move || {
             let url = Url2::parse(&format!("wss://127.0.0.1:{}", port));
 
-            let mut sim2h = Sim2h::new(Box::new(SodiumCryptoSystem::new()), Lib3hUri(url.into()));
+            let mut sim2h = Sim2h::new(
+                Box::new(SodiumCryptoSystem::new()),
+                Lib3hUri(url.into()),
+                DhtAlgorithm::FullSync,
+            );
 
             snd1.send(sim2h.bound_uri.clone().unwrap()).unwrap();
             drop(snd1);
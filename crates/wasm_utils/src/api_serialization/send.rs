@@ -8,7 +8,15 @@ pub struct SendArgs {
     pub to_agent: Address,
     pub payload: String,
     pub options: SendOptions,
+    /// have the network hold the message if the recipient is offline
+    /// and deliver it once they are back, instead of failing
+    #[serde(default)]
+    pub store_and_forward: bool,
 }
 
 #[derive(Deserialize, Clone, PartialEq, Debug, Serialize, DefaultJson)]
 pub struct SendOptions(pub Timeout);
+
+/// error message of a store-and-forward send whose recipient is offline,
+/// the answer will arrive later as a `DirectMessageReceipt` signal
+pub use holochain_core_types::network::DIRECT_MESSAGE_QUEUED;